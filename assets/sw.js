@@ -1,3 +1,30 @@
 self.addEventListener('fetch', function(event) {
     event.respondWith(fetch(event.request));
-});
\ No newline at end of file
+});
+
+// Pushes carry no payload, the notification is asked from the app when one arrives
+self.addEventListener('push', function(event) {
+    event.waitUntil(
+        fetch('/notifications/push', {credentials: 'same-origin'})
+            .then(function(response) {
+                return response.ok ? response.json() : null;
+            })
+            .catch(function() {
+                return null;
+            })
+            .then(function(notification) {
+                return self.registration.showNotification('ACSPA', {
+                    body: notification ? notification.message : 'Ai o notificare nouă',
+                    icon: '/assets/fav/apple-touch-icon.png',
+                    tag: 'acspa',
+                    renotify: true,
+                    data: {url: notification ? notification.link : '/notifications'},
+                });
+            })
+    );
+});
+
+self.addEventListener('notificationclick', function(event) {
+    event.notification.close();
+    event.waitUntil(clients.openWindow(event.notification.data.url));
+});
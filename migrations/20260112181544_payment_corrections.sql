@@ -0,0 +1,44 @@
+ALTER TABLE payments
+    ADD voided_at DATETIME;
+ALTER TABLE payments
+    ADD voided_by INTEGER REFERENCES users (id);
+ALTER TABLE payments
+    ADD void_reason TEXT;
+
+-- Refunds are stored as negative ledger entries pointing to the original payment
+ALTER TABLE payments
+    ADD refund_of INTEGER REFERENCES payments (id);
+
+CREATE TABLE payment_history
+(
+    id           INTEGER  NOT NULL PRIMARY KEY,
+    payment_id   INTEGER  NOT NULL,
+    action       TEXT     NOT NULL CHECK (action IN ('created', 'edited', 'voided', 'refunded')),
+
+    -- Snapshot of the payment after the action was applied
+    amount       INTEGER  NOT NULL,
+    payment_date DATE     NOT NULL,
+    notes        TEXT,
+    months       TEXT     NOT NULL, -- Format: "M-YYYY,M-YYYY"
+
+    created_at   DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_by   INTEGER  NOT NULL,
+
+    FOREIGN KEY (payment_id) REFERENCES payments (id) ON DELETE CASCADE,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE RESTRICT
+);
+
+INSERT INTO payment_history (payment_id, action, amount, payment_date, notes, months, created_at, created_by)
+SELECT p.id,
+       'created',
+       p.amount,
+       p.payment_date,
+       p.notes,
+       coalesce((SELECT group_concat(pa.month || '-' || pa.year, ',')
+                 FROM payment_allocations pa
+                 WHERE pa.payment_id = p.id), ''),
+       p.created_at,
+       p.created_by
+FROM payments p;
+
+CREATE INDEX idx_payment_history_payment_id ON payment_history (payment_id);
@@ -0,0 +1,25 @@
+-- Monthly membership fee in cents, online payments are disabled while it is 0
+ALTER TABLE global_vars
+    ADD monthly_fee INTEGER NOT NULL DEFAULT 0 CHECK (monthly_fee >= 0);
+
+CREATE TABLE online_payments
+(
+    id             INTEGER  NOT NULL PRIMARY KEY,
+    provider       TEXT     NOT NULL,
+    session_id     TEXT     NOT NULL,
+    user_id        INTEGER  NOT NULL,
+    amount         INTEGER  NOT NULL CHECK (amount > 0),
+    months         TEXT     NOT NULL, -- Format: "M-YYYY,M-YYYY"
+    status         TEXT     NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'completed', 'cancelled', 'failed')),
+    failure_reason TEXT,
+    payment_id     INTEGER,
+
+    created_at     DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_at   DATETIME,
+
+    UNIQUE (provider, session_id),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE RESTRICT,
+    FOREIGN KEY (payment_id) REFERENCES payments (id) ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_online_payments_user_id ON online_payments (user_id);
@@ -0,0 +1,48 @@
+CREATE TABLE bank_imports
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    file_name  TEXT     NOT NULL,
+    format     TEXT     NOT NULL CHECK (format IN ('csv', 'camt053')),
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_by INTEGER  NOT NULL,
+
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE RESTRICT
+);
+
+CREATE TABLE bank_transactions
+(
+    id                INTEGER NOT NULL PRIMARY KEY,
+    import_id         INTEGER NOT NULL,
+    -- Identifier given by the bank, used to skip transfers already present in an older statement
+    bank_reference    TEXT    NOT NULL UNIQUE,
+    booking_date      DATE    NOT NULL,
+    amount            INTEGER NOT NULL CHECK (amount > 0),
+    counterparty_name TEXT,
+    counterparty_iban TEXT,
+    reference         TEXT,
+
+    -- Proposed match, editable until the transaction is confirmed
+    user_id           INTEGER,
+    match_method      TEXT CHECK (match_method IN ('iban', 'reference', 'name', 'manual')),
+    months            TEXT    NOT NULL DEFAULT '', -- Format: "M-YYYY,M-YYYY"
+
+    status            TEXT    NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'confirmed', 'ignored')),
+    error             TEXT,
+    payment_id        INTEGER,
+
+    FOREIGN KEY (import_id) REFERENCES bank_imports (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE SET NULL,
+    FOREIGN KEY (payment_id) REFERENCES payments (id) ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_bank_transactions_status ON bank_transactions (status);
+
+-- Accounts learned from confirmed transfers, the most reliable way to match future ones
+CREATE TABLE member_bank_accounts
+(
+    iban       TEXT     NOT NULL PRIMARY KEY,
+    user_id    INTEGER  NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
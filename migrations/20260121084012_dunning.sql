@@ -0,0 +1,32 @@
+ALTER TABLE global_vars
+    ADD dunning_enabled BOOLEAN NOT NULL DEFAULT FALSE CHECK (dunning_enabled IN (FALSE, TRUE));
+
+CREATE TABLE dunning_steps
+(
+    step              INTEGER NOT NULL PRIMARY KEY,
+    name              TEXT    NOT NULL,
+    -- The step is used once a member has at least this many unpaid months
+    min_unpaid_months INTEGER NOT NULL CHECK (min_unpaid_months > 0),
+    recipient         TEXT    NOT NULL CHECK (recipient IN ('member', 'admins')),
+    enabled           BOOLEAN NOT NULL DEFAULT TRUE CHECK (enabled IN (FALSE, TRUE))
+);
+
+INSERT INTO dunning_steps (step, name, min_unpaid_months, recipient)
+VALUES (1, 'Memento', 1, 'member'),
+       (2, 'Al doilea memento', 2, 'member'),
+       (3, 'Alertă administratori', 3, 'admins');
+
+CREATE TABLE dunning_reminders
+(
+    id            INTEGER  NOT NULL PRIMARY KEY,
+    user_id       INTEGER  NOT NULL,
+    step          INTEGER  NOT NULL,
+    unpaid_months INTEGER  NOT NULL,
+    months        TEXT     NOT NULL, -- Format: "M-YYYY,M-YYYY"
+    sent_at       DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (step) REFERENCES dunning_steps (step) ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_dunning_reminders_user_id ON dunning_reminders (user_id, sent_at);
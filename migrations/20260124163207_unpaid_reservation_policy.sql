@@ -0,0 +1,6 @@
+-- Members that owe more months than the grace allows, can only make a limited number of reservations
+-- each week. A NULL grace disables the policy for the role.
+ALTER TABLE user_roles
+    ADD unpaid_grace_months INTEGER CHECK (unpaid_grace_months >= 0);
+ALTER TABLE user_roles
+    ADD unpaid_reservations TINYINT NOT NULL DEFAULT 0 CHECK (unpaid_reservations >= 0);
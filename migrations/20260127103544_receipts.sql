@@ -0,0 +1,48 @@
+ALTER TABLE global_vars
+    ADD club_name TEXT NOT NULL DEFAULT 'ACSPA';
+ALTER TABLE global_vars
+    ADD club_details TEXT NOT NULL DEFAULT '';
+
+-- Everything printed on a receipt is copied here when it is issued,
+-- so it can be regenerated identically, even if the payment or the member change later
+CREATE TABLE receipts
+(
+    number         INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    payment_id     INTEGER  NOT NULL UNIQUE,
+    club_name      TEXT     NOT NULL,
+    club_details   TEXT     NOT NULL,
+    member_name    TEXT     NOT NULL,
+    member_email   TEXT     NOT NULL,
+    amount         INTEGER  NOT NULL,
+    payment_date   DATE     NOT NULL,
+    months         TEXT     NOT NULL, -- Format: "M-YYYY,M-YYYY"
+    issued_by_name TEXT     NOT NULL,
+    issued_at      DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (payment_id) REFERENCES payments (id) ON DELETE RESTRICT
+);
+
+-- Issue receipts for the payments recorded so far, in the order they were made
+INSERT INTO receipts (payment_id, club_name, club_details, member_name, member_email, amount, payment_date,
+                      months, issued_by_name, issued_at)
+SELECT p.id,
+       g.club_name,
+       g.club_details,
+       m.name,
+       m.email,
+       p.amount,
+       p.payment_date,
+       coalesce((SELECT group_concat(month_year, ',')
+                 FROM (SELECT pa.month || '-' || pa.year AS month_year
+                       FROM payment_allocations pa
+                       WHERE pa.payment_id = p.id
+                       ORDER BY pa.year, pa.month)), ''),
+       a.name,
+       p.created_at
+FROM payments p
+         JOIN users m ON m.id = p.user_id
+         JOIN users a ON a.id = p.created_by
+         CROSS JOIN global_vars g
+WHERE p.voided_at IS NULL
+  AND p.refund_of IS NULL
+ORDER BY p.created_at, p.id;
@@ -0,0 +1,28 @@
+CREATE TABLE break_requests
+(
+    id                   INTEGER  NOT NULL PRIMARY KEY,
+    user_id              INTEGER  NOT NULL,
+
+    -- Same as the breaks, the 1st of the month
+    start_date           DATE     NOT NULL CHECK (strftime('%d', start_date) = '01'),
+    end_date             DATE     NOT NULL CHECK (strftime('%d', end_date) = '01'),
+    reason               TEXT     NOT NULL,
+
+    -- Optional document supporting the request, like a medical note
+    attachment           BLOB,
+    attachment_name      TEXT,
+    attachment_type      TEXT,
+
+    status               TEXT     NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+    rejection_reason     TEXT,
+    break_id             INTEGER,
+    created_at           DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    decided_at           DATETIME,
+    decided_by           INTEGER,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (break_id) REFERENCES payment_breaks (id) ON DELETE SET NULL,
+    FOREIGN KEY (decided_by) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX break_requests_status_idx ON break_requests (status);
@@ -0,0 +1,39 @@
+-- Members billed together, like parents and their children
+CREATE TABLE households
+(
+    id               INTEGER  NOT NULL PRIMARY KEY,
+    name             TEXT     NOT NULL UNIQUE,
+    discount_percent INTEGER  NOT NULL DEFAULT 0 CHECK (discount_percent >= 0 AND discount_percent <= 100),
+    created_at       DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE household_members
+(
+    household_id INTEGER NOT NULL,
+    user_id      INTEGER NOT NULL UNIQUE, -- A member belongs to a single household
+
+    PRIMARY KEY (household_id, user_id),
+    FOREIGN KEY (household_id) REFERENCES households (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- A single payment covering months of several members. Each covered member gets a payment
+-- with their share of the amount, linked to this one, so coverage stays per member.
+CREATE TABLE household_payments
+(
+    id               INTEGER  NOT NULL PRIMARY KEY,
+    household_id     INTEGER,
+    household_name   TEXT     NOT NULL,
+    amount           INTEGER  NOT NULL,
+    discount_percent INTEGER  NOT NULL,
+    payment_date     DATE     NOT NULL,
+    notes            TEXT,
+    created_at       DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_by       INTEGER  NOT NULL,
+
+    FOREIGN KEY (household_id) REFERENCES households (id) ON DELETE SET NULL,
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE RESTRICT
+);
+
+ALTER TABLE payments
+    ADD household_payment_id INTEGER REFERENCES household_payments (id);
@@ -0,0 +1,46 @@
+-- The membership state replaces the meaning of is_active and is_deleted, which are kept in sync with it
+ALTER TABLE users
+    ADD membership_status TEXT NOT NULL DEFAULT 'active'
+        CHECK (membership_status IN ('applicant', 'active', 'suspended', 'expired', 'resigned', 'deleted'));
+ALTER TABLE users
+    ADD status_reason TEXT;
+-- Only used by suspensions, which end automatically after this date
+ALTER TABLE users
+    ADD status_until DATE;
+
+UPDATE users
+SET membership_status = 'expired'
+WHERE is_active = FALSE;
+UPDATE users
+SET membership_status = 'deleted'
+WHERE is_deleted = TRUE;
+
+DROP VIEW users_with_role;
+
+CREATE VIEW users_with_role AS
+SELECT u.*,
+       r.name AS role,
+       r.admin_panel_access
+FROM users u
+         INNER JOIN user_roles r ON u.role_id = r.id
+WHERE is_deleted = FALSE;
+
+CREATE TABLE membership_status_history
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    user_id    INTEGER  NOT NULL,
+    status     TEXT     NOT NULL,
+    reason     TEXT,
+    until      DATE,
+    changed_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    changed_by INTEGER, -- NULL when changed automatically
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+    FOREIGN KEY (changed_by) REFERENCES users (id) ON DELETE SET NULL
+);
+
+CREATE INDEX membership_status_history_user_idx ON membership_status_history (user_id, changed_at);
+
+-- Months left unpaid after which members are suspended automatically, NULL disables it
+ALTER TABLE global_vars
+    ADD auto_suspend_unpaid_months INTEGER;
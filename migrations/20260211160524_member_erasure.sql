@@ -0,0 +1,3 @@
+-- Set when the personal data of a member was erased, which, unlike deleting them, can't be undone
+ALTER TABLE users
+    ADD erased_at DATETIME;
@@ -0,0 +1,7 @@
+-- Deleting a member can now be undone, their personal data is only removed when it's erased explicitly
+DROP TRIGGER anonymize_deleted_user;
+
+-- Members deleted so far were already anonymized, so they can't be restored
+UPDATE users
+SET erased_at = CURRENT_TIMESTAMP
+WHERE is_deleted = TRUE;
@@ -0,0 +1,37 @@
+-- Replaces the single admin panel flag when deciding what a role can manage,
+-- the flag is still used for exempting the role from payments
+CREATE TABLE role_permissions
+(
+    role_id    INTEGER NOT NULL,
+    permission TEXT    NOT NULL
+        CHECK (permission IN ('manage_members', 'manage_payments', 'manage_schedule', 'manage_roles',
+                              'manage_settings', 'view_reports', 'check_in')),
+
+    PRIMARY KEY (role_id, permission),
+    FOREIGN KEY (role_id) REFERENCES user_roles (id) ON DELETE CASCADE
+);
+
+-- Roles with access to the admin panel could do everything until now
+INSERT INTO role_permissions (role_id, permission)
+SELECT r.id, p.permission
+FROM user_roles r,
+     (SELECT 'manage_members' AS permission
+      UNION ALL SELECT 'manage_payments'
+      UNION ALL SELECT 'manage_schedule'
+      UNION ALL SELECT 'manage_roles'
+      UNION ALL SELECT 'manage_settings'
+      UNION ALL SELECT 'view_reports'
+      UNION ALL SELECT 'check_in') p
+WHERE r.admin_panel_access = TRUE;
+
+DROP VIEW users_with_role;
+
+CREATE VIEW users_with_role AS
+SELECT u.*,
+       r.name AS role,
+       r.admin_panel_access,
+       COALESCE((SELECT group_concat(p.permission) FROM role_permissions p WHERE p.role_id = u.role_id),
+                '') AS permissions
+FROM users u
+         INNER JOIN user_roles r ON u.role_id = r.id
+WHERE is_deleted = FALSE;
@@ -0,0 +1,32 @@
+-- NULL until the member enrolls an authenticator app
+ALTER TABLE users
+    ADD totp_secret TEXT;
+
+-- Members of these roles only get their admin permissions after enrolling
+ALTER TABLE user_roles
+    ADD require_two_factor BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE recovery_codes
+(
+    id        INTEGER NOT NULL PRIMARY KEY,
+    user_id   INTEGER NOT NULL,
+    code_hash TEXT    NOT NULL,
+    used_at   DATETIME,
+
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_recovery_codes_user_id ON recovery_codes (user_id);
+
+DROP VIEW users_with_role;
+
+CREATE VIEW users_with_role AS
+SELECT u.*,
+       r.name AS role,
+       r.admin_panel_access,
+       COALESCE((SELECT group_concat(p.permission) FROM role_permissions p WHERE p.role_id = u.role_id),
+                '') AS permissions,
+       r.require_two_factor
+FROM users u
+         INNER JOIN user_roles r ON u.role_id = r.id
+WHERE is_deleted = FALSE;
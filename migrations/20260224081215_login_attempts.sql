@@ -0,0 +1,18 @@
+-- Failed logins, kept for a day to throttle guessing passwords by email address and IP
+CREATE TABLE login_attempts
+(
+    id           INTEGER  NOT NULL PRIMARY KEY,
+    email        TEXT     NOT NULL,
+    ip           TEXT     NOT NULL,
+    attempted_at DATETIME NOT NULL
+);
+
+CREATE INDEX idx_login_attempts_email ON login_attempts (email, attempted_at);
+CREATE INDEX idx_login_attempts_ip ON login_attempts (ip, attempted_at);
+
+-- Email addresses, existing or not, locked after too many failures
+CREATE TABLE login_lockouts
+(
+    email        TEXT     NOT NULL PRIMARY KEY,
+    locked_until DATETIME NOT NULL
+);
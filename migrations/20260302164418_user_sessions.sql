@@ -0,0 +1,13 @@
+-- The device and address behind each logged in session, so members can see and end them.
+-- The session data itself is kept by the session store, under the same id.
+CREATE TABLE user_sessions
+(
+    session_id   TEXT     NOT NULL PRIMARY KEY,
+    user_id      INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    user_agent   TEXT     NOT NULL,
+    ip           TEXT     NOT NULL,
+    created_at   DATETIME NOT NULL,
+    last_seen_at DATETIME NOT NULL
+);
+
+CREATE INDEX idx_user_sessions_user_id ON user_sessions (user_id);
@@ -0,0 +1,50 @@
+-- SQLite can't change a CHECK constraint, so the permissions table is recreated with the new permission
+DROP VIEW users_with_role;
+
+CREATE TABLE role_permissions_new
+(
+    role_id    INTEGER NOT NULL,
+    permission TEXT    NOT NULL
+        CHECK (permission IN ('manage_members', 'manage_payments', 'manage_schedule', 'manage_roles',
+                              'manage_settings', 'view_reports', 'check_in', 'impersonate_members')),
+
+    PRIMARY KEY (role_id, permission),
+    FOREIGN KEY (role_id) REFERENCES user_roles (id) ON DELETE CASCADE
+);
+
+INSERT INTO role_permissions_new (role_id, permission)
+SELECT role_id, permission
+FROM role_permissions;
+
+DROP TABLE role_permissions;
+ALTER TABLE role_permissions_new RENAME TO role_permissions;
+
+-- Only the roles that can already change any permission get it from the start
+INSERT INTO role_permissions (role_id, permission)
+SELECT role_id, 'impersonate_members'
+FROM role_permissions
+WHERE permission = 'manage_roles';
+
+CREATE VIEW users_with_role AS
+SELECT u.*,
+       r.name AS role,
+       r.admin_panel_access,
+       COALESCE((SELECT group_concat(p.permission) FROM role_permissions p WHERE p.role_id = u.role_id),
+                '') AS permissions,
+       r.require_two_factor
+FROM users u
+         INNER JOIN user_roles r ON u.role_id = r.id
+WHERE is_deleted = FALSE;
+
+-- Every time an admin used the app as a member, for auditing
+CREATE TABLE impersonations
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    admin_id   INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    member_id  INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    read_only  BOOLEAN  NOT NULL,
+    started_at DATETIME NOT NULL,
+    ended_at   DATETIME
+);
+
+CREATE INDEX idx_impersonations_member_id ON impersonations (member_id, started_at);
@@ -0,0 +1,10 @@
+-- Single-use links for logging in without a password, only the SHA-256 of the token is stored
+CREATE TABLE login_tokens
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    user_id    INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash TEXT     NOT NULL UNIQUE,
+    created_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL,
+    used_at    DATETIME
+);
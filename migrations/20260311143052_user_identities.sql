@@ -0,0 +1,12 @@
+-- Accounts of an external OpenID Connect provider that members log in with, one per provider for each member
+CREATE TABLE user_identities
+(
+    id        INTEGER  NOT NULL PRIMARY KEY,
+    user_id   INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    issuer    TEXT     NOT NULL,
+    subject   TEXT     NOT NULL,
+    email     TEXT     NOT NULL,
+    linked_at DATETIME NOT NULL,
+    UNIQUE (issuer, subject),
+    UNIQUE (user_id, issuer)
+);
@@ -0,0 +1,8 @@
+-- Scheduled maintenance, active between the two moments, in addition to the in_maintenance switch
+ALTER TABLE global_vars
+    ADD maintenance_starts_at DATETIME;
+ALTER TABLE global_vars
+    ADD maintenance_ends_at DATETIME;
+-- Whether members are warned about the scheduled maintenance before it begins
+ALTER TABLE global_vars
+    ADD maintenance_banner BOOLEAN NOT NULL DEFAULT FALSE CHECK (maintenance_banner IN (FALSE, TRUE));
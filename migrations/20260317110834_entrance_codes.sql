@@ -0,0 +1,31 @@
+-- Every entrance code used, global_vars.entrance_code is the current one
+CREATE TABLE entrance_codes
+(
+    id          INTEGER  NOT NULL PRIMARY KEY,
+    code        TEXT     NOT NULL,
+    valid_from  DATETIME NOT NULL,
+    -- Set once the code is replaced
+    valid_until DATETIME,
+    -- NULL when it was rotated automatically
+    created_by  INTEGER REFERENCES users (id) ON DELETE SET NULL
+);
+
+INSERT INTO entrance_codes (code, valid_from)
+SELECT entrance_code, CURRENT_TIMESTAMP
+FROM global_vars;
+
+-- Codes only given to the members with a reservation in the slot
+CREATE TABLE slot_entrance_codes
+(
+    date       DATE     NOT NULL,
+    hour       INTEGER  NOT NULL,
+    code       TEXT     NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (date, hour)
+);
+
+-- Days after which the entrance code is replaced automatically, NULL only changes it by hand
+ALTER TABLE global_vars
+    ADD entrance_code_rotation_days INTEGER;
+ALTER TABLE global_vars
+    ADD slot_entrance_codes BOOLEAN NOT NULL DEFAULT FALSE CHECK (slot_entrance_codes IN (FALSE, TRUE));
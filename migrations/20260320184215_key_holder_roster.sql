@@ -0,0 +1,21 @@
+-- The member with a key that can be called to open the club on a day
+CREATE TABLE key_holder_roster
+(
+    date        DATE     NOT NULL PRIMARY KEY,
+    user_id     INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    assigned_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    assigned_at DATETIME NOT NULL
+);
+
+-- Slots that were already warned about having no key-holder
+CREATE TABLE key_holder_warnings
+(
+    date    DATE     NOT NULL,
+    hour    INTEGER  NOT NULL,
+    sent_at DATETIME NOT NULL,
+    PRIMARY KEY (date, hour)
+);
+
+-- Hours before a slot without a key-holder starts when the warning is sent, NULL disables it
+ALTER TABLE global_vars
+    ADD key_holder_warning_hours INTEGER;
@@ -0,0 +1,40 @@
+CREATE TABLE announcements
+(
+    id           INTEGER  NOT NULL PRIMARY KEY,
+    title        TEXT     NOT NULL,
+    -- Markdown
+    body         TEXT     NOT NULL,
+    pinned       BOOLEAN  NOT NULL DEFAULT FALSE CHECK (pinned IN (FALSE, TRUE)),
+    starts_at    DATETIME NOT NULL,
+    -- NULL keeps it visible until deleted
+    ends_at      DATETIME,
+    -- Set once the members online were notified about it
+    published_at DATETIME,
+    created_by   INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    created_at   DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The roles that see the announcement, everyone sees the ones without any
+CREATE TABLE announcement_roles
+(
+    announcement_id INTEGER NOT NULL REFERENCES announcements (id) ON DELETE CASCADE,
+    role_id         INTEGER NOT NULL REFERENCES user_roles (id) ON DELETE CASCADE,
+    PRIMARY KEY (announcement_id, role_id)
+);
+
+CREATE TABLE announcement_reads
+(
+    announcement_id INTEGER  NOT NULL REFERENCES announcements (id) ON DELETE CASCADE,
+    user_id         INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    read_at         DATETIME NOT NULL,
+    PRIMARY KEY (announcement_id, user_id)
+);
+
+-- The homepage message becomes a pinned announcement for everyone
+INSERT INTO announcements (title, body, pinned, starts_at, published_at)
+SELECT 'Anunț', homepage_message, TRUE, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+FROM global_vars
+WHERE trim(homepage_message) != '';
+
+ALTER TABLE global_vars
+    DROP COLUMN homepage_message;
@@ -0,0 +1,14 @@
+CREATE TABLE notifications
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    user_id    INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    -- promotion, cancellation, payment or announcement
+    kind       TEXT     NOT NULL,
+    message    TEXT     NOT NULL,
+    -- Where the member is taken when opening it
+    link       TEXT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    read_at    DATETIME
+);
+
+CREATE INDEX idx_notifications_user_id ON notifications (user_id, read_at);
@@ -0,0 +1,13 @@
+-- The browsers of the members that accepted phone notifications.
+-- Pushes carry no payload, so the encryption keys of the subscriptions are not needed.
+CREATE TABLE push_subscriptions
+(
+    id         INTEGER  NOT NULL PRIMARY KEY,
+    user_id    INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    endpoint   TEXT     NOT NULL UNIQUE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Set once the notification was pushed to the phones of the member
+ALTER TABLE notifications
+    ADD COLUMN pushed_at DATETIME;
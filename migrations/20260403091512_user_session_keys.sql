@@ -0,0 +1,20 @@
+-- The pages refer to the sessions by a key of their own, the session id is also the cookie value
+CREATE TABLE user_sessions_new
+(
+    id           INTEGER  NOT NULL PRIMARY KEY,
+    session_id   TEXT     NOT NULL UNIQUE,
+    user_id      INTEGER  NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    user_agent   TEXT     NOT NULL,
+    ip           TEXT     NOT NULL,
+    created_at   DATETIME NOT NULL,
+    last_seen_at DATETIME NOT NULL
+);
+
+INSERT INTO user_sessions_new (session_id, user_id, user_agent, ip, created_at, last_seen_at)
+SELECT session_id, user_id, user_agent, ip, created_at, last_seen_at
+FROM user_sessions;
+
+DROP TABLE user_sessions;
+ALTER TABLE user_sessions_new RENAME TO user_sessions;
+
+CREATE INDEX idx_user_sessions_user_id ON user_sessions (user_id);
@@ -0,0 +1,12 @@
+-- Requested login links, kept for a day to limit how many are sent to an address or from an IP.
+-- Separate from login_attempts, so asking for links doesn't lock out password logins.
+CREATE TABLE magic_link_requests
+(
+    id           INTEGER  NOT NULL PRIMARY KEY,
+    email        TEXT     NOT NULL,
+    ip           TEXT     NOT NULL,
+    requested_at DATETIME NOT NULL
+);
+
+CREATE INDEX idx_magic_link_requests_email ON magic_link_requests (email, requested_at);
+CREATE INDEX idx_magic_link_requests_ip ON magic_link_requests (ip, requested_at);
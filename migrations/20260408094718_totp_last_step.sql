@@ -0,0 +1,16 @@
+-- Time step of the last accepted authenticator code, codes up to it are refused so they can't be replayed
+ALTER TABLE users
+    ADD totp_last_step INTEGER;
+
+DROP VIEW users_with_role;
+
+CREATE VIEW users_with_role AS
+SELECT u.*,
+       r.name AS role,
+       r.admin_panel_access,
+       COALESCE((SELECT group_concat(p.permission) FROM role_permissions p WHERE p.role_id = u.role_id),
+                '') AS permissions,
+       r.require_two_factor
+FROM users u
+         INNER JOIN user_roles r ON u.role_id = r.id
+WHERE is_deleted = FALSE;
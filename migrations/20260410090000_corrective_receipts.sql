@@ -0,0 +1,41 @@
+-- A payment can get several receipts: edits that change what was printed issue a new one,
+-- which replaces the previous, and refunds get a storno receipt of the refunded payment's receipt
+CREATE TABLE receipts_new
+(
+    number         INTEGER  NOT NULL PRIMARY KEY AUTOINCREMENT,
+    payment_id     INTEGER  NOT NULL,
+    club_name      TEXT     NOT NULL,
+    club_details   TEXT     NOT NULL,
+    member_name    TEXT     NOT NULL,
+    member_email   TEXT     NOT NULL,
+    amount         INTEGER  NOT NULL,
+    payment_date   DATE     NOT NULL,
+    months         TEXT     NOT NULL, -- Format: "M-YYYY,M-YYYY"
+    issued_by_name TEXT     NOT NULL,
+    issued_at      DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    corrects       INTEGER,
+
+    FOREIGN KEY (payment_id) REFERENCES payments (id) ON DELETE RESTRICT,
+    FOREIGN KEY (corrects) REFERENCES receipts_new (number) ON DELETE RESTRICT
+);
+
+INSERT INTO receipts_new (number, payment_id, club_name, club_details, member_name, member_email, amount,
+                          payment_date, months, issued_by_name, issued_at)
+SELECT number,
+       payment_id,
+       club_name,
+       club_details,
+       member_name,
+       member_email,
+       amount,
+       payment_date,
+       months,
+       issued_by_name,
+       issued_at
+FROM receipts;
+
+DROP TABLE receipts;
+ALTER TABLE receipts_new
+    RENAME TO receipts;
+
+CREATE INDEX idx_receipts_payment_id ON receipts (payment_id);
@@ -0,0 +1,124 @@
+use crate::bank_statement::{
+    BankStatementError, BankTransaction, non_empty, normalize_iban, parse_amount, parse_date,
+};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+/// Fields of an `<Ntry>` element, filled in while walking the document
+#[derive(Default)]
+struct Entry {
+    amount: Option<String>,
+    is_credit: bool,
+    is_pending: bool,
+    booking_date: Option<String>,
+    bank_reference: Option<String>,
+    counterparty_name: Option<String>,
+    counterparty_iban: Option<String>,
+    remittance: Vec<String>,
+}
+
+impl Entry {
+    fn into_transaction(self, line: usize) -> Result<Option<BankTransaction>, BankStatementError> {
+        if !self.is_credit || self.is_pending {
+            return Ok(None);
+        }
+
+        let invalid = |reason: String| BankStatementError::InvalidRow { line, reason };
+
+        let amount = self.amount.unwrap_or_default();
+        let amount =
+            parse_amount(&amount).ok_or_else(|| invalid(format!("sumă invalidă `{amount}`")))?;
+        let booking_date = self.booking_date.unwrap_or_default();
+        let booking_date = parse_date(&booking_date)
+            .ok_or_else(|| invalid(format!("data invalidă `{booking_date}`")))?;
+
+        Ok(Some(BankTransaction {
+            bank_reference: self.bank_reference.unwrap_or_default(),
+            booking_date,
+            amount,
+            counterparty_name: self.counterparty_name,
+            counterparty_iban: self.counterparty_iban,
+            reference: non_empty(&self.remittance.join(" ")),
+        }))
+    }
+}
+
+fn ends_with(path: &[String], suffix: &[&str]) -> bool {
+    path.len() >= suffix.len()
+        && path[path.len() - suffix.len()..]
+            .iter()
+            .zip(suffix)
+            .all(|(a, b)| a == b)
+}
+
+/// Reads the booked credit entries of an ISO 20022 `camt.053` statement.
+/// Only the elements we need are looked at, so the different versions of the schema all work.
+pub fn parse(content: &[u8]) -> Result<Vec<BankTransaction>, BankStatementError> {
+    let mut reader = Reader::from_reader(content);
+    reader.config_mut().trim_text(true);
+
+    let mut path = Vec::<String>::new();
+    let mut entry: Option<Entry> = None;
+    let mut entries_count = 0;
+    let mut transactions = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                if name == "Ntry" {
+                    entry = Some(Entry::default());
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                let closed = path.pop();
+                if closed.as_deref() == Some("Ntry") {
+                    entries_count += 1;
+                    if let Some(transaction) = entry
+                        .take()
+                        .map(|entry| entry.into_transaction(entries_count))
+                        .transpose()?
+                        .flatten()
+                    {
+                        transactions.push(transaction);
+                    }
+                }
+            }
+            Event::Text(text) => {
+                let Some(entry) = entry.as_mut() else {
+                    continue;
+                };
+                let text = text.unescape()?.trim().to_string();
+                // Only the first entry level is relevant, nested `Amt` elements hold currency conversions
+                let entry_level = path.iter().rposition(|p| p == "Ntry").unwrap_or_default();
+                let relative_path = &path[entry_level + 1..];
+
+                match relative_path {
+                    [amount] if amount == "Amt" => entry.amount = Some(text),
+                    [indicator] if indicator == "CdtDbtInd" => entry.is_credit = text == "CRDT",
+                    [status] | [status, _] if status == "Sts" => entry.is_pending = text == "PDNG",
+                    [booking, _] if booking == "BookgDt" => entry.booking_date = Some(text),
+                    [reference] if reference == "AcctSvcrRef" => entry.bank_reference = Some(text),
+                    _ if ends_with(&path, &["Refs", "AcctSvcrRef"]) => {
+                        entry.bank_reference.get_or_insert(text);
+                    }
+                    _ if ends_with(&path, &["RltdPties", "Dbtr", "Nm"])
+                        || ends_with(&path, &["RltdPties", "Dbtr", "Pty", "Nm"]) =>
+                    {
+                        entry.counterparty_name.get_or_insert(text);
+                    }
+                    _ if ends_with(&path, &["DbtrAcct", "Id", "IBAN"]) => {
+                        entry.counterparty_iban = normalize_iban(&text);
+                    }
+                    _ if ends_with(&path, &["RmtInf", "Ustrd"]) => entry.remittance.push(text),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(transactions)
+}
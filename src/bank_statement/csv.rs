@@ -0,0 +1,147 @@
+use crate::bank_statement::{
+    BankStatementError, BankTransaction, non_empty, normalize_iban, normalize_text, parse_amount,
+    parse_date,
+};
+use csv::{ReaderBuilder, StringRecord};
+
+// Header names used by the banks we have statements from, after `normalize_text`
+const DATE_COLUMNS: &[&str] = &[
+    "data",
+    "date",
+    "data tranzactiei",
+    "data operatiunii",
+    "data contabila",
+    "data inregistrarii",
+    "booking date",
+];
+const AMOUNT_COLUMNS: &[&str] = &["suma", "amount", "credit", "suma credit", "valoare"];
+const NAME_COLUMNS: &[&str] = &[
+    "nume",
+    "platitor",
+    "nume platitor",
+    "ordonator",
+    "partener",
+    "counterparty",
+    "name",
+];
+const IBAN_COLUMNS: &[&str] = &[
+    "iban",
+    "iban platitor",
+    "cont platitor",
+    "cont",
+    "account",
+    "counterparty iban",
+];
+const REFERENCE_COLUMNS: &[&str] = &[
+    "detalii",
+    "descriere",
+    "explicatie",
+    "mesaj",
+    "details",
+    "description",
+    "reference",
+];
+const ID_COLUMNS: &[&str] = &[
+    "referinta",
+    "referinta banca",
+    "id tranzactie",
+    "transaction id",
+    "id",
+];
+
+struct Columns {
+    date: usize,
+    amount: usize,
+    name: Option<usize>,
+    iban: Option<usize>,
+    reference: Option<usize>,
+    id: Option<usize>,
+}
+
+impl Columns {
+    /// Returns `None` if the record is not the header, banks like to add a preamble to the export
+    fn find(record: &StringRecord) -> Option<Self> {
+        let headers: Vec<_> = record.iter().map(normalize_text).collect();
+        let find = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+        Some(Self {
+            date: find(DATE_COLUMNS)?,
+            amount: find(AMOUNT_COLUMNS)?,
+            name: find(NAME_COLUMNS),
+            iban: find(IBAN_COLUMNS),
+            reference: find(REFERENCE_COLUMNS),
+            id: find(ID_COLUMNS),
+        })
+    }
+}
+
+fn detect_delimiter(content: &[u8]) -> u8 {
+    let first_line = content.split(|c| *c == b'\n').next().unwrap_or_default();
+
+    [b';', b'\t', b',']
+        .into_iter()
+        .max_by_key(|delimiter| first_line.iter().filter(|c| *c == delimiter).count())
+        .unwrap_or(b',')
+}
+
+pub fn parse(content: &[u8]) -> Result<Vec<BankTransaction>, BankStatementError> {
+    let content = content.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(content);
+    let mut reader = ReaderBuilder::new()
+        .delimiter(detect_delimiter(content))
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content);
+
+    let mut header = None;
+    let mut transactions = Vec::new();
+
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = index + 1;
+
+        let Some(columns) = &header else {
+            header = Columns::find(&record);
+            continue;
+        };
+
+        let field = |column: Option<usize>| column.and_then(|c| record.get(c)).and_then(non_empty);
+
+        let Some(date) = field(Some(columns.date)) else {
+            // Empty lines and totals at the end of the statement
+            continue;
+        };
+        let booking_date = parse_date(&date).ok_or_else(|| BankStatementError::InvalidRow {
+            line,
+            reason: format!("data invalidă `{date}`"),
+        })?;
+
+        let amount = field(Some(columns.amount))
+            .map(|amount| {
+                parse_amount(&amount).ok_or_else(|| BankStatementError::InvalidRow {
+                    line,
+                    reason: format!("sumă invalidă `{amount}`"),
+                })
+            })
+            .transpose()?
+            .unwrap_or_default();
+        if amount <= 0 {
+            // Outgoing transfer
+            continue;
+        }
+
+        transactions.push(BankTransaction {
+            bank_reference: field(columns.id).unwrap_or_default(),
+            booking_date,
+            amount,
+            counterparty_name: field(columns.name),
+            counterparty_iban: field(columns.iban).as_deref().and_then(normalize_iban),
+            reference: field(columns.reference),
+        });
+    }
+
+    if header.is_none() {
+        return Err(BankStatementError::MissingColumns);
+    }
+
+    Ok(transactions)
+}
@@ -0,0 +1,109 @@
+use crate::bank_statement::{BankTransaction, normalize_text};
+use crate::model::user::User;
+use std::collections::{HashMap, HashSet};
+use strum::{AsRefStr, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum MatchMethod {
+    /// The account was used by the member for a previously confirmed transfer
+    Iban,
+    /// The member's name or email appears in the transfer details
+    Reference,
+    /// The account holder has the member's name
+    Name,
+    /// Chosen by the treasurer
+    Manual,
+}
+
+impl MatchMethod {
+    pub fn display(&self) -> &'static str {
+        match self {
+            Self::Iban => "IBAN cunoscut",
+            Self::Reference => "Detalii plată",
+            Self::Name => "Nume plătitor",
+            Self::Manual => "Manual",
+        }
+    }
+}
+
+fn name_words(name: &str) -> HashSet<String> {
+    normalize_text(name)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| word.len() > 1)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whole words that could be emails, not normalized since `-`, `_` and `+` are valid in them
+fn email_tokens(reference: &str) -> HashSet<String> {
+    reference
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || ",;:()<>[]\"\\".contains(c))
+        .map(|token| token.trim_end_matches('.'))
+        .filter(|token| token.contains('@'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the member only when a single one matches, an ambiguous guess is worse than none
+fn single_match<'a>(mut matches: impl Iterator<Item = &'a User>) -> Option<i64> {
+    let first = matches.next()?;
+    matches.next().is_none().then_some(first.id)
+}
+
+/// Tries the heuristics from the most reliable to the least reliable one
+pub fn find_member(
+    transaction: &BankTransaction,
+    members: &[User],
+    known_accounts: &HashMap<String, i64>,
+) -> Option<(i64, MatchMethod)> {
+    if let Some(user_id) = transaction
+        .counterparty_iban
+        .as_ref()
+        .and_then(|iban| known_accounts.get(iban))
+    {
+        return Some((*user_id, MatchMethod::Iban));
+    }
+
+    let members_words: Vec<_> = members
+        .iter()
+        .map(|member| (member, name_words(&member.name)))
+        // A single word is too likely to appear by chance
+        .filter(|(_, words)| words.len() >= 2)
+        .collect();
+
+    if let Some(reference) = &transaction.reference {
+        let reference_words = name_words(reference);
+        let reference_emails = email_tokens(reference);
+
+        let by_email = members
+            .iter()
+            .filter(|member| reference_emails.contains(&member.email.to_lowercase()));
+        if let Some(user_id) = single_match(by_email) {
+            return Some((user_id, MatchMethod::Reference));
+        }
+
+        let by_name = members_words
+            .iter()
+            .filter(|(_, words)| words.is_subset(&reference_words))
+            .map(|(member, _)| *member);
+        if let Some(user_id) = single_match(by_name) {
+            return Some((user_id, MatchMethod::Reference));
+        }
+    }
+
+    if let Some(name) = &transaction.counterparty_name {
+        // Banks often have the full legal name, with all the middle names
+        let account_words = name_words(name);
+        let by_name = members_words
+            .iter()
+            .filter(|(_, words)| words.is_subset(&account_words))
+            .map(|(member, _)| *member);
+        if let Some(user_id) = single_match(by_name) {
+            return Some((user_id, MatchMethod::Name));
+        }
+    }
+
+    None
+}
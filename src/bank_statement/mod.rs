@@ -0,0 +1,184 @@
+mod camt053;
+mod csv;
+mod matching;
+#[cfg(test)]
+mod test;
+
+use sha2::{Digest, Sha256};
+use strum::{AsRefStr, EnumString};
+use thiserror::Error;
+use time::Date;
+use time::macros::format_description;
+
+pub use matching::{MatchMethod, find_member};
+
+#[derive(Error, Debug)]
+pub enum BankStatementError {
+    #[error("Fișier CSV invalid: {0}")]
+    Csv(#[from] ::csv::Error),
+    #[error("Fișier XML invalid: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("Nu au fost găsite coloanele pentru dată și sumă")]
+    MissingColumns,
+    #[error("Tranzacție invalidă pe linia {line}: {reason}")]
+    InvalidRow { line: usize, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum StatementFormat {
+    Csv,
+    Camt053,
+}
+
+impl StatementFormat {
+    pub fn detect(file_name: &str, content: &[u8]) -> Self {
+        let is_xml = file_name.to_lowercase().ends_with(".xml")
+            || content
+                .iter()
+                .find(|c| !c.is_ascii_whitespace())
+                .is_some_and(|c| *c == b'<');
+
+        if is_xml { Self::Camt053 } else { Self::Csv }
+    }
+}
+
+/// An incoming transfer, outgoing ones are skipped while parsing
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankTransaction {
+    pub bank_reference: String,
+    pub booking_date: Date,
+    /// Amount in cents
+    pub amount: i64,
+    pub counterparty_name: Option<String>,
+    pub counterparty_iban: Option<String>,
+    pub reference: Option<String>,
+}
+
+pub fn parse_statement(
+    format: StatementFormat,
+    content: &[u8],
+) -> Result<Vec<BankTransaction>, BankStatementError> {
+    let mut transactions = match format {
+        StatementFormat::Csv => csv::parse(content)?,
+        StatementFormat::Camt053 => camt053::parse(content)?,
+    };
+
+    // Not every bank exports an unique identifier, so one is derived from the contents.
+    // Identical transfers on the same day are told apart by their position in the statement.
+    let mut seen = Vec::<String>::new();
+    for transaction in transactions
+        .iter_mut()
+        .filter(|t| t.bank_reference.is_empty())
+    {
+        let key = format!(
+            "{}|{}|{}|{}|{}",
+            transaction.booking_date,
+            transaction.amount,
+            transaction.counterparty_iban.as_deref().unwrap_or_default(),
+            transaction.counterparty_name.as_deref().unwrap_or_default(),
+            transaction.reference.as_deref().unwrap_or_default(),
+        );
+        let occurrence = seen.iter().filter(|k| **k == key).count();
+        let hash = Sha256::digest(format!("{key}|{occurrence}"));
+        seen.push(key);
+
+        transaction.bank_reference = format!("sha256:{}", hex::encode(&hash[..16]));
+    }
+
+    Ok(transactions)
+}
+
+/// Lowercase ASCII words, without diacritics or punctuation
+pub fn normalize_text(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ă' | 'â' | 'Ă' | 'Â' | 'á' | 'Á' | 'ä' | 'Ä' => 'a',
+            'î' | 'Î' | 'í' | 'Í' => 'i',
+            'ș' | 'ş' | 'Ș' | 'Ş' => 's',
+            'ț' | 'ţ' | 'Ț' | 'Ţ' => 't',
+            'é' | 'É' | 'ë' | 'Ë' => 'e',
+            'ö' | 'Ö' | 'ó' | 'Ó' => 'o',
+            'ü' | 'Ü' | 'ú' | 'Ú' => 'u',
+            c if c.is_alphanumeric() || c == '@' || c == '.' => c.to_ascii_lowercase(),
+            _ => ' ',
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn normalize_iban(iban: &str) -> Option<String> {
+    let iban: String = iban
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    (!iban.is_empty()).then_some(iban)
+}
+
+/// Parses "1234.56", "1.234,56" or "1234,56" into cents
+fn parse_amount(amount: &str) -> Option<i64> {
+    let amount: String = amount
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'')
+        .collect();
+    let (negative, amount) = match amount.strip_prefix('-') {
+        Some(amount) => (true, amount),
+        None => (false, amount.strip_prefix('+').unwrap_or(&amount)),
+    };
+
+    // With a single kind of separator, it can only be the decimal one if it appears once
+    // and is followed by at most 2 digits, otherwise it groups the thousands
+    let is_decimal = |index: usize, separator: char| {
+        amount.matches(separator).count() == 1 && amount.len() - index - 1 <= 2
+    };
+    let decimal_separator = match (amount.rfind('.'), amount.rfind(',')) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (Some(dot), None) if is_decimal(dot, '.') => Some(dot),
+        (None, Some(comma)) if is_decimal(comma, ',') => Some(comma),
+        _ => None,
+    };
+
+    let (whole, fraction) = match decimal_separator {
+        Some(index) => (&amount[..index], &amount[index + 1..]),
+        None => (amount, ""),
+    };
+    let whole: String = whole.chars().filter(|c| *c != '.' && *c != ',').collect();
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    if fraction.len() > 2 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<i64>().ok()?
+    };
+    let fraction = format!("{fraction:0<2}").parse::<i64>().ok()?;
+    let cents = whole * 100 + fraction;
+
+    Some(if negative { -cents } else { cents })
+}
+
+fn parse_date(date: &str) -> Option<Date> {
+    let date = date.trim().get(..10)?;
+
+    [
+        format_description!("[year]-[month]-[day]"),
+        format_description!("[day].[month].[year]"),
+        format_description!("[day]/[month]/[year]"),
+        format_description!("[day]-[month]-[year]"),
+    ]
+    .iter()
+    .find_map(|format| Date::parse(date, format).ok())
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
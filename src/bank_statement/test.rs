@@ -0,0 +1,203 @@
+use super::*;
+use crate::model::user::User;
+use std::collections::HashMap;
+use time::macros::date;
+
+fn member(id: i64, name: &str, email: &str) -> User {
+    User {
+        id,
+        name: name.into(),
+        email: email.into(),
+        ..User::empty()
+    }
+}
+
+fn transfer(name: Option<&str>, iban: Option<&str>, reference: Option<&str>) -> BankTransaction {
+    BankTransaction {
+        bank_reference: "ref".into(),
+        booking_date: date!(2024 - 03 - 01),
+        amount: 10000,
+        counterparty_name: name.map(Into::into),
+        counterparty_iban: iban.map(Into::into),
+        reference: reference.map(Into::into),
+    }
+}
+
+#[test]
+fn parses_amounts() {
+    assert_eq!(parse_amount("100"), Some(10000));
+    assert_eq!(parse_amount("100.5"), Some(10050));
+    assert_eq!(parse_amount("1.234,56"), Some(123456));
+    assert_eq!(parse_amount("1,234.56"), Some(123456));
+    assert_eq!(parse_amount("1.234"), Some(123400));
+    assert_eq!(parse_amount("150,00"), Some(15000));
+    assert_eq!(parse_amount("-75.00"), Some(-7500));
+    assert_eq!(parse_amount("abc"), None);
+    assert_eq!(parse_amount(""), None);
+}
+
+#[test]
+fn parses_csv_with_preamble() {
+    let csv = "Extras de cont;RO49AAAA1B31007593840000\n\
+        \n\
+        Data;Detalii;Nume platitor;IBAN platitor;Sumă;Referință\n\
+        05.03.2024;Cotizatie martie;POPESCU ION;RO49 AAAA 1B31 0075 9384 0000;150,00;TX1\n\
+        06.03.2024;Chirie sala;;;-500,00;TX2\n\
+        07.03.2024;Cotizatie;Ionescu Maria;;1.234,50;\n\
+        ;Total;;;884,50;\n";
+
+    let transactions = parse_statement(StatementFormat::Csv, csv.as_bytes()).unwrap();
+
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(
+        transactions[0],
+        BankTransaction {
+            bank_reference: "TX1".into(),
+            booking_date: date!(2024 - 03 - 05),
+            amount: 15000,
+            counterparty_name: Some("POPESCU ION".into()),
+            counterparty_iban: Some("RO49AAAA1B31007593840000".into()),
+            reference: Some("Cotizatie martie".into()),
+        }
+    );
+    assert_eq!(transactions[1].amount, 123450);
+    assert!(transactions[1].bank_reference.starts_with("sha256:"));
+}
+
+#[test]
+fn derived_references_are_stable_and_unique() {
+    let csv = "Date,Amount,Name\n2024-03-05,100,Ion Popescu\n2024-03-05,100,Ion Popescu\n";
+
+    let first = parse_statement(StatementFormat::Csv, csv.as_bytes()).unwrap();
+    let second = parse_statement(StatementFormat::Csv, csv.as_bytes()).unwrap();
+
+    assert_ne!(first[0].bank_reference, first[1].bank_reference);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn rejects_csv_without_header() {
+    assert!(matches!(
+        parse_statement(StatementFormat::Csv, b"a,b,c\n1,2,3\n"),
+        Err(BankStatementError::MissingColumns)
+    ));
+}
+
+#[test]
+fn parses_camt053() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Ntry>
+        <Amt Ccy="RON">150.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-03-05</Dt></BookgDt>
+        <AcctSvcrRef>BANK-1</AcctSvcrRef>
+        <NtryDtls><TxDtls>
+          <AmtDtls><InstdAmt><Amt Ccy="EUR">30.00</Amt></InstdAmt></AmtDtls>
+          <RltdPties>
+            <Dbtr><Nm>Popescu Ion</Nm></Dbtr>
+            <DbtrAcct><Id><IBAN>RO49AAAA1B31007593840000</IBAN></Id></DbtrAcct>
+            <Cdtr><Nm>ACSPA</Nm></Cdtr>
+          </RltdPties>
+          <RmtInf><Ustrd>Cotizatie</Ustrd><Ustrd>martie</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="RON">500.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-03-06</Dt></BookgDt>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="RON">150.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>PDNG</Cd></Sts>
+        <BookgDt><DtTm>2024-03-07T10:00:00</DtTm></BookgDt>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    assert_eq!(
+        StatementFormat::detect("extras.txt", xml.as_bytes()),
+        StatementFormat::Camt053
+    );
+    assert_eq!(
+        parse_statement(StatementFormat::Camt053, xml.as_bytes()).unwrap(),
+        vec![BankTransaction {
+            bank_reference: "BANK-1".into(),
+            booking_date: date!(2024 - 03 - 05),
+            amount: 15000,
+            counterparty_name: Some("Popescu Ion".into()),
+            counterparty_iban: Some("RO49AAAA1B31007593840000".into()),
+            reference: Some("Cotizatie martie".into()),
+        }]
+    );
+}
+
+#[test]
+fn matches_members() {
+    let members = [
+        member(1, "Ion Popescu", "ion@test.com"),
+        member(2, "Maria Ionescu", "maria@test.com"),
+        member(3, "Maria Ionescu", "maria2@test.com"),
+        member(4, "Ștefan Țurcanu", "stefan@test.com"),
+        member(5, "Dan", "dan@test.com"),
+        member(6, "An", "an@test.com"),
+        member(7, "Ana", "ana-maria_pop+acspa@test.com"),
+    ];
+    let known_accounts = HashMap::from([("RO01".to_string(), 2)]);
+    let find = |transaction: BankTransaction| find_member(&transaction, &members, &known_accounts);
+
+    assert_eq!(
+        find(transfer(Some("Popescu Ion"), Some("RO01"), None)),
+        Some((2, MatchMethod::Iban))
+    );
+    assert_eq!(
+        find(transfer(None, None, Some("Cotizatie maria2@test.com"))),
+        Some((3, MatchMethod::Reference))
+    );
+    assert_eq!(
+        find(transfer(
+            Some("Tatal lui Ion"),
+            None,
+            Some("cotizatie turcanu stefan")
+        )),
+        Some((4, MatchMethod::Reference))
+    );
+    assert_eq!(
+        find(transfer(
+            Some("POPESCU ION ANDREI"),
+            None,
+            Some("cotizatie")
+        )),
+        Some((1, MatchMethod::Name))
+    );
+    // Emails are compared as whole words, with the characters that normalizing would drop
+    assert_eq!(
+        find(transfer(None, None, Some("Cotizatie DAN@TEST.COM."))),
+        Some((5, MatchMethod::Reference))
+    );
+    assert_eq!(
+        find(transfer(None, None, Some("cotizatie;an@test.com"))),
+        Some((6, MatchMethod::Reference))
+    );
+    assert_eq!(
+        find(transfer(
+            None,
+            None,
+            Some("Cotizatie (ana-maria_pop+acspa@test.com)")
+        )),
+        Some((7, MatchMethod::Reference))
+    );
+    assert_eq!(
+        find(transfer(None, None, Some("ana-maria pop acspa@test.com"))),
+        None
+    );
+    // Two members with the same name
+    assert_eq!(find(transfer(Some("Ionescu Maria"), None, None)), None);
+    assert_eq!(find(transfer(Some("Ion"), None, Some("martie"))), None);
+}
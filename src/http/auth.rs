@@ -6,7 +6,10 @@ use axum_login::{AuthnBackend, AuthzBackend, UserId};
 use sqlx::{SqlitePool, query_as};
 use tokio::task;
 
-use crate::model::user::{User, UserCredentials};
+use crate::model::role::Permission;
+use crate::model::user::{Credentials, ExternalIdentity, User, UserCredentials};
+
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$r7gp/pJoX038RwBEe8IzzQ$9L3znCPi4Va1ENFjxU4mIUkqsJdDHW2BiO81aPpfjiM";
 
 #[derive(Clone)]
 pub struct UserAuthenticator {
@@ -17,45 +20,93 @@ impl UserAuthenticator {
     pub fn new(pool: SqlitePool) -> Self {
         Self { pool }
     }
+
+    async fn authenticate_password(
+        &self,
+        creds: UserCredentials,
+    ) -> Result<Option<User>, std::io::Error> {
+        let user = self.get_user(&creds.email).await?;
+
+        task::spawn_blocking(move || {
+            // Unknown addresses are checked against a dummy hash, so they take as long as a wrong password
+            let password_hash = user
+                .as_ref()
+                .map_or(DUMMY_PASSWORD_HASH, |user| user.password_hash.as_str());
+            let is_valid = validate_credentials(creds.password, password_hash)
+                .map_err(std::io::Error::other)?;
+
+            Ok(user.filter(|_| is_valid))
+        })
+        .await
+        .expect("Password verification failed unexpectedly")
+    }
+
+    /// An account linked before logs in its member, otherwise the one with the same verified email,
+    /// if they didn't link another account of the provider already
+    async fn authenticate_external(
+        &self,
+        identity: ExternalIdentity,
+    ) -> Result<Option<User>, std::io::Error> {
+        let linked = query_as!(
+            User,
+            "select * from users_with_role
+             where id = (select user_id from user_identities where issuer = $1 and subject = $2)",
+            identity.issuer,
+            identity.subject
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(std::io::Error::other)?;
+
+        let user = match linked {
+            Some(user) => Some(user),
+            None if identity.email_verified => query_as!(
+                User,
+                "select * from users_with_role
+                 where lower(email) = lower($1)
+                 and id not in (select user_id from user_identities where issuer = $2)",
+                identity.email,
+                identity.issuer
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(std::io::Error::other)?,
+            None => None,
+        };
+
+        Ok(user.filter(|user| user.membership_status().can_log_in()))
+    }
 }
 
 impl AuthnBackend for UserAuthenticator {
     type User = User;
-    type Credentials = UserCredentials;
+    type Credentials = Credentials;
     type Error = std::io::Error;
 
     async fn authenticate(
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let Some(user) = self.get_user(&creds.email).await? else {
-            return Ok(None);
-        };
-
-        task::spawn_blocking(|| {
-            Ok(
-                if validate_credentials(creds.password, &user.password_hash)
-                    .map_err(std::io::Error::other)?
-                {
-                    Some(user)
-                } else {
-                    None
-                },
-            )
-        })
-        .await
-        .expect("Password verification failed unexpectedly")
+        match creds {
+            Credentials::Password(creds) => self.authenticate_password(creds).await,
+            Credentials::External(identity) => self.authenticate_external(identity).await,
+        }
     }
 
+    /// Members whose state doesn't allow logging in are treated as unknown,
+    /// which also ends any session they still have
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        query_as!(
+        // Going through the id, since with the email index sqlx can't tell the type of the permissions
+        let user = query_as!(
             User,
-            r#"select * from users_with_role where email = $1"#,
+            r#"select * from users_with_role where id = (select id from users where email = $1)"#,
             user_id
         )
         .fetch_optional(&self.pool)
         .await
-        .map_err(std::io::Error::other)
+        .map_err(std::io::Error::other)?;
+
+        Ok(user.filter(|user| user.membership_status().can_log_in()))
     }
 }
 
@@ -66,14 +117,16 @@ impl AuthzBackend for UserAuthenticator {
         &self,
         user: &Self::User,
     ) -> Result<HashSet<Self::Permission>, Self::Error> {
-        Ok(HashSet::from([
-            user.role.clone(),
-            if user.admin_panel_access {
-                "admin_panel".to_string()
-            } else {
-                "".to_string()
-            },
-        ]))
+        let mut permissions: HashSet<_> = user
+            .permissions()
+            .map(|permission| permission.as_ref().to_string())
+            .collect();
+        permissions.insert(user.role.clone());
+        if user.has_admin_panel() {
+            permissions.insert(Permission::ADMIN_PANEL.to_string());
+        }
+
+        Ok(permissions)
     }
 }
 
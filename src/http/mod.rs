@@ -1,11 +1,25 @@
 use crate::http::auth::UserAuthenticator;
+use crate::http::pages::admin::entrance_codes::{get_upcoming_slots, update_entrance_codes};
+use crate::http::pages::admin::key_holders::{
+    KEY_HOLDER_WARNING_MAX_HOURS, send_key_holder_warnings,
+};
+use crate::http::pages::admin::members::dunning::send_due_reminders;
+use crate::http::pages::admin::members::lifecycle::review_memberships;
+use crate::http::pages::deliver_pending_pushes;
+use crate::http::pages::home::announcements::publish_due_announcements;
 use crate::http::pages::notification_template::error_bubble_response;
 use crate::http::template_into_response::TemplateIntoResponse;
+use crate::mail::Mailer;
 use crate::model::location::Location;
+use crate::oidc::{MockIssuer, OidcProvider};
+use crate::online_payment::OnlinePaymentProvider;
+use crate::smart_lock::SmartLockController;
 use crate::utils::local_time;
+use crate::web_push::WebPushSender;
 use askama::Template;
 use axum::Router;
 use axum::http::StatusCode;
+use axum::middleware::{from_fn, from_fn_with_state};
 use axum::response::{IntoResponse, Response};
 use axum_login::AuthManagerLayerBuilder;
 use axum_login::tower_sessions::cookie::SameSite;
@@ -14,27 +28,63 @@ use sqlx::{SqlitePool, query, query_as};
 use std::any::Any;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::signal;
-use tokio::sync::watch;
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
 use tokio::time::interval;
+use tokio::{select, signal};
 use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::timeout::TimeoutLayer;
 use tower_http::trace;
 use tower_http::trace::TraceLayer;
 use tower_sessions_sqlx_store::SqliteStore;
-use tracing::{Level, error, info};
+use tracing::{Level, error, info, warn};
 
 mod auth;
 mod error;
 mod pages;
 mod template_into_response;
 
+/// Sessions not used for this long are logged out
+pub const SESSION_INACTIVITY_EXPIRY: time::Duration = time::Duration::days(60);
+
 #[derive(Clone)]
 pub struct AppState {
     pub read_pool: SqlitePool,
     pub write_pool: SqlitePool,
     pub location: Arc<Location>,
     pub reservation_notifier: Arc<watch::Sender<()>>,
+    /// Receives the id of each announcement once it becomes visible
+    pub announcement_notifier: Arc<broadcast::Sender<i64>>,
+    /// Receives the id of the member each time they get a notification
+    pub notification_notifier: Arc<broadcast::Sender<i64>>,
+    /// Base URL under which the app is reachable, used in links sent outside the app
+    pub public_url: Arc<str>,
+    pub payment_provider: Option<Arc<OnlinePaymentProvider>>,
+    pub mailer: Arc<Mailer>,
+    /// Whether the client address can be taken from `X-Forwarded-For`, set when behind a reverse proxy
+    pub trust_forwarded_for: bool,
+    /// Whether members can ask for a login link by email instead of entering their password
+    pub magic_link_login: bool,
+    /// Also used directly to end the sessions of a member
+    pub session_store: SqliteStore,
+    /// Lets members log in with an external account, like Google, when configured
+    pub oidc: Option<Arc<OidcProvider>>,
+    /// Receives the entrance codes when they change, when configured
+    pub smart_lock: Option<Arc<SmartLockController>>,
+    /// Pushes the reservation notifications to the phones of the members, when configured
+    pub web_push: Option<Arc<WebPushSender>>,
+}
+
+/// Only needed for the links sent outside the app, so older deployments without it keep working,
+/// with links that only open on the server itself
+fn public_url_from_env() -> String {
+    std::env::var("PUBLIC_URL").unwrap_or_else(|_| {
+        let port = std::env::var("SERVER_PORT").unwrap_or_default();
+        let public_url = format!("http://localhost:{port}");
+        warn!("PUBLIC_URL is not set, the links in emails will point to {public_url}");
+        public_url
+    })
 }
 
 impl AppState {
@@ -45,15 +95,30 @@ impl AppState {
             .await
             .expect("No locations found");
 
+        let public_url = public_url_from_env();
+        let public_url = public_url.trim_end_matches('/');
+
         Self {
             location: Arc::new(location),
+            session_store: SqliteStore::new(write_pool.clone()),
             read_pool,
             write_pool,
             reservation_notifier: Arc::new(tx),
+            announcement_notifier: Arc::new(broadcast::channel(16).0),
+            notification_notifier: Arc::new(broadcast::channel(64).0),
+            oidc: OidcProvider::from_env(public_url).map(Arc::new),
+            public_url: public_url.into(),
+            payment_provider: OnlinePaymentProvider::from_env().map(Arc::new),
+            smart_lock: SmartLockController::from_env().map(Arc::new),
+            web_push: WebPushSender::from_env().map(Arc::new),
+            mailer: Arc::new(Mailer::from_env()),
+            trust_forwarded_for: std::env::var("TRUST_FORWARDED_FOR")
+                .is_ok_and(|value| value == "true"),
+            magic_link_login: std::env::var("MAGIC_LINK_LOGIN").is_ok_and(|value| value == "true"),
         }
     }
 
-    const CSS_VERSION: &str = env!("CSS_VERSION");
+    pub(crate) const CSS_VERSION: &str = env!("CSS_VERSION");
 }
 
 pub async fn periodic_cleanup_of_waiting_reservations(state: AppState) {
@@ -90,6 +155,147 @@ pub async fn periodic_cleanup_of_waiting_reservations(state: AppState) {
     }
 }
 
+/// Checks hourly, during the day, for members that should be reminded of their unpaid months
+pub async fn periodic_dunning(state: AppState) {
+    let mut interval = interval(std::time::Duration::from_hours(1));
+
+    loop {
+        interval.tick().await;
+
+        let current_time = local_time();
+        if !(9..21).contains(&current_time.hour()) {
+            continue;
+        }
+
+        let result = send_due_reminders(
+            &state.write_pool,
+            &state.mailer,
+            &state.public_url,
+            current_time.date(),
+            OffsetDateTime::now_utc(),
+        )
+        .await;
+
+        match result {
+            Ok(0) => {}
+            Ok(reminded) => info!("Sent payment reminders for {reminded} members"),
+            Err(e) => error!("Failed to send payment reminders: {e}"),
+        }
+    }
+}
+
+/// Suspends and reactivates memberships according to the payment rules
+pub async fn periodic_membership_review(state: AppState) {
+    let mut interval = interval(std::time::Duration::from_hours(1));
+
+    loop {
+        interval.tick().await;
+
+        match review_memberships(&state.write_pool, local_time().date()).await {
+            Ok(review) => {
+                if review.suspended != 0 || review.reactivated != 0 {
+                    info!(
+                        "Suspended {} and reactivated {} memberships",
+                        review.suspended, review.reactivated
+                    );
+                }
+            }
+            Err(e) => error!("Failed to review memberships: {e}"),
+        }
+    }
+}
+
+/// Rotates the entrance code when it's due and keeps the smart lock up to date with the slot codes
+pub async fn periodic_entrance_codes(state: AppState) {
+    let mut interval = interval(std::time::Duration::from_mins(15));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = update_entrance_codes(&state, OffsetDateTime::now_utc()).await {
+            error!("Failed to update the entrance codes: {e}");
+        }
+    }
+}
+
+/// Warns about the upcoming slots that have members booked, but no one with a key
+pub async fn periodic_key_holder_warnings(state: AppState) {
+    let mut interval = interval(std::time::Duration::from_mins(15));
+
+    loop {
+        interval.tick().await;
+
+        let current_time = local_time();
+        let slots = get_upcoming_slots(
+            &state,
+            current_time.date(),
+            KEY_HOLDER_WARNING_MAX_HOURS / 24,
+        )
+        .await;
+
+        let result = send_key_holder_warnings(
+            &state.write_pool,
+            &state.mailer,
+            &state.public_url,
+            &slots,
+            current_time.offset(),
+            OffsetDateTime::now_utc(),
+        )
+        .await;
+
+        match result {
+            Ok(0) => {}
+            Ok(warned) => info!("Warned about {warned} slots without a key-holder"),
+            Err(e) => error!("Failed to warn about slots without a key-holder: {e}"),
+        }
+    }
+}
+
+/// Notifies the members online about the announcements scheduled to become visible
+pub async fn periodic_announcements(state: AppState) {
+    let mut interval = interval(std::time::Duration::from_mins(1));
+
+    loop {
+        interval.tick().await;
+
+        let result = publish_due_announcements(
+            &state.write_pool,
+            &state.announcement_notifier,
+            OffsetDateTime::now_utc(),
+        )
+        .await;
+        if let Err(e) = result {
+            error!("Failed to publish the announcements: {e}");
+        }
+    }
+}
+
+/// Pushes the notifications as soon as they are created, retrying the failed ones every minute
+pub async fn periodic_push_delivery(state: AppState) {
+    let Some(sender) = state.web_push.clone() else {
+        return;
+    };
+    let mut new_notifications = state.notification_notifier.subscribe();
+    let mut interval = interval(std::time::Duration::from_mins(1));
+
+    loop {
+        select! {
+            _ = interval.tick() => {}
+            result = new_notifications.recv() => {
+                if let Err(RecvError::Closed) = result {
+                    return;
+                }
+            }
+        }
+
+        if let Err(e) =
+            deliver_pending_pushes(&state.write_pool, &sender, OffsetDateTime::now_utc()).await
+        {
+            error!("Failed to deliver the push notifications: {e}");
+        }
+    }
+}
+
 async fn handler_404() -> impl IntoResponse {
     #[derive(Template)]
     #[template(path = "404.html")]
@@ -130,9 +336,9 @@ fn handle_panic(err: Box<dyn Any + Send + 'static>) -> Response {
     error_bubble_response(details)
 }
 
-pub async fn http_server(app_state: AppState, session_store: SqliteStore) {
-    let session_layer = SessionManagerLayer::new(session_store)
-        .with_expiry(Expiry::OnInactivity(time::Duration::days(60)))
+pub async fn http_server(app_state: AppState) {
+    let session_layer = SessionManagerLayer::new(app_state.session_store.clone())
+        .with_expiry(Expiry::OnInactivity(SESSION_INACTIVITY_EXPIRY))
         .with_same_site(SameSite::Lax);
 
     let auth_layer = AuthManagerLayerBuilder::new(
@@ -141,9 +347,24 @@ pub async fn http_server(app_state: AppState, session_store: SqliteStore) {
     )
     .build();
 
-    let app = Router::new()
-        .nest_service("/assets", tower_http::services::ServeDir::new("assets"))
+    let mut router = Router::new()
         .merge(pages::router())
+        .layer(from_fn_with_state(app_state.clone(), pages::track_session))
+        .layer(from_fn(pages::impersonation_guard))
+        .layer(from_fn_with_state(
+            app_state.clone(),
+            pages::maintenance_guard,
+        ));
+    if let Some(mock_router) = app_state
+        .oidc
+        .as_deref()
+        .and_then(OidcProvider::mock_router)
+    {
+        router = router.nest_service(MockIssuer::PATH, mock_router);
+    }
+
+    let app = router
+        .nest_service("/assets", tower_http::services::ServeDir::new("assets"))
         .with_state(app_state)
         .fallback(handler_404)
         .layer(CatchPanicLayer::custom(handle_panic))
@@ -162,7 +383,7 @@ pub async fn http_server(app_state: AppState, session_store: SqliteStore) {
     let port: u16 = port_str.parse().expect("Invalid port");
 
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let http_service = app.into_make_service();
+    let http_service = app.into_make_service_with_connect_info::<SocketAddr>();
 
     println!("Server started on port {port}");
 
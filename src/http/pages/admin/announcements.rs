@@ -0,0 +1,373 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::home::announcements::publish_due_announcements;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::user::User;
+use crate::utils::date_formats;
+use crate::utils::markdown::render_markdown;
+use askama::Template;
+use axum::Router;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, post};
+use axum_extra::extract::Form as AxumExtraForm;
+use serde::Deserialize;
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as, query_scalar};
+use time::OffsetDateTime;
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(announcements_page))
+        .route("/", post(create))
+        .route("/{id}", get(edit_page))
+        .route("/{id}", post(update))
+        .route("/{id}", delete(remove))
+}
+
+pub struct AnnouncementEntry {
+    pub id: i64,
+    pub title: String,
+    pub body: String,
+    pub pinned: bool,
+    pub starts_at: OffsetDateTime,
+    pub ends_at: Option<OffsetDateTime>,
+    pub created_by: Option<String>,
+    /// The names of the roles that see it, empty for everyone
+    pub roles: Option<String>,
+    pub reads: i64,
+}
+
+impl AnnouncementEntry {
+    pub fn body_html(&self) -> String {
+        render_markdown(&self.body)
+    }
+}
+
+pub struct RoleOption {
+    pub id: i64,
+    pub name: String,
+}
+
+pub struct NewAnnouncement {
+    pub title: String,
+    pub body: String,
+    pub pinned: bool,
+    pub starts_at: OffsetDateTime,
+    pub ends_at: Option<OffsetDateTime>,
+    /// Empty for everyone
+    pub role_ids: Vec<i64>,
+}
+
+/// The values of the announcement form, empty for a new announcement
+#[derive(Default)]
+struct AnnouncementFormValues {
+    id: Option<i64>,
+    title: String,
+    body: String,
+    pinned: bool,
+    starts_at: String,
+    ends_at: String,
+    role_ids: Vec<i64>,
+}
+
+async fn set_announcement_roles(
+    tx: &mut SqliteConnection,
+    id: i64,
+    role_ids: &[i64],
+) -> sqlx::Result<()> {
+    query!(
+        "delete from announcement_roles where announcement_id = $1",
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    for role_id in role_ids {
+        query!(
+            "insert into announcement_roles (announcement_id, role_id) values ($1, $2)",
+            id,
+            role_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn create_announcement(
+    tx: &mut SqliteConnection,
+    announcement: &NewAnnouncement,
+    created_by: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<i64> {
+    let id = query_scalar!(
+        "insert into announcements (title, body, pinned, starts_at, ends_at, created_by, created_at)
+         values ($1, $2, $3, $4, $5, $6, $7) returning id",
+        announcement.title,
+        announcement.body,
+        announcement.pinned,
+        announcement.starts_at,
+        announcement.ends_at,
+        created_by,
+        now
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    set_announcement_roles(tx, id, &announcement.role_ids).await?;
+
+    Ok(id)
+}
+
+/// Members are notified again only if it was moved to start in the future
+pub async fn update_announcement(
+    tx: &mut SqliteConnection,
+    id: i64,
+    announcement: &NewAnnouncement,
+    now: OffsetDateTime,
+) -> sqlx::Result<bool> {
+    let result = query!(
+        "update announcements set title = $2, body = $3, pinned = $4, starts_at = $5, ends_at = $6,
+         published_at = case when $5 > $7 then null else published_at end
+         where id = $1",
+        id,
+        announcement.title,
+        announcement.body,
+        announcement.pinned,
+        announcement.starts_at,
+        announcement.ends_at,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    set_announcement_roles(tx, id, &announcement.role_ids).await?;
+
+    Ok(true)
+}
+
+pub async fn delete_announcement(executor: impl SqliteExecutor<'_>, id: i64) -> sqlx::Result<bool> {
+    let result = query!("delete from announcements where id = $1", id)
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() != 0)
+}
+
+pub async fn get_announcements(
+    executor: impl SqliteExecutor<'_>,
+) -> sqlx::Result<Vec<AnnouncementEntry>> {
+    query_as!(
+        AnnouncementEntry,
+        r#"select a.id, a.title, a.body, a.pinned, a.starts_at as "starts_at: OffsetDateTime",
+            a.ends_at as "ends_at: OffsetDateTime", u.name as "created_by?",
+            (select group_concat(r.name, ', ') from announcement_roles ar
+             inner join user_roles r on r.id = ar.role_id where ar.announcement_id = a.id) as "roles?: String",
+            (select count(*) from announcement_reads ar where ar.announcement_id = a.id) as "reads!: i64"
+           from announcements a left join users u on u.id = a.created_by
+           order by a.pinned desc, a.starts_at desc, a.id desc"#
+    )
+    .fetch_all(executor)
+    .await
+}
+
+async fn get_roles(executor: impl SqliteExecutor<'_>) -> sqlx::Result<Vec<RoleOption>> {
+    query_as!(RoleOption, "select id, name from user_roles order by name")
+        .fetch_all(executor)
+        .await
+}
+
+async fn get_form_values(
+    conn: &mut SqliteConnection,
+    id: i64,
+) -> sqlx::Result<Option<AnnouncementFormValues>> {
+    let announcement = query!(
+        r#"select title, body, pinned, starts_at as "starts_at: OffsetDateTime",
+            ends_at as "ends_at: OffsetDateTime" from announcements where id = $1"#,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(announcement) = announcement else {
+        return Ok(None);
+    };
+    let role_ids = query_scalar!(
+        "select role_id from announcement_roles where announcement_id = $1",
+        id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(Some(AnnouncementFormValues {
+        id: Some(id),
+        title: announcement.title,
+        body: announcement.body,
+        pinned: announcement.pinned,
+        starts_at: date_formats::as_local_input(&announcement.starts_at),
+        ends_at: announcement
+            .ends_at
+            .map(|ends_at| date_formats::as_local_input(&ends_at))
+            .unwrap_or_default(),
+        role_ids,
+    }))
+}
+
+#[derive(Deserialize)]
+struct AnnouncementForm {
+    title: String,
+    body: String,
+    pinned: Option<String>,
+    /// Left empty to show it right away
+    starts_at: String,
+    /// Left empty to show it until deleted
+    ends_at: String,
+    #[serde(default)]
+    role_id: Vec<i64>, // From checkboxes, the roles that see it
+}
+
+fn parse_announcement_time(input: &str) -> HttpResult<Option<OffsetDateTime>> {
+    match input.trim() {
+        "" => Ok(None),
+        input => date_formats::parse_local_input(input)
+            .map(Some)
+            .or_bail("Data anunțului nu este validă"),
+    }
+}
+
+impl AnnouncementForm {
+    fn parse(self, now: OffsetDateTime) -> HttpResult<NewAnnouncement> {
+        let title = self.title.trim();
+        let body = self.body.trim();
+        if title.is_empty() || body.is_empty() {
+            return Err(HttpError::Message(
+                "Anunțul trebuie să aibă un titlu și un conținut".into(),
+            ));
+        }
+
+        let starts_at = parse_announcement_time(&self.starts_at)?.unwrap_or(now);
+        let ends_at = parse_announcement_time(&self.ends_at)?;
+        if ends_at.is_some_and(|ends_at| ends_at <= starts_at) {
+            return Err(HttpError::Message(
+                "Anunțul trebuie să dispară după ce apare".into(),
+            ));
+        }
+
+        Ok(NewAnnouncement {
+            title: title.to_string(),
+            body: body.to_string(),
+            pinned: self.pinned.is_some(),
+            starts_at,
+            ends_at,
+            role_ids: self.role_id,
+        })
+    }
+}
+
+async fn announcements_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/announcements/announcements_page.html")]
+    struct AnnouncementsTemplate {
+        user: User,
+        announcements: Vec<AnnouncementEntry>,
+        roles: Vec<RoleOption>,
+        form: AnnouncementFormValues,
+    }
+
+    AnnouncementsTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        announcements: get_announcements(&state.read_pool).await?,
+        roles: get_roles(&state.read_pool).await?,
+        form: AnnouncementFormValues::default(),
+    }
+    .try_into_response()
+}
+
+async fn edit_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(id): Path<i64>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/announcements/edit_page.html")]
+    struct EditAnnouncementTemplate {
+        user: User,
+        roles: Vec<RoleOption>,
+        form: AnnouncementFormValues,
+    }
+
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let form = {
+        let mut conn = state.read_pool.acquire().await?;
+        get_form_values(conn.as_mut(), id)
+            .await?
+            .or_bail("Anunțul nu există")?
+    };
+
+    EditAnnouncementTemplate {
+        user,
+        roles: get_roles(&state.read_pool).await?,
+        form,
+    }
+    .try_into_response()
+}
+
+async fn create(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    AxumExtraForm(form): AxumExtraForm<AnnouncementForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let now = OffsetDateTime::now_utc();
+    let announcement = form.parse(now)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    let id = create_announcement(tx.as_mut(), &announcement, user.id, now).await?;
+    tx.commit().await?;
+    info!("User {} created the announcement {id}", user.id);
+
+    publish_due_announcements(&state.write_pool, &state.announcement_notifier, now).await?;
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+async fn update(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(id): Path<i64>,
+    AxumExtraForm(form): AxumExtraForm<AnnouncementForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let now = OffsetDateTime::now_utc();
+    let announcement = form.parse(now)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    if !update_announcement(tx.as_mut(), id, &announcement, now).await? {
+        return Err(HttpError::Message("Anunțul nu există".into()));
+    }
+    tx.commit().await?;
+    info!("User {} updated the announcement {id}", user.id);
+
+    publish_due_announcements(&state.write_pool, &state.announcement_notifier, now).await?;
+    Ok([("HX-Redirect", "/admin/announcements")].into_response())
+}
+
+async fn remove(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    if delete_announcement(&state.write_pool, id).await? {
+        info!("User {} deleted the announcement {id}", user.id);
+    }
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
@@ -0,0 +1,107 @@
+use super::*;
+use sqlx::SqlitePool;
+use time::macros::datetime;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into user_roles VALUES (200, 'Other Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn form(title: &str, starts_at: &str, ends_at: &str) -> AnnouncementForm {
+    AnnouncementForm {
+        title: title.to_string(),
+        body: "Body".to_string(),
+        pinned: None,
+        starts_at: starts_at.to_string(),
+        ends_at: ends_at.to_string(),
+        role_id: vec![100],
+    }
+}
+
+#[test]
+fn should_validate_the_announcement_form() {
+    let now = datetime!(2025-06-10 12:00 UTC);
+
+    let announcement = form(" Title ", "", "").parse(now).ok().unwrap();
+    assert_eq!(announcement.title, "Title");
+    assert_eq!(announcement.starts_at, now);
+    assert_eq!(announcement.ends_at, None);
+    assert_eq!(announcement.role_ids, vec![100]);
+
+    assert!(form(" ", "", "").parse(now).is_err());
+    assert!(form("Title", "not a date", "").parse(now).is_err());
+    assert!(
+        form("Title", "2025-06-12T10:00", "2025-06-11T10:00")
+            .parse(now)
+            .is_err()
+    );
+}
+
+#[sqlx::test]
+async fn should_manage_announcements(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-10 12:00 UTC);
+    let announcement = NewAnnouncement {
+        title: "Title".to_string(),
+        body: "Body".to_string(),
+        pinned: false,
+        starts_at: now,
+        ends_at: None,
+        role_ids: vec![100, 200],
+    };
+
+    let mut tx = pool.begin().await?;
+    let id = create_announcement(tx.as_mut(), &announcement, 1000, now).await?;
+    tx.commit().await?;
+    query!("update announcements set published_at = $1", now)
+        .execute(&pool)
+        .await?;
+    query!(
+        "insert into announcement_reads (announcement_id, user_id, read_at) values ($1, 1000, $2)",
+        id,
+        now
+    )
+    .execute(&pool)
+    .await?;
+
+    let entries = get_announcements(&pool).await?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].created_by.as_deref(), Some("Test 1"));
+    assert_eq!(entries[0].reads, 1);
+    let mut roles: Vec<_> = entries[0].roles.as_deref().unwrap().split(", ").collect();
+    roles.sort_unstable();
+    assert_eq!(roles, vec!["Other Role", "Test Role"]);
+
+    // Moving it to the future notifies the members again when it starts
+    let mut tx = pool.begin().await?;
+    let updated = NewAnnouncement {
+        starts_at: datetime!(2025-06-20 12:00 UTC),
+        role_ids: Vec::new(),
+        ..announcement
+    };
+    assert!(update_announcement(tx.as_mut(), id, &updated, now).await?);
+    assert!(!update_announcement(tx.as_mut(), id + 1, &updated, now).await?);
+    tx.commit().await?;
+
+    let published_at = query_scalar!("select published_at from announcements where id = $1", id)
+        .fetch_one(&pool)
+        .await?;
+    assert!(published_at.is_none());
+    assert!(get_announcements(&pool).await?[0].roles.is_none());
+
+    assert!(delete_announcement(&pool, id).await?);
+    assert!(!delete_announcement(&pool, id).await?);
+    assert!(get_announcements(&pool).await?.is_empty());
+
+    Ok(())
+}
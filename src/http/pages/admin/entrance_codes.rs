@@ -0,0 +1,317 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::AuthSession;
+use crate::smart_lock::{LockCode, LockCodeKind, SmartLock};
+use crate::utils::queries::get_day_structure;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as, query_scalar};
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+use tracing::{error, info};
+
+#[cfg(test)]
+mod test;
+
+pub const ENTRANCE_CODE_DIGITS: usize = 6;
+/// Slot codes open the door a bit before the slot begins
+const SLOT_CODE_EARLY_ACCESS: Duration = Duration::minutes(15);
+/// Slot codes are created for today and this many following days
+const SLOT_CODE_DAYS_AHEAD: i64 = 1;
+/// Slot codes older than this are deleted
+const SLOT_CODE_HISTORY_DAYS: i64 = 90;
+
+pub struct EntranceCodeEntry {
+    pub code: String,
+    pub valid_from: OffsetDateTime,
+    pub valid_until: Option<OffsetDateTime>,
+    /// The admin who set it, `None` when it was rotated automatically
+    pub created_by: Option<String>,
+}
+
+/// A reservation slot that gets its own code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodeSlot {
+    pub date: Date,
+    pub hour: u8,
+    pub minute: u8,
+    /// In hours
+    pub duration: i64,
+}
+
+impl CodeSlot {
+    pub fn start(&self, offset: UtcOffset) -> OffsetDateTime {
+        let time = Time::from_hms(self.hour, self.minute, 0).unwrap_or(Time::MIDNIGHT);
+        PrimitiveDateTime::new(self.date, time).assume_offset(offset)
+    }
+
+    /// When the code of the slot opens the door, in the given offset
+    pub fn window(&self, offset: UtcOffset) -> (OffsetDateTime, OffsetDateTime) {
+        let start = self.start(offset);
+        (
+            start - SLOT_CODE_EARLY_ACCESS,
+            start + Duration::hours(self.duration),
+        )
+    }
+}
+
+pub struct MemberSlotCode {
+    pub date: Date,
+    pub hour: i64,
+    pub code: String,
+}
+
+pub fn generate_code() -> String {
+    let mut bytes = [0u8; 8];
+    getrandom::fill(&mut bytes).expect("The system random number generator is unavailable");
+    let value = u64::from_le_bytes(bytes) % 10u64.pow(ENTRANCE_CODE_DIGITS as u32);
+    format!("{value:0width$}", width = ENTRANCE_CODE_DIGITS)
+}
+
+/// Replaces the entrance code, keeping the previous one in the history
+pub async fn set_entrance_code(
+    tx: &mut SqliteConnection,
+    code: &str,
+    created_by: Option<i64>,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    query!(
+        "update entrance_codes set valid_until = $1 where valid_until is null",
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(
+        "insert into entrance_codes (code, valid_from, created_by) values ($1, $2, $3)",
+        code,
+        now,
+        created_by
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!("update global_vars set entrance_code = $1", code)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+/// Replaces the entrance code once it's older than the rotation period, returning the new one
+pub async fn rotate_entrance_code_if_due(
+    tx: &mut SqliteConnection,
+    rotation_days: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<String>> {
+    let valid_from = query_scalar!(
+        r#"select valid_from as "valid_from: OffsetDateTime" from entrance_codes
+           where valid_until is null order by id desc limit 1"#
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if valid_from.is_some_and(|valid_from| now < valid_from + Duration::days(rotation_days)) {
+        return Ok(None);
+    }
+
+    let code = generate_code();
+    set_entrance_code(tx, &code, None, now).await?;
+    Ok(Some(code))
+}
+
+pub async fn get_entrance_code_history(
+    executor: impl SqliteExecutor<'_>,
+    limit: i64,
+) -> sqlx::Result<Vec<EntranceCodeEntry>> {
+    query_as!(
+        EntranceCodeEntry,
+        r#"select e.code, e.valid_from as "valid_from: OffsetDateTime",
+            e.valid_until as "valid_until: OffsetDateTime", u.name as "created_by?"
+           from entrance_codes e left join users u on u.id = e.created_by
+           order by e.id desc limit $1"#,
+        limit
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Creates the codes of the slots that don't have one yet and forgets the old ones
+pub async fn create_slot_codes(
+    tx: &mut SqliteConnection,
+    slots: &[CodeSlot],
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    for slot in slots {
+        let code = generate_code();
+        query!(
+            "insert into slot_entrance_codes (date, hour, code, created_at) values ($1, $2, $3, $4)
+             on conflict do nothing",
+            slot.date,
+            slot.hour,
+            code,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let oldest_kept = now.date() - Duration::days(SLOT_CODE_HISTORY_DAYS);
+    query!(
+        "delete from slot_entrance_codes where date < $1",
+        oldest_kept
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// The codes of the slots the member has an active reservation in, from `from_date` on
+pub async fn get_member_slot_codes(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    from_date: Date,
+) -> sqlx::Result<Vec<MemberSlotCode>> {
+    query_as!(
+        MemberSlotCode,
+        r#"select distinct s.date as "date: Date", s.hour, s.code from slot_entrance_codes s
+           inner join reservations r on r.date = s.date and r.hour = s.hour
+           where r.user_id = $1 and r.cancelled = FALSE and r.in_waiting = FALSE and s.date >= $2
+           order by s.date, s.hour"#,
+        user_id,
+        from_date
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Every code the lock should accept: the entrance code and,
+/// if enabled, the codes of the slots that didn't end yet
+pub async fn get_lock_codes(
+    conn: &mut SqliteConnection,
+    slots: &[CodeSlot],
+    offset: UtcOffset,
+    now: OffsetDateTime,
+) -> sqlx::Result<Vec<LockCode>> {
+    let mut codes = Vec::new();
+
+    let entrance_code = query!(
+        r#"select code, valid_from as "valid_from: OffsetDateTime" from entrance_codes
+           where valid_until is null order by id desc limit 1"#
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if let Some(entrance_code) = entrance_code {
+        codes.push(LockCode::new(
+            entrance_code.code,
+            LockCodeKind::Entrance,
+            entrance_code.valid_from,
+            None,
+        ));
+    }
+
+    let slot_codes_enabled = query_scalar!("select slot_entrance_codes from global_vars")
+        .fetch_one(&mut *conn)
+        .await?;
+    if !slot_codes_enabled {
+        return Ok(codes);
+    }
+
+    for slot in slots {
+        let (valid_from, valid_until) = slot.window(offset);
+        if valid_until <= now {
+            continue;
+        }
+
+        let code = query_scalar!(
+            "select code from slot_entrance_codes where date = $1 and hour = $2",
+            slot.date,
+            slot.hour
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(code) = code {
+            codes.push(LockCode::new(
+                code,
+                LockCodeKind::Slot,
+                valid_from,
+                Some(valid_until),
+            ));
+        }
+    }
+
+    Ok(codes)
+}
+
+/// The slots of today and the next days, following the schedule of each day
+pub async fn get_upcoming_slots(state: &AppState, today: Date, days_ahead: i64) -> Vec<CodeSlot> {
+    let mut slots = Vec::new();
+    for days in 0..=days_ahead {
+        let date = today + Duration::days(days);
+        let structure = get_day_structure(state, date).await;
+        let minute = structure.slots_start_minute.unwrap_or(0) as u8;
+
+        slots.extend(structure.iter().map(|hour| CodeSlot {
+            date,
+            hour,
+            minute,
+            duration: structure.slot_duration,
+        }));
+    }
+    slots
+}
+
+/// Sends the current codes to the smart lock, if there is one
+pub async fn push_lock_codes(state: &AppState, now: OffsetDateTime) -> sqlx::Result<()> {
+    let Some(smart_lock) = &state.smart_lock else {
+        return Ok(());
+    };
+
+    let offset = UtcOffset::current_local_offset().expect("Failed to determine local offset");
+    let slots = get_upcoming_slots(state, now.to_offset(offset).date(), SLOT_CODE_DAYS_AHEAD).await;
+    let codes = {
+        let mut conn = state.read_pool.acquire().await?;
+        get_lock_codes(conn.as_mut(), &slots, offset, now).await?
+    };
+
+    if let Err(e) = smart_lock.push_codes(&codes).await {
+        error!("Failed to push the entrance codes to the smart lock: {e}");
+    }
+    Ok(())
+}
+
+/// Rotates the entrance code when it's due, prepares the slot codes and pushes them to the lock
+pub async fn update_entrance_codes(state: &AppState, now: OffsetDateTime) -> sqlx::Result<()> {
+    let settings =
+        query!("select entrance_code_rotation_days, slot_entrance_codes from global_vars")
+            .fetch_one(&state.read_pool)
+            .await?;
+
+    let mut tx = state.write_pool.begin().await?;
+    if let Some(rotation_days) = settings.entrance_code_rotation_days
+        && rotate_entrance_code_if_due(tx.as_mut(), rotation_days, now)
+            .await?
+            .is_some()
+    {
+        info!("Rotated the entrance code");
+    }
+    if settings.slot_entrance_codes {
+        let offset = UtcOffset::current_local_offset().expect("Failed to determine local offset");
+        let slots =
+            get_upcoming_slots(state, now.to_offset(offset).date(), SLOT_CODE_DAYS_AHEAD).await;
+        create_slot_codes(tx.as_mut(), &slots, now).await?;
+    }
+    tx.commit().await?;
+
+    push_lock_codes(state, now).await
+}
+
+pub async fn rotate_now(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let now = OffsetDateTime::now_utc();
+
+    let mut tx = state.write_pool.begin().await?;
+    set_entrance_code(tx.as_mut(), &generate_code(), Some(user.id), now).await?;
+    tx.commit().await?;
+    info!("User {} generated a new entrance code", user.id);
+
+    push_lock_codes(&state, now).await?;
+    Ok([("HX-Refresh", "true")].into_response())
+}
@@ -0,0 +1,178 @@
+use super::*;
+use sqlx::SqlitePool;
+use time::macros::{date, datetime, offset};
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        insert into locations (id, name, slot_capacity, slots_start_hour, slot_duration, slots_per_day)
+        VALUES (100, 'test_location', 1, 18, 2, 2);
+        insert into reservations (user_id, date, hour, location, created_for, cancelled, in_waiting)
+        VALUES (1000, '2025-06-01', 18, 100, null, FALSE, FALSE),
+               (1000, '2025-06-01', 20, 100, null, TRUE, FALSE),
+               (1000, '2025-06-02', 18, 100, null, FALSE, TRUE);
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn slots() -> Vec<CodeSlot> {
+    [
+        (date!(2025 - 06 - 01), 18),
+        (date!(2025 - 06 - 01), 20),
+        (date!(2025 - 06 - 02), 18),
+    ]
+    .into_iter()
+    .map(|(date, hour)| CodeSlot {
+        date,
+        hour,
+        minute: 0,
+        duration: 2,
+    })
+    .collect()
+}
+
+#[test]
+fn should_generate_numeric_codes() {
+    for _ in 0..100 {
+        let code = generate_code();
+        assert_eq!(code.len(), ENTRANCE_CODE_DIGITS);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+}
+
+#[sqlx::test]
+async fn should_keep_entrance_code_history(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let mut tx = pool.begin().await?;
+    set_entrance_code(
+        tx.as_mut(),
+        "1111",
+        Some(1000),
+        datetime!(2025-06-01 12:00 UTC),
+    )
+    .await?;
+    set_entrance_code(tx.as_mut(), "2222", None, datetime!(2025-06-08 12:00 UTC)).await?;
+    tx.commit().await?;
+
+    let entrance_code = query_scalar!("select entrance_code from global_vars")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(entrance_code, "2222");
+
+    let history = get_entrance_code_history(&pool, 2).await?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].code, "2222");
+    assert_eq!(history[0].valid_until, None);
+    assert_eq!(history[0].created_by, None);
+    assert_eq!(history[1].code, "1111");
+    assert_eq!(
+        history[1].valid_until,
+        Some(datetime!(2025-06-08 12:00 UTC))
+    );
+    assert_eq!(history[1].created_by.as_deref(), Some("Test User"));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_rotate_entrance_code_when_due(pool: SqlitePool) -> sqlx::Result<()> {
+    let set_at = datetime!(2025-06-01 12:00 UTC);
+    let mut tx = pool.begin().await?;
+    set_entrance_code(tx.as_mut(), "1111", None, set_at).await?;
+
+    let early = set_at + Duration::days(7) - Duration::minutes(1);
+    assert_eq!(
+        rotate_entrance_code_if_due(tx.as_mut(), 7, early).await?,
+        None
+    );
+
+    let due = set_at + Duration::days(7);
+    let code = rotate_entrance_code_if_due(tx.as_mut(), 7, due)
+        .await?
+        .unwrap();
+    assert_ne!(code, "1111");
+    // The new code starts a new period
+    assert_eq!(
+        rotate_entrance_code_if_due(tx.as_mut(), 7, due).await?,
+        None
+    );
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_only_show_codes_of_active_reservations(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-01 08:00 UTC);
+    let mut tx = pool.begin().await?;
+    create_slot_codes(tx.as_mut(), &slots(), now).await?;
+    let codes = query_scalar!("select code from slot_entrance_codes order by date, hour")
+        .fetch_all(&mut *tx)
+        .await?;
+    // Creating them again keeps the codes members already saw
+    create_slot_codes(tx.as_mut(), &slots(), now).await?;
+    tx.commit().await?;
+
+    assert_eq!(codes.len(), 3);
+    let member_codes = get_member_slot_codes(&pool, 1000, date!(2025 - 06 - 01)).await?;
+    assert_eq!(member_codes.len(), 1);
+    assert_eq!(member_codes[0].date, date!(2025 - 06 - 01));
+    assert_eq!(member_codes[0].hour, 18);
+    assert_eq!(member_codes[0].code, codes[0]);
+
+    assert!(
+        get_member_slot_codes(&pool, 1000, date!(2025 - 06 - 02))
+            .await?
+            .is_empty()
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_give_lock_the_current_codes(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-01 18:30 +3);
+    let mut tx = pool.begin().await?;
+    set_entrance_code(tx.as_mut(), "1111", None, datetime!(2025-06-01 12:00 UTC)).await?;
+    create_slot_codes(tx.as_mut(), &slots(), now).await?;
+
+    let codes = get_lock_codes(tx.as_mut(), &slots(), offset!(+3), now).await?;
+    assert_eq!(codes.len(), 1);
+    assert_eq!(codes[0].code, "1111");
+    assert_eq!(codes[0].kind, LockCodeKind::Entrance);
+
+    query!("update global_vars set slot_entrance_codes = TRUE")
+        .execute(&mut *tx)
+        .await?;
+    let codes = get_lock_codes(tx.as_mut(), &slots(), offset!(+3), now).await?;
+    assert_eq!(codes.len(), 4);
+    assert!(
+        codes[1..]
+            .iter()
+            .all(|code| code.kind == LockCodeKind::Slot)
+    );
+    assert_eq!(
+        codes[1].valid_from,
+        datetime!(2025-06-01 17:45 +3).unix_timestamp()
+    );
+    assert_eq!(
+        codes[1].valid_until,
+        Some(datetime!(2025-06-01 20:00 +3).unix_timestamp())
+    );
+
+    // Slots that ended are left out
+    let later = datetime!(2025-06-01 20:00 +3);
+    let codes = get_lock_codes(tx.as_mut(), &slots(), offset!(+3), later).await?;
+    assert_eq!(codes.len(), 3);
+
+    Ok(())
+}
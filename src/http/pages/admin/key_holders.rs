@@ -0,0 +1,390 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::entrance_codes::CodeSlot;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::mail::{Email, Mailer};
+use crate::model::role::Permission;
+use crate::model::user::User;
+use crate::utils::{date_formats, local_date};
+use askama::Template;
+use axum::extract::State;
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+use sqlx::{SqliteExecutor, SqlitePool, query, query_as, query_scalar};
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+use tracing::{error, info};
+
+#[cfg(test)]
+mod test;
+
+/// How many days ahead the roster can be planned
+const ROSTER_DAYS: i64 = 14;
+/// The earliest a slot without a key-holder can be warned about
+pub const KEY_HOLDER_WARNING_MAX_HOURS: i64 = 48;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(roster_page))
+        .route("/", post(assign_key_holder))
+}
+
+pub struct KeyHolder {
+    pub id: i64,
+    pub name: String,
+}
+
+pub struct OnCallKeyHolder {
+    pub name: String,
+    pub email: String,
+}
+
+pub struct RosterDay {
+    pub date: Date,
+    pub user_id: Option<i64>,
+    pub assigned_by: Option<String>,
+    /// Hours of the day with members booked but none of them with a key
+    pub uncovered_hours: Vec<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UncoveredSlot {
+    pub date: Date,
+    pub hour: i64,
+}
+
+struct SlotBooker {
+    name: String,
+    email: String,
+}
+
+/// Active members that have a key of the club
+pub async fn get_key_holders(executor: impl SqliteExecutor<'_>) -> sqlx::Result<Vec<KeyHolder>> {
+    query_as!(
+        KeyHolder,
+        "select id, name from users where has_key = true and is_active = true order by name"
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn get_on_call_key_holder(
+    executor: impl SqliteExecutor<'_>,
+    date: Date,
+) -> sqlx::Result<Option<OnCallKeyHolder>> {
+    query_as!(
+        OnCallKeyHolder,
+        "select u.name, u.email from key_holder_roster k
+         inner join users u on u.id = k.user_id where k.date = $1",
+        date
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Assigns the on-call key-holder of the day, `None` leaves the day without one
+pub async fn set_on_call_key_holder(
+    executor: impl SqliteExecutor<'_>,
+    date: Date,
+    user_id: Option<i64>,
+    assigned_by: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    match user_id {
+        Some(user_id) => query!(
+            "insert into key_holder_roster (date, user_id, assigned_by, assigned_at) values ($1, $2, $3, $4)
+             on conflict (date) do update set user_id = excluded.user_id, assigned_by = excluded.assigned_by,
+             assigned_at = excluded.assigned_at",
+            date,
+            user_id,
+            assigned_by,
+            now
+        )
+        .execute(executor)
+        .await?,
+        None => query!("delete from key_holder_roster where date = $1", date)
+            .execute(executor)
+            .await?,
+    };
+
+    Ok(())
+}
+
+/// The slots between the two dates with active reservations, but none made by a member with a key
+pub async fn get_uncovered_slots(
+    executor: impl SqliteExecutor<'_>,
+    from: Date,
+    to: Date,
+) -> sqlx::Result<Vec<UncoveredSlot>> {
+    query_as!(
+        UncoveredSlot,
+        r#"select r.date as "date!: Date", r.hour as "hour!" from reservations r
+           inner join users u on u.id = r.user_id
+           where r.date >= $1 and r.date <= $2 and r.cancelled = FALSE and r.in_waiting = FALSE
+           group by r.date, r.hour
+           having max(u.has_key = TRUE and r.created_for is null) = FALSE
+           order by r.date, r.hour"#,
+        from,
+        to
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn get_roster(pool: &SqlitePool, from: Date, days: i64) -> sqlx::Result<Vec<RosterDay>> {
+    let to = from + Duration::days(days - 1);
+    let assigned = query!(
+        r#"select k.date as "date: Date", k.user_id, a.name as "assigned_by?" from key_holder_roster k
+           left join users a on a.id = k.assigned_by
+           where k.date >= $1 and k.date <= $2"#,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+    let uncovered = get_uncovered_slots(pool, from, to).await?;
+
+    Ok((0..days)
+        .map(|day| {
+            let date = from + Duration::days(day);
+            let assigned = assigned.iter().find(|record| record.date == date);
+            RosterDay {
+                date,
+                user_id: assigned.map(|record| record.user_id),
+                assigned_by: assigned.and_then(|record| record.assigned_by.clone()),
+                uncovered_hours: uncovered
+                    .iter()
+                    .filter(|slot| slot.date == date)
+                    .map(|slot| slot.hour)
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+async fn get_slot_bookers(
+    executor: impl SqliteExecutor<'_>,
+    date: Date,
+    hour: i64,
+) -> sqlx::Result<Vec<SlotBooker>> {
+    query_as!(
+        SlotBooker,
+        r#"select coalesce(r.created_for, u.name) as "name!: String", u.email from reservations r
+           inner join users u on u.id = r.user_id
+           where r.date = $1 and r.hour = $2 and r.cancelled = FALSE and r.in_waiting = FALSE
+           order by r.as_guest, r.created_at"#,
+        date,
+        hour
+    )
+    .fetch_all(executor)
+    .await
+}
+
+#[derive(Template)]
+#[template(path = "mail/key_holder_warning.txt")]
+struct KeyHolderWarningTemplate<'a> {
+    date: String,
+    hour: i64,
+    members: &'a [String],
+    on_call: Option<&'a str>,
+    home_url: String,
+}
+
+/// Warns the members booked in the upcoming slots without a key-holder, the admins managing
+/// the schedule and the on-call key-holder of the day, returns how many slots were warned about.
+/// Each slot is warned about once, unless none of the emails could be delivered.
+pub async fn send_key_holder_warnings(
+    pool: &SqlitePool,
+    mailer: &Mailer,
+    public_url: &str,
+    slots: &[CodeSlot],
+    offset: UtcOffset,
+    now: OffsetDateTime,
+) -> sqlx::Result<usize> {
+    let warning_hours = query_scalar!("select key_holder_warning_hours from global_vars")
+        .fetch_one(pool)
+        .await?;
+    let (Some(warning_hours), Some(first), Some(last)) =
+        (warning_hours, slots.first(), slots.last())
+    else {
+        return Ok(0);
+    };
+
+    let uncovered = get_uncovered_slots(pool, first.date, last.date).await?;
+    let admins: Vec<_> = query_as!(User, "select * from users_with_role where is_active = true")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .filter(|user| user.has_permission(Permission::ManageSchedule))
+        .collect();
+    let mut warned = 0;
+
+    for slot in slots {
+        let starts_at = slot.start(offset);
+        if starts_at <= now || starts_at > now + Duration::hours(warning_hours) {
+            continue;
+        }
+        let hour = slot.hour as i64;
+        if !uncovered.contains(&UncoveredSlot {
+            date: slot.date,
+            hour,
+        }) {
+            continue;
+        }
+
+        let already_warned = query_scalar!(
+            "select exists (select 1 from key_holder_warnings where date = $1 and hour = $2) as 'exists!'",
+            slot.date,
+            hour
+        )
+        .fetch_one(pool)
+        .await?
+            != 0;
+        if already_warned {
+            continue;
+        }
+
+        let bookers = get_slot_bookers(pool, slot.date, hour).await?;
+        let on_call = get_on_call_key_holder(pool, slot.date).await?;
+        let members: Vec<_> = bookers.iter().map(|booker| booker.name.clone()).collect();
+        let body = KeyHolderWarningTemplate {
+            date: date_formats::as_readable(&slot.date),
+            hour,
+            members: &members,
+            on_call: on_call.as_ref().map(|on_call| on_call.name.as_str()),
+            home_url: format!("{public_url}/"),
+        }
+        .render();
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to compose the key-holder warning: {e}");
+                continue;
+            }
+        };
+
+        let mut recipients: Vec<&str> = bookers
+            .iter()
+            .map(|booker| booker.email.as_str())
+            .chain(admins.iter().map(|admin| admin.email.as_str()))
+            .chain(on_call.as_ref().map(|on_call| on_call.email.as_str()))
+            .collect();
+        recipients.sort_unstable();
+        recipients.dedup();
+
+        let mut delivered = false;
+        for to in recipients {
+            let email = Email {
+                to: to.to_string(),
+                subject: format!(
+                    "Nicio persoană cu cheie la ora {hour} pe {}",
+                    date_formats::as_readable(&slot.date)
+                ),
+                body: body.clone(),
+            };
+            match mailer.send(&email).await {
+                Ok(()) => delivered = true,
+                Err(e) => error!("Failed to send the key-holder warning to {to}: {e}"),
+            }
+        }
+        if !delivered {
+            continue;
+        }
+
+        query!(
+            "insert into key_holder_warnings (date, hour, sent_at) values ($1, $2, $3)",
+            slot.date,
+            hour,
+            now
+        )
+        .execute(pool)
+        .await?;
+
+        info!(
+            "Warned about the slot at {hour} on {} having no key-holder",
+            slot.date
+        );
+        warned += 1;
+    }
+
+    Ok(warned)
+}
+
+#[derive(Template)]
+#[template(path = "admin/key_holders/roster_row.html")]
+struct RosterRowTemplate {
+    day: RosterDay,
+    key_holders: Vec<KeyHolder>,
+}
+
+async fn roster_page(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/key_holders/roster_page.html")]
+    struct RosterTemplate {
+        user: User,
+        days: Vec<RosterDay>,
+        key_holders: Vec<KeyHolder>,
+    }
+
+    RosterTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        days: get_roster(&state.read_pool, local_date(), ROSTER_DAYS).await?,
+        key_holders: get_key_holders(&state.read_pool).await?,
+    }
+    .try_into_response()
+}
+
+#[derive(Deserialize)]
+struct AssignedKeyHolder {
+    date: String,
+    /// Empty to leave the day without an on-call key-holder
+    user_id: String,
+}
+
+async fn assign_key_holder(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Form(form): Form<AssignedKeyHolder>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let date = Date::parse(&form.date, date_formats::ISO_DATE).or_bail("Data este invalidă")?;
+    let today = local_date();
+    if date < today || date >= today + Duration::days(ROSTER_DAYS) {
+        return Err(HttpError::Message(
+            "Se pot programa doar următoarele două săptămâni".into(),
+        ));
+    }
+
+    let key_holders = get_key_holders(&state.read_pool).await?;
+    let user_id = match form.user_id.trim() {
+        "" => None,
+        user_id => Some(
+            user_id
+                .parse::<i64>()
+                .ok()
+                .filter(|user_id| key_holders.iter().any(|holder| holder.id == *user_id))
+                .or_bail("Membrul ales nu are cheie")?,
+        ),
+    };
+
+    set_on_call_key_holder(
+        &state.write_pool,
+        date,
+        user_id,
+        user.id,
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+    info!(
+        "User {} set the on-call key-holder of {date} to {user_id:?}",
+        user.id
+    );
+
+    let day = get_roster(&state.read_pool, date, 1)
+        .await?
+        .pop()
+        .or_bail("Ziua nu a fost găsită")?;
+
+    RosterRowTemplate { day, key_holders }.try_into_response()
+}
@@ -0,0 +1,155 @@
+use super::*;
+use std::path::{Path, PathBuf};
+use time::macros::{date, datetime};
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (2000, 'test2@test.com', 'Key Holder', '', 100, TRUE, '2000-01-01', '2024-01-01');
+        insert into locations (id, name, slot_capacity, slots_start_hour, slot_duration, slots_per_day)
+        VALUES (100, 'test_location', 1, 18, 2, 2);
+
+        -- The guest of a key-holder doesn't have a key
+        insert into reservations (user_id, date, hour, location, created_for, cancelled, in_waiting)
+        VALUES (1000, '2025-06-01', 18, 100, null, FALSE, FALSE),
+               (1000, '2025-06-01', 20, 100, null, FALSE, FALSE),
+               (2000, '2025-06-01', 20, 100, null, FALSE, FALSE),
+               (1000, '2025-06-02', 18, 100, null, TRUE, FALSE),
+               (2000, '2025-06-02', 18, 100, 'Guest', FALSE, FALSE),
+               (1000, '2025-06-02', 20, 100, null, FALSE, TRUE);
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn slots() -> Vec<CodeSlot> {
+    [
+        (date!(2025 - 06 - 01), 18),
+        (date!(2025 - 06 - 01), 20),
+        (date!(2025 - 06 - 02), 18),
+        (date!(2025 - 06 - 02), 20),
+    ]
+    .into_iter()
+    .map(|(date, hour)| CodeSlot {
+        date,
+        hour,
+        minute: 0,
+        duration: 2,
+    })
+    .collect()
+}
+
+fn mail_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "acspa_key_holders_{name}_{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    ))
+}
+
+fn sent_emails(directory: &Path) -> usize {
+    std::fs::read_dir(directory).map_or(0, |entries| entries.count())
+}
+
+#[sqlx::test]
+async fn should_find_slots_without_a_key_holder(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let uncovered =
+        get_uncovered_slots(&pool, date!(2025 - 06 - 01), date!(2025 - 06 - 02)).await?;
+    assert_eq!(
+        uncovered,
+        vec![
+            UncoveredSlot {
+                date: date!(2025 - 06 - 01),
+                hour: 18
+            },
+            UncoveredSlot {
+                date: date!(2025 - 06 - 02),
+                hour: 18
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_keep_one_on_call_key_holder_per_day(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-05-30 12:00 UTC);
+
+    set_on_call_key_holder(&pool, date!(2025 - 06 - 01), Some(2000), 1000, now).await?;
+    set_on_call_key_holder(&pool, date!(2025 - 06 - 01), Some(0), 1000, now).await?;
+    let on_call = get_on_call_key_holder(&pool, date!(2025 - 06 - 01))
+        .await?
+        .unwrap();
+    assert_eq!(on_call.name, "Test Administrator");
+
+    let roster = get_roster(&pool, date!(2025 - 06 - 01), 3).await?;
+    assert_eq!(roster.len(), 3);
+    assert_eq!(roster[0].user_id, Some(0));
+    assert_eq!(roster[0].assigned_by.as_deref(), Some("Test User"));
+    assert_eq!(roster[0].uncovered_hours, vec![18]);
+    assert_eq!(roster[1].user_id, None);
+    assert_eq!(roster[1].uncovered_hours, vec![18]);
+    assert!(roster[2].uncovered_hours.is_empty());
+
+    set_on_call_key_holder(&pool, date!(2025 - 06 - 01), None, 1000, now).await?;
+    assert!(
+        get_on_call_key_holder(&pool, date!(2025 - 06 - 01))
+            .await?
+            .is_none()
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_warn_once_before_uncovered_slots(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let directory = mail_directory("warn");
+    let mailer = Mailer::file(&directory, "ACSPA <noreply@acspa.ro>".parse().unwrap());
+    let slots = slots();
+    let now = datetime!(2025-06-01 16:00 UTC);
+
+    // Disabled by default
+    assert_eq!(
+        send_key_holder_warnings(&pool, &mailer, "", &slots, UtcOffset::UTC, now).await?,
+        0
+    );
+
+    query!("update global_vars set key_holder_warning_hours = 3")
+        .execute(&pool)
+        .await?;
+    set_on_call_key_holder(&pool, date!(2025 - 06 - 01), Some(2000), 0, now).await?;
+
+    // Only the slot at 18 starts soon enough, the one at 20 has a key-holder
+    assert_eq!(
+        send_key_holder_warnings(&pool, &mailer, "", &slots, UtcOffset::UTC, now).await?,
+        1
+    );
+    // The member, the administrator and the on-call key-holder
+    assert_eq!(sent_emails(&directory), 3);
+
+    assert_eq!(
+        send_key_holder_warnings(&pool, &mailer, "", &slots, UtcOffset::UTC, now).await?,
+        0
+    );
+
+    // Only the guest is booked the next day, so the member that brought them is warned
+    let now = datetime!(2025-06-02 16:00 UTC);
+    assert_eq!(
+        send_key_holder_warnings(&pool, &mailer, "", &slots, UtcOffset::UTC, now).await?,
+        1
+    );
+    assert_eq!(sent_emails(&directory), 5);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    Ok(())
+}
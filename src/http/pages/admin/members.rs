@@ -1,24 +1,49 @@
-mod breaks;
+mod bank_import;
+pub mod break_requests;
+pub mod breaks;
 pub mod debtors;
-mod payments;
-mod payments_summary;
+pub mod dunning;
+pub mod gdpr;
+pub mod households;
+pub mod impersonation;
+pub mod lifecycle;
+pub mod payments;
+pub mod payments_summary;
+#[cfg(test)]
+mod test;
 
 use crate::http::AppState;
-use crate::http::auth::generate_hash_from_password;
+use crate::http::auth::{UserAuthenticator, generate_hash_from_password};
 use crate::http::error::{HttpError, HttpResult, OrBail};
 use crate::http::pages::AuthSession;
-use crate::http::pages::admin::members::breaks::{
-    add_break, delete_break, get_user_payment_breaks,
+use crate::http::pages::admin::members::breaks::{add_break, delete_break};
+use crate::http::pages::admin::members::households::{Household, get_user_household};
+use crate::http::pages::admin::members::impersonation::{
+    ImpersonationLogEntry, get_impersonation_log,
 };
-use crate::http::pages::admin::members::payments::{add_payment, get_user_payments};
-use crate::http::pages::admin::members::payments_summary::MonthStatus;
-use crate::http::pages::admin::members::payments_summary::{
-    MonthStatusView, calculate_year_status, payments_status_partial,
+use crate::http::pages::admin::members::lifecycle::{
+    StatusChange, StatusHistoryEntry, change_status, get_status_history, set_membership_status,
 };
+use crate::http::pages::admin::members::payments::{
+    add_payment, download_receipt, edit_payment, manage_payment_partial, refund_payment,
+    void_payment,
+};
+use crate::http::pages::admin::members::payments_summary::payments_status_partial;
+use crate::http::pages::user::login_attempts::{
+    LoginStatus, clear_failures, get_login_status, normalize_email,
+};
+use crate::http::pages::user::sessions::{
+    ActiveSession, end_sessions, forget_sessions, get_active_sessions,
+};
+use crate::http::pages::user::two_factor::disable_two_factor;
 use crate::http::template_into_response::TemplateIntoResponse;
 use crate::model::payment::{PaymentBreak, PaymentWithAllocations};
-use crate::model::user::User;
-use crate::utils::queries::{GroupedUserReservations, get_user, get_user_reservations};
+use crate::model::role::Permission;
+use crate::model::user::{MembershipStatus, User};
+use crate::utils::queries::{
+    GroupedUserReservations, MonthStatus, MonthStatusView, calculate_year_status, get_user,
+    get_user_payment_breaks, get_user_payments, get_user_reservations,
+};
 use crate::utils::{date_formats, local_date};
 use askama::Template;
 use axum::extract::{Path, State};
@@ -26,12 +51,33 @@ use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::routing::{delete, get, post};
 use axum::{Form, Router};
+use axum_login::permission_required;
 use serde::Deserialize;
-use sqlx::{query, query_as, query_scalar};
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as, query_scalar};
 use std::collections::HashSet;
-use time::{Date, Month};
+use time::{Date, Month, OffsetDateTime};
+use tracing::info;
 
 pub fn router() -> Router<AppState> {
+    let payments_router = Router::new()
+        .route("/payments/{id}", post(add_payment))
+        .route("/payments/manage/{payment_id}", get(manage_payment_partial))
+        .route("/payments/edit/{payment_id}", post(edit_payment))
+        .route("/payments/void/{payment_id}", post(void_payment))
+        .route("/payments/refund/{payment_id}", post(refund_payment))
+        .route("/payments/receipt/{payment_id}", get(download_receipt))
+        .route("/breaks/{id}", post(add_break))
+        .route("/breaks/{id}", delete(delete_break))
+        .route("/payment_status/{id}/{year}", get(payments_status_partial))
+        .nest("/bank_import", bank_import::router())
+        .nest("/break_requests", break_requests::router())
+        .nest("/households", households::router())
+        .route_layer(permission_required!(
+            UserAuthenticator,
+            login_url = "/",
+            Permission::ManagePayments.as_ref()
+        ));
+
     Router::new()
         .route("/", get(members_page))
         .route("/search", post(search_members))
@@ -42,12 +88,22 @@ pub fn router() -> Router<AppState> {
         .route("/edit/{id}", post(update_member))
         .route("/change_password/{id}", get(change_password_page))
         .route("/change_password/{id}", post(update_password))
-        .route("/toggle_active/{id}", post(toggle_active_user))
+        .route("/status/{id}", post(change_status))
         .route("/delete/{id}", post(delete_user))
-        .route("/payments/{id}", post(add_payment))
-        .route("/breaks/{id}", post(add_break))
-        .route("/breaks/{id}", delete(delete_break))
-        .route("/payment_status/{id}/{year}", get(payments_status_partial))
+        .route("/deleted", get(deleted_members_page))
+        .route("/restore/{id}", post(restore_user))
+        .route("/export/{id}", get(gdpr::export_member))
+        .route("/erase/{id}", post(gdpr::erase_member_data))
+        .route("/two_factor/reset/{id}", post(reset_two_factor))
+        .route("/unlock_login/{id}", post(unlock_login))
+        .route("/logout/{id}", post(logout_member))
+        .route_layer(permission_required!(
+            UserAuthenticator,
+            login_url = "/",
+            Permission::ManageMembers.as_ref()
+        ))
+        .merge(payments_router)
+        .merge(impersonation::router())
 }
 
 async fn get_all_roles(state: &AppState) -> sqlx::Result<Vec<String>> {
@@ -75,9 +131,7 @@ async fn members_page(State(state): State<AppState>, auth_session: AuthSession)
         members: Vec<User>,
     }
 
-    let members = query_as!(User, "select * from users_with_role order by name")
-        .fetch_all(&state.read_pool)
-        .await?;
+    let members = find_members(&state.read_pool, "", MembersSortOrder::Alphabetical, "").await?;
 
     MembersTemplate {
         user: auth_session.user.ok_or(HttpError::Unauthorized)?,
@@ -94,10 +148,41 @@ enum MembersSortOrder {
     ClosestBirthday,
 }
 
+/// Deleted members are left out, they are only listed on their own page
+async fn find_members(
+    executor: impl SqliteExecutor<'_>,
+    search: &str,
+    sort: MembersSortOrder,
+    status: &str,
+) -> sqlx::Result<Vec<User>> {
+    let query = format!("%{search}%");
+    let sort_order = format!("{sort:?}");
+
+    query_as!(
+        User,
+        "select * from users_with_role where (name like $1 or email like $1 or role like $1)
+            and ($3 = '' or membership_status = $3)
+         order by case 
+          when $2 = 'Alphabetical' then name
+          when $2 = 'Birthday' then birthday
+          when $2 = 'Gift' then received_gift
+          when $2 = 'ClosestBirthday' then ((strftime('%j', birthday) - strftime('%j', 'now') + 365) % 365)
+         end, email, role",
+        query,
+        sort_order,
+        status
+    )
+    .fetch_all(executor)
+    .await
+}
+
 #[derive(Deserialize)]
 struct SearchQuery {
     search: String,
     sort: MembersSortOrder,
+    /// Empty to include every membership status
+    #[serde(default)]
+    status: String,
 }
 
 async fn search_members(
@@ -110,23 +195,13 @@ async fn search_members(
         members: Vec<User>,
     }
 
-    let query = format!("%{}%", search_query.search);
-    let sort_order = format!("{:?}", search_query.sort);
-
-    let members = query_as!(
-        User,
-        "select * from users_with_role where name like $1 or email like $1 or role like $1
-         order by case 
-          when $2 = 'Alphabetical' then name
-          when $2 = 'Birthday' then birthday
-          when $2 = 'Gift' then received_gift
-          when $2 = 'ClosestBirthday' then ((strftime('%j', birthday) - strftime('%j', 'now') + 365) % 365)
-         end, email, role",
-        query,
-        sort_order
+    let members = find_members(
+        &state.read_pool,
+        &search_query.search,
+        search_query.sort,
+        &search_query.status,
     )
-        .fetch_all(&state.read_pool)
-        .await?;
+    .await?;
 
     MembersListTemplate { members }.try_into_response()
 }
@@ -195,6 +270,11 @@ async fn view_member_page(
         payments: Vec<PaymentWithAllocations>,
         breaks: Vec<PaymentBreak>,
         months_status_view: Vec<MonthStatusView>,
+        household: Option<Household>,
+        status_history: Vec<StatusHistoryEntry>,
+        login_status: LoginStatus,
+        active_sessions: Vec<ActiveSession>,
+        impersonation_log: Vec<ImpersonationLogEntry>,
     }
 
     impl ViewMemberTemplate {
@@ -215,15 +295,29 @@ async fn view_member_page(
 
     let current_date = local_date();
     let member = get_user(&state.read_pool, user_id).await?;
-    let payments = get_user_payments(&state.read_pool, user_id)
+    let payments = get_user_payments(&mut *state.read_pool.acquire().await?, user_id)
         .await
         .unwrap_or_default();
 
     let breaks = get_user_payment_breaks(&state.read_pool, user_id)
         .await
         .unwrap_or_default();
-    let months_status_view =
-        calculate_year_status(current_date.year(), &member, &payments, &breaks);
+    let months_status_view = calculate_year_status(
+        current_date.year(),
+        &member,
+        &payments,
+        &breaks,
+        current_date,
+    );
+
+    let login_status = get_login_status(
+        &state.read_pool,
+        &normalize_email(&member.email),
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+    let active_sessions =
+        get_active_sessions(&state.read_pool, member.id, OffsetDateTime::now_utc()).await?;
 
     ViewMemberTemplate {
         user: auth_session.user.ok_or(HttpError::Unauthorized)?,
@@ -234,6 +328,11 @@ async fn view_member_page(
         payments,
         breaks,
         months_status_view,
+        household: get_user_household(&state.read_pool, user_id).await?,
+        status_history: get_status_history(&state.read_pool, user_id).await?,
+        login_status,
+        active_sessions,
+        impersonation_log: get_impersonation_log(&state.read_pool, user_id).await?,
     }
     .try_into_response()
 }
@@ -266,7 +365,6 @@ struct UpdatedUser {
     email: String,
     name: String,
     role: String,
-    is_active: Option<String>,
     has_key: Option<String>,
     birthday: String,
     member_since: String,
@@ -287,7 +385,6 @@ async fn update_member(
         .await?
         .or_bail("Rolul selectat nu există")?;
     let user_name = updated_user.name.trim();
-    let is_active = updated_user.is_active.is_some();
     let has_key = updated_user.has_key.is_some();
     let Some(birthday) = parse_date(Some(updated_user.birthday)) else {
         return Ok(StatusCode::UNPROCESSABLE_ENTITY.into_response());
@@ -298,7 +395,7 @@ async fn update_member(
     let received_gift = parse_date(updated_user.received_gift);
 
     query!(
-        "update users set email = $2, name = $3, role_id = $4, has_key = $5, birthday = $6, member_since = $7, received_gift = $8, is_active = $9
+        "update users set email = $2, name = $3, role_id = $4, has_key = $5, birthday = $6, member_since = $7, received_gift = $8
          where id = $1",
         user_id,
         updated_user.email,
@@ -307,8 +404,7 @@ async fn update_member(
         has_key,
         birthday,
         member_since,
-        received_gift,
-        is_active
+        received_gift
     )
         .execute(&state.write_pool)
         .await?;
@@ -316,17 +412,6 @@ async fn update_member(
     Ok([("HX-Redirect", "/admin/members")].into_response())
 }
 
-async fn toggle_active_user(State(state): State<AppState>, Path(user_id): Path<i64>) -> HttpResult {
-    query!(
-        "update users set is_active = not is_active where id = $1",
-        user_id
-    )
-    .execute(&state.write_pool)
-    .await?;
-
-    Ok([("HX-Refresh", "true")].into_response())
-}
-
 async fn change_password_page(
     State(state): State<AppState>,
     auth_session: AuthSession,
@@ -346,22 +431,137 @@ async fn change_password_page(
     .try_into_response()
 }
 
-async fn delete_user(State(state): State<AppState>, Path(user_id): Path<i64>) -> HttpResult {
+async fn delete_user(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(user_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
     let mut tx = state.write_pool.begin().await?;
 
-    query!("delete from reservations where user_id = $1", user_id)
-        .execute(tx.as_mut())
-        .await?;
-
-    query!("update users set is_deleted = true where id = $1 ", user_id)
-        .execute(tx.as_mut())
-        .await?;
+    set_membership_status(
+        tx.as_mut(),
+        user_id,
+        StatusChange {
+            status: MembershipStatus::Deleted,
+            reason: None,
+            until: None,
+            changed_by: Some(user.id),
+        },
+    )
+    .await?;
 
     tx.commit().await?;
 
     Ok([("HX-Redirect", "/admin/members")].into_response())
 }
 
+async fn get_deleted_members(executor: impl SqliteExecutor<'_>) -> sqlx::Result<Vec<User>> {
+    query_as!(
+        User,
+        "select u.*, r.name as role, r.admin_panel_access, '' as permissions,
+            r.require_two_factor from users u
+            join user_roles r on r.id = u.role_id
+         where u.is_deleted = true order by u.name"
+    )
+    .fetch_all(executor)
+    .await
+}
+
+async fn deleted_members_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/deleted_page.html")]
+    struct DeletedMembersTemplate {
+        user: User,
+        members: Vec<User>,
+    }
+
+    DeletedMembersTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        members: get_deleted_members(&state.read_pool).await?,
+    }
+    .try_into_response()
+}
+
+/// Only members whose personal data was not erased can be brought back
+async fn restore_member(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    restored_by: i64,
+) -> HttpResult<()> {
+    let is_restorable = query_scalar!(
+        "select id from users where id = $1 and is_deleted = true and erased_at is null",
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .is_some();
+    if !is_restorable {
+        return Err(HttpError::Message(
+            "Datele acestui membru au fost șterse definitiv".into(),
+        ));
+    }
+
+    set_membership_status(
+        &mut *tx,
+        user_id,
+        StatusChange {
+            status: MembershipStatus::Active,
+            reason: Some("Restaurat"),
+            until: None,
+            changed_by: Some(restored_by),
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn restore_user(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(user_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    restore_member(tx.as_mut(), user_id, user.id).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Redirect", format!("/admin/members/view/{user_id}"))].into_response())
+}
+
+/// For members who lost both their authenticator app and their recovery codes
+async fn reset_two_factor(State(state): State<AppState>, Path(user_id): Path<i64>) -> HttpResult {
+    let mut tx = state.write_pool.begin().await?;
+    disable_two_factor(tx.as_mut(), user_id).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+async fn unlock_login(State(state): State<AppState>, Path(user_id): Path<i64>) -> HttpResult {
+    let member = get_user(&state.read_pool, user_id).await?;
+
+    let mut tx = state.write_pool.begin().await?;
+    clear_failures(tx.as_mut(), &normalize_email(&member.email)).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+/// Logs the member out on all their devices, for example after a lost phone
+async fn logout_member(State(state): State<AppState>, Path(user_id): Path<i64>) -> HttpResult {
+    let session_ids = forget_sessions(&state.write_pool, user_id, None).await?;
+    end_sessions(&state.session_store, &session_ids).await?;
+    info!("Ended {} sessions of user {user_id}", session_ids.len());
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
 #[derive(Deserialize)]
 pub struct ChangePasswordForm {
     password: String,
@@ -0,0 +1,501 @@
+use crate::bank_statement::{
+    BankTransaction, MatchMethod, StatementFormat, find_member, parse_statement,
+};
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::payments::{
+    NewPaymentRecord, create_payment, format_allocations, parse_requested_allocations,
+};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::payment::display_amount;
+use crate::model::user::User;
+use crate::utils::queries::{
+    MonthStatus, YearMonth, calculate_payable_months, get_global_vars, get_user,
+    get_user_payment_breaks, get_user_payments,
+};
+use crate::utils::{date_formats, local_date};
+use askama::Template;
+use axum::extract::{Multipart, Path, State};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use axum_extra::extract::Form as AxumExtraForm;
+use serde::Deserialize;
+use sqlx::{SqlitePool, query, query_as};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use time::{Date, OffsetDateTime};
+use tracing::info;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(bank_import_page))
+        .route("/", post(upload_statement))
+        .route("/confirm", post(confirm_transactions))
+        .route("/transactions/{id}", post(update_transaction))
+        .route("/transactions/{id}/ignore", post(ignore_transaction))
+}
+
+pub struct MemberOption {
+    id: i64,
+    name: String,
+}
+
+pub struct BankTransactionRow {
+    id: i64,
+    booking_date: Date,
+    amount: i64,
+    counterparty_name: Option<String>,
+    counterparty_iban: Option<String>,
+    reference: Option<String>,
+    user_id: Option<i64>,
+    match_method: Option<String>,
+    months: String,
+    error: Option<String>,
+}
+
+impl BankTransactionRow {
+    pub fn display_amount(&self) -> String {
+        display_amount(self.amount)
+    }
+
+    pub fn display_match_method(&self) -> &'static str {
+        self.match_method
+            .as_deref()
+            .and_then(|method| MatchMethod::from_str(method).ok())
+            .map(|method| method.display())
+            .unwrap_or("Nepotrivit")
+    }
+}
+
+pub struct BankImportSummary {
+    file_name: String,
+    format: String,
+    created_at: OffsetDateTime,
+    created_by_name: String,
+    total: i64,
+    pending: i64,
+}
+
+async fn get_member_options(pool: &SqlitePool) -> sqlx::Result<Vec<MemberOption>> {
+    query_as!(
+        MemberOption,
+        "select id, name from users_with_role order by name"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn get_pending_transactions(pool: &SqlitePool) -> sqlx::Result<Vec<BankTransactionRow>> {
+    query_as!(
+        BankTransactionRow,
+        "select id, booking_date, amount, counterparty_name, counterparty_iban, reference, user_id, match_method, months, error
+         from bank_transactions where status = 'pending' order by booking_date, id"
+    )
+        .fetch_all(pool)
+        .await
+}
+
+async fn get_transaction(pool: &SqlitePool, id: i64) -> HttpResult<BankTransactionRow> {
+    query_as!(
+        BankTransactionRow,
+        "select id, booking_date, amount, counterparty_name, counterparty_iban, reference, user_id, match_method, months, error
+         from bank_transactions where id = $1 and status = 'pending'",
+        id
+    )
+        .fetch_optional(pool)
+        .await?
+        .or_bail("Tranzacția nu există sau a fost deja procesată")
+}
+
+async fn bank_import_page(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/bank_import_page.html")]
+    struct BankImportTemplate {
+        user: User,
+        imports: Vec<BankImportSummary>,
+        transactions: Vec<BankTransactionRow>,
+        members: Vec<MemberOption>,
+    }
+
+    let imports = query_as!(
+        BankImportSummary,
+        r#"select i.file_name, i.format, i.created_at, u.name as created_by_name,
+            (select count(*) from bank_transactions t where t.import_id = i.id) as "total!: i64",
+            (select count(*) from bank_transactions t where t.import_id = i.id and t.status = 'pending') as "pending!: i64"
+         from bank_imports i join users u on u.id = i.created_by
+         order by i.created_at desc limit 10"#
+    )
+        .fetch_all(&state.read_pool)
+        .await?;
+
+    BankImportTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        imports,
+        transactions: get_pending_transactions(&state.read_pool).await?,
+        members: get_member_options(&state.read_pool).await?,
+    }
+    .try_into_response()
+}
+
+/// Picks the oldest payable months the amount can cover, skipping those already proposed for
+/// another transfer of the same member. Without a monthly fee, only the oldest month is proposed.
+fn propose_months(
+    payable: &[(YearMonth, MonthStatus)],
+    amount: i64,
+    monthly_fee: i64,
+    already_proposed: &mut HashSet<YearMonth>,
+) -> Vec<YearMonth> {
+    let count = if monthly_fee > 0 {
+        (amount / monthly_fee) as usize
+    } else {
+        1
+    };
+
+    let months: Vec<_> = payable
+        .iter()
+        .map(|(month, _)| *month)
+        .filter(|month| !already_proposed.contains(month))
+        .take(count)
+        .collect();
+    already_proposed.extend(months.iter().copied());
+
+    months
+}
+
+async fn get_payable_months(
+    pool: &SqlitePool,
+    member: &User,
+) -> sqlx::Result<Vec<(YearMonth, MonthStatus)>> {
+    let payments = get_user_payments(&mut *pool.acquire().await?, member.id).await?;
+    let breaks = get_user_payment_breaks(pool, member.id).await?;
+
+    Ok(calculate_payable_months(
+        member,
+        &payments,
+        &breaks,
+        local_date(),
+    ))
+}
+
+async fn upload_statement(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    mut multipart: Multipart,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut statement = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .or_bail("Fișierul nu a putut fi încărcat")?
+    {
+        if field.name() == Some("statement") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            let content = field
+                .bytes()
+                .await
+                .or_bail("Fișierul nu a putut fi încărcat")?;
+            statement = Some((file_name, content));
+        }
+    }
+    let (file_name, content) = statement
+        .filter(|(_, content)| !content.is_empty())
+        .or_bail("Nu a fost selectat niciun fișier")?;
+
+    let format = StatementFormat::detect(&file_name, &content);
+    let transactions =
+        parse_statement(format, &content).map_err(|e| HttpError::Message(e.to_string()))?;
+
+    let members = query_as!(User, "select * from users_with_role")
+        .fetch_all(&state.read_pool)
+        .await?;
+    let known_accounts: HashMap<String, i64> =
+        query!("select iban, user_id from member_bank_accounts")
+            .fetch_all(&state.read_pool)
+            .await?
+            .into_iter()
+            .map(|account| (account.iban, account.user_id))
+            .collect();
+    let monthly_fee = get_global_vars(&state.read_pool).await?.monthly_fee;
+
+    struct Proposal {
+        transaction: BankTransaction,
+        user_id: Option<i64>,
+        match_method: Option<MatchMethod>,
+        months: String,
+    }
+
+    let mut payable_months = HashMap::new();
+    let mut already_proposed = HashMap::<i64, HashSet<YearMonth>>::new();
+    let mut proposals = Vec::with_capacity(transactions.len());
+    for transaction in transactions {
+        let found = find_member(&transaction, &members, &known_accounts);
+        let member = found.and_then(|(user_id, _)| members.iter().find(|m| m.id == user_id));
+
+        let months = match member {
+            Some(member) => {
+                let payable = match payable_months.entry(member.id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        entry.insert(get_payable_months(&state.read_pool, member).await?)
+                    }
+                };
+                let proposed = propose_months(
+                    payable,
+                    transaction.amount,
+                    monthly_fee,
+                    already_proposed.entry(member.id).or_default(),
+                );
+                format_allocations(&proposed)
+            }
+            None => String::new(),
+        };
+
+        proposals.push(Proposal {
+            transaction,
+            user_id: found.map(|(user_id, _)| user_id),
+            match_method: found.map(|(_, method)| method),
+            months,
+        });
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+    let format_name = format.as_ref();
+    let import_id = query!(
+        "insert into bank_imports (file_name, format, created_by) values ($1, $2, $3) returning id",
+        file_name,
+        format_name,
+        user.id
+    )
+    .fetch_one(tx.as_mut())
+    .await?
+    .id;
+
+    let mut imported = 0;
+    for proposal in proposals {
+        let transaction = proposal.transaction;
+        let match_method = proposal
+            .match_method
+            .map(|method| method.as_ref().to_string());
+        imported += query!(
+            "insert into bank_transactions (import_id, bank_reference, booking_date, amount, counterparty_name, counterparty_iban, reference, user_id, match_method, months)
+             values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) on conflict (bank_reference) do nothing",
+            import_id,
+            transaction.bank_reference,
+            transaction.booking_date,
+            transaction.amount,
+            transaction.counterparty_name,
+            transaction.counterparty_iban,
+            transaction.reference,
+            proposal.user_id,
+            match_method,
+            proposal.months
+        )
+            .execute(tx.as_mut())
+            .await?
+            .rows_affected();
+    }
+
+    if imported == 0 {
+        return Err(HttpError::Message(
+            "Extrasul nu conține transferuri noi".into(),
+        ));
+    }
+
+    tx.commit().await?;
+
+    info!(
+        "Bank statement {file_name} imported by {} with {imported} new transactions",
+        user.email
+    );
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+#[derive(Deserialize)]
+struct UpdatedTransaction {
+    user_id: String, // From a select, empty when no member is chosen
+    months: String,
+}
+
+/// Changing the member proposes their months again, otherwise the months are saved as typed
+async fn update_transaction(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<UpdatedTransaction>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/bank_transaction_row.html")]
+    struct BankTransactionRowTemplate {
+        transaction: BankTransactionRow,
+        members: Vec<MemberOption>,
+    }
+
+    let transaction = get_transaction(&state.read_pool, id).await?;
+    let user_id = form.user_id.parse::<i64>().ok();
+
+    let months = match user_id {
+        Some(user_id) if transaction.user_id != Some(user_id) => {
+            let member = get_user(&state.read_pool, user_id).await?;
+            let monthly_fee = get_global_vars(&state.read_pool).await?.monthly_fee;
+            let payable = get_payable_months(&state.read_pool, &member).await?;
+            let mut already_proposed: HashSet<_> = query!(
+                "select months from bank_transactions where user_id = $1 and status = 'pending' and id != $2",
+                user_id,
+                id
+            )
+                .fetch_all(&state.read_pool)
+                .await?
+                .iter()
+                .flat_map(|row| {
+                    parse_requested_allocations(&member, row.months.split(','), local_date())
+                        .unwrap_or_default()
+                })
+                .collect();
+
+            format_allocations(&propose_months(
+                &payable,
+                transaction.amount,
+                monthly_fee,
+                &mut already_proposed,
+            ))
+        }
+        Some(_) => form.months.trim().to_string(),
+        None => String::new(),
+    };
+
+    let manual = MatchMethod::Manual;
+    let manual = manual.as_ref();
+    query!(
+        "update bank_transactions set user_id = $2, months = $3, error = null,
+         match_method = case when user_id is $2 then match_method when $2 is null then null else $4 end
+         where id = $1",
+        id,
+        user_id,
+        months,
+        manual
+    )
+    .execute(&state.write_pool)
+    .await?;
+
+    BankTransactionRowTemplate {
+        transaction: get_transaction(&state.read_pool, id).await?,
+        members: get_member_options(&state.read_pool).await?,
+    }
+    .try_into_response()
+}
+
+async fn ignore_transaction(State(state): State<AppState>, Path(id): Path<i64>) -> HttpResult {
+    query!(
+        "update bank_transactions set status = 'ignored' where id = $1 and status = 'pending'",
+        id
+    )
+    .execute(&state.write_pool)
+    .await?;
+
+    // The row is replaced with nothing
+    Ok(().into_response())
+}
+
+async fn confirm_transaction(
+    state: &AppState,
+    transaction: &BankTransactionRow,
+    created_by: i64,
+) -> HttpResult<()> {
+    let user_id = transaction
+        .user_id
+        .or_bail("Nu a fost ales niciun membru")?;
+    let member = get_user(&state.read_pool, user_id).await?;
+    let allocations =
+        parse_requested_allocations(&member, transaction.months.split(','), local_date())?;
+
+    let notes = match &transaction.reference {
+        Some(reference) => format!("Transfer bancar: {reference}"),
+        None => "Transfer bancar".to_string(),
+    };
+
+    let mut tx = state.write_pool.begin().await?;
+    let payment_id = create_payment(
+        tx.as_mut(),
+        NewPaymentRecord {
+            member_id: member.id,
+            amount: transaction.amount,
+            payment_date: transaction.booking_date,
+            notes: Some(&notes),
+            allocations: &allocations,
+            created_by,
+        },
+    )
+    .await?;
+
+    query!(
+        "update bank_transactions set status = 'confirmed', payment_id = $2, error = null where id = $1",
+        transaction.id,
+        payment_id
+    )
+        .execute(tx.as_mut())
+        .await?;
+
+    if let Some(iban) = &transaction.counterparty_iban {
+        query!(
+            "insert into member_bank_accounts (iban, user_id) values ($1, $2)
+             on conflict (iban) do update set user_id = excluded.user_id",
+            iban,
+            member.id
+        )
+        .execute(tx.as_mut())
+        .await?;
+    }
+
+    tx.commit().await?;
+    let _ = state.notification_notifier.send(member.id);
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ConfirmTransactions {
+    #[serde(default)]
+    selected: Vec<i64>,
+}
+
+/// Each transaction is confirmed on its own, the ones that fail keep the reason next to them
+async fn confirm_transactions(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    AxumExtraForm(form): AxumExtraForm<ConfirmTransactions>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    if form.selected.is_empty() {
+        return Err(HttpError::Message(
+            "Nu a fost selectată nicio tranzacție".into(),
+        ));
+    }
+
+    let mut confirmed = 0;
+    for id in form.selected {
+        let transaction = get_transaction(&state.read_pool, id).await?;
+
+        match confirm_transaction(&state, &transaction, user.id).await {
+            Ok(()) => confirmed += 1,
+            Err(HttpError::Message(error)) => {
+                query!(
+                    "update bank_transactions set error = $2 where id = $1",
+                    id,
+                    error
+                )
+                .execute(&state.write_pool)
+                .await?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    info!("{confirmed} bank transfers confirmed by {}", user.email);
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
@@ -0,0 +1,418 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::breaks::{create_break, validate_break};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::user::User;
+use crate::utils::date_formats;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use serde::Deserialize;
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as};
+use std::str::FromStr;
+use strum::{AsRefStr, EnumString};
+use time::{Date, OffsetDateTime};
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+/// Attachments are kept in the database, so they are limited in size
+pub const MAX_ATTACHMENT_SIZE: usize = 2 * 1024 * 1024;
+const ALLOWED_ATTACHMENT_TYPES: [&str; 3] = ["application/pdf", "image/jpeg", "image/png"];
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(break_requests_page))
+        .route("/{id}/approve", post(approve_request))
+        .route("/{id}/reject", post(reject_request))
+        .route("/{id}/attachment", get(download_attachment))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum BreakRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakRequest {
+    pub id: i64,
+    pub user_id: i64,
+    pub member_name: String,
+    pub start_date: Date,
+    pub end_date: Date,
+    pub reason: String,
+    pub attachment_name: Option<String>,
+    pub status: String,
+    pub rejection_reason: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub decided_at: Option<OffsetDateTime>,
+    pub decided_by_name: Option<String>,
+}
+
+impl BreakRequest {
+    pub fn status(&self) -> BreakRequestStatus {
+        BreakRequestStatus::from_str(&self.status).unwrap_or(BreakRequestStatus::Pending)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status() == BreakRequestStatus::Pending
+    }
+
+    pub fn display_status(&self) -> &'static str {
+        match self.status() {
+            BreakRequestStatus::Pending => "În așteptare",
+            BreakRequestStatus::Approved => "Aprobată",
+            BreakRequestStatus::Rejected => "Respinsă",
+        }
+    }
+
+    pub fn badge_class(&self) -> &'static str {
+        match self.status() {
+            BreakRequestStatus::Pending => "badge-info",
+            BreakRequestStatus::Approved => "badge-success",
+            BreakRequestStatus::Rejected => "badge-error",
+        }
+    }
+}
+
+pub struct Attachment {
+    pub name: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn validate(&self) -> HttpResult<()> {
+        if self.content.len() > MAX_ATTACHMENT_SIZE {
+            return Err(HttpError::Message(
+                "Documentul atașat nu poate depăși 2 MB".into(),
+            ));
+        }
+        if !ALLOWED_ATTACHMENT_TYPES.contains(&self.content_type.as_str()) {
+            return Err(HttpError::Message(
+                "Documentul atașat trebuie să fie PDF, JPEG sau PNG".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn into_download(self) -> Response {
+        (
+            [
+                (header::CONTENT_TYPE, self.content_type),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!(
+                        "attachment; filename=\"{}\"",
+                        self.name.replace(['"', '\\'], "_")
+                    ),
+                ),
+            ],
+            self.content,
+        )
+            .into_response()
+    }
+}
+
+pub struct NewBreakRequest<'a> {
+    pub start_date: Date,
+    pub end_date: Date,
+    pub reason: &'a str,
+    pub attachment: Option<Attachment>,
+}
+
+pub async fn get_break_requests(
+    executor: impl SqliteExecutor<'_>,
+    status: Option<BreakRequestStatus>,
+    user_id: Option<i64>,
+) -> sqlx::Result<Vec<BreakRequest>> {
+    let status: Option<&str> = status.as_ref().map(|status| status.as_ref());
+    query_as!(
+        BreakRequest,
+        r#"select r.id, r.user_id, m.name as member_name, r.start_date, r.end_date, r.reason, r.attachment_name,
+            r.status, r.rejection_reason, r.created_at, r.decided_at, a.name as 'decided_by_name?'
+         from break_requests r
+            join users m on m.id = r.user_id
+            left join users a on a.id = r.decided_by
+         where ($1 is null or r.status = $1) and ($2 is null or r.user_id = $2)
+         order by r.created_at desc, r.id desc"#,
+        status,
+        user_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn get_attachment(
+    executor: impl SqliteExecutor<'_>,
+    request_id: i64,
+) -> sqlx::Result<Option<(i64, Attachment)>> {
+    let record = query!(
+        r#"select user_id, attachment as "attachment!", attachment_name as "attachment_name!",
+            attachment_type as "attachment_type!"
+         from break_requests where id = $1 and attachment is not null"#,
+        request_id
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|record| {
+        (
+            record.user_id,
+            Attachment {
+                name: record.attachment_name,
+                content_type: record.attachment_type,
+                content: record.attachment,
+            },
+        )
+    }))
+}
+
+/// Members can't request a break that couldn't be approved, or one overlapping another pending request
+pub async fn create_break_request(
+    tx: &mut SqliteConnection,
+    member: &User,
+    request: NewBreakRequest<'_>,
+) -> HttpResult<i64> {
+    if request.reason.trim().is_empty() {
+        return Err(HttpError::Message("Motivul pauzei este obligatoriu".into()));
+    }
+    if let Some(attachment) = &request.attachment {
+        attachment.validate()?;
+    }
+
+    validate_break(&mut *tx, member, request.start_date, request.end_date).await?;
+
+    let pending = BreakRequestStatus::Pending.as_ref();
+    let overlapping_request = query!(
+        "select id from break_requests
+         where user_id = $1 and status = $2 and start_date <= $4 and $3 <= end_date",
+        member.id,
+        pending,
+        request.start_date,
+        request.end_date
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if overlapping_request.is_some() {
+        return Err(HttpError::Message(
+            "Ai deja o cerere în așteptare pentru această perioadă".into(),
+        ));
+    }
+
+    let (attachment, attachment_name, attachment_type) = match request.attachment {
+        Some(attachment) => (
+            Some(attachment.content),
+            Some(attachment.name),
+            Some(attachment.content_type),
+        ),
+        None => (None, None, None),
+    };
+    let reason = request.reason.trim();
+
+    let request_id = query!(
+        "insert into break_requests (user_id, start_date, end_date, reason, attachment, attachment_name, attachment_type)
+         values ($1, $2, $3, $4, $5, $6, $7) returning id",
+        member.id,
+        request.start_date,
+        request.end_date,
+        reason,
+        attachment,
+        attachment_name,
+        attachment_type
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    info!(
+        "Break requested by member {}: {} to {}",
+        member.id, request.start_date, request.end_date
+    );
+
+    Ok(request_id)
+}
+
+/// Members can withdraw their requests while they are still pending
+pub async fn cancel_break_request(
+    executor: impl SqliteExecutor<'_>,
+    request_id: i64,
+    user_id: i64,
+) -> sqlx::Result<bool> {
+    let pending = BreakRequestStatus::Pending.as_ref();
+    query!(
+        "delete from break_requests where id = $1 and user_id = $2 and status = $3",
+        request_id,
+        user_id,
+        pending
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
+
+async fn get_pending_request(
+    tx: &mut SqliteConnection,
+    request_id: i64,
+) -> HttpResult<(i64, Date, Date, String)> {
+    let pending = BreakRequestStatus::Pending.as_ref();
+    let request = query!(
+        "select user_id, start_date, end_date, reason from break_requests where id = $1 and status = $2",
+        request_id,
+        pending
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .or_bail("Cererea nu mai este în așteptare")?;
+
+    Ok((
+        request.user_id,
+        request.start_date,
+        request.end_date,
+        request.reason,
+    ))
+}
+
+/// Creates the break, with the same validation as when an admin adds it, then marks the request approved
+pub async fn approve_break_request(
+    tx: &mut SqliteConnection,
+    request_id: i64,
+    admin_id: i64,
+) -> HttpResult<i64> {
+    let (user_id, start_date, end_date, reason) = get_pending_request(&mut *tx, request_id).await?;
+    let member = query_as!(User, "select * from users_with_role where id = $1", user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let break_id = create_break(
+        &mut *tx,
+        &member,
+        start_date,
+        end_date,
+        Some(&reason),
+        admin_id,
+    )
+    .await?;
+
+    let approved = BreakRequestStatus::Approved.as_ref();
+    query!(
+        "update break_requests set status = $2, break_id = $3, decided_at = CURRENT_TIMESTAMP, decided_by = $4
+         where id = $1",
+        request_id,
+        approved,
+        break_id,
+        admin_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(break_id)
+}
+
+pub async fn reject_break_request(
+    tx: &mut SqliteConnection,
+    request_id: i64,
+    admin_id: i64,
+    rejection_reason: &str,
+) -> HttpResult<()> {
+    let rejection_reason = rejection_reason.trim();
+    if rejection_reason.is_empty() {
+        return Err(HttpError::Message(
+            "Motivul respingerii este obligatoriu".into(),
+        ));
+    }
+    get_pending_request(&mut *tx, request_id).await?;
+
+    let rejected = BreakRequestStatus::Rejected.as_ref();
+    query!(
+        "update break_requests set status = $2, rejection_reason = $3, decided_at = CURRENT_TIMESTAMP, decided_by = $4
+         where id = $1",
+        request_id,
+        rejected,
+        rejection_reason,
+        admin_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn break_requests_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/break_requests_page.html")]
+    struct BreakRequestsTemplate {
+        user: User,
+        pending: Vec<BreakRequest>,
+        decided: Vec<BreakRequest>,
+    }
+
+    let (pending, decided): (Vec<_>, Vec<_>) = get_break_requests(&state.read_pool, None, None)
+        .await?
+        .into_iter()
+        .partition(BreakRequest::is_pending);
+
+    BreakRequestsTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        pending,
+        decided,
+    }
+    .try_into_response()
+}
+
+async fn approve_request(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(request_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    approve_break_request(tx.as_mut(), request_id, user.id).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+#[derive(Deserialize)]
+struct Rejection {
+    rejection_reason: String,
+}
+
+async fn reject_request(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(request_id): Path<i64>,
+    Form(form): Form<Rejection>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    reject_break_request(tx.as_mut(), request_id, user.id, &form.rejection_reason).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+async fn download_attachment(
+    State(state): State<AppState>,
+    Path(request_id): Path<i64>,
+) -> HttpResult {
+    let (_, attachment) = get_attachment(&state.read_pool, request_id)
+        .await?
+        .or_bail("Cererea nu are un document atașat")?;
+
+    Ok(attachment.into_download())
+}
@@ -0,0 +1,291 @@
+use super::*;
+use sqlx::{SqlitePool, query, query_scalar};
+use time::macros::date;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<User> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    query_as!(User, "select * from users_with_role where id = 1000")
+        .fetch_one(pool)
+        .await
+}
+
+async fn request(
+    pool: &SqlitePool,
+    member: &User,
+    start_date: Date,
+    end_date: Date,
+    attachment: Option<Attachment>,
+) -> HttpResult<i64> {
+    let mut tx = pool.begin().await?;
+    let id = create_break_request(
+        tx.as_mut(),
+        member,
+        NewBreakRequest {
+            start_date,
+            end_date,
+            reason: "Accidentare",
+            attachment,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+fn error_message<T>(result: HttpResult<T>) -> String {
+    match result {
+        Err(HttpError::Message(message)) => message,
+        _ => panic!("Expected an error message"),
+    }
+}
+
+#[sqlx::test]
+async fn should_create_break_on_approval(pool: SqlitePool) -> sqlx::Result<()> {
+    let member = setup(&pool).await?;
+    let attachment = Attachment {
+        name: "adeverinta.pdf".into(),
+        content_type: "application/pdf".into(),
+        content: b"%PDF-1.4".to_vec(),
+    };
+    let request_id = request(
+        &pool,
+        &member,
+        date!(2024 - 03 - 01),
+        date!(2024 - 04 - 01),
+        Some(attachment),
+    )
+    .await
+    .unwrap();
+
+    // Overlapping another pending request
+    assert_eq!(
+        error_message(
+            request(
+                &pool,
+                &member,
+                date!(2024 - 04 - 01),
+                date!(2024 - 05 - 01),
+                None
+            )
+            .await
+        ),
+        "Ai deja o cerere în așteptare pentru această perioadă"
+    );
+
+    let mut tx = pool.begin().await?;
+    let break_id = approve_break_request(tx.as_mut(), request_id, 0)
+        .await
+        .unwrap();
+    tx.commit().await?;
+
+    let created = query!(
+        "select start_date, end_date, reason, created_by from payment_breaks where id = $1",
+        break_id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(created.start_date, date!(2024 - 03 - 01));
+    assert_eq!(created.end_date, date!(2024 - 04 - 01));
+    assert_eq!(created.reason.as_deref(), Some("Accidentare"));
+    assert_eq!(created.created_by, 0);
+
+    let requests = get_break_requests(&pool, None, Some(member.id)).await?;
+    assert_eq!(requests[0].status(), BreakRequestStatus::Approved);
+    assert_eq!(
+        requests[0].decided_by_name.as_deref(),
+        Some("Test Administrator")
+    );
+    assert_eq!(
+        get_attachment(&pool, request_id)
+            .await?
+            .map(|(owner, attachment)| (owner, attachment.name)),
+        Some((member.id, "adeverinta.pdf".to_string()))
+    );
+
+    // Can't be decided twice
+    let mut tx = pool.begin().await?;
+    assert!(
+        approve_break_request(tx.as_mut(), request_id, 0)
+            .await
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_validate_on_approval(pool: SqlitePool) -> sqlx::Result<()> {
+    let member = setup(&pool).await?;
+    let request_id = request(
+        &pool,
+        &member,
+        date!(2024 - 03 - 01),
+        date!(2024 - 04 - 01),
+        None,
+    )
+    .await
+    .unwrap();
+
+    // April was paid in the meantime
+    query!(
+        r#"
+        insert into payments (id, user_id, amount, payment_date, created_by)
+        VALUES (1, 1000, 10000, '2024-03-05', 0);
+        insert into payment_allocations (payment_id, year, month) VALUES (1, 2024, 4);
+        "#
+    )
+    .execute(&pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    assert_eq!(
+        error_message(approve_break_request(tx.as_mut(), request_id, 0).await),
+        "Perioada se suprapune cu o lună deja plătită"
+    );
+    drop(tx);
+
+    let status = query_scalar!(
+        "select status from break_requests where id = $1",
+        request_id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(status, "pending");
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_keep_rejection_reason(pool: SqlitePool) -> sqlx::Result<()> {
+    let member = setup(&pool).await?;
+    let attachment = Attachment {
+        name: "scan.pdf".into(),
+        content_type: "application/pdf".into(),
+        content: vec![0; MAX_ATTACHMENT_SIZE + 1],
+    };
+    assert_eq!(
+        error_message(
+            request(
+                &pool,
+                &member,
+                date!(2024 - 03 - 01),
+                date!(2024 - 03 - 01),
+                Some(attachment)
+            )
+            .await
+        ),
+        "Documentul atașat nu poate depăși 2 MB"
+    );
+
+    let request_id = request(
+        &pool,
+        &member,
+        date!(2024 - 03 - 01),
+        date!(2024 - 03 - 01),
+        None,
+    )
+    .await
+    .unwrap();
+
+    let mut tx = pool.begin().await?;
+    assert!(
+        reject_break_request(tx.as_mut(), request_id, 0, " ")
+            .await
+            .is_err()
+    );
+    reject_break_request(tx.as_mut(), request_id, 0, "Lipsește adeverința")
+        .await
+        .unwrap();
+    tx.commit().await?;
+
+    let requests = get_break_requests(&pool, None, Some(member.id)).await?;
+    assert_eq!(requests[0].status(), BreakRequestStatus::Rejected);
+    assert_eq!(
+        requests[0].rejection_reason.as_deref(),
+        Some("Lipsește adeverința")
+    );
+
+    // Decided requests can't be withdrawn
+    assert!(!cancel_break_request(&pool, request_id, member.id).await?);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_validate_requests(pool: SqlitePool) -> sqlx::Result<()> {
+    let member = setup(&pool).await?;
+
+    assert_eq!(
+        error_message(
+            request(
+                &pool,
+                &member,
+                date!(2023 - 12 - 01),
+                date!(2024 - 01 - 01),
+                None
+            )
+            .await
+        ),
+        "Nu poți adăuga o pauză înainte de înscriere"
+    );
+
+    let attachment = Attachment {
+        name: "script.exe".into(),
+        content_type: "application/octet-stream".into(),
+        content: vec![0; 16],
+    };
+    assert!(
+        request(
+            &pool,
+            &member,
+            date!(2024 - 03 - 01),
+            date!(2024 - 03 - 01),
+            Some(attachment)
+        )
+        .await
+        .is_err()
+    );
+
+    let attachment = Attachment {
+        name: "scan.pdf".into(),
+        content_type: "application/pdf".into(),
+        content: vec![0; MAX_ATTACHMENT_SIZE + 1],
+    };
+    assert_eq!(
+        error_message(
+            request(
+                &pool,
+                &member,
+                date!(2024 - 03 - 01),
+                date!(2024 - 03 - 01),
+                Some(attachment)
+            )
+            .await
+        ),
+        "Documentul atașat nu poate depăși 2 MB"
+    );
+
+    let request_id = request(
+        &pool,
+        &member,
+        date!(2024 - 03 - 01),
+        date!(2024 - 03 - 01),
+        None,
+    )
+    .await
+    .unwrap();
+    // Only the member who made it can withdraw it
+    assert!(!cancel_break_request(&pool, request_id, 0).await?);
+    assert!(cancel_break_request(&pool, request_id, member.id).await?);
+
+    Ok(())
+}
@@ -2,32 +2,17 @@ use crate::http::AppState;
 use crate::http::error::{HttpError, HttpResult, OrBail};
 use crate::http::pages::AuthSession;
 use crate::http::pages::admin::members::payments::get_payment_allocations;
-use crate::model::payment::PaymentBreak;
+use crate::model::user::User;
 use crate::utils::date_formats;
-use crate::utils::queries::{YearMonth, get_user};
+use crate::utils::queries::{YearMonth, get_user, get_user_payment_breaks};
 use axum::Form;
 use axum::extract::{Path, State};
 use axum::response::IntoResponse;
 use serde::Deserialize;
-use sqlx::{SqliteExecutor, query, query_as};
+use sqlx::{SqliteConnection, query};
 use time::Date;
 use tracing::info;
 
-pub async fn get_user_payment_breaks(
-    executor: impl SqliteExecutor<'_>,
-    user_id: i64,
-) -> sqlx::Result<Vec<PaymentBreak>> {
-    query_as!(
-        PaymentBreak,
-        "select m.*, u.name as created_by_name
-         from payment_breaks m join users u on u.id = m.created_by
-         where user_id = $1 order by start_date desc",
-        user_id
-    )
-    .fetch_all(executor)
-    .await
-}
-
 #[derive(Deserialize, Debug)]
 pub struct NewBreak {
     start_month: String, // Format "2024-06"
@@ -35,23 +20,20 @@ pub struct NewBreak {
     reason: Option<String>,
 }
 
-pub async fn add_break(
-    State(state): State<AppState>,
-    Path(member_id): Path<i64>,
-    auth_session: AuthSession,
-    Form(form): Form<NewBreak>,
-) -> HttpResult {
-    fn parse_month_input(input: &str) -> Option<Date> {
-        let date_str = format!("{}-01", input);
-        Date::parse(&date_str, date_formats::ISO_DATE).ok()
-    }
-
-    let created_by = auth_session.user.ok_or(HttpError::Unauthorized)?;
-
-    let member = get_user(&state.read_pool, member_id).await?;
-    let start_date = parse_month_input(&form.start_month).or_bail("Început de lună invalid")?;
-    let end_date = parse_month_input(&form.end_month).or_bail("Sfârșit de lună invalid")?;
+/// Parses a month from an `<input type="month">`, as the first day of that month
+pub fn parse_month_input(input: &str) -> Option<Date> {
+    let date_str = format!("{}-01", input);
+    Date::parse(&date_str, date_formats::ISO_DATE).ok()
+}
 
+/// Checks that a break is a valid period after the member joined,
+/// which doesn't overlap other breaks or months that were already paid
+pub async fn validate_break(
+    tx: &mut SqliteConnection,
+    member: &User,
+    start_date: Date,
+    end_date: Date,
+) -> HttpResult<()> {
     if end_date < start_date {
         return Err(HttpError::Message("Data selectată este invalidă".into()));
     }
@@ -65,10 +47,8 @@ pub async fn add_break(
         ));
     }
 
-    let mut tx = state.write_pool.begin().await?;
-
-    let existing_breaks = get_user_payment_breaks(tx.as_mut(), member_id).await?;
-    let existing_allocations = get_payment_allocations(tx.as_mut(), member_id).await?;
+    let existing_breaks = get_user_payment_breaks(&mut *tx, member.id).await?;
+    let existing_allocations = get_payment_allocations(&mut *tx, member.id).await?;
 
     for brk in existing_breaks {
         if start_date <= brk.end_date && brk.start_date <= end_date {
@@ -88,24 +68,66 @@ pub async fn add_break(
         }
     }
 
-    let reason = form.reason.filter(|reason| !reason.is_empty());
-    query!(
+    Ok(())
+}
+
+/// Validates and inserts a break, returning its id
+pub async fn create_break(
+    tx: &mut SqliteConnection,
+    member: &User,
+    start_date: Date,
+    end_date: Date,
+    reason: Option<&str>,
+    created_by: i64,
+) -> HttpResult<i64> {
+    validate_break(&mut *tx, member, start_date, end_date).await?;
+
+    let break_id = query!(
         "insert into payment_breaks (user_id, start_date, end_date, reason, created_by)
-         values ($1, $2, $3, $4, $5)",
-        member_id,
+         values ($1, $2, $3, $4, $5) returning id",
+        member.id,
         start_date,
         end_date,
         reason,
-        created_by.id
+        created_by
     )
-    .execute(tx.as_mut())
-    .await?;
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
 
     info!(
         "Payment Break added for member {}: {} to {}",
-        member_id, start_date, end_date
+        member.id, start_date, end_date
     );
 
+    Ok(break_id)
+}
+
+pub async fn add_break(
+    State(state): State<AppState>,
+    Path(member_id): Path<i64>,
+    auth_session: AuthSession,
+    Form(form): Form<NewBreak>,
+) -> HttpResult {
+    let created_by = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let member = get_user(&state.read_pool, member_id).await?;
+    let start_date = parse_month_input(&form.start_month).or_bail("Început de lună invalid")?;
+    let end_date = parse_month_input(&form.end_month).or_bail("Sfârșit de lună invalid")?;
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let reason = form.reason.filter(|reason| !reason.is_empty());
+    create_break(
+        tx.as_mut(),
+        &member,
+        start_date,
+        end_date,
+        reason.as_deref(),
+        created_by.id,
+    )
+    .await?;
+
     tx.commit().await?;
 
     Ok([("HX-Refresh", "true")].into_response())
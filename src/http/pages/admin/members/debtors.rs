@@ -2,13 +2,23 @@ use crate::model::user::User;
 use crate::utils::queries::YearMonth;
 use crate::utils::{date_formats, local_date};
 use itertools::Itertools;
-use sqlx::{SqlitePool, query_as};
-use std::collections::HashSet;
-use time::{Date, Month};
+use sqlx::{SqlitePool, query, query_as};
+use std::collections::{HashMap, HashSet};
+use time::{Date, Month, OffsetDateTime};
+
+pub struct LastReminder {
+    pub user_id: i64,
+    pub step_name: String,
+    pub is_admin_alert: bool,
+    pub sent_at: OffsetDateTime,
+}
 
 pub struct DebtorItem {
     pub member: User,
     pub unpaid_months: Vec<&'static str>,
+    /// Dunning status, the most recent reminder sent for the unpaid months
+    pub last_reminder: Option<LastReminder>,
+    pub household_name: Option<String>,
 }
 
 pub async fn compute_debtors(
@@ -61,6 +71,30 @@ pub async fn compute_debtors(
         .into_iter()
         .into_group_map_by(|br| br.user_id);
 
+    let mut last_reminders: HashMap<_, _> = query_as!(
+        LastReminder,
+        r#"select r.user_id, s.name as step_name, s.recipient = 'admins' as "is_admin_alert!: bool", r.sent_at
+           from dunning_reminders r
+           join dunning_steps s on s.step = r.step
+           where r.id in (select max(id) from dunning_reminders group by user_id)"#
+    )
+    .fetch_all(conn.as_mut())
+    .await?
+    .into_iter()
+    .map(|reminder| (reminder.user_id, reminder))
+    .collect();
+
+    // Months paid together with the household are allocated to each member, so the household
+    // is only needed to show debtors who are billed together
+    let mut household_names: HashMap<_, _> = query!(
+        "select hm.user_id, h.name from household_members hm join households h on h.id = hm.household_id"
+    )
+    .fetch_all(conn.as_mut())
+    .await?
+    .into_iter()
+    .map(|record| (record.user_id, record.name))
+    .collect();
+
     // D. Calculate Unpaid Months
     let current_month_start = YearMonth::from(current_date).to_date();
     let year_months: Vec<_> = (1..=12)
@@ -113,6 +147,8 @@ pub async fn compute_debtors(
 
             if !unpaid_months.is_empty() {
                 Some(DebtorItem {
+                    last_reminder: last_reminders.remove(&member.id),
+                    household_name: household_names.remove(&member.id),
                     member,
                     unpaid_months,
                 })
@@ -0,0 +1,284 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::admin::members::payments::format_allocations;
+use crate::mail::{Email, Mailer};
+use crate::model::role::Permission;
+use crate::model::user::User;
+use crate::utils::date_formats;
+use crate::utils::queries::{YearMonth, get_unpaid_months};
+use askama::Template;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum_extra::extract::Form as AxumExtraForm;
+use serde::Deserialize;
+use sqlx::{SqlitePool, query, query_as, query_scalar};
+use std::str::FromStr;
+use strum::{AsRefStr, EnumString};
+use time::{Date, Duration, OffsetDateTime};
+use tracing::{error, info};
+
+#[cfg(test)]
+mod test;
+
+/// A member is not reminded again of the same step before this many days have passed
+const REMINDER_INTERVAL_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum DunningRecipient {
+    Member,
+    Admins,
+}
+
+#[derive(Debug, Clone)]
+pub struct DunningStep {
+    pub step: i64,
+    pub name: String,
+    pub min_unpaid_months: i64,
+    pub recipient: String,
+    pub enabled: bool,
+}
+
+impl DunningStep {
+    pub fn is_admin_alert(&self) -> bool {
+        DunningRecipient::from_str(&self.recipient) == Ok(DunningRecipient::Admins)
+    }
+}
+
+pub async fn get_dunning_steps(pool: &SqlitePool) -> sqlx::Result<Vec<DunningStep>> {
+    query_as!(DunningStep, "select * from dunning_steps order by step")
+        .fetch_all(pool)
+        .await
+}
+
+/// Picks the most severe step the member qualifies for. Nothing is sent if the same or a more
+/// severe step was sent recently, so members are escalated as soon as they owe more months,
+/// but otherwise reminded at most once per interval.
+pub fn select_step<'a>(
+    steps: &'a [DunningStep],
+    unpaid_months: usize,
+    recently_sent_steps: &[i64],
+) -> Option<&'a DunningStep> {
+    let step = steps
+        .iter()
+        .filter(|step| step.enabled && step.min_unpaid_months as usize <= unpaid_months)
+        .max_by_key(|step| (step.min_unpaid_months, step.step))?;
+
+    if recently_sent_steps.iter().any(|sent| *sent >= step.step) {
+        return None;
+    }
+
+    Some(step)
+}
+
+#[derive(Template)]
+#[template(path = "mail/dunning_member.txt")]
+struct MemberReminderTemplate<'a> {
+    member: &'a User,
+    step: &'a DunningStep,
+    months: &'a [String],
+    profile_url: String,
+}
+
+#[derive(Template)]
+#[template(path = "mail/dunning_admins.txt")]
+struct AdminAlertTemplate<'a> {
+    member: &'a User,
+    months: &'a [String],
+    member_url: String,
+}
+
+fn display_months(months: &[YearMonth]) -> Vec<String> {
+    months
+        .iter()
+        .map(|month| {
+            format!(
+                "{} {}",
+                date_formats::month_as_str(&month.month),
+                month.year
+            )
+        })
+        .collect()
+}
+
+/// Builds the emails for a step, either for the member or for every admin
+fn compose_reminder(
+    step: &DunningStep,
+    member: &User,
+    admins: &[User],
+    months: &[YearMonth],
+    public_url: &str,
+) -> askama::Result<Vec<Email>> {
+    let months = display_months(months);
+
+    if step.is_admin_alert() {
+        let body = AdminAlertTemplate {
+            member,
+            months: &months,
+            member_url: format!("{public_url}/admin/members/view/{}", member.id),
+        }
+        .render()?;
+
+        Ok(admins
+            .iter()
+            .map(|admin| Email {
+                to: admin.email.clone(),
+                subject: format!("Restanțe: {} ({} luni)", member.name, months.len()),
+                body: body.clone(),
+            })
+            .collect())
+    } else {
+        Ok(vec![Email {
+            to: member.email.clone(),
+            subject: format!("{} - cotizație ACSPA", step.name),
+            body: MemberReminderTemplate {
+                member,
+                step,
+                months: &months,
+                profile_url: format!("{public_url}/profile"),
+            }
+            .render()?,
+        }])
+    }
+}
+
+/// Sends the reminders that are due and records them, returns how many members were reminded.
+/// Reminders that fail to be delivered are not recorded, so they are retried on the next run.
+pub async fn send_due_reminders(
+    pool: &SqlitePool,
+    mailer: &Mailer,
+    public_url: &str,
+    today: Date,
+    now: OffsetDateTime,
+) -> sqlx::Result<usize> {
+    let dunning_enabled = query_scalar!("select dunning_enabled from global_vars")
+        .fetch_one(pool)
+        .await?;
+    if !dunning_enabled {
+        return Ok(0);
+    }
+
+    let steps = get_dunning_steps(pool).await?;
+    let members = query_as!(
+        User,
+        "select * from users_with_role where is_active = true and admin_panel_access = false"
+    )
+    .fetch_all(pool)
+    .await?;
+    let admins: Vec<_> = query_as!(User, "select * from users_with_role where is_active = true")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .filter(|user| user.has_permission(Permission::ManagePayments))
+        .collect();
+
+    let recent_cutoff = now - Duration::days(REMINDER_INTERVAL_DAYS);
+    let mut reminded = 0;
+    let mut conn = pool.acquire().await?;
+
+    for member in members {
+        let unpaid_months = get_unpaid_months(&mut conn, &member, today).await?;
+        if unpaid_months.is_empty() {
+            continue;
+        }
+
+        let recently_sent_steps = query_scalar!(
+            "select step from dunning_reminders where user_id = $1 and sent_at >= $2",
+            member.id,
+            recent_cutoff
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let Some(step) = select_step(&steps, unpaid_months.len(), &recently_sent_steps) else {
+            continue;
+        };
+
+        let emails = match compose_reminder(step, &member, &admins, &unpaid_months, public_url) {
+            Ok(emails) => emails,
+            Err(e) => {
+                error!("Failed to compose reminder for {}: {e}", member.email);
+                continue;
+            }
+        };
+
+        let mut delivered = false;
+        for email in &emails {
+            match mailer.send(email).await {
+                Ok(()) => delivered = true,
+                Err(e) => error!("Failed to send reminder to {}: {e}", email.to),
+            }
+        }
+        if !delivered {
+            continue;
+        }
+
+        let unpaid_count = unpaid_months.len() as i64;
+        let months = format_allocations(&unpaid_months);
+        query!(
+            "insert into dunning_reminders (user_id, step, unpaid_months, months, sent_at) values ($1, $2, $3, $4, $5)",
+            member.id,
+            step.step,
+            unpaid_count,
+            months,
+            now
+        )
+            .execute(pool)
+            .await?;
+
+        info!(
+            "Sent '{}' to {} for {unpaid_count} unpaid months",
+            step.name, member.email
+        );
+        reminded += 1;
+    }
+
+    Ok(reminded)
+}
+
+#[derive(Deserialize)]
+pub struct UpdatedDunningSteps {
+    step: Vec<i64>,
+    min_unpaid_months: Vec<i64>,
+    #[serde(default)]
+    enabled: Vec<i64>, // From checkboxes, the steps that are enabled
+}
+
+pub async fn update_dunning_steps(
+    State(state): State<AppState>,
+    AxumExtraForm(form): AxumExtraForm<UpdatedDunningSteps>,
+) -> HttpResult {
+    if form.step.len() != form.min_unpaid_months.len() {
+        return Err(HttpError::Message("Formular invalid".into()));
+    }
+
+    let mut thresholds: Vec<_> = form.step.iter().zip(&form.min_unpaid_months).collect();
+    thresholds.sort_by_key(|(step, _)| **step);
+
+    if thresholds.iter().any(|(_, months)| **months <= 0) {
+        return Err(HttpError::Message(
+            "Numărul de luni trebuie să fie pozitiv".into(),
+        ));
+    }
+    if !thresholds.is_sorted_by_key(|(_, months)| **months) {
+        return Err(HttpError::Message(
+            "Pașii trebuie să crească odată cu numărul de luni restante".into(),
+        ));
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+    for (step, min_unpaid_months) in thresholds {
+        let enabled = form.enabled.contains(step);
+        query!(
+            "update dunning_steps set min_unpaid_months = $2, enabled = $3 where step = $1",
+            step,
+            min_unpaid_months,
+            enabled
+        )
+        .execute(tx.as_mut())
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok("Pașii de notificare au fost salvați".into_response())
+}
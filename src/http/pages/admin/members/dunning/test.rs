@@ -0,0 +1,139 @@
+use super::*;
+use sqlx::{SqlitePool, query, query_scalar};
+use std::path::{Path, PathBuf};
+use time::macros::datetime;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        update global_vars set dunning_enabled = TRUE;
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+        (2000, 'test2@test.com', 'Test 2', '', 100, FALSE, '2000-01-01', '2024-01-01');
+
+        -- The second member is on a break for the whole year
+        insert into payment_breaks (user_id, start_date, end_date, created_by)
+        VALUES (2000, '2024-01-01', '2024-12-01', 0);
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn mail_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "acspa_dunning_{name}_{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    ))
+}
+
+fn mailer(directory: &Path) -> Mailer {
+    Mailer::file(directory, "ACSPA <noreply@acspa.ro>".parse().unwrap())
+}
+
+async fn sent_steps(pool: &SqlitePool) -> sqlx::Result<Vec<i64>> {
+    query_scalar!("select step from dunning_reminders order by id")
+        .fetch_all(pool)
+        .await
+}
+
+#[sqlx::test]
+async fn should_escalate_reminders(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let directory = mail_directory("escalate");
+    let mailer = mailer(&directory);
+
+    // One unpaid month: friendly reminder
+    let now = datetime!(2024-01-10 10:00:00 UTC);
+    assert_eq!(
+        send_due_reminders(&pool, &mailer, "", now.date(), now).await?,
+        1
+    );
+    assert_eq!(sent_steps(&pool).await?, vec![1]);
+
+    // Nothing new to say a few days later
+    let now = datetime!(2024-01-20 10:00:00 UTC);
+    assert_eq!(
+        send_due_reminders(&pool, &mailer, "", now.date(), now).await?,
+        0
+    );
+
+    // A second unpaid month escalates right away
+    let now = datetime!(2024-02-01 10:00:00 UTC);
+    assert_eq!(
+        send_due_reminders(&pool, &mailer, "", now.date(), now).await?,
+        1
+    );
+
+    // The third one alerts the admins
+    let now = datetime!(2024-03-01 10:00:00 UTC);
+    assert_eq!(
+        send_due_reminders(&pool, &mailer, "", now.date(), now).await?,
+        1
+    );
+    assert_eq!(sent_steps(&pool).await?, vec![1, 2, 3]);
+
+    let emails = std::fs::read_dir(&directory).unwrap().count();
+    assert_eq!(emails, 3);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_respect_payments_and_breaks(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    query!(
+        r#"
+        insert into payments (id, user_id, amount, payment_date, created_by)
+        VALUES (1, 1000, 10000, '2024-01-05', 0);
+        insert into payment_allocations (payment_id, year, month) VALUES (1, 2024, 1);
+        "#
+    )
+    .execute(&pool)
+    .await?;
+    let directory = mail_directory("paid");
+    let mailer = mailer(&directory);
+
+    let now = datetime!(2024-01-20 10:00:00 UTC);
+    assert_eq!(
+        send_due_reminders(&pool, &mailer, "", now.date(), now).await?,
+        0
+    );
+
+    query!("update global_vars set dunning_enabled = FALSE")
+        .execute(&pool)
+        .await?;
+    let now = datetime!(2024-03-20 10:00:00 UTC);
+    assert_eq!(
+        send_due_reminders(&pool, &mailer, "", now.date(), now).await?,
+        0
+    );
+    assert!(sent_steps(&pool).await?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn should_select_most_severe_step() {
+    let step = |step, min_unpaid_months, enabled| DunningStep {
+        step,
+        name: String::new(),
+        min_unpaid_months,
+        recipient: "member".into(),
+        enabled,
+    };
+    let steps = [step(1, 1, true), step(2, 2, false), step(3, 3, true)];
+    let selected = |unpaid, recent: &[i64]| select_step(&steps, unpaid, recent).map(|s| s.step);
+
+    assert_eq!(selected(0, &[]), None);
+    assert_eq!(selected(1, &[]), Some(1));
+    // Disabled steps are skipped
+    assert_eq!(selected(2, &[]), Some(1));
+    assert_eq!(selected(2, &[1]), None);
+    assert_eq!(selected(5, &[1]), Some(3));
+    assert_eq!(selected(5, &[3]), None);
+}
@@ -0,0 +1,413 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::break_requests::get_break_requests;
+use crate::http::pages::admin::members::households::get_user_household;
+use crate::http::pages::admin::members::lifecycle::{
+    StatusChange, get_status_history, set_membership_status,
+};
+use crate::http::pages::admin::members::payments::format_allocations;
+use crate::http::pages::user::login_attempts::{clear_failures, normalize_email};
+use crate::http::pages::user::sessions::{end_sessions, forget_sessions};
+use crate::model::payment::display_amount;
+use crate::model::user::MembershipStatus;
+use crate::utils::date_formats;
+use crate::utils::queries::{get_user, get_user_payment_breaks, get_user_payments};
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use serde_json::{Value, json};
+use sqlx::{SqliteConnection, SqlitePool, query};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+fn timestamp(time: &OffsetDateTime) -> String {
+    time.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Everything the club stores about a member, in a form they can take with them
+pub async fn export_member_data(pool: &SqlitePool, user_id: i64) -> HttpResult<Value> {
+    let member = get_user(pool, user_id).await?;
+
+    let reservations = query!(
+        "select date, hour, created_for, as_guest, cancelled, in_waiting, created_at
+         from reservations where user_id = $1 order by date, hour",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    let (guests, reservations): (Vec<_>, Vec<_>) = reservations
+        .into_iter()
+        .map(|r| {
+            let is_guest = r.created_for.is_some();
+            let value = json!({
+                "date": date_formats::as_iso(&r.date),
+                "hour": r.hour,
+                "created_for": r.created_for,
+                "as_guest": r.as_guest,
+                "cancelled": r.cancelled,
+                "in_waiting": r.in_waiting,
+                "created_at": timestamp(&r.created_at),
+            });
+            (is_guest, value)
+        })
+        .partition(|(is_guest, _)| *is_guest);
+
+    let payments: Vec<_> = get_user_payments(&mut *pool.acquire().await?, user_id)
+        .await?
+        .into_iter()
+        .map(|p| {
+            json!({
+                "id": p.id,
+                "amount": p.display_amount(),
+                "payment_date": date_formats::as_iso(&p.payment_date),
+                "months": format_allocations(&p.allocations),
+                "notes": p.notes,
+                "receipt_number": p.receipt_number,
+                "voided_at": p.voided_at.as_ref().map(timestamp),
+                "void_reason": p.void_reason,
+                "refund_of": p.refund_of,
+                "created_at": timestamp(&p.created_at),
+            })
+        })
+        .collect();
+
+    let breaks: Vec<_> = get_user_payment_breaks(pool, user_id)
+        .await?
+        .into_iter()
+        .map(|b| {
+            json!({
+                "start_month": date_formats::as_month_year(&b.start_date),
+                "end_month": date_formats::as_month_year(&b.end_date),
+                "reason": b.reason,
+                "created_at": timestamp(&b.created_at),
+            })
+        })
+        .collect();
+
+    let break_requests: Vec<_> = get_break_requests(pool, None, Some(user_id))
+        .await?
+        .into_iter()
+        .map(|r| {
+            json!({
+                "start_month": date_formats::as_month_year(&r.start_date),
+                "end_month": date_formats::as_month_year(&r.end_date),
+                "reason": r.reason,
+                "attachment_name": r.attachment_name,
+                "status": r.status,
+                "rejection_reason": r.rejection_reason,
+                "created_at": timestamp(&r.created_at),
+            })
+        })
+        .collect();
+
+    let status_history: Vec<_> = get_status_history(pool, user_id)
+        .await?
+        .into_iter()
+        .map(|entry| {
+            json!({
+                "status": entry.status,
+                "reason": entry.reason,
+                "until": entry.until.as_ref().map(date_formats::as_iso),
+                "changed_at": timestamp(&entry.changed_at),
+            })
+        })
+        .collect();
+
+    let online_payments: Vec<_> = query!(
+        "select provider, amount, months, status, created_at from online_payments where user_id = $1 order by id",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|p| {
+        json!({
+            "provider": p.provider,
+            "amount": display_amount(p.amount),
+            "months": p.months,
+            "status": p.status,
+            "created_at": timestamp(&p.created_at),
+        })
+    })
+    .collect();
+
+    let bank_accounts = query!(
+        "select iban from member_bank_accounts where user_id = $1",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|account| account.iban)
+    .collect::<Vec<_>>();
+
+    let email = normalize_email(&member.email);
+    let failed_logins: Vec<_> = query!(
+        "select ip, attempted_at from login_attempts where email = $1 order by attempted_at",
+        email
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|attempt| {
+        json!({
+            "ip": attempt.ip,
+            "attempted_at": timestamp(&attempt.attempted_at),
+        })
+    })
+    .collect();
+
+    let sessions: Vec<_> = query!(
+        r#"select user_agent, ip, created_at as "created_at: OffsetDateTime",
+            last_seen_at as "last_seen_at: OffsetDateTime"
+           from user_sessions where user_id = $1 order by created_at"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|session| {
+        json!({
+            "user_agent": session.user_agent,
+            "ip": session.ip,
+            "created_at": timestamp(&session.created_at),
+            "last_seen_at": timestamp(&session.last_seen_at),
+        })
+    })
+    .collect();
+
+    let linked_accounts: Vec<_> = query!(
+        r#"select issuer, email, linked_at as "linked_at: OffsetDateTime"
+           from user_identities where user_id = $1 order by linked_at"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|identity| {
+        json!({
+            "issuer": identity.issuer,
+            "email": identity.email,
+            "linked_at": timestamp(&identity.linked_at),
+        })
+    })
+    .collect();
+
+    let notifications: Vec<_> = query!(
+        r#"select kind, message, link, created_at as "created_at: OffsetDateTime",
+            read_at as "read_at: OffsetDateTime"
+           from notifications where user_id = $1 order by created_at, id"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|notification| {
+        json!({
+            "kind": notification.kind,
+            "message": notification.message,
+            "link": notification.link,
+            "created_at": timestamp(&notification.created_at),
+            "read_at": notification.read_at.as_ref().map(timestamp),
+        })
+    })
+    .collect();
+
+    let push_subscriptions: Vec<_> = query!(
+        r#"select endpoint, created_at as "created_at: OffsetDateTime"
+           from push_subscriptions where user_id = $1 order by created_at"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|subscription| {
+        json!({
+            "endpoint": subscription.endpoint,
+            "created_at": timestamp(&subscription.created_at),
+        })
+    })
+    .collect();
+
+    let household = get_user_household(pool, user_id)
+        .await?
+        .map(|household| household.name);
+
+    Ok(json!({
+        "exported_at": timestamp(&OffsetDateTime::now_utc()),
+        "profile": {
+            "id": member.id,
+            "email": member.email,
+            "name": member.name,
+            "role": member.role,
+            "birthday": date_formats::as_iso(&member.birthday),
+            "member_since": date_formats::as_iso(&member.member_since),
+            "received_gift": member.received_gift.as_ref().map(date_formats::as_iso),
+            "has_key": member.has_key,
+            "two_factor_enabled": member.has_two_factor(),
+            "membership_status": member.membership_status,
+            "household": household,
+            "bank_accounts": bank_accounts,
+            "linked_accounts": linked_accounts,
+        },
+        "reservations": reservations.into_iter().map(|(_, r)| r).collect::<Vec<_>>(),
+        "guests": guests.into_iter().map(|(_, r)| r).collect::<Vec<_>>(),
+        "payments": payments,
+        "online_payments": online_payments,
+        "payment_breaks": breaks,
+        "break_requests": break_requests,
+        "membership_history": status_history,
+        "failed_logins": failed_logins,
+        "sessions": sessions,
+        "notifications": notifications,
+        "push_subscriptions": push_subscriptions,
+    }))
+}
+
+pub fn export_response(user_id: i64, export: &Value) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"date_personale_{user_id}.json\""),
+            ),
+        ],
+        serde_json::to_string_pretty(export).unwrap_or_default(),
+    )
+        .into_response()
+}
+
+/// Irreversibly removes the personal data of a member. Payments, receipts and bank transactions
+/// are kept, as they are needed for accounting, but only reference the pseudonymized member.
+/// Returns the sessions of the member, to be ended in the store once the erasure is committed.
+pub async fn erase_member(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    erased_by: i64,
+) -> HttpResult<Vec<String>> {
+    if user_id == erased_by {
+        return Err(HttpError::Message(
+            "Nu îți poți șterge propriile date".into(),
+        ));
+    }
+
+    // Failed logins and login link requests are tracked by email, which is about to be replaced
+    let member = query!("select email, name from users where id = $1", user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .or_bail("Membrul nu există")?;
+    let email = normalize_email(&member.email);
+    clear_failures(&mut *tx, &email).await?;
+    query!("delete from magic_link_requests where email = $1", email)
+        .execute(&mut *tx)
+        .await?;
+
+    // The reservations of the member are deleted with the status change, but other members
+    // might have invited them as a guest before they joined
+    query!(
+        "update reservations set created_for = 'Invitat șters #' || _rowid_
+         where user_id != $1 and lower(trim(created_for)) = lower(trim($2))",
+        user_id,
+        member.name
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    set_membership_status(
+        &mut *tx,
+        user_id,
+        StatusChange {
+            status: MembershipStatus::Deleted,
+            reason: None,
+            until: None,
+            changed_by: Some(erased_by),
+        },
+    )
+    .await?;
+
+    query!(
+        "update users set email = 'erased_' || id || '@archived.acspa.ro', name = 'Membru șters #' || id,
+            password_hash = 'REMOVED', has_key = FALSE, birthday = '1970-01-01', received_gift = NULL,
+            status_reason = NULL, status_until = NULL, erased_at = CURRENT_TIMESTAMP,
+            totp_secret = NULL, totp_last_step = NULL
+         where id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    query!("delete from break_requests where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!(
+        "delete from member_bank_accounts where user_id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!("delete from push_subscriptions where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from notifications where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from user_identities where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from login_tokens where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from recovery_codes where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from household_members where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from dunning_reminders where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    query!(
+        "update payment_breaks set reason = NULL where user_id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(
+        "update membership_status_history set reason = NULL where user_id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let session_ids = forget_sessions(&mut *tx, user_id, None).await?;
+
+    info!("Personal data of user {user_id} erased by admin {erased_by}");
+
+    Ok(session_ids)
+}
+
+pub async fn export_member(State(state): State<AppState>, Path(user_id): Path<i64>) -> HttpResult {
+    let export = export_member_data(&state.read_pool, user_id).await?;
+    Ok(export_response(user_id, &export))
+}
+
+pub async fn erase_member_data(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(user_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    let session_ids = erase_member(tx.as_mut(), user_id, user.id).await?;
+    tx.commit().await?;
+    end_sessions(&state.session_store, &session_ids).await?;
+
+    Ok([("HX-Redirect", "/admin/members")].into_response())
+}
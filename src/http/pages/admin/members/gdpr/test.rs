@@ -0,0 +1,160 @@
+use super::*;
+use sqlx::query_scalar;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since, totp_secret)
+        VALUES (1000, 'test1@test.com', 'Ion Popescu', 'hash', 100, TRUE, '2000-05-01', '2024-01-01', 'JBSWY3DPEHPK3PXP');
+        insert into recovery_codes (user_id, code_hash) VALUES (1000, 'hash');
+        insert into login_attempts (email, ip, attempted_at) VALUES ('test1@test.com', '10.0.0.1', '2024-03-01 10:00:00');
+        insert into user_sessions (session_id, user_id, user_agent, ip, created_at, last_seen_at)
+        VALUES ('session', 1000, 'Firefox', '10.0.0.1', '2024-03-01 10:00:00', '2024-03-01 11:00:00');
+        insert into login_tokens (user_id, token_hash, created_at, expires_at)
+        VALUES (1000, 'token_hash', '2024-03-01 10:00:00', '2024-03-01 10:15:00');
+        insert into user_identities (user_id, issuer, subject, email, linked_at)
+        VALUES (1000, 'https://accounts.google.com', '1234', 'ion@gmail.com', '2024-03-01 10:00:00');
+        insert into notifications (user_id, kind, message)
+        VALUES (1000, 'payment', 'Plata de 100 RON a fost înregistrată');
+        insert into push_subscriptions (user_id, endpoint) VALUES (1000, 'https://push.example.com/1');
+        insert into magic_link_requests (email, ip, requested_at) VALUES ('test1@test.com', '10.0.0.1', '2024-03-01 10:00:00');
+        insert into login_lockouts (email, locked_until) VALUES ('test1@test.com', '2024-03-01 10:30:00');
+        insert into locations (id, name, slot_capacity, slots_start_hour, slot_duration, slots_per_day)
+        VALUES (100, 'test_location', 1, 18, 2, 2);
+        insert into reservations (user_id, date, hour, location, created_for)
+        VALUES (1000, '2024-03-01', 18, 100, null), (1000, '2024-03-02', 18, 100, 'Invitat');
+        insert into payments (id, user_id, amount, payment_date, notes, created_by)
+        VALUES (1, 1000, 10000, '2024-03-01', 'Cash', 0);
+        insert into payment_allocations (payment_id, year, month) VALUES (1, 2024, 1), (1, 2024, 2);
+        insert into payment_breaks (user_id, start_date, end_date, reason, created_by)
+        VALUES (1000, '2024-06-01', '2024-07-01', 'Operație la genunchi', 0);
+        insert into break_requests (user_id, start_date, end_date, reason)
+        VALUES (1000, '2024-09-01', '2024-09-01', 'Concediu medical');
+        insert into member_bank_accounts (iban, user_id) VALUES ('RO49AAAA1B31007593840000', 1000);
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_export_all_member_data(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let export = export_member_data(&pool, 1000).await.unwrap();
+
+    assert_eq!(export["profile"]["name"], "Ion Popescu");
+    assert_eq!(export["profile"]["birthday"], "2000-05-01");
+    assert_eq!(
+        export["profile"]["bank_accounts"][0],
+        "RO49AAAA1B31007593840000"
+    );
+    assert_eq!(export["reservations"].as_array().unwrap().len(), 1);
+    assert_eq!(export["guests"][0]["created_for"], "Invitat");
+    assert_eq!(export["payments"][0]["amount"], "100");
+    assert_eq!(
+        export["payment_breaks"][0]["reason"],
+        "Operație la genunchi"
+    );
+    assert_eq!(export["break_requests"][0]["reason"], "Concediu medical");
+    assert_eq!(export["profile"]["two_factor_enabled"], true);
+    assert_eq!(export["failed_logins"][0]["ip"], "10.0.0.1");
+    assert_eq!(export["sessions"][0]["user_agent"], "Firefox");
+    assert_eq!(
+        export["notifications"][0]["message"],
+        "Plata de 100 RON a fost înregistrată"
+    );
+    assert_eq!(
+        export["push_subscriptions"][0]["endpoint"],
+        "https://push.example.com/1"
+    );
+    assert_eq!(
+        export["profile"]["linked_accounts"][0]["email"],
+        "ion@gmail.com"
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_erase_personal_data_but_keep_payments(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    // Invited as a guest by another member, before joining
+    query!(
+        r#"
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1001, 'test2@test.com', 'Maria Ionescu', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        insert into reservations (user_id, date, hour, location, created_for)
+        VALUES (1001, '2023-12-01', 18, 100, 'ion popescu '), (1001, '2023-12-01', 18, 100, 'Alt Invitat');
+        "#
+    )
+    .execute(&pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let session_ids = erase_member(tx.as_mut(), 1000, 0).await.unwrap();
+    tx.commit().await?;
+    assert_eq!(session_ids, vec!["session".to_string()]);
+
+    let member = query!(
+        "select email, name, birthday, has_key, is_deleted, erased_at from users where id = 1000"
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(member.email, "erased_1000@archived.acspa.ro");
+    assert_eq!(member.name, "Membru șters #1000");
+    assert_eq!(member.birthday.to_string(), "1970-01-01");
+    assert!(!member.has_key);
+    assert!(member.is_deleted);
+    assert!(member.erased_at.is_some());
+
+    // Accounting records are kept
+    let payments = query_scalar!("select count(*) from payment_allocations where payment_id = 1")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(payments, 2);
+
+    let leftovers = query_scalar!(
+        "select (select count(*) from reservations where user_id = 1000)
+            + (select count(*) from break_requests where user_id = 1000)
+            + (select count(*) from member_bank_accounts where user_id = 1000)
+            + (select count(*) from payment_breaks where user_id = 1000 and reason is not null)
+            + (select count(*) from users where id = 1000 and totp_secret is not null)
+            + (select count(*) from recovery_codes where user_id = 1000)
+            + (select count(*) from login_attempts where email = 'test1@test.com')
+            + (select count(*) from login_lockouts where email = 'test1@test.com')
+            + (select count(*) from user_sessions where user_id = 1000)
+            + (select count(*) from login_tokens where user_id = 1000)
+            + (select count(*) from magic_link_requests where email = 'test1@test.com')
+            + (select count(*) from user_identities where user_id = 1000)
+            + (select count(*) from notifications where user_id = 1000)
+            + (select count(*) from push_subscriptions where user_id = 1000)"
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(leftovers, 0);
+
+    let guests = query_scalar!(
+        "select created_for as 'created_for!' from reservations where user_id = 1001 order by created_for"
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(guests.len(), 2);
+    assert_eq!(guests[0], "Alt Invitat");
+    assert!(guests[1].starts_with("Invitat șters #"));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_not_erase_own_data(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let mut tx = pool.begin().await?;
+    assert!(erase_member(tx.as_mut(), 1000, 1000).await.is_err());
+
+    Ok(())
+}
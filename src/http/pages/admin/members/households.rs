@@ -0,0 +1,560 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::payments::{
+    NewPaymentRecord, create_payment, parse_requested_allocations,
+};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::payment::display_amount;
+use crate::model::user::User;
+use crate::utils::queries::{
+    MonthStatus, YearMonth, calculate_payable_months, get_global_vars, get_user,
+    get_user_payment_breaks, get_user_payments,
+};
+use crate::utils::{date_formats, local_date};
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::routing::{delete, get, post};
+use axum::{Form, Router};
+use axum_extra::extract::Form as AxumExtraForm;
+use itertools::Itertools;
+use serde::Deserialize;
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as};
+use time::Date;
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(households_page))
+        .route("/", post(create_household))
+        .route("/{id}", get(household_page))
+        .route("/{id}", post(update_household))
+        .route("/{id}", delete(delete_household))
+        .route("/{id}/members", post(add_member))
+        .route("/{id}/members/{user_id}", delete(remove_member))
+        .route("/{id}/payments", post(add_household_payment))
+}
+
+#[derive(Debug, Clone)]
+pub struct HouseholdMember {
+    pub user_id: i64,
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Household {
+    pub id: i64,
+    pub name: String,
+    pub discount_percent: i64,
+    pub members: Vec<HouseholdMember>,
+}
+
+pub async fn get_households(executor: impl SqliteExecutor<'_>) -> sqlx::Result<Vec<Household>> {
+    let rows = query!(
+        r#"select h.id, h.name, h.discount_percent, u.id as 'user_id?', u.name as 'member_name?',
+            u.email as 'member_email?'
+         from households h
+            left join household_members hm on hm.household_id = h.id
+            left join users u on u.id = hm.user_id and u.is_deleted = false
+         order by h.name, u.name"#
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .chunk_by(|row| row.id)
+        .into_iter()
+        .filter_map(|(_, rows)| {
+            let rows: Vec<_> = rows.collect();
+            let first = rows.first()?;
+            Some(Household {
+                id: first.id,
+                name: first.name.clone(),
+                discount_percent: first.discount_percent,
+                members: rows
+                    .iter()
+                    .filter_map(|row| {
+                        Some(HouseholdMember {
+                            user_id: row.user_id?,
+                            name: row.member_name.clone()?,
+                            email: row.member_email.clone()?,
+                        })
+                    })
+                    .collect(),
+            })
+        })
+        .collect())
+}
+
+pub async fn get_household(
+    executor: impl SqliteExecutor<'_>,
+    household_id: i64,
+) -> sqlx::Result<Option<Household>> {
+    Ok(get_households(executor)
+        .await?
+        .into_iter()
+        .find(|household| household.id == household_id))
+}
+
+pub async fn get_user_household(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<Option<Household>> {
+    Ok(get_households(executor)
+        .await?
+        .into_iter()
+        .find(|household| {
+            household
+                .members
+                .iter()
+                .any(|member| member.user_id == user_id)
+        }))
+}
+
+/// The price of `months` monthly fees, after the household discount
+pub fn discounted_amount(monthly_fee: i64, months: usize, discount_percent: i64) -> i64 {
+    let full_amount = monthly_fee * months as i64;
+    (full_amount * (100 - discount_percent) + 50) / 100
+}
+
+/// Splits `total` proportionally to the number of months of each member,
+/// giving the leftover cents to the first ones so the parts always add up to the total
+pub fn split_amount(total: i64, shares: &[usize]) -> Vec<i64> {
+    let total_shares: usize = shares.iter().sum();
+    if total_shares == 0 {
+        return vec![0; shares.len()];
+    }
+
+    let mut parts: Vec<_> = shares
+        .iter()
+        .map(|share| total * *share as i64 / total_shares as i64)
+        .collect();
+    let mut remainder = total - parts.iter().sum::<i64>();
+    for (part, share) in parts.iter_mut().zip(shares) {
+        if remainder == 0 {
+            break;
+        }
+        if *share > 0 {
+            *part += 1;
+            remainder -= 1;
+        }
+    }
+
+    parts
+}
+
+pub struct NewHouseholdPayment<'a> {
+    pub household_id: i64,
+    /// When missing, it's computed from the monthly fee and the household discount
+    pub amount: Option<i64>,
+    pub payment_date: Date,
+    pub notes: Option<&'a str>,
+    pub allocations: &'a [(i64, Vec<YearMonth>)],
+    pub created_by: i64,
+}
+
+/// Records a single payment for several members of a household.
+/// Each member gets their own payment for their months, with a share of the amount,
+/// so the months are covered exactly as if they were paid individually.
+pub async fn create_household_payment(
+    tx: &mut SqliteConnection,
+    payment: NewHouseholdPayment<'_>,
+    monthly_fee: i64,
+) -> HttpResult<i64> {
+    let household = get_household(&mut *tx, payment.household_id)
+        .await?
+        .or_bail("Familia nu există")?;
+
+    let allocations: Vec<_> = payment
+        .allocations
+        .iter()
+        .filter(|(_, months)| !months.is_empty())
+        .collect();
+    if allocations.is_empty() {
+        return Err(HttpError::Message(
+            "O plată trebuie să acopere cel puțin o lună validă".into(),
+        ));
+    }
+    for (member_id, _) in &allocations {
+        if !household.members.iter().any(|m| m.user_id == *member_id) {
+            return Err(HttpError::Message(
+                "Plata poate acoperi doar membrii familiei".into(),
+            ));
+        }
+    }
+
+    let shares: Vec<_> = allocations.iter().map(|(_, months)| months.len()).collect();
+    let amount = match payment.amount {
+        Some(amount) => amount,
+        None if monthly_fee > 0 => {
+            discounted_amount(monthly_fee, shares.iter().sum(), household.discount_percent)
+        }
+        None => {
+            return Err(HttpError::Message(
+                "Suma este obligatorie cât timp cotizația lunară nu este configurată".into(),
+            ));
+        }
+    };
+    // Fully discounted households still get their months recorded, with zero-amount payments
+    let is_free = household.discount_percent == 100;
+    if amount < 0 || (amount == 0 && !is_free) {
+        return Err(HttpError::Message("Suma trebuie să fie pozitivă".into()));
+    }
+
+    let household_payment_id = query!(
+        "insert into household_payments (household_id, household_name, amount, discount_percent, payment_date, notes, created_by)
+         values ($1, $2, $3, $4, $5, $6, $7) returning id",
+        household.id,
+        household.name,
+        amount,
+        household.discount_percent,
+        payment.payment_date,
+        payment.notes,
+        payment.created_by
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    let notes = match payment.notes {
+        Some(notes) => format!("Plată familie {}: {notes}", household.name),
+        None => format!("Plată familie {}", household.name),
+    };
+    for ((member_id, months), member_amount) in
+        allocations.iter().zip(split_amount(amount, &shares))
+    {
+        let payment_id = create_payment(
+            &mut *tx,
+            NewPaymentRecord {
+                member_id: *member_id,
+                amount: member_amount,
+                payment_date: payment.payment_date,
+                notes: Some(&notes),
+                allocations: months,
+                created_by: payment.created_by,
+            },
+        )
+        .await?;
+
+        query!(
+            "update payments set household_payment_id = $2 where id = $1",
+            payment_id,
+            household_payment_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    info!(
+        "Household payment added: household {} paid {} for {} members (Req by Admin {})",
+        household.id,
+        display_amount(amount),
+        allocations.len(),
+        payment.created_by
+    );
+
+    Ok(household_payment_id)
+}
+
+fn validate_discount(discount_percent: i64) -> HttpResult<()> {
+    if !(0..=100).contains(&discount_percent) {
+        return Err(HttpError::Message(
+            "Reducerea trebuie să fie între 0% și 100%".into(),
+        ));
+    }
+    Ok(())
+}
+
+async fn households_page(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/households_page.html")]
+    struct HouseholdsTemplate {
+        user: User,
+        households: Vec<Household>,
+    }
+
+    HouseholdsTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        households: get_households(&state.read_pool).await?,
+    }
+    .try_into_response()
+}
+
+/// A member of the household together with the months that can still be paid for them
+pub struct PayableMember {
+    pub member: HouseholdMember,
+    pub months: Vec<(YearMonth, MonthStatus)>,
+}
+
+impl PayableMember {
+    pub fn month_value(&self, month: &YearMonth) -> String {
+        format!(
+            "{}:{}-{}",
+            self.member.user_id, month.month as u8, month.year
+        )
+    }
+
+    pub fn is_overdue(status: &MonthStatus) -> bool {
+        *status == MonthStatus::Unpaid
+    }
+
+    pub fn display_month(month: &YearMonth) -> String {
+        format!(
+            "{} {}",
+            date_formats::month_as_str(&month.month),
+            month.year
+        )
+    }
+}
+
+async fn household_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(household_id): Path<i64>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/household_page.html")]
+    struct HouseholdTemplate {
+        user: User,
+        household: Household,
+        payable_members: Vec<PayableMember>,
+        available_members: Vec<User>,
+        /// Only known when the monthly fee is configured
+        monthly_fee: Option<String>,
+        current_date: Date,
+    }
+
+    let household = get_household(&state.read_pool, household_id)
+        .await?
+        .or_bail("Familia nu există")?;
+    let current_date = local_date();
+
+    let mut payable_members = Vec::with_capacity(household.members.len());
+    for household_member in &household.members {
+        let member = get_user(&state.read_pool, household_member.user_id).await?;
+        let payments = get_user_payments(&mut *state.read_pool.acquire().await?, member.id).await?;
+        let breaks = get_user_payment_breaks(&state.read_pool, member.id).await?;
+        payable_members.push(PayableMember {
+            member: household_member.clone(),
+            months: calculate_payable_months(&member, &payments, &breaks, current_date),
+        });
+    }
+
+    let available_members = query_as!(
+        User,
+        "select * from users_with_role
+         where id not in (select user_id from household_members) order by name"
+    )
+    .fetch_all(&state.read_pool)
+    .await?;
+    let global_vars = get_global_vars(&state.read_pool).await?;
+
+    HouseholdTemplate {
+        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        household,
+        payable_members,
+        available_members,
+        monthly_fee: (global_vars.monthly_fee > 0).then(|| global_vars.display_monthly_fee()),
+        current_date,
+    }
+    .try_into_response()
+}
+
+#[derive(Deserialize)]
+struct HouseholdForm {
+    name: String,
+    discount_percent: i64,
+}
+
+async fn create_household(
+    State(state): State<AppState>,
+    Form(form): Form<HouseholdForm>,
+) -> HttpResult {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(HttpError::Message(
+            "Numele familiei este obligatoriu".into(),
+        ));
+    }
+    validate_discount(form.discount_percent)?;
+
+    let household_id = query!(
+        "insert into households (name, discount_percent) values ($1, $2) returning id",
+        name,
+        form.discount_percent
+    )
+    .fetch_one(&state.write_pool)
+    .await
+    .or_bail("Există deja o familie cu acest nume")?
+    .id;
+
+    Ok([(
+        "HX-Redirect",
+        format!("/admin/members/households/{household_id}"),
+    )]
+    .into_response())
+}
+
+async fn update_household(
+    State(state): State<AppState>,
+    Path(household_id): Path<i64>,
+    Form(form): Form<HouseholdForm>,
+) -> HttpResult {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(HttpError::Message(
+            "Numele familiei este obligatoriu".into(),
+        ));
+    }
+    validate_discount(form.discount_percent)?;
+
+    query!(
+        "update households set name = $2, discount_percent = $3 where id = $1",
+        household_id,
+        name,
+        form.discount_percent
+    )
+    .execute(&state.write_pool)
+    .await
+    .or_bail("Există deja o familie cu acest nume")?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+/// Payments already made by the household are kept, only the grouping is removed
+async fn delete_household(
+    State(state): State<AppState>,
+    Path(household_id): Path<i64>,
+) -> HttpResult {
+    query!("delete from households where id = $1", household_id)
+        .execute(&state.write_pool)
+        .await?;
+
+    Ok([("HX-Redirect", "/admin/members/households")].into_response())
+}
+
+#[derive(Deserialize)]
+struct NewMember {
+    user_id: i64,
+}
+
+async fn add_member(
+    State(state): State<AppState>,
+    Path(household_id): Path<i64>,
+    Form(form): Form<NewMember>,
+) -> HttpResult {
+    query!(
+        "insert into household_members (household_id, user_id) values ($1, $2)",
+        household_id,
+        form.user_id
+    )
+    .execute(&state.write_pool)
+    .await
+    .or_bail("Membrul face deja parte dintr-o familie")?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+async fn remove_member(
+    State(state): State<AppState>,
+    Path((household_id, user_id)): Path<(i64, i64)>,
+) -> HttpResult {
+    query!(
+        "delete from household_members where household_id = $1 and user_id = $2",
+        household_id,
+        user_id
+    )
+    .execute(&state.write_pool)
+    .await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+#[derive(Deserialize)]
+struct HouseholdPaymentForm {
+    /// Left empty to use the discounted monthly fee
+    amount: String,
+    payment_date: Date,
+    #[serde(default)]
+    months: Vec<String>, // From checkboxes (format: "userId:M-YYYY")
+    notes: Option<String>,
+}
+
+/// Groups the "userId:M-YYYY" values by member, keeping only valid months of each
+async fn parse_household_allocations(
+    state: &AppState,
+    values: &[String],
+    today: Date,
+) -> HttpResult<Vec<(i64, Vec<YearMonth>)>> {
+    let grouped = values
+        .iter()
+        .filter_map(|value| {
+            let (user_id, month) = value.split_once(':')?;
+            Some((user_id.parse::<i64>().ok()?, month))
+        })
+        .into_group_map();
+
+    let mut allocations = Vec::with_capacity(grouped.len());
+    for (user_id, months) in grouped.into_iter().sorted_by_key(|(user_id, _)| *user_id) {
+        let member = get_user(&state.read_pool, user_id).await?;
+        allocations.push((
+            user_id,
+            parse_requested_allocations(&member, months, today)?,
+        ));
+    }
+
+    Ok(allocations)
+}
+
+async fn add_household_payment(
+    State(state): State<AppState>,
+    Path(household_id): Path<i64>,
+    auth_session: AuthSession,
+    AxumExtraForm(form): AxumExtraForm<HouseholdPaymentForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let amount = match form.amount.trim() {
+        "" => None,
+        amount => {
+            let amount = amount
+                .parse::<f64>()
+                .ok()
+                .or_bail("Suma introdusă nu este validă")?;
+            Some((amount * 100.0).round() as i64)
+        }
+    };
+    let allocations = parse_household_allocations(&state, &form.months, local_date()).await?;
+    let monthly_fee = get_global_vars(&state.read_pool).await?.monthly_fee;
+    let notes = form.notes.filter(|notes| !notes.trim().is_empty());
+
+    let mut tx = state.write_pool.begin().await?;
+    create_household_payment(
+        tx.as_mut(),
+        NewHouseholdPayment {
+            household_id,
+            amount,
+            payment_date: form.payment_date,
+            notes: notes.as_deref(),
+            allocations: &allocations,
+            created_by: user.id,
+        },
+        monthly_fee,
+    )
+    .await?;
+    tx.commit().await?;
+
+    for (member_id, months) in &allocations {
+        if !months.is_empty() {
+            let _ = state.notification_notifier.send(*member_id);
+        }
+    }
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
@@ -0,0 +1,196 @@
+use super::*;
+use crate::utils::queries::get_unpaid_months;
+use sqlx::{SqlitePool, query_scalar};
+use time::Month;
+use time::macros::date;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'parent@test.com', 'Parent', '', 100, FALSE, '1980-01-01', '2024-01-01'),
+               (1001, 'child@test.com', 'Child', '', 100, FALSE, '2010-01-01', '2024-01-01'),
+               (1002, 'other@test.com', 'Other', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        insert into households (id, name, discount_percent) VALUES (1, 'Popescu', 20);
+        insert into household_members (household_id, user_id) VALUES (1, 1000), (1, 1001);
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn pay(
+    pool: &SqlitePool,
+    amount: Option<i64>,
+    allocations: &[(i64, Vec<YearMonth>)],
+) -> HttpResult<i64> {
+    let mut tx = pool.begin().await?;
+    let id = create_household_payment(
+        tx.as_mut(),
+        NewHouseholdPayment {
+            household_id: 1,
+            amount,
+            payment_date: date!(2024 - 03 - 05),
+            notes: None,
+            allocations,
+            created_by: 0,
+        },
+        5000,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+#[test]
+fn should_split_amounts_by_months() {
+    assert_eq!(split_amount(10000, &[3, 1]), vec![7500, 2500]);
+    assert_eq!(split_amount(1000, &[1, 1, 1]), vec![334, 333, 333]);
+    assert_eq!(split_amount(1001, &[2, 0, 1]), vec![668, 0, 333]);
+}
+
+#[test]
+fn should_apply_household_discount() {
+    assert_eq!(discounted_amount(5000, 4, 20), 16000);
+    assert_eq!(discounted_amount(3333, 1, 10), 3000);
+    assert_eq!(discounted_amount(5000, 2, 0), 10000);
+}
+
+#[sqlx::test]
+async fn should_cover_months_of_every_member(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    pay(
+        &pool,
+        None,
+        &[
+            (
+                1000,
+                vec![
+                    YearMonth::new(2024, Month::January),
+                    YearMonth::new(2024, Month::February),
+                    YearMonth::new(2024, Month::March),
+                ],
+            ),
+            (
+                1001,
+                vec![
+                    YearMonth::new(2024, Month::January),
+                    YearMonth::new(2024, Month::February),
+                    YearMonth::new(2024, Month::March),
+                ],
+            ),
+        ],
+    )
+    .await
+    .unwrap();
+
+    // 6 months at 50 RON with 20% off, split equally
+    let amounts = query_scalar!(
+        "select amount from payments where household_payment_id is not null order by user_id"
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(amounts, vec![12000, 12000]);
+
+    let household_amount = query_scalar!("select amount from household_payments")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(household_amount, 24000);
+
+    let mut conn = pool.acquire().await?;
+    for user_id in [1000, 1001] {
+        let member = get_user(&pool, user_id).await?;
+        let unpaid = get_unpaid_months(conn.as_mut(), &member, date!(2024 - 03 - 20))
+            .await?
+            .len();
+        assert_eq!(unpaid, 0);
+    }
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_reject_members_outside_household(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let result = pay(
+        &pool,
+        Some(10000),
+        &[
+            (1000, vec![YearMonth::new(2024, Month::January)]),
+            (1002, vec![YearMonth::new(2024, Month::January)]),
+        ],
+    )
+    .await;
+    assert!(result.is_err());
+
+    let payments = query_scalar!("select count(*) from payments")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(payments, 0);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_reject_months_already_paid(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    pay(
+        &pool,
+        Some(5000),
+        &[(1001, vec![YearMonth::new(2024, Month::January)])],
+    )
+    .await
+    .unwrap();
+
+    let result = pay(
+        &pool,
+        Some(10000),
+        &[
+            (1000, vec![YearMonth::new(2024, Month::January)]),
+            (1001, vec![YearMonth::new(2024, Month::January)]),
+        ],
+    )
+    .await;
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_record_months_of_fully_discounted_household(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let allocations = [
+        (1000, vec![YearMonth::new(2024, Month::January)]),
+        (1001, vec![YearMonth::new(2024, Month::January)]),
+    ];
+
+    // Only free when the household doesn't pay anything
+    assert!(pay(&pool, Some(0), &allocations).await.is_err());
+
+    query!("update households set discount_percent = 100 where id = 1")
+        .execute(&pool)
+        .await?;
+    pay(&pool, None, &allocations).await.unwrap();
+
+    let amounts = query_scalar!(
+        "select amount from payments where household_payment_id is not null order by user_id"
+    )
+    .fetch_all(&pool)
+    .await?;
+    assert_eq!(amounts, vec![0, 0]);
+
+    let mut conn = pool.acquire().await?;
+    for user_id in [1000, 1001] {
+        let member = get_user(&pool, user_id).await?;
+        let unpaid = get_unpaid_months(conn.as_mut(), &member, date!(2024 - 01 - 20)).await?;
+        assert!(unpaid.is_empty());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use crate::http::AppState;
+use crate::http::auth::UserAuthenticator;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::notification_template::{add_page_banner, error_bubble_response};
+use crate::http::pages::user::sessions::forget_session;
+use crate::model::role::Permission;
+use crate::utils::queries::get_user;
+use askama::Template;
+use axum::extract::{Path, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Form, Router};
+use axum_login::tower_sessions::Session;
+use axum_login::{AuthnBackend, permission_required};
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteExecutor, query, query_as, query_scalar};
+use time::OffsetDateTime;
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+/// Set while an admin is logged in as a member, so they can get back to their own account
+const IMPERSONATION_SESSION_KEY: &str = "impersonation";
+/// Posts allowed in read-only mode, because they only show something or end the impersonation.
+/// Choosing an hour runs all the reservation checks without booking, which is what's usually debugged.
+const READ_ONLY_ALLOWED_PATHS: [&str; 4] = [
+    "/choose_hour",
+    "/profile/reservations",
+    "/impersonation/stop",
+    "/logout",
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct Impersonation {
+    pub admin_email: String,
+    pub member_id: i64,
+    pub member_name: String,
+    /// Requests that change anything are refused
+    pub read_only: bool,
+    pub log_id: i64,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/impersonate/{id}", post(start_impersonation))
+        .route_layer(permission_required!(
+            UserAuthenticator,
+            login_url = "/",
+            Permission::ImpersonateMembers.as_ref()
+        ))
+}
+
+pub async fn get_impersonation(session: &Session) -> Option<Impersonation> {
+    session
+        .get::<Impersonation>(IMPERSONATION_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+}
+
+pub async fn log_impersonation_start(
+    executor: impl SqliteExecutor<'_>,
+    admin_id: i64,
+    member_id: i64,
+    read_only: bool,
+    now: OffsetDateTime,
+) -> sqlx::Result<i64> {
+    query_scalar!(
+        "insert into impersonations (admin_id, member_id, read_only, started_at)
+         values ($1, $2, $3, $4) returning id",
+        admin_id,
+        member_id,
+        read_only,
+        now
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn log_impersonation_end(
+    executor: impl SqliteExecutor<'_>,
+    log_id: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    query!(
+        "update impersonations set ended_at = $2 where id = $1 and ended_at is null",
+        log_id,
+        now
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Takes the impersonation out of the session and closes its log entry, returning it if there was one
+pub async fn end_impersonation(
+    executor: impl SqliteExecutor<'_>,
+    session: &Session,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<Impersonation>> {
+    let Some(impersonation) = get_impersonation(session).await else {
+        return Ok(None);
+    };
+
+    log_impersonation_end(executor, impersonation.log_id, now).await?;
+    let _ = session
+        .remove::<Impersonation>(IMPERSONATION_SESSION_KEY)
+        .await;
+
+    Ok(Some(impersonation))
+}
+
+pub struct ImpersonationLogEntry {
+    pub admin_name: String,
+    pub read_only: bool,
+    pub started_at: OffsetDateTime,
+    pub ended_at: Option<OffsetDateTime>,
+}
+
+pub async fn get_impersonation_log(
+    executor: impl SqliteExecutor<'_>,
+    member_id: i64,
+) -> sqlx::Result<Vec<ImpersonationLogEntry>> {
+    query_as!(
+        ImpersonationLogEntry,
+        r#"select u.name as admin_name, i.read_only, i.started_at as "started_at: OffsetDateTime",
+            i.ended_at as "ended_at: OffsetDateTime"
+           from impersonations i join users u on i.admin_id = u.id
+           where i.member_id = $1 order by i.started_at desc limit 20"#,
+        member_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+#[derive(Deserialize)]
+pub struct ImpersonationForm {
+    read_only: Option<String>,
+}
+
+async fn start_impersonation(
+    State(state): State<AppState>,
+    mut auth_session: AuthSession,
+    Path(member_id): Path<i64>,
+    Form(form): Form<ImpersonationForm>,
+) -> HttpResult {
+    let admin = auth_session.user.clone().ok_or(HttpError::Unauthorized)?;
+    let member = get_user(&state.read_pool, member_id).await?;
+
+    if member.id == admin.id {
+        return Err(HttpError::Message("Nu te poți vizualiza pe tine".into()));
+    }
+    // Otherwise the admin would gain the permissions of the other role
+    if !member.permissions.is_empty() {
+        return Err(HttpError::Message(
+            "Nu poți vizualiza aplicația ca un membru cu permisiuni de administrare".into(),
+        ));
+    }
+    if !member.membership_status().can_log_in() {
+        return Err(HttpError::Message(
+            "Membrul nu se mai poate autentifica".into(),
+        ));
+    }
+
+    let read_only = form.read_only.is_some();
+    let log_id = log_impersonation_start(
+        &state.write_pool,
+        admin.id,
+        member.id,
+        read_only,
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+
+    // Logging in as the member replaces the session id, the admin's one is tracked again once they're back
+    if let Some(session_id) = auth_session.session.id() {
+        forget_session(&state.write_pool, admin.id, &session_id.to_string()).await?;
+    }
+
+    let impersonation = Impersonation {
+        admin_email: admin.email.clone(),
+        member_id: member.id,
+        member_name: member.name.clone(),
+        read_only,
+        log_id,
+    };
+    auth_session
+        .session
+        .insert(IMPERSONATION_SESSION_KEY, impersonation)
+        .await
+        .or_bail("Sesiunea nu a putut fi salvată")?;
+    auth_session
+        .login(&member)
+        .await
+        .or_bail("Nu te-ai putut autentifica ca membrul")?;
+    info!(
+        "User {} started impersonating user {}, read only: {read_only}",
+        admin.id, member.id
+    );
+
+    Ok([("HX-Redirect", "/")].into_response())
+}
+
+pub async fn stop_impersonation(
+    State(state): State<AppState>,
+    mut auth_session: AuthSession,
+) -> HttpResult {
+    let impersonation = end_impersonation(
+        &state.write_pool,
+        &auth_session.session,
+        OffsetDateTime::now_utc(),
+    )
+    .await?
+    .or_bail("Nu vizualizezi aplicația ca un alt membru")?;
+
+    let admin = auth_session
+        .backend
+        .get_user(&impersonation.admin_email)
+        .await
+        .or_bail("Contul tău nu a putut fi încărcat")?;
+    let Some(admin) = admin else {
+        // The admin lost access in the meantime
+        let _ = auth_session.logout().await;
+        return Ok([("HX-Redirect", "/")].into_response());
+    };
+
+    auth_session
+        .login(&admin)
+        .await
+        .or_bail("Nu te-ai putut autentifica din nou")?;
+    info!(
+        "User {} stopped impersonating user {}",
+        admin.id, impersonation.member_id
+    );
+
+    Ok([(
+        "HX-Redirect",
+        format!("/admin/members/view/{}", impersonation.member_id),
+    )]
+    .into_response())
+}
+
+#[derive(Template)]
+#[template(path = "components/impersonation_banner.html")]
+struct BannerTemplate<'a> {
+    impersonation: &'a Impersonation,
+}
+
+/// Whether the request would change something the read-only impersonation doesn't allow
+fn is_refused(impersonation: &Impersonation, method: &Method, path: &str) -> bool {
+    let is_change = !matches!(*method, Method::GET | Method::HEAD);
+    impersonation.read_only && is_change && !READ_ONLY_ALLOWED_PATHS.contains(&path)
+}
+
+/// Adds the banner to every full page and refuses changes in read-only mode, while impersonating
+pub async fn impersonation_guard(
+    auth_session: AuthSession,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(impersonation) = get_impersonation(&auth_session.session).await else {
+        return next.run(request).await;
+    };
+
+    if is_refused(&impersonation, request.method(), request.uri().path()) {
+        return error_bubble_response("Modul de vizualizare nu permite modificări");
+    }
+
+    // HTMX requests only replace parts of a page, which already has the banner
+    let is_full_page = !request.headers().contains_key("hx-request");
+    let response = next.run(request).await;
+    if !is_full_page {
+        return response;
+    }
+
+    let banner = BannerTemplate {
+        impersonation: &impersonation,
+    }
+    .render()
+    .unwrap_or_default();
+    add_page_banner(response, &banner).await
+}
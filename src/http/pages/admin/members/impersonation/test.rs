@@ -0,0 +1,132 @@
+use super::*;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use time::macros::datetime;
+use tower_sessions_sqlx_store::SqliteStore;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_log_start_and_end(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let started_at = datetime!(2025-06-01 12:00 UTC);
+    let ended_at = datetime!(2025-06-01 12:10 UTC);
+
+    let first = log_impersonation_start(&pool, 0, 1000, true, started_at).await?;
+    log_impersonation_end(&pool, first, ended_at).await?;
+    // Ending it twice, for example by logging out afterwards, keeps the first time
+    log_impersonation_end(&pool, first, datetime!(2025-06-01 13:00 UTC)).await?;
+    log_impersonation_start(&pool, 0, 1000, false, datetime!(2025-06-02 09:00 UTC)).await?;
+
+    let log = get_impersonation_log(&pool, 1000).await?;
+    assert_eq!(log.len(), 2);
+    assert!(!log[0].read_only);
+    assert_eq!(log[0].ended_at, None);
+    assert!(log[1].read_only);
+    assert_eq!(log[1].started_at, started_at);
+    assert_eq!(log[1].ended_at, Some(ended_at));
+
+    assert!(get_impersonation_log(&pool, 0).await?.is_empty());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_only_grant_impersonation_to_role_managers(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let admin = get_user(&pool, 0).await?;
+    assert!(admin.has_permission(Permission::ImpersonateMembers));
+    let member = get_user(&pool, 1000).await?;
+    assert!(!member.has_permission(Permission::ImpersonateMembers));
+
+    Ok(())
+}
+
+fn impersonation(read_only: bool, log_id: i64) -> Impersonation {
+    Impersonation {
+        admin_email: "admin@admin.com".to_string(),
+        member_id: 1000,
+        member_name: "Test User".to_string(),
+        read_only,
+        log_id,
+    }
+}
+
+#[test]
+fn should_refuse_changes_in_read_only_mode() {
+    let read_only = impersonation(true, 1);
+    assert!(is_refused(&read_only, &Method::POST, "/reserve"));
+    assert!(is_refused(
+        &read_only,
+        &Method::DELETE,
+        "/profile/sessions/1"
+    ));
+    assert!(!is_refused(&read_only, &Method::GET, "/reserve"));
+    // Only shows the checks or ends the impersonation
+    assert!(!is_refused(&read_only, &Method::POST, "/choose_hour"));
+    assert!(!is_refused(
+        &read_only,
+        &Method::POST,
+        "/impersonation/stop"
+    ));
+    assert!(!is_refused(&read_only, &Method::POST, "/logout"));
+
+    assert!(!is_refused(
+        &impersonation(false, 1),
+        &Method::POST,
+        "/reserve"
+    ));
+}
+
+#[sqlx::test]
+async fn should_close_log_entry_when_ending(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let session = Session::new(None, Arc::new(SqliteStore::new(pool.clone())), None);
+    let started_at = datetime!(2025-06-01 12:00 UTC);
+    let ended_at = datetime!(2025-06-01 12:10 UTC);
+
+    // Logging out without impersonating has nothing to end
+    assert!(
+        end_impersonation(&pool, &session, started_at)
+            .await?
+            .is_none()
+    );
+
+    let log_id = log_impersonation_start(&pool, 0, 1000, true, started_at).await?;
+    session
+        .insert(IMPERSONATION_SESSION_KEY, impersonation(true, log_id))
+        .await
+        .unwrap();
+
+    let ended = end_impersonation(&pool, &session, ended_at).await?.unwrap();
+    assert_eq!(ended.log_id, log_id);
+    assert_eq!(ended.admin_email, "admin@admin.com");
+    assert!(get_impersonation(&session).await.is_none());
+    assert_eq!(
+        get_impersonation_log(&pool, 1000).await?[0].ended_at,
+        Some(ended_at)
+    );
+
+    // Stopping and then logging out only ends it once
+    let later = datetime!(2025-06-01 13:00 UTC);
+    assert!(end_impersonation(&pool, &session, later).await?.is_none());
+    assert_eq!(
+        get_impersonation_log(&pool, 1000).await?[0].ended_at,
+        Some(ended_at)
+    );
+
+    Ok(())
+}
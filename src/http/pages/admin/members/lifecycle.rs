@@ -0,0 +1,291 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::AuthSession;
+use crate::model::user::{MembershipStatus, User};
+use crate::utils::queries::{get_unpaid_months, get_user};
+use crate::utils::{date_formats, local_date};
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use sqlx::{SqliteConnection, SqliteExecutor, SqlitePool, query, query_as, query_scalar};
+use std::str::FromStr;
+use time::{Date, OffsetDateTime};
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+pub struct StatusChange<'a> {
+    pub status: MembershipStatus,
+    pub reason: Option<&'a str>,
+    /// Only kept for suspensions
+    pub until: Option<Date>,
+    /// `None` when the change is made automatically
+    pub changed_by: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusHistoryEntry {
+    pub status: String,
+    pub reason: Option<String>,
+    pub until: Option<Date>,
+    pub changed_at: OffsetDateTime,
+    pub changed_by_name: Option<String>,
+}
+
+impl StatusHistoryEntry {
+    pub fn status(&self) -> MembershipStatus {
+        MembershipStatus::from_str(&self.status).unwrap_or(MembershipStatus::Active)
+    }
+}
+
+pub async fn get_status_history(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<Vec<StatusHistoryEntry>> {
+    query_as!(
+        StatusHistoryEntry,
+        r#"select h.status, h.reason, h.until, h.changed_at, u.name as 'changed_by_name?'
+         from membership_status_history h left join users u on u.id = h.changed_by
+         where h.user_id = $1 order by h.changed_at desc, h.id desc"#,
+        user_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Changes the state of a membership, keeping `is_active` and `is_deleted` in sync with it,
+/// and records the change in the member's history
+pub async fn set_membership_status(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    change: StatusChange<'_>,
+) -> sqlx::Result<()> {
+    let status = change.status.as_ref();
+    let reason = change
+        .reason
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty());
+    let until = change
+        .until
+        .filter(|_| change.status == MembershipStatus::Suspended);
+    let is_active = change.status == MembershipStatus::Active;
+    let is_deleted = change.status == MembershipStatus::Deleted;
+    // The reason of a reactivation is only kept in the history
+    let status_reason = reason.filter(|_| !is_active);
+
+    if is_deleted {
+        query!("delete from reservations where user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    query!(
+        "update users set membership_status = $2, status_reason = $3, status_until = $4, is_active = $5, is_deleted = $6
+         where id = $1",
+        user_id,
+        status,
+        status_reason,
+        until,
+        is_active,
+        is_deleted
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    query!(
+        "insert into membership_status_history (user_id, status, reason, until, changed_by)
+         values ($1, $2, $3, $4, $5)",
+        user_id,
+        status,
+        reason,
+        until,
+        change.changed_by
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    info!(
+        "Membership of user {user_id} changed to {status} (by {:?})",
+        change.changed_by
+    );
+
+    Ok(())
+}
+
+/// Checks a change requested by an admin, before it's applied
+pub fn validate_status_change(
+    member: &User,
+    change: &StatusChange<'_>,
+    today: Date,
+) -> HttpResult<()> {
+    if change.status == MembershipStatus::Deleted {
+        return Err(HttpError::Message(
+            "Membrii se șterg din pagina de editare".into(),
+        ));
+    }
+    if change.status == MembershipStatus::Suspended
+        && change.reason.is_none_or(|reason| reason.trim().is_empty())
+    {
+        return Err(HttpError::Message(
+            "Motivul suspendării este obligatoriu".into(),
+        ));
+    }
+    if change.status == MembershipStatus::Suspended
+        && change.until.is_some_and(|until| until < today)
+    {
+        return Err(HttpError::Message(
+            "Suspendarea nu se poate încheia în trecut".into(),
+        ));
+    }
+    if change.status == member.membership_status() && change.status != MembershipStatus::Suspended {
+        return Err(HttpError::Message(format!(
+            "Membrul este deja {}",
+            change.status.display_name().to_lowercase()
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct MembershipReview {
+    pub suspended: usize,
+    pub reactivated: usize,
+}
+
+/// Lifts suspensions that ran out and, when enabled, suspends members with too many unpaid months,
+/// reactivating those suspended automatically once they are no longer behind
+pub async fn review_memberships(pool: &SqlitePool, today: Date) -> sqlx::Result<MembershipReview> {
+    let mut review = MembershipReview::default();
+    let mut tx = pool.begin().await?;
+
+    let suspended = MembershipStatus::Suspended.as_ref();
+    let ended_suspensions = query_scalar!(
+        "select id from users where membership_status = $1 and status_until < $2",
+        suspended,
+        today
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+    for user_id in ended_suspensions {
+        set_membership_status(
+            tx.as_mut(),
+            user_id,
+            StatusChange {
+                status: MembershipStatus::Active,
+                reason: Some("Suspendarea a expirat"),
+                until: None,
+                changed_by: None,
+            },
+        )
+        .await?;
+        review.reactivated += 1;
+    }
+
+    let threshold = query_scalar!("select auto_suspend_unpaid_months from global_vars")
+        .fetch_one(tx.as_mut())
+        .await?;
+    if let Some(threshold) = threshold {
+        let active = MembershipStatus::Active.as_ref();
+        let members = query_as!(
+            User,
+            "select * from users_with_role where membership_status = $1 and admin_panel_access = false",
+            active
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+        for member in members {
+            let unpaid_months = get_unpaid_months(tx.as_mut(), &member, today).await?.len();
+            if unpaid_months as i64 > threshold {
+                let reason = format!("Cotizație neachitată de {unpaid_months} luni");
+                set_membership_status(
+                    tx.as_mut(),
+                    member.id,
+                    StatusChange {
+                        status: MembershipStatus::Suspended,
+                        reason: Some(&reason),
+                        until: None,
+                        changed_by: None,
+                    },
+                )
+                .await?;
+                review.suspended += 1;
+            }
+        }
+
+        // Only the suspensions made by this review are lifted, never those made by an admin
+        let auto_suspended = query_as!(
+            User,
+            "select * from users_with_role u where membership_status = $1 and status_until is null
+                and (select h.changed_by is null from membership_status_history h
+                     where h.user_id = u.id order by h.id desc limit 1)",
+            suspended
+        )
+        .fetch_all(tx.as_mut())
+        .await?;
+        for member in auto_suspended {
+            let unpaid_months = get_unpaid_months(tx.as_mut(), &member, today).await?.len();
+            if unpaid_months as i64 <= threshold {
+                set_membership_status(
+                    tx.as_mut(),
+                    member.id,
+                    StatusChange {
+                        status: MembershipStatus::Active,
+                        reason: Some("Cotizația a fost achitată"),
+                        until: None,
+                        changed_by: None,
+                    },
+                )
+                .await?;
+                review.reactivated += 1;
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(review)
+}
+
+#[derive(Deserialize)]
+pub struct StatusForm {
+    status: String,
+    reason: Option<String>,
+    until: Option<String>,
+}
+
+fn parse_until(until: Option<&str>) -> HttpResult<Option<Date>> {
+    until
+        .filter(|until| !until.is_empty())
+        .map(|until| Date::parse(until, date_formats::ISO_DATE))
+        .transpose()
+        .map_err(|_| HttpError::Message("Data de final este invalidă".into()))
+}
+
+pub async fn change_status(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(user_id): Path<i64>,
+    Form(form): Form<StatusForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let member = get_user(&state.read_pool, user_id).await?;
+
+    let status = MembershipStatus::from_str(&form.status)
+        .map_err(|_| HttpError::Message("Statut invalid".into()))?;
+    let until = parse_until(form.until.as_deref())?;
+    let change = StatusChange {
+        status,
+        reason: form.reason.as_deref(),
+        until,
+        changed_by: Some(user.id),
+    };
+    validate_status_change(&member, &change, local_date())?;
+
+    let mut tx = state.write_pool.begin().await?;
+    set_membership_status(tx.as_mut(), user_id, change).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
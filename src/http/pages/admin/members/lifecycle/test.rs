@@ -0,0 +1,221 @@
+use super::*;
+use crate::utils::queries::get_user;
+use sqlx::SqlitePool;
+use time::macros::date;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (1001, 'test2@test.com', 'Test 2', '', 100, FALSE, '2000-01-01', '2024-03-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn change(pool: &SqlitePool, user_id: i64, change: StatusChange<'_>) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    set_membership_status(tx.as_mut(), user_id, change).await?;
+    tx.commit().await
+}
+
+#[sqlx::test]
+async fn should_keep_flags_and_history_in_sync(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    change(
+        &pool,
+        1000,
+        StatusChange {
+            status: MembershipStatus::Suspended,
+            reason: Some("Comportament"),
+            until: Some(date!(2024 - 05 - 01)),
+            changed_by: Some(0),
+        },
+    )
+    .await?;
+
+    let member = get_user(&pool, 1000).await?;
+    assert_eq!(member.membership_status(), MembershipStatus::Suspended);
+    assert!(!member.is_active);
+    assert_eq!(member.status_until, Some(date!(2024 - 05 - 01)));
+
+    change(
+        &pool,
+        1000,
+        StatusChange {
+            status: MembershipStatus::Active,
+            reason: None,
+            until: Some(date!(2024 - 06 - 01)),
+            changed_by: Some(0),
+        },
+    )
+    .await?;
+
+    let member = get_user(&pool, 1000).await?;
+    assert!(member.is_active);
+    // Only suspensions have an end date
+    assert_eq!(member.status_until, None);
+
+    let history = get_status_history(&pool, 1000).await?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].status(), MembershipStatus::Active);
+    assert_eq!(history[1].reason.as_deref(), Some("Comportament"));
+    assert_eq!(
+        history[1].changed_by_name.as_deref(),
+        Some("Test Administrator")
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_require_a_reason_for_suspensions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let member = get_user(&pool, 1000).await?;
+    let today = date!(2024 - 04 - 10);
+
+    let suspension = |reason, until| StatusChange {
+        status: MembershipStatus::Suspended,
+        reason,
+        until,
+        changed_by: Some(0),
+    };
+    assert!(validate_status_change(&member, &suspension(None, None), today).is_err());
+    assert!(validate_status_change(&member, &suspension(Some(" "), None), today).is_err());
+    assert!(
+        validate_status_change(
+            &member,
+            &suspension(Some("Motiv"), Some(date!(2024 - 04 - 01))),
+            today
+        )
+        .is_err()
+    );
+    assert!(validate_status_change(&member, &suspension(Some("Motiv"), None), today).is_ok());
+
+    let active = StatusChange {
+        status: MembershipStatus::Active,
+        reason: None,
+        until: None,
+        changed_by: Some(0),
+    };
+    assert!(validate_status_change(&member, &active, today).is_err());
+
+    assert_eq!(parse_until(None).ok(), Some(None));
+    assert_eq!(parse_until(Some("")).ok(), Some(None));
+    assert_eq!(
+        parse_until(Some("2024-05-01")).ok(),
+        Some(Some(date!(2024 - 05 - 01)))
+    );
+    assert!(parse_until(Some("01.05.2024")).is_err());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_suspend_and_reactivate_automatically(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let today = date!(2024 - 04 - 10);
+
+    // Disabled by default
+    assert_eq!(
+        review_memberships(&pool, today).await?,
+        MembershipReview::default()
+    );
+
+    query!("update global_vars set auto_suspend_unpaid_months = 2")
+        .execute(&pool)
+        .await?;
+
+    // January to April are unpaid for the first member, March and April for the second one
+    assert_eq!(
+        review_memberships(&pool, today).await?,
+        MembershipReview {
+            suspended: 1,
+            reactivated: 0
+        }
+    );
+    let member = get_user(&pool, 1000).await?;
+    assert_eq!(member.membership_status(), MembershipStatus::Suspended);
+    assert_eq!(
+        get_user(&pool, 1001).await?.membership_status(),
+        MembershipStatus::Active
+    );
+
+    query!(
+        r#"
+        insert into payments (id, user_id, amount, payment_date, created_by) VALUES (1, 1000, 10000, '2024-04-10', 0);
+        insert into payment_allocations (payment_id, year, month) VALUES (1, 2024, 1), (1, 2024, 2);
+        "#
+    )
+    .execute(&pool)
+    .await?;
+
+    assert_eq!(
+        review_memberships(&pool, today).await?,
+        MembershipReview {
+            suspended: 0,
+            reactivated: 1
+        }
+    );
+    assert_eq!(
+        get_user(&pool, 1000).await?.membership_status(),
+        MembershipStatus::Active
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_keep_manual_suspensions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    query!("update global_vars set auto_suspend_unpaid_months = 12")
+        .execute(&pool)
+        .await?;
+
+    change(
+        &pool,
+        1000,
+        StatusChange {
+            status: MembershipStatus::Suspended,
+            reason: Some("Comportament"),
+            until: None,
+            changed_by: Some(0),
+        },
+    )
+    .await?;
+    change(
+        &pool,
+        1001,
+        StatusChange {
+            status: MembershipStatus::Suspended,
+            reason: Some("Accidentare"),
+            until: Some(date!(2024 - 04 - 09)),
+            changed_by: Some(0),
+        },
+    )
+    .await?;
+
+    assert_eq!(
+        review_memberships(&pool, date!(2024 - 04 - 10)).await?,
+        MembershipReview {
+            suspended: 0,
+            reactivated: 1
+        }
+    );
+    assert_eq!(
+        get_user(&pool, 1000).await?.membership_status(),
+        MembershipStatus::Suspended
+    );
+    assert_eq!(
+        get_user(&pool, 1001).await?.membership_status(),
+        MembershipStatus::Active
+    );
+
+    Ok(())
+}
@@ -1,64 +1,30 @@
 use crate::http::AppState;
-use crate::http::error::{HttpError, HttpResult};
+use crate::http::error::{HttpError, HttpResult, OrBail};
 use crate::http::pages::AuthSession;
-use crate::http::pages::admin::members::breaks::get_user_payment_breaks;
-use crate::model::payment::PaymentWithAllocations;
-use crate::utils::local_date;
-use crate::utils::queries::{YearMonth, get_user};
+use crate::http::pages::user::notifications::create_notification;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::notification::NotificationKind;
+use crate::model::payment::{
+    PaymentAction, PaymentHistoryEntry, PaymentWithAllocations, display_amount,
+};
+use crate::model::user::User;
+use crate::receipt::{Receipt, get_receipt, issue_receipt, receipt_months};
+use crate::utils::queries::{YearMonth, get_user, get_user_payment_breaks, get_user_payments};
+use crate::utils::{date_formats, local_date};
+use askama::Template;
 use axum::Form;
 use axum::extract::{Path, State};
-use axum::response::IntoResponse;
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::Form as AxumExtraForm;
+use itertools::Itertools;
 use serde::Deserialize;
-use sqlx::{SqliteExecutor, SqlitePool, query};
-use time::{Date, Month};
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as};
+use time::{Date, Month, OffsetDateTime};
 use tracing::info;
 
-pub async fn get_user_payments(
-    pool: &SqlitePool,
-    user_id: i64,
-) -> sqlx::Result<Vec<PaymentWithAllocations>> {
-    let payments = query!(
-        "select p.id, amount, payment_date, notes, created_at, created_by, u.name as created_by_name from payments p
-         join users u on u.id = p.created_by
-         where user_id = $1 order by payment_date desc",
-        user_id
-    )
-        .fetch_all(pool)
-        .await?;
-
-    let all_allocations = query!(
-        "select payment_id, year, month from payment_allocations where payment_id in (select id from payments where user_id = ?) order by year desc, month desc",
-        user_id
-    )
-        .fetch_all(pool)
-        .await?;
-
-    Ok(payments
-        .into_iter()
-        .map(|p| {
-            let allocations = all_allocations
-                .iter()
-                .filter(|a| a.payment_id == p.id)
-                .filter_map(|a| {
-                    Some(YearMonth::new(
-                        a.year as i32,
-                        Month::try_from(a.month as u8).ok()?,
-                    ))
-                })
-                .collect();
-
-            PaymentWithAllocations {
-                amount: p.amount,
-                payment_date: p.payment_date,
-                notes: p.notes,
-                created_at: p.created_at,
-                created_by: p.created_by,
-                created_by_name: p.created_by_name,
-                allocations,
-            }
-        })
-        .collect())
-}
+#[cfg(test)]
+mod test;
 
 pub async fn get_payment_allocations(
     executor: impl SqliteExecutor<'_>,
@@ -77,34 +43,65 @@ pub async fn get_payment_allocations(
         )
 }
 
-#[derive(Deserialize, Debug)]
-pub struct NewPayment {
-    amount: f64,        // From input type="number" step="0.01"
-    payment_date: Date, // From input type="date"
-    months: String,     // From checkboxes (format: "M-YYYY")
-    notes: Option<String>,
+pub async fn get_payment_history(
+    executor: impl SqliteExecutor<'_>,
+    payment_id: i64,
+) -> sqlx::Result<Vec<PaymentHistoryEntry>> {
+    query_as!(
+        PaymentHistoryEntry,
+        "select h.action, h.amount, h.payment_date, h.notes, h.months, h.created_at, u.name as created_by_name
+         from payment_history h join users u on u.id = h.created_by
+         where h.payment_id = $1 order by h.created_at desc, h.id desc",
+        payment_id
+    )
+    .fetch_all(executor)
+    .await
 }
 
-pub async fn add_payment(
-    State(state): State<AppState>,
-    Path(member_id): Path<i64>,
-    auth_session: AuthSession,
-    Form(form): Form<NewPayment>,
-) -> HttpResult {
-    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
-    let member = get_user(&state.read_pool, member_id).await?;
+/// Saves a snapshot of the payment, as it is after `action` was applied
+async fn record_payment_history(
+    tx: &mut SqliteConnection,
+    payment_id: i64,
+    action: PaymentAction,
+    created_by: i64,
+) -> sqlx::Result<()> {
+    let action = action.as_ref();
+    query!(
+        "insert into payment_history (payment_id, action, amount, payment_date, notes, months, created_by)
+         select p.id, $2, p.amount, p.payment_date, p.notes,
+            coalesce((select group_concat(pa.month || '-' || pa.year, ',') from payment_allocations pa where pa.payment_id = p.id), ''),
+            $3
+         from payments p where p.id = $1",
+        payment_id,
+        action,
+        created_by
+    )
+    .execute(&mut *tx)
+    .await?;
 
-    if form.amount <= 0.0 {
-        return Err(HttpError::Message("Suma trebuie să fie pozitivă".into()));
-    }
+    Ok(())
+}
+
+/// Formats months the way `parse_requested_allocations` expects them
+pub fn format_allocations(allocations: &[YearMonth]) -> String {
+    allocations
+        .iter()
+        .map(|allocation| format!("{}-{}", allocation.month as u8, allocation.year))
+        .join(",")
+}
 
-    let current_year = local_date().year();
+/// Parses months in the "M-YYYY" format, skipping those outside the member's valid range
+pub fn parse_requested_allocations<'a>(
+    member: &User,
+    months: impl IntoIterator<Item = &'a str>,
+    today: Date,
+) -> HttpResult<Vec<YearMonth>> {
+    let current_year = today.year();
     let valid_year_range = member.member_since.year()..=current_year + 1;
+    let joining_month = YearMonth::from(member.member_since).to_date();
 
-    // Parse requested months first
-    let requested_allocations: Vec<_> = form
-        .months
-        .split(',')
+    let requested_allocations: Vec<_> = months
+        .into_iter()
         .filter_map(|s| {
             let (month_str, year_str) = s.trim().split_once('-')?;
 
@@ -118,13 +115,6 @@ pub async fn add_payment(
                 .ok()
                 .filter(|y| valid_year_range.contains(y))?;
 
-            let joining_month = Date::from_calendar_date(
-                member.member_since.year(),
-                member.member_since.month(),
-                1,
-            )
-            .ok()?;
-
             if Date::from_calendar_date(year, month, 1).ok()? < joining_month {
                 // Skip months before they joined
                 return None;
@@ -140,12 +130,19 @@ pub async fn add_payment(
         ));
     }
 
-    let mut tx = state.write_pool.begin().await?;
+    Ok(requested_allocations)
+}
 
-    let existing_allocations = get_payment_allocations(tx.as_mut(), member_id).await?;
-    let existing_breaks = get_user_payment_breaks(tx.as_mut(), member_id).await?;
+/// Makes sure none of the requested months are already paid or part of a break
+pub async fn validate_requested_allocations(
+    tx: &mut SqliteConnection,
+    member_id: i64,
+    requested_allocations: &[YearMonth],
+) -> HttpResult<()> {
+    let existing_allocations = get_payment_allocations(&mut *tx, member_id).await?;
+    let existing_breaks = get_user_payment_breaks(&mut *tx, member_id).await?;
 
-    for requested in &requested_allocations {
+    for requested in requested_allocations {
         if existing_allocations.contains(requested) {
             return Err(HttpError::Message(format!(
                 "Luna {}-{} este deja plătită",
@@ -154,7 +151,7 @@ pub async fn add_payment(
         }
 
         // Check for breaks
-        let req_date = Date::from_calendar_date(requested.year, requested.month, 1).unwrap();
+        let req_date = requested.to_date();
 
         let is_break = existing_breaks
             .iter()
@@ -168,23 +165,138 @@ pub async fn add_payment(
         }
     }
 
-    // Convert amount to cents (integer) for storage
-    let amount_cents = (form.amount * 100.0).round() as i64;
+    Ok(())
+}
+
+pub async fn insert_payment_allocations(
+    tx: &mut SqliteConnection,
+    payment_id: i64,
+    allocations: &[YearMonth],
+) -> sqlx::Result<()> {
+    for allocation in allocations {
+        let month = allocation.month as u8;
+        query!(
+            "insert into payment_allocations (payment_id, year, month) values ($1, $2, $3)",
+            payment_id,
+            allocation.year,
+            month,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Converts an amount introduced in RON to cents (integer) for storage
+fn amount_to_cents(amount: f64) -> HttpResult<i64> {
+    if amount <= 0.0 {
+        return Err(HttpError::Message("Suma trebuie să fie pozitivă".into()));
+    }
+
+    Ok((amount * 100.0).round() as i64)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewPayment {
+    amount: f64,        // From input type="number" step="0.01"
+    payment_date: Date, // From input type="date"
+    months: String,     // From checkboxes (format: "M-YYYY")
+    notes: Option<String>,
+}
+
+pub struct NewPaymentRecord<'a> {
+    pub member_id: i64,
+    pub amount: i64,
+    pub payment_date: Date,
+    pub notes: Option<&'a str>,
+    pub allocations: &'a [YearMonth],
+    pub created_by: i64,
+}
+
+/// Validates the allocations, then inserts the payment together with its months, history and receipt
+pub async fn create_payment(
+    tx: &mut SqliteConnection,
+    payment: NewPaymentRecord<'_>,
+) -> HttpResult<i64> {
+    validate_requested_allocations(&mut *tx, payment.member_id, payment.allocations).await?;
 
-    let notes = form.notes.filter(|notes| !notes.is_empty());
     let payment_id = query!(
         "insert into payments (user_id, amount, payment_date, notes, created_by)
          values ($1, $2, $3, $4, $5) returning id",
-        member_id,
-        amount_cents,
-        form.payment_date,
-        notes,
-        user.id,
+        payment.member_id,
+        payment.amount,
+        payment.payment_date,
+        payment.notes,
+        payment.created_by,
     )
-    .fetch_one(tx.as_mut())
+    .fetch_one(&mut *tx)
     .await?
     .id;
 
+    insert_payment_allocations(&mut *tx, payment_id, payment.allocations).await?;
+    record_payment_history(
+        &mut *tx,
+        payment_id,
+        PaymentAction::Created,
+        payment.created_by,
+    )
+    .await?;
+    issue_receipt(
+        &mut *tx,
+        payment_id,
+        payment.allocations,
+        payment.created_by,
+        None,
+    )
+    .await?;
+
+    // Shown live by the callers, once the transaction is committed
+    create_notification(
+        &mut *tx,
+        payment.member_id,
+        NotificationKind::Payment,
+        &format!(
+            "Plata de {} RON a fost înregistrată",
+            display_amount(payment.amount)
+        ),
+        Some("/profile"),
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+
+    Ok(payment_id)
+}
+
+pub async fn add_payment(
+    State(state): State<AppState>,
+    Path(member_id): Path<i64>,
+    auth_session: AuthSession,
+    Form(form): Form<NewPayment>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let member = get_user(&state.read_pool, member_id).await?;
+
+    let amount_cents = amount_to_cents(form.amount)?;
+    let requested_allocations =
+        parse_requested_allocations(&member, form.months.split(','), local_date())?;
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let notes = form.notes.filter(|notes| !notes.is_empty());
+    create_payment(
+        tx.as_mut(),
+        NewPaymentRecord {
+            member_id,
+            amount: amount_cents,
+            payment_date: form.payment_date,
+            notes: notes.as_deref(),
+            allocations: &requested_allocations,
+            created_by: user.id,
+        },
+    )
+    .await?;
+
     info!(
         "Payment added: Member {} paid {:.2} RON for {} months (Req by Admin {})",
         member_id,
@@ -193,19 +305,476 @@ pub async fn add_payment(
         user.id
     );
 
-    for requested in requested_allocations {
-        let month = requested.month as u8;
-        query!(
-            "insert into payment_allocations (payment_id, year, month) values ($1, $2, $3)",
+    tx.commit().await?;
+    let _ = state.notification_notifier.send(member_id);
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+struct ExistingPayment {
+    user_id: i64,
+    amount: i64,
+    voided_at: Option<OffsetDateTime>,
+    refund_of: Option<i64>,
+}
+
+async fn get_existing_payment(
+    executor: impl SqliteExecutor<'_>,
+    payment_id: i64,
+) -> HttpResult<ExistingPayment> {
+    query_as!(
+        ExistingPayment,
+        "select user_id, amount, voided_at, refund_of from payments where id = $1",
+        payment_id
+    )
+    .fetch_optional(executor)
+    .await?
+    .or_bail("Plata nu există")
+}
+
+pub struct PaymentChanges<'a> {
+    pub amount: i64,
+    pub payment_date: Date,
+    pub notes: Option<&'a str>,
+    pub allocations: &'a [YearMonth],
+    pub changed_by: i64,
+}
+
+/// Replaces the amount, date, notes and months of a payment, keeping a snapshot in its history
+pub async fn update_payment(
+    tx: &mut SqliteConnection,
+    payment_id: i64,
+    changes: PaymentChanges<'_>,
+) -> HttpResult<()> {
+    let payment = get_existing_payment(&mut *tx, payment_id).await?;
+    if payment.voided_at.is_some() {
+        return Err(HttpError::Message(
+            "O plată anulată nu poate fi modificată".into(),
+        ));
+    }
+    if payment.refund_of.is_some() {
+        return Err(HttpError::Message(
+            "O rambursare nu poate fi modificată, doar anulată".into(),
+        ));
+    }
+
+    let refunded = get_refunded_amount(&mut *tx, payment_id).await?;
+    if changes.amount < refunded {
+        return Err(HttpError::Message(format!(
+            "Suma nu poate fi mai mică decât cea deja rambursată ({} RON)",
+            display_amount(refunded)
+        )));
+    }
+
+    // Free the months of this payment first, so they can be re-allocated
+    query!(
+        "delete from payment_allocations where payment_id = $1",
+        payment_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    validate_requested_allocations(&mut *tx, payment.user_id, changes.allocations).await?;
+
+    query!(
+        "update payments set amount = $2, payment_date = $3, notes = $4 where id = $1",
+        payment_id,
+        changes.amount,
+        changes.payment_date,
+        changes.notes,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    insert_payment_allocations(&mut *tx, payment_id, changes.allocations).await?;
+    record_payment_history(
+        &mut *tx,
+        payment_id,
+        PaymentAction::Edited,
+        changes.changed_by,
+    )
+    .await?;
+
+    // The issued receipt no longer matches the payment, so a new one replaces it
+    let receipt = get_receipt(&mut *tx, payment_id).await?;
+    if let Some(receipt) = receipt.filter(|receipt| {
+        receipt.amount != changes.amount
+            || receipt.payment_date != changes.payment_date
+            || receipt.months != receipt_months(changes.allocations)
+    }) {
+        issue_receipt(
+            &mut *tx,
             payment_id,
-            requested.year,
-            month,
+            changes.allocations,
+            changes.changed_by,
+            Some(receipt.number),
         )
-        .execute(tx.as_mut())
         .await?;
     }
 
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EditedPayment {
+    amount: f64,
+    payment_date: Date,
+    #[serde(default)]
+    months: Vec<String>, // From checkboxes (format: "M-YYYY")
+    notes: Option<String>,
+}
+
+pub async fn edit_payment(
+    State(state): State<AppState>,
+    Path(payment_id): Path<i64>,
+    auth_session: AuthSession,
+    AxumExtraForm(form): AxumExtraForm<EditedPayment>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let payment = get_existing_payment(&state.read_pool, payment_id).await?;
+
+    let member = get_user(&state.read_pool, payment.user_id).await?;
+    let amount_cents = amount_to_cents(form.amount)?;
+    let requested_allocations = parse_requested_allocations(
+        &member,
+        form.months.iter().map(String::as_str),
+        local_date(),
+    )?;
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let notes = form.notes.filter(|notes| !notes.is_empty());
+    update_payment(
+        tx.as_mut(),
+        payment_id,
+        PaymentChanges {
+            amount: amount_cents,
+            payment_date: form.payment_date,
+            notes: notes.as_deref(),
+            allocations: &requested_allocations,
+            changed_by: user.id,
+        },
+    )
+    .await?;
+
     tx.commit().await?;
 
+    info!(
+        "Payment {payment_id} of member {} edited by Admin {}",
+        member.id, user.id
+    );
+
     Ok([("HX-Refresh", "true")].into_response())
 }
+
+/// Keeps the payment for accounting, but frees its months, returns the member it belonged to
+pub async fn cancel_payment(
+    tx: &mut SqliteConnection,
+    payment_id: i64,
+    reason: &str,
+    voided_by: i64,
+) -> HttpResult<i64> {
+    let payment = get_existing_payment(&mut *tx, payment_id).await?;
+    if payment.voided_at.is_some() {
+        return Err(HttpError::Message("Plata este deja anulată".into()));
+    }
+
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(HttpError::Message(
+            "Motivul anulării este obligatoriu".into(),
+        ));
+    }
+    // Otherwise the refunds would stay in the ledger against a payment that no longer counts
+    let refunded = get_refunded_amount(&mut *tx, payment_id).await?;
+    if refunded > 0 {
+        return Err(HttpError::Message(format!(
+            "Plata are rambursări de {} RON, anulează-le înainte de a anula plata",
+            display_amount(refunded)
+        )));
+    }
+
+    query!(
+        "update payments set voided_at = CURRENT_TIMESTAMP, voided_by = $2, void_reason = $3 where id = $1",
+        payment_id,
+        voided_by,
+        reason
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    query!(
+        "delete from payment_allocations where payment_id = $1",
+        payment_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    record_payment_history(&mut *tx, payment_id, PaymentAction::Voided, voided_by).await?;
+
+    Ok(payment.user_id)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VoidPayment {
+    reason: String,
+}
+
+pub async fn void_payment(
+    State(state): State<AppState>,
+    Path(payment_id): Path<i64>,
+    auth_session: AuthSession,
+    Form(form): Form<VoidPayment>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    let member_id = cancel_payment(tx.as_mut(), payment_id, &form.reason, user.id).await?;
+    tx.commit().await?;
+
+    info!(
+        "Payment {payment_id} of member {member_id} voided by Admin {}",
+        user.id
+    );
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+async fn get_refunded_amount(
+    executor: impl SqliteExecutor<'_>,
+    payment_id: i64,
+) -> sqlx::Result<i64> {
+    query!(
+        "select coalesce(-sum(amount), 0) as 'refunded!: i64' from payments
+         where refund_of = $1 and voided_at is null",
+        payment_id
+    )
+    .fetch_one(executor)
+    .await
+    .map(|record| record.refunded)
+}
+
+pub struct NewRefundRecord<'a> {
+    pub amount: i64,
+    pub payment_date: Date,
+    pub notes: Option<&'a str>,
+    pub created_by: i64,
+}
+
+/// Records the refund as a negative payment of the member, up to what is left of the payment
+pub async fn create_refund(
+    tx: &mut SqliteConnection,
+    payment_id: i64,
+    refund: NewRefundRecord<'_>,
+) -> HttpResult<i64> {
+    let payment = get_existing_payment(&mut *tx, payment_id).await?;
+    if payment.voided_at.is_some() || payment.refund_of.is_some() {
+        return Err(HttpError::Message(
+            "Această plată nu poate fi rambursată".into(),
+        ));
+    }
+
+    let refundable = payment.amount - get_refunded_amount(&mut *tx, payment_id).await?;
+    if refund.amount > refundable {
+        return Err(HttpError::Message(format!(
+            "Suma rambursată depășește valoarea rămasă a plății ({} RON)",
+            display_amount(refundable)
+        )));
+    }
+
+    let refund_amount = -refund.amount;
+    let refund_id = query!(
+        "insert into payments (user_id, amount, payment_date, notes, created_by, refund_of)
+         values ($1, $2, $3, $4, $5, $6) returning id",
+        payment.user_id,
+        refund_amount,
+        refund.payment_date,
+        refund.notes,
+        refund.created_by,
+        payment_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    record_payment_history(
+        &mut *tx,
+        refund_id,
+        PaymentAction::Created,
+        refund.created_by,
+    )
+    .await?;
+
+    let refunded_receipt = get_receipt(&mut *tx, payment_id).await?;
+    issue_receipt(
+        &mut *tx,
+        refund_id,
+        &[],
+        refund.created_by,
+        refunded_receipt.map(|receipt| receipt.number),
+    )
+    .await?;
+
+    // The original payment's history references the refund entry
+    let action = PaymentAction::Refunded.as_ref();
+    query!(
+        "insert into payment_history (payment_id, action, amount, payment_date, notes, months, created_by)
+         values ($1, $2, $3, $4, $5, '', $6)",
+        payment_id,
+        action,
+        refund_amount,
+        refund.payment_date,
+        refund.notes,
+        refund.created_by
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(refund_id)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewRefund {
+    amount: f64,
+    payment_date: Date,
+    notes: Option<String>,
+}
+
+pub async fn refund_payment(
+    State(state): State<AppState>,
+    Path(payment_id): Path<i64>,
+    auth_session: AuthSession,
+    Form(form): Form<NewRefund>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let amount_cents = amount_to_cents(form.amount)?;
+
+    let mut tx = state.write_pool.begin().await?;
+
+    let notes = form.notes.filter(|notes| !notes.is_empty());
+    create_refund(
+        tx.as_mut(),
+        payment_id,
+        NewRefundRecord {
+            amount: amount_cents,
+            payment_date: form.payment_date,
+            notes: notes.as_deref(),
+            created_by: user.id,
+        },
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    info!(
+        "Payment {payment_id} refunded {:.2} RON by Admin {}",
+        form.amount, user.id
+    );
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+pub struct MonthOption {
+    pub value: String,
+    pub name: &'static str,
+    pub checked: bool,
+    pub disabled: bool,
+}
+
+pub struct YearOptions {
+    pub year: i32,
+    pub months: Vec<MonthOption>,
+}
+
+pub async fn manage_payment_partial(
+    State(state): State<AppState>,
+    Path(payment_id): Path<i64>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/payment_manage_partial.html")]
+    struct ManagePaymentTemplate {
+        payment: PaymentWithAllocations,
+        current_date: Date,
+        refundable_amount: String,
+        can_refund: bool,
+        years: Vec<YearOptions>,
+        history: Vec<PaymentHistoryEntry>,
+    }
+
+    let existing = get_existing_payment(&state.read_pool, payment_id).await?;
+    let member = get_user(&state.read_pool, existing.user_id).await?;
+    let payments = get_user_payments(&mut *state.read_pool.acquire().await?, member.id).await?;
+    let breaks = get_user_payment_breaks(&state.read_pool, member.id).await?;
+
+    let payment = payments
+        .iter()
+        .find(|p| p.id == payment_id)
+        .cloned()
+        .or_bail("Plata nu există")?;
+
+    let current_date = local_date();
+    let joining_month = YearMonth::from(member.member_since).to_date();
+    let years = (member.member_since.year()..=current_date.year() + 1)
+        .map(|year| YearOptions {
+            year,
+            months: (1..=12)
+                .filter_map(|m| Month::try_from(m).ok())
+                .map(|month| {
+                    let year_month = YearMonth::new(year, month);
+                    let date = year_month.to_date();
+                    let checked = payment.allocations.contains(&year_month);
+                    let paid_by_other = payments
+                        .iter()
+                        .any(|p| p.id != payment_id && p.allocations.contains(&year_month));
+                    let on_break = breaks
+                        .iter()
+                        .any(|b| (b.start_date..=b.end_date).contains(&date));
+
+                    MonthOption {
+                        value: format!("{}-{}", month as u8, year),
+                        name: date_formats::month_as_str(&month),
+                        checked,
+                        disabled: !checked && (paid_by_other || on_break || date < joining_month),
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    let refundable = payment.amount - get_refunded_amount(&state.read_pool, payment_id).await?;
+
+    ManagePaymentTemplate {
+        can_refund: !payment.is_voided() && !payment.is_refund() && refundable > 0,
+        refundable_amount: display_amount(refundable),
+        history: get_payment_history(&state.read_pool, payment_id).await?,
+        current_date,
+        payment,
+        years,
+    }
+    .try_into_response()
+}
+
+pub async fn download_receipt(
+    State(state): State<AppState>,
+    Path(payment_id): Path<i64>,
+) -> HttpResult {
+    let receipt = get_receipt(&state.read_pool, payment_id)
+        .await?
+        .ok_or_else(|| HttpError::Message("Plata nu are o chitanță".into()))?;
+
+    Ok(receipt_response(&receipt))
+}
+
+pub fn receipt_response(receipt: &Receipt) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", receipt.file_name()),
+            ),
+        ],
+        receipt.render_pdf(),
+    )
+        .into_response()
+}
@@ -0,0 +1,308 @@
+use super::*;
+use crate::receipt::get_user_receipts;
+use crate::utils::queries::get_user_payments;
+use sqlx::SqlitePool;
+use time::macros::date;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn months(months: &[Month]) -> Vec<YearMonth> {
+    months
+        .iter()
+        .map(|month| YearMonth::new(2024, *month))
+        .collect()
+}
+
+fn error_message<T>(result: HttpResult<T>) -> String {
+    match result {
+        Err(HttpError::Message(message)) => message,
+        _ => panic!("Expected an error message"),
+    }
+}
+
+async fn pay(pool: &SqlitePool, amount: i64, allocations: &[YearMonth]) -> HttpResult<i64> {
+    let mut tx = pool.begin().await?;
+    let id = create_payment(
+        tx.as_mut(),
+        NewPaymentRecord {
+            member_id: 1000,
+            amount,
+            payment_date: date!(2024 - 03 - 05),
+            notes: None,
+            allocations,
+            created_by: 0,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+async fn edit(
+    pool: &SqlitePool,
+    payment_id: i64,
+    amount: i64,
+    allocations: &[YearMonth],
+) -> HttpResult<()> {
+    let mut tx = pool.begin().await?;
+    update_payment(
+        tx.as_mut(),
+        payment_id,
+        PaymentChanges {
+            amount,
+            payment_date: date!(2024 - 03 - 06),
+            notes: Some("Corectată"),
+            allocations,
+            changed_by: 0,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn void(pool: &SqlitePool, payment_id: i64, reason: &str) -> HttpResult<i64> {
+    let mut tx = pool.begin().await?;
+    let member_id = cancel_payment(tx.as_mut(), payment_id, reason, 0).await?;
+    tx.commit().await?;
+    Ok(member_id)
+}
+
+async fn refund(pool: &SqlitePool, payment_id: i64, amount: i64) -> HttpResult<i64> {
+    let mut tx = pool.begin().await?;
+    let id = create_refund(
+        tx.as_mut(),
+        payment_id,
+        NewRefundRecord {
+            amount,
+            payment_date: date!(2024 - 03 - 10),
+            notes: None,
+            created_by: 0,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+async fn payment(pool: &SqlitePool, payment_id: i64) -> sqlx::Result<PaymentWithAllocations> {
+    let payments = get_user_payments(&mut *pool.acquire().await?, 1000).await?;
+    Ok(payments.into_iter().find(|p| p.id == payment_id).unwrap())
+}
+
+fn actions(history: &[PaymentHistoryEntry]) -> Vec<&str> {
+    history.iter().map(|entry| entry.action.as_str()).collect()
+}
+
+#[sqlx::test]
+async fn should_edit_payment_and_keep_history(pool: SqlitePool) -> HttpResult<()> {
+    setup(&pool).await?;
+    let payment_id = pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+
+    edit(
+        &pool,
+        payment_id,
+        15000,
+        &months(&[Month::February, Month::March, Month::April]),
+    )
+    .await?;
+
+    let edited = payment(&pool, payment_id).await?;
+    assert_eq!(edited.amount, 15000);
+    assert_eq!(edited.payment_date, date!(2024 - 03 - 06));
+    assert_eq!(edited.notes.as_deref(), Some("Corectată"));
+    assert_eq!(
+        edited.allocations,
+        months(&[Month::April, Month::March, Month::February])
+    );
+
+    // January was freed by the edit
+    pay(&pool, 5000, &months(&[Month::January])).await?;
+    // But March is still taken
+    error_message(pay(&pool, 5000, &months(&[Month::March])).await);
+
+    let history = get_payment_history(&pool, payment_id).await?;
+    assert_eq!(actions(&history), vec!["edited", "created"]);
+    assert_eq!(history[0].amount, 15000);
+    assert_eq!(history[0].months, "2-2024,3-2024,4-2024");
+    assert_eq!(history[0].created_by_name, "Test Administrator");
+    assert_eq!(history[1].amount, 10000);
+    assert_eq!(history[1].months, "1-2024,2-2024");
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_void_payment_and_free_its_months(pool: SqlitePool) -> HttpResult<()> {
+    setup(&pool).await?;
+    let payment_id = pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+
+    error_message(void(&pool, payment_id, "  ").await);
+    assert_eq!(void(&pool, payment_id, "Greșeală").await?, 1000);
+
+    let voided = payment(&pool, payment_id).await?;
+    assert!(voided.is_voided());
+    assert_eq!(voided.void_reason.as_deref(), Some("Greșeală"));
+    assert!(voided.allocations.is_empty());
+
+    // Voided payments can't be changed anymore
+    error_message(void(&pool, payment_id, "Din nou").await);
+    error_message(edit(&pool, payment_id, 10000, &months(&[Month::January])).await);
+    error_message(refund(&pool, payment_id, 1000).await);
+
+    pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+
+    let history = get_payment_history(&pool, payment_id).await?;
+    assert_eq!(actions(&history), vec!["voided", "created"]);
+    assert_eq!(history[0].months, "");
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_not_refund_more_than_paid(pool: SqlitePool) -> HttpResult<()> {
+    setup(&pool).await?;
+    let payment_id = pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+
+    let first_refund = refund(&pool, payment_id, 6000).await?;
+    assert_eq!(
+        error_message(refund(&pool, payment_id, 5000).await),
+        "Suma rambursată depășește valoarea rămasă a plății (40 RON)"
+    );
+    let second_refund = refund(&pool, payment_id, 4000).await?;
+    error_message(refund(&pool, payment_id, 1).await);
+
+    // Refunds are negative payments, which can't be refunded themselves
+    assert_eq!(payment(&pool, first_refund).await?.amount, -6000);
+    assert_eq!(
+        payment(&pool, first_refund).await?.refund_of,
+        Some(payment_id)
+    );
+    error_message(refund(&pool, first_refund, 1000).await);
+    error_message(edit(&pool, first_refund, 1000, &[]).await);
+
+    // Voiding a refund makes its amount refundable again
+    void(&pool, second_refund, "Rambursată din greșeală").await?;
+    refund(&pool, payment_id, 4000).await?;
+
+    let history = get_payment_history(&pool, payment_id).await?;
+    assert_eq!(
+        actions(&history),
+        vec!["refunded", "refunded", "refunded", "created"]
+    );
+    assert_eq!(history[0].amount, -4000);
+    assert_eq!(
+        actions(&get_payment_history(&pool, first_refund).await?),
+        vec!["created"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_not_edit_below_refunded_amount(pool: SqlitePool) -> HttpResult<()> {
+    setup(&pool).await?;
+    let payment_id = pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+    refund(&pool, payment_id, 6000).await?;
+
+    assert_eq!(
+        error_message(edit(&pool, payment_id, 5000, &months(&[Month::January])).await),
+        "Suma nu poate fi mai mică decât cea deja rambursată (60 RON)"
+    );
+    // Nothing was changed
+    let unchanged = payment(&pool, payment_id).await?;
+    assert_eq!(unchanged.amount, 10000);
+    assert_eq!(unchanged.allocations.len(), 2);
+
+    edit(&pool, payment_id, 6000, &months(&[Month::January])).await?;
+    assert_eq!(payment(&pool, payment_id).await?.amount, 6000);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_not_void_refunded_payment(pool: SqlitePool) -> HttpResult<()> {
+    setup(&pool).await?;
+    let payment_id = pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+    let refund_id = refund(&pool, payment_id, 6000).await?;
+
+    assert_eq!(
+        error_message(void(&pool, payment_id, "Greșeală").await),
+        "Plata are rambursări de 60 RON, anulează-le înainte de a anula plata"
+    );
+    assert!(!payment(&pool, payment_id).await?.is_voided());
+
+    // Once the refund is voided, the payment can be voided too
+    void(&pool, refund_id, "Rambursată din greșeală").await?;
+    void(&pool, payment_id, "Greșeală").await?;
+    assert!(payment(&pool, payment_id).await?.is_voided());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_issue_corrective_receipts(pool: SqlitePool) -> HttpResult<()> {
+    setup(&pool).await?;
+    let payment_id = pay(&pool, 10000, &months(&[Month::January, Month::February])).await?;
+    let original = get_receipt(&pool, payment_id).await?.unwrap();
+
+    edit(
+        &pool,
+        payment_id,
+        15000,
+        &months(&[Month::February, Month::March]),
+    )
+    .await?;
+    let corrected = get_receipt(&pool, payment_id).await?.unwrap();
+    assert_eq!(corrected.corrects, Some(original.number));
+    assert_eq!(corrected.amount, 15000);
+    assert_eq!(corrected.months, "2-2024,3-2024");
+    assert_eq!(
+        payment(&pool, payment_id).await?.receipt_number,
+        Some(corrected.number)
+    );
+
+    // Nothing printed on the receipt changed
+    edit(
+        &pool,
+        payment_id,
+        15000,
+        &months(&[Month::February, Month::March]),
+    )
+    .await?;
+    assert_eq!(
+        get_receipt(&pool, payment_id).await?.unwrap().number,
+        corrected.number
+    );
+
+    let refund_id = refund(&pool, payment_id, 6000).await?;
+    let storno = get_receipt(&pool, refund_id).await?.unwrap();
+    assert!(storno.is_storno());
+    assert_eq!(storno.amount, -6000);
+    assert_eq!(storno.corrects, Some(corrected.number));
+    assert!(String::from_utf8_lossy(&storno.render_pdf()).contains("(CHITANTA STORNO)"));
+
+    // The replaced receipt is no longer listed
+    assert_eq!(
+        get_user_receipts(&pool, 1000)
+            .await?
+            .iter()
+            .map(|receipt| receipt.number)
+            .collect::<Vec<_>>(),
+        vec![storno.number, corrected.number]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use super::*;
+use crate::http::auth::UserAuthenticator;
+use crate::http::pages::admin::members::debtors::compute_debtors;
+use crate::model::user::UserCredentials;
+use crate::utils::queries::get_celebrated_members;
+use axum_login::AuthnBackend;
+use sqlx::SqlitePool;
+use time::macros::date;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    let password_hash = generate_hash_from_password("parola");
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'active@test.com', 'Membru Activ', $1, 100, FALSE, '2000-05-01', '2024-01-01'),
+               (1001, 'deleted@test.com', 'Membru Șters', $1, 100, FALSE, '2000-05-01', '2024-01-01');
+        "#,
+        password_hash
+    )
+    .execute(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    set_membership_status(
+        tx.as_mut(),
+        1001,
+        StatusChange {
+            status: MembershipStatus::Deleted,
+            reason: None,
+            until: None,
+            changed_by: Some(0),
+        },
+    )
+    .await?;
+    tx.commit().await
+}
+
+#[sqlx::test]
+async fn should_keep_deleted_member_data(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let deleted = get_deleted_members(&pool).await?;
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].email, "deleted@test.com");
+    assert_eq!(deleted[0].name, "Membru Șters");
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_exclude_deleted_members_from_lists(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let members = find_members(&pool, "Membru", MembersSortOrder::Alphabetical, "").await?;
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].id, 1000);
+
+    let deleted_status = MembershipStatus::Deleted.as_ref();
+    let members = find_members(&pool, "", MembersSortOrder::Alphabetical, deleted_status).await?;
+    assert!(members.is_empty());
+
+    let debtors = compute_debtors(&pool, local_date().year()).await?;
+    assert!(debtors.iter().all(|debtor| debtor.member.id != 1001));
+
+    let celebrated = get_celebrated_members(&pool, date!(2025 - 05 - 01)).await?;
+    assert_eq!(celebrated.len(), 1);
+    assert_eq!(celebrated[0].id, 1000);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_not_log_in_deleted_members(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let authenticator = UserAuthenticator::new(pool.clone());
+
+    let user = authenticator
+        .authenticate(
+            UserCredentials {
+                email: "deleted@test.com".to_string(),
+                password: "parola".to_string(),
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+    assert!(user.is_none());
+
+    let session_user = authenticator
+        .get_user(&"deleted@test.com".to_string())
+        .await
+        .unwrap();
+    assert!(session_user.is_none());
+
+    let user = authenticator
+        .authenticate(
+            UserCredentials {
+                email: "active@test.com".to_string(),
+                password: "parola".to_string(),
+            }
+            .into(),
+        )
+        .await
+        .unwrap();
+    assert!(user.is_some());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_restore_deleted_member(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let mut tx = pool.begin().await?;
+    restore_member(tx.as_mut(), 1001, 0).await.unwrap();
+    tx.commit().await?;
+
+    let member = get_user(&pool, 1001).await?;
+    assert_eq!(member.membership_status(), MembershipStatus::Active);
+    assert!(member.is_active);
+    assert!(get_deleted_members(&pool).await?.is_empty());
+
+    let mut tx = pool.begin().await?;
+    assert!(restore_member(tx.as_mut(), 1000, 0).await.is_err());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_not_restore_erased_member(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    query!("update users set erased_at = CURRENT_TIMESTAMP where id = 1001")
+        .execute(&pool)
+        .await?;
+
+    let mut tx = pool.begin().await?;
+    assert!(restore_member(tx.as_mut(), 1001, 0).await.is_err());
+
+    Ok(())
+}
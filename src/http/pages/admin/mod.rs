@@ -1,33 +1,93 @@
 use crate::http::AppState;
-use crate::http::error::{HttpError, HttpResult};
+use crate::http::auth::UserAuthenticator;
+use crate::http::error::{HttpError, HttpResult, OrBail};
 use crate::http::pages::AuthSession;
+use crate::http::pages::admin::entrance_codes::{
+    EntranceCodeEntry, get_entrance_code_history, set_entrance_code, update_entrance_codes,
+};
+use crate::http::pages::admin::key_holders::KEY_HOLDER_WARNING_MAX_HOURS;
 use crate::http::pages::admin::members::debtors::{DebtorItem, compute_debtors};
+use crate::http::pages::admin::members::dunning::{
+    DunningStep, get_dunning_steps, update_dunning_steps,
+};
 use crate::http::template_into_response::TemplateIntoResponse;
 use crate::model::global_vars::GlobalVars;
+use crate::model::role::Permission;
 use crate::model::user::User;
-use crate::utils::local_date;
 use crate::utils::queries::get_global_vars;
+use crate::utils::{date_formats, local_date};
 use askama::Template;
 use axum::extract::{Query, State};
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Form, Router};
+use axum_login::permission_required;
 use serde::Deserialize;
 use sqlx::query;
+use time::OffsetDateTime;
 
+pub mod announcements;
+pub mod entrance_codes;
 mod guests;
-mod members;
+pub mod key_holders;
+pub mod members;
 mod roles;
 mod schedule_overrides;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(admin_page))
-        .route("/apply_settings", post(apply_settings))
+        .merge(
+            Router::new()
+                .route("/apply_settings", post(apply_settings))
+                .route("/dunning_steps", post(update_dunning_steps))
+                .route("/entrance_code/rotate", post(entrance_codes::rotate_now))
+                .route_layer(permission_required!(
+                    UserAuthenticator,
+                    login_url = "/",
+                    Permission::ManageSettings.as_ref()
+                )),
+        )
+        .nest(
+            "/announcements",
+            announcements::router().route_layer(permission_required!(
+                UserAuthenticator,
+                login_url = "/",
+                Permission::ManageSettings.as_ref()
+            )),
+        )
         .nest("/members", members::router())
-        .nest("/roles", roles::router())
-        .nest("/guests", guests::router())
-        .merge(schedule_overrides::router())
+        .nest(
+            "/roles",
+            roles::router().route_layer(permission_required!(
+                UserAuthenticator,
+                login_url = "/",
+                Permission::ManageRoles.as_ref()
+            )),
+        )
+        .nest(
+            "/guests",
+            guests::router().route_layer(permission_required!(
+                UserAuthenticator,
+                login_url = "/",
+                Permission::CheckIn.as_ref()
+            )),
+        )
+        .nest(
+            "/key_holders",
+            key_holders::router().route_layer(permission_required!(
+                UserAuthenticator,
+                login_url = "/",
+                Permission::ManageSchedule.as_ref()
+            )),
+        )
+        .merge(
+            schedule_overrides::router().route_layer(permission_required!(
+                UserAuthenticator,
+                login_url = "/",
+                Permission::ManageSchedule.as_ref()
+            )),
+        )
 }
 
 #[derive(Deserialize)]
@@ -47,16 +107,25 @@ async fn admin_page(
         global_vars: GlobalVars,
         selected_year: i32,
         debtors: Vec<DebtorItem>,
+        dunning_steps: Vec<DunningStep>,
+        entrance_code_history: Vec<EntranceCodeEntry>,
     }
 
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
     let selected_year = query.year.unwrap_or_else(|| local_date().year());
-    let debtors = compute_debtors(&state.read_pool, selected_year).await?;
+    let debtors = if user.has_permission(Permission::ViewReports) {
+        compute_debtors(&state.read_pool, selected_year).await?
+    } else {
+        Vec::new()
+    };
 
     AdminTemplate {
-        user: auth_session.user.ok_or(HttpError::Unauthorized)?,
+        user,
         global_vars: get_global_vars(&state.read_pool).await?,
         selected_year,
         debtors,
+        dunning_steps: get_dunning_steps(&state.read_pool).await?,
+        entrance_code_history: get_entrance_code_history(&state.read_pool, 10).await?,
     }
     .try_into_response()
 }
@@ -64,23 +133,132 @@ async fn admin_page(
 #[derive(Deserialize)]
 struct NewSettings {
     in_maintenance: Option<String>,
+    dunning_enabled: Option<String>,
     entrance_code: String,
-    homepage_message: String,
+    monthly_fee: f64,
+    club_name: String,
+    club_details: String,
+    /// Left empty to disable automatic suspensions
+    auto_suspend_unpaid_months: String,
+    /// Left empty when no maintenance is scheduled
+    maintenance_starts_at: String,
+    maintenance_ends_at: String,
+    maintenance_banner: Option<String>,
+    /// Left empty to only change the entrance code by hand
+    entrance_code_rotation_days: String,
+    slot_entrance_codes: Option<String>,
+    /// Left empty to never warn about slots without a key-holder
+    key_holder_warning_hours: String,
+}
+
+fn parse_maintenance_time(input: &str) -> HttpResult<Option<OffsetDateTime>> {
+    match input.trim() {
+        "" => Ok(None),
+        input => date_formats::parse_local_input(input)
+            .map(Some)
+            .or_bail("Data mentenanței nu este validă"),
+    }
 }
 
 async fn apply_settings(
     State(state): State<AppState>,
+    auth_session: AuthSession,
     Form(settings): Form<NewSettings>,
 ) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
     let in_maintenance = settings.in_maintenance.is_some();
+    let dunning_enabled = settings.dunning_enabled.is_some();
+    if settings.monthly_fee < 0.0 {
+        return Err(HttpError::Message("Cotizația nu poate fi negativă".into()));
+    }
+    let monthly_fee = (settings.monthly_fee * 100.0).round() as i64;
+    let auto_suspend_unpaid_months = match settings.auto_suspend_unpaid_months.trim() {
+        "" => None,
+        months => Some(
+            months
+                .parse::<i64>()
+                .ok()
+                .filter(|months| *months >= 0)
+                .or_bail("Numărul de luni pentru suspendare nu este valid")?,
+        ),
+    };
+    let maintenance_starts_at = parse_maintenance_time(&settings.maintenance_starts_at)?;
+    let maintenance_ends_at = parse_maintenance_time(&settings.maintenance_ends_at)?;
+    let maintenance_banner = settings.maintenance_banner.is_some();
+    match (maintenance_starts_at, maintenance_ends_at) {
+        (None, Some(_)) => {
+            return Err(HttpError::Message(
+                "Mentenanța programată trebuie să aibă un început".into(),
+            ));
+        }
+        (Some(starts_at), Some(ends_at)) if ends_at <= starts_at => {
+            return Err(HttpError::Message(
+                "Mentenanța trebuie să se termine după ce începe".into(),
+            ));
+        }
+        _ => {}
+    }
+    let entrance_code = settings.entrance_code.trim();
+    if entrance_code.is_empty() {
+        return Err(HttpError::Message(
+            "Codul de intrare nu poate fi gol".into(),
+        ));
+    }
+    let entrance_code_rotation_days = match settings.entrance_code_rotation_days.trim() {
+        "" => None,
+        days => Some(
+            days.parse::<i64>()
+                .ok()
+                .filter(|days| *days >= 1)
+                .or_bail("Numărul de zile pentru schimbarea codului nu este valid")?,
+        ),
+    };
+    let slot_entrance_codes = settings.slot_entrance_codes.is_some();
+    let key_holder_warning_hours = match settings.key_holder_warning_hours.trim() {
+        "" => None,
+        hours => Some(
+            hours
+                .parse::<i64>()
+                .ok()
+                .filter(|hours| (1..=KEY_HOLDER_WARNING_MAX_HOURS).contains(hours))
+                .or_bail("Numărul de ore pentru avertizarea lipsei cheii nu este valid")?,
+        ),
+    };
+
+    let previous = get_global_vars(&state.read_pool).await?;
+    let now = OffsetDateTime::now_utc();
+    let mut tx = state.write_pool.begin().await?;
+    if previous.entrance_code != entrance_code {
+        set_entrance_code(tx.as_mut(), entrance_code, Some(user.id), now).await?;
+    }
     query!(
-        "update global_vars set in_maintenance = $1, entrance_code = $2, homepage_message = $3",
+        "update global_vars set in_maintenance = $1, monthly_fee = $2, dunning_enabled = $3,
+         club_name = $4, club_details = $5, auto_suspend_unpaid_months = $6, maintenance_starts_at = $7,
+         maintenance_ends_at = $8, maintenance_banner = $9, entrance_code_rotation_days = $10,
+         slot_entrance_codes = $11, key_holder_warning_hours = $12",
         in_maintenance,
-        settings.entrance_code,
-        settings.homepage_message
+        monthly_fee,
+        dunning_enabled,
+        settings.club_name,
+        settings.club_details,
+        auto_suspend_unpaid_months,
+        maintenance_starts_at,
+        maintenance_ends_at,
+        maintenance_banner,
+        entrance_code_rotation_days,
+        slot_entrance_codes,
+        key_holder_warning_hours
     )
-    .execute(&state.write_pool)
+    .execute(&mut *tx)
     .await?;
+    tx.commit().await?;
+
+    if previous.entrance_code != entrance_code
+        || previous.slot_entrance_codes != slot_entrance_codes
+    {
+        // Also creates the slot codes right away, when they were just enabled
+        update_entrance_codes(&state, now).await?;
+    }
 
     Ok("Setările au fost aplicate".into_response())
 }
@@ -3,19 +3,23 @@ use crate::http::error::{HttpError, HttpResult};
 use crate::http::pages::AuthSession;
 use crate::http::pages::notification_template::error_bubble_response;
 use crate::http::template_into_response::TemplateIntoResponse;
-use crate::model::role::UserRole;
+use crate::model::role::{Permission, UserRole};
 use crate::model::user::User;
 use crate::utils::CssColor;
 use askama::Template;
+use axum::Router;
 use axum::extract::{Path, State};
 use axum::response::IntoResponse;
 use axum::routing::{delete, get, post};
-use axum::{Form, Router};
+use axum_extra::extract::Form as AxumExtraForm;
 use serde::Deserialize;
-use sqlx::{query, query_as};
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as, query_scalar};
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 
+#[cfg(test)]
+mod test;
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(roles_page))
@@ -26,6 +30,44 @@ pub fn router() -> Router<AppState> {
         .route("/{id}", delete(delete_role))
 }
 
+pub async fn get_role_permissions(
+    executor: impl SqliteExecutor<'_>,
+    role_id: i64,
+) -> sqlx::Result<Vec<Permission>> {
+    Ok(query_scalar!(
+        "select permission from role_permissions where role_id = $1",
+        role_id
+    )
+    .fetch_all(executor)
+    .await?
+    .into_iter()
+    .filter_map(|permission| Permission::from_str(&permission).ok())
+    .collect())
+}
+
+pub async fn set_role_permissions(
+    tx: &mut SqliteConnection,
+    role_id: i64,
+    permissions: &[Permission],
+) -> sqlx::Result<()> {
+    query!("delete from role_permissions where role_id = $1", role_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for permission in permissions {
+        let permission = permission.as_ref();
+        query!(
+            "insert or ignore into role_permissions (role_id, permission) values ($1, $2)",
+            role_id,
+            permission
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
 async fn roles_page(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
     struct UserRoleWithCount {
         pub id: i64,
@@ -34,7 +76,21 @@ async fn roles_page(State(state): State<AppState>, auth_session: AuthSession) ->
         pub guest_reservations: i64,
         pub color: Option<String>,
         pub admin_panel_access: bool,
+        pub unpaid_grace_months: Option<i64>,
+        pub unpaid_reservations: i64,
+        pub require_two_factor: bool,
         pub members_count: i64,
+        pub permissions: String,
+    }
+
+    impl UserRoleWithCount {
+        fn permission_names(&self) -> Vec<&'static str> {
+            self.permissions
+                .split(',')
+                .filter_map(|permission| Permission::from_str(permission).ok())
+                .map(|permission| permission.display_name())
+                .collect()
+        }
     }
 
     #[derive(Template)]
@@ -44,7 +100,9 @@ async fn roles_page(State(state): State<AppState>, auth_session: AuthSession) ->
         roles: Vec<UserRoleWithCount>,
     }
 
-    let roles = query_as!(UserRoleWithCount, "select r.*, (select count(*) from users u where u.role_id = r.id) as 'members_count' from user_roles r")
+    let roles = query_as!(UserRoleWithCount, "select r.*, (select count(*) from users_with_role u where u.role_id = r.id) as 'members_count',
+        coalesce((select group_concat(p.permission) from role_permissions p where p.role_id = r.id), '') as 'permissions!: String'
+        from user_roles r")
         .fetch_all(&state.read_pool)
         .await?;
 
@@ -61,6 +119,32 @@ struct NewRole {
     reservations: i64,
     as_guest: i64,
     color: String,
+    enforce_payments: Option<String>,
+    #[serde(default)]
+    unpaid_grace_months: i64,
+    #[serde(default)]
+    unpaid_reservations: i64,
+    #[serde(default)]
+    permissions: Vec<String>,
+    require_two_factor: Option<String>,
+}
+
+impl NewRole {
+    fn unpaid_grace_months(&self) -> Option<i64> {
+        self.enforce_payments
+            .is_some()
+            .then_some(self.unpaid_grace_months)
+    }
+
+    fn permissions(&self) -> HttpResult<Vec<Permission>> {
+        self.permissions
+            .iter()
+            .map(|permission| {
+                Permission::from_str(permission)
+                    .map_err(|_| HttpError::Message("Permisiune invalidă".into()))
+            })
+            .collect()
+    }
 }
 
 #[derive(Template)]
@@ -68,25 +152,48 @@ struct NewRole {
 struct NewOrEditRoleTemplate {
     user: User,
     current: Option<UserRole>,
+    permissions: Vec<Permission>,
+}
+
+impl NewOrEditRoleTemplate {
+    fn has_permission(&self, permission: &Permission) -> bool {
+        self.permissions.contains(permission)
+    }
 }
 
 async fn new_role_page(auth_session: AuthSession) -> HttpResult {
     NewOrEditRoleTemplate {
         user: auth_session.user.ok_or(HttpError::Unauthorized)?,
         current: None,
+        permissions: Vec::new(),
     }
     .try_into_response()
 }
 
-async fn create_new_role(State(state): State<AppState>, Form(role): Form<NewRole>) -> HttpResult {
-    query!(
-        "insert into user_roles (name, reservations, guest_reservations) values ($1, $2, $3)",
+async fn create_new_role(
+    State(state): State<AppState>,
+    AxumExtraForm(role): AxumExtraForm<NewRole>,
+) -> HttpResult {
+    let unpaid_grace_months = role.unpaid_grace_months();
+    let permissions = role.permissions()?;
+    let require_two_factor = role.require_two_factor.is_some();
+
+    let mut tx = state.write_pool.begin().await?;
+    let role_id = query!(
+        "insert into user_roles (name, reservations, guest_reservations, unpaid_grace_months, unpaid_reservations, require_two_factor)
+         values ($1, $2, $3, $4, $5, $6)",
         role.name,
         role.reservations,
-        role.as_guest
+        role.as_guest,
+        unpaid_grace_months,
+        role.unpaid_reservations,
+        require_two_factor
     )
-    .execute(&state.write_pool)
-    .await?;
+    .execute(tx.as_mut())
+    .await?
+    .last_insert_rowid();
+    set_role_permissions(tx.as_mut(), role_id, &permissions).await?;
+    tx.commit().await?;
 
     Ok([("HX-Redirect", "/admin/roles")].into_response())
 }
@@ -107,27 +214,46 @@ async fn edit_role_page(
     NewOrEditRoleTemplate {
         user: auth_session.user.ok_or(HttpError::Unauthorized)?,
         current: role,
+        permissions: get_role_permissions(&state.read_pool, role_id).await?,
     }
     .try_into_response()
 }
 
 async fn update_role(
     State(state): State<AppState>,
+    auth_session: AuthSession,
     Path(role_id): Path<i64>,
-    Form(role): Form<NewRole>,
+    AxumExtraForm(role): AxumExtraForm<NewRole>,
 ) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
     let color = CssColor::from_str(role.color.as_str()).unwrap_or(CssColor::None);
     let color = color.as_ref();
+    let unpaid_grace_months = role.unpaid_grace_months();
+    let permissions = role.permissions()?;
+    let require_two_factor = role.require_two_factor.is_some();
+    if user.role_id == role_id && !permissions.contains(&Permission::ManageRoles) {
+        return Err(HttpError::Message(
+            "Nu îți poți retrage permisiunea de a gestiona rolurile".into(),
+        ));
+    }
+
+    let mut tx = state.write_pool.begin().await?;
     query!(
-        "update user_roles set name = $2, reservations = $3, guest_reservations = $4, color = $5 where id = $1",
+        "update user_roles set name = $2, reservations = $3, guest_reservations = $4, color = $5,
+         unpaid_grace_months = $6, unpaid_reservations = $7, require_two_factor = $8 where id = $1",
         role_id,
         role.name,
         role.reservations,
         role.as_guest,
-        color
+        color,
+        unpaid_grace_months,
+        role.unpaid_reservations,
+        require_two_factor
     )
-    .execute(&state.write_pool)
+    .execute(tx.as_mut())
     .await?;
+    set_role_permissions(tx.as_mut(), role_id, &permissions).await?;
+    tx.commit().await?;
 
     Ok([("HX-Redirect", "/admin/roles")].into_response())
 }
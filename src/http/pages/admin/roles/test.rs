@@ -0,0 +1,79 @@
+use super::*;
+use crate::http::auth::UserAuthenticator;
+use crate::utils::queries::get_user;
+use axum_login::AuthzBackend;
+use sqlx::SqlitePool;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_grant_every_permission_to_existing_admins(pool: SqlitePool) -> sqlx::Result<()> {
+    let admin = get_user(&pool, 0).await?;
+
+    assert!(admin.has_admin_panel());
+    assert!(Permission::iter().all(|permission| admin.has_permission(permission)));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_only_grant_the_role_permissions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let authenticator = UserAuthenticator::new(pool.clone());
+
+    let member = get_user(&pool, 1000).await?;
+    assert!(!member.has_admin_panel());
+    let permissions = authenticator.get_all_permissions(&member).await.unwrap();
+    assert!(!permissions.contains(Permission::ADMIN_PANEL));
+
+    let mut tx = pool.begin().await?;
+    set_role_permissions(
+        tx.as_mut(),
+        100,
+        &[Permission::CheckIn, Permission::ViewReports],
+    )
+    .await?;
+    tx.commit().await?;
+
+    let member = get_user(&pool, 1000).await?;
+    assert!(member.has_admin_panel());
+    assert!(member.has_permission(Permission::CheckIn));
+    assert!(!member.has_permission(Permission::ManagePayments));
+
+    let permissions = authenticator.get_all_permissions(&member).await.unwrap();
+    assert!(permissions.contains(Permission::ADMIN_PANEL));
+    assert!(permissions.contains("check_in"));
+    assert!(permissions.contains("view_reports"));
+    assert!(!permissions.contains("manage_settings"));
+
+    assert_eq!(get_role_permissions(&pool, 100).await?.len(), 2);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_replace_role_permissions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let mut tx = pool.begin().await?;
+    set_role_permissions(tx.as_mut(), 100, &[Permission::ManageMembers]).await?;
+    set_role_permissions(tx.as_mut(), 100, &[]).await?;
+    tx.commit().await?;
+
+    assert!(get_role_permissions(&pool, 100).await?.is_empty());
+    assert!(!get_user(&pool, 1000).await?.has_admin_panel());
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::notification_template::NotificationBubbleResponse;
+use crate::http::pages::user::notifications::notifications_badge;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::announcement::Announcement;
+use crate::model::notification::NotificationKind;
+use crate::model::user::User;
+use crate::utils::date_formats;
+use askama::Template;
+use axum::extract::{Path, State};
+use sqlx::{SqliteExecutor, SqlitePool, query, query_as, query_scalar};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use tracing::error;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Template)]
+#[template(path = "home/announcements.html")]
+pub struct AnnouncementsTemplate {
+    pub announcements: Vec<Announcement>,
+}
+
+#[derive(Template)]
+#[template(path = "home/announcement_card.html")]
+struct AnnouncementCardTemplate {
+    announcement: Announcement,
+}
+
+/// The announcements the member can see right now, pinned ones first
+pub async fn get_visible_announcements(
+    executor: impl SqliteExecutor<'_>,
+    user: &User,
+    now: OffsetDateTime,
+) -> sqlx::Result<Vec<Announcement>> {
+    query_as!(
+        Announcement,
+        r#"select a.id, a.title, a.body, a.pinned, a.starts_at as "starts_at: OffsetDateTime",
+            exists (select 1 from announcement_reads r where r.announcement_id = a.id and r.user_id = $1) as "read!: bool"
+           from announcements a
+           where a.starts_at <= $3 and (a.ends_at is null or a.ends_at > $3)
+             and (not exists (select 1 from announcement_roles ar where ar.announcement_id = a.id)
+                  or exists (select 1 from announcement_roles ar where ar.announcement_id = a.id and ar.role_id = $2))
+           order by a.pinned desc, a.starts_at desc, a.id desc"#,
+        user.id,
+        user.role_id,
+        now
+    )
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn get_visible_announcement(
+    executor: impl SqliteExecutor<'_>,
+    id: i64,
+    user: &User,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<Announcement>> {
+    Ok(get_visible_announcements(executor, user, now)
+        .await?
+        .into_iter()
+        .find(|announcement| announcement.id == id))
+}
+
+pub async fn mark_announcement_read(
+    executor: impl SqliteExecutor<'_>,
+    id: i64,
+    user_id: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    query!(
+        "insert into announcement_reads (announcement_id, user_id, read_at) values ($1, $2, $3)
+         on conflict do nothing",
+        id,
+        user_id,
+        now
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Notifies the audience of the announcements that became visible since the last time
+/// and tells the members online about them, returns how many there were
+pub async fn publish_due_announcements(
+    pool: &SqlitePool,
+    notifier: &broadcast::Sender<i64>,
+    now: OffsetDateTime,
+) -> sqlx::Result<usize> {
+    let mut tx = pool.begin().await?;
+    let ids = query_scalar!(
+        "update announcements set published_at = $1
+         where published_at is null and starts_at <= $1 and (ends_at is null or ends_at > $1)
+         returning id",
+        now
+    )
+    .fetch_all(tx.as_mut())
+    .await?;
+
+    let kind = NotificationKind::Announcement.as_ref();
+    for id in &ids {
+        query!(
+            "insert into notifications (user_id, kind, message, link, created_at)
+             select u.id, $2, 'Anunț nou: ' || a.title, '/', $3 from users u
+             inner join announcements a on a.id = $1
+             where u.is_active = true
+               and (not exists (select 1 from announcement_roles ar where ar.announcement_id = a.id)
+                    or exists (select 1 from announcement_roles ar where ar.announcement_id = a.id and ar.role_id = u.role_id))",
+            id,
+            kind,
+            now
+        )
+        .execute(tx.as_mut())
+        .await?;
+    }
+    tx.commit().await?;
+
+    for id in &ids {
+        // Fails only when no one is connected
+        let _ = notifier.send(*id);
+    }
+    Ok(ids.len())
+}
+
+/// What is sent over the socket of a member when an announcement is published,
+/// `None` when the member is not in its audience
+pub async fn announcement_update(state: &AppState, user: &User, id: i64) -> Option<String> {
+    let now = OffsetDateTime::now_utc();
+    let result = async {
+        let Some(announcement) = get_visible_announcement(&state.read_pool, id, user, now).await?
+        else {
+            return Ok::<_, sqlx::Error>(None);
+        };
+        let announcements = get_visible_announcements(&state.read_pool, user, now).await?;
+        let badge = notifications_badge(&state.read_pool, user.id).await?;
+        Ok(Some((announcement, announcements, badge)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((announcement, announcements, badge))) => {
+            let message = format!("Anunț nou: {}", announcement.title);
+            Some(format!(
+                "{}{}{}",
+                NotificationBubbleResponse {
+                    message: message.as_str()
+                },
+                AnnouncementsTemplate { announcements },
+                badge
+            ))
+        }
+        Ok(None) => None,
+        Err(e) => {
+            error!("Failed to get the announcement {id}: {e}");
+            None
+        }
+    }
+}
+
+pub async fn mark_read(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let now = OffsetDateTime::now_utc();
+    get_visible_announcement(&state.read_pool, id, &user, now)
+        .await?
+        .or_bail("Anunțul nu mai este disponibil")?;
+
+    mark_announcement_read(&state.write_pool, id, user.id, now).await?;
+
+    AnnouncementCardTemplate {
+        announcement: get_visible_announcement(&state.write_pool, id, &user, now)
+            .await?
+            .or_bail("Anunțul nu mai este disponibil")?,
+    }
+    .try_into_response()
+}
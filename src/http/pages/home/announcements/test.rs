@@ -0,0 +1,164 @@
+use super::*;
+use crate::http::pages::admin::announcements::{NewAnnouncement, create_announcement};
+use crate::utils::markdown::render_markdown;
+use crate::utils::queries::get_user;
+use time::macros::datetime;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into user_roles VALUES (200, 'Other Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (2000, 'test2@test.com', 'Test 2', '', 200, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn announcement(title: &str, starts_at: OffsetDateTime) -> NewAnnouncement {
+    NewAnnouncement {
+        title: title.to_string(),
+        body: "**Important**".to_string(),
+        pinned: false,
+        starts_at,
+        ends_at: None,
+        role_ids: Vec::new(),
+    }
+}
+
+async fn create(pool: &SqlitePool, announcement: NewAnnouncement) -> sqlx::Result<i64> {
+    let mut tx = pool.begin().await?;
+    let id = create_announcement(tx.as_mut(), &announcement, 0, announcement.starts_at).await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+fn titles(announcements: &[Announcement]) -> Vec<&str> {
+    announcements
+        .iter()
+        .map(|announcement| announcement.title.as_str())
+        .collect()
+}
+
+#[sqlx::test]
+async fn should_only_show_announcements_to_their_audience(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-10 12:00 UTC);
+
+    create(
+        &pool,
+        announcement("Everyone", datetime!(2025-06-01 12:00 UTC)),
+    )
+    .await?;
+    create(
+        &pool,
+        NewAnnouncement {
+            pinned: true,
+            ..announcement("Pinned", datetime!(2025-05-01 12:00 UTC))
+        },
+    )
+    .await?;
+    create(
+        &pool,
+        NewAnnouncement {
+            role_ids: vec![200],
+            ..announcement("Other role", datetime!(2025-06-02 12:00 UTC))
+        },
+    )
+    .await?;
+    create(
+        &pool,
+        announcement("Scheduled", datetime!(2025-06-20 12:00 UTC)),
+    )
+    .await?;
+    create(
+        &pool,
+        NewAnnouncement {
+            ends_at: Some(datetime!(2025-06-05 12:00 UTC)),
+            ..announcement("Expired", datetime!(2025-06-03 12:00 UTC))
+        },
+    )
+    .await?;
+
+    let user = get_user(&pool, 1000).await?;
+    let visible = get_visible_announcements(&pool, &user, now).await?;
+    assert_eq!(titles(&visible), vec!["Pinned", "Everyone"]);
+
+    let other = get_user(&pool, 2000).await?;
+    let visible = get_visible_announcements(&pool, &other, now).await?;
+    assert_eq!(titles(&visible), vec!["Pinned", "Other role", "Everyone"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_track_reads_per_member(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-10 12:00 UTC);
+    let id = create(
+        &pool,
+        announcement("Everyone", datetime!(2025-06-01 12:00 UTC)),
+    )
+    .await?;
+
+    mark_announcement_read(&pool, id, 1000, now).await?;
+    mark_announcement_read(&pool, id, 1000, now).await?;
+
+    let user = get_user(&pool, 1000).await?;
+    let announcement = get_visible_announcement(&pool, id, &user, now).await?;
+    assert!(announcement.unwrap().read);
+
+    let other = get_user(&pool, 2000).await?;
+    let announcement = get_visible_announcement(&pool, id, &other, now).await?;
+    assert!(!announcement.unwrap().read);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_publish_announcements_once_they_start(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let (notifier, mut receiver) = broadcast::channel(16);
+    let current = create(
+        &pool,
+        announcement("Current", datetime!(2025-06-01 12:00 UTC)),
+    )
+    .await?;
+    let scheduled = create(
+        &pool,
+        announcement("Scheduled", datetime!(2025-06-20 12:00 UTC)),
+    )
+    .await?;
+
+    let now = datetime!(2025-06-10 12:00 UTC);
+    assert_eq!(publish_due_announcements(&pool, &notifier, now).await?, 1);
+    assert_eq!(receiver.try_recv().ok(), Some(current));
+    assert_eq!(publish_due_announcements(&pool, &notifier, now).await?, 0);
+
+    let now = datetime!(2025-06-20 12:00 UTC);
+    assert_eq!(publish_due_announcements(&pool, &notifier, now).await?, 1);
+    assert_eq!(receiver.try_recv().ok(), Some(scheduled));
+    assert!(receiver.try_recv().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn should_render_markdown_without_html_or_scripts() {
+    assert_eq!(
+        render_markdown("**Bold** [site](https://acspa.ro)"),
+        "<p><strong>Bold</strong> <a href=\"https://acspa.ro\">site</a></p>\n"
+    );
+    let html = render_markdown("<script>alert(1)</script>");
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("&lt;script&gt;"));
+    assert_eq!(
+        render_markdown("[click](javascript:alert(1))"),
+        "<p><a href=\"#\">click</a></p>\n"
+    );
+}
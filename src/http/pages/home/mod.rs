@@ -1,10 +1,16 @@
 use crate::http::AppState;
 use crate::http::error::{HttpError, HttpResult, OrBail};
 use crate::http::pages::AuthSession;
+use crate::http::pages::admin::entrance_codes::{MemberSlotCode, get_member_slot_codes};
+use crate::http::pages::home::announcements::get_visible_announcements;
 use crate::http::pages::home::reservation_hours::{ReservationHours, get_reservation_hours};
 use crate::http::pages::home::socket::handle_ws;
+use crate::http::pages::user::notifications::send_notification;
 use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::announcement::Announcement;
 use crate::model::global_vars::GlobalVars;
+use crate::model::notification::NotificationKind;
+use crate::model::role::Permission;
 use crate::model::user::User;
 use crate::reservation;
 use crate::reservation::{
@@ -23,9 +29,10 @@ use axum::{Form, Router};
 use serde::Deserialize;
 use sqlx::query;
 use std::str::FromStr;
-use time::Date;
+use time::{Date, OffsetDateTime};
 use tracing::{error, warn};
 
+pub mod announcements;
 pub mod reservation_hours;
 pub mod socket;
 
@@ -38,6 +45,7 @@ pub fn router() -> Router<AppState> {
         .route("/choose_hour", post(hour_picker))
         .route("/reservation", post(confirm_reservation))
         .route("/reservation", delete(cancel_reservation))
+        .route("/announcements/{id}/read", post(announcements::mark_read))
 }
 
 async fn index(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
@@ -59,6 +67,8 @@ async fn index(State(state): State<AppState>, auth_session: AuthSession) -> Http
         global_vars: GlobalVars,
         reservation_color_code: Vec<ColorCode>,
         has_paid: bool,
+        slot_codes: Vec<MemberSlotCode>,
+        announcements: Vec<Announcement>,
     }
 
     let current_date = local_time().date();
@@ -77,6 +87,14 @@ async fn index(State(state): State<AppState>, auth_session: AuthSession) -> Http
     let reservation_hours = get_reservation_hours(&state, current_date).await?;
     let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
     let has_paid = check_user_has_paid(&state.read_pool, &user).await?;
+    let global_vars = get_global_vars(&state.read_pool).await?;
+    let slot_codes = if global_vars.slot_entrance_codes {
+        get_member_slot_codes(&state.read_pool, user.id, current_date).await?
+    } else {
+        Vec::new()
+    };
+    let announcements =
+        get_visible_announcements(&state.read_pool, &user, OffsetDateTime::now_utc()).await?;
 
     HomeTemplate {
         current_date,
@@ -84,9 +102,11 @@ async fn index(State(state): State<AppState>, auth_session: AuthSession) -> Http
         days: DateIter::weeks_in_range(current_date, current_date + DAYS_AHEAD_ALLOWED),
         user,
         reservation_hours,
-        global_vars: get_global_vars(&state.read_pool).await?,
+        global_vars,
         reservation_color_code,
         has_paid,
+        slot_codes,
+        announcements,
     }
     .try_into_response()
 }
@@ -239,12 +259,14 @@ async fn cancel_reservation(
     let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
     let user_id = query.user_id.unwrap_or(user.id);
 
-    if (user_id != user.id || query.created_for.is_some()) && !user.admin_panel_access {
+    if (user_id != user.id || query.created_for.is_some())
+        && !user.has_permission(Permission::CheckIn)
+    {
         return Ok(StatusCode::UNAUTHORIZED.into_response());
     }
 
     let tx = state.write_pool.begin().await?;
-    let reservation_cancelled = reservation::cancel_reservation(
+    let cancellation = reservation::cancel_reservation(
         tx,
         &state.location,
         date,
@@ -254,11 +276,49 @@ async fn cancel_reservation(
     )
     .await?;
 
-    if !reservation_cancelled {
+    let Some(cancellation) = cancellation else {
         return Ok(StatusCode::BAD_REQUEST.into_response());
-    }
+    };
 
     let _ = state.reservation_notifier.send(());
 
+    let slot = format!(
+        "de la ora {} din {}",
+        query.hour,
+        date_formats::as_readable(&date)
+    );
+    if user_id != user.id {
+        let message = match &query.created_for {
+            Some(created_for) => {
+                format!("Rezervarea pentru {created_for} {slot} a fost anulată de un administrator")
+            }
+            None => format!("Rezervarea ta {slot} a fost anulată de un administrator"),
+        };
+        send_notification(
+            &state,
+            user_id,
+            NotificationKind::Cancellation,
+            &message,
+            Some("/profile"),
+        )
+        .await;
+    }
+    if let Some(promoted) = cancellation.promoted {
+        let message = match &promoted.created_for {
+            Some(created_for) => format!(
+                "S-a eliberat un loc, rezervarea pentru {created_for} {slot} nu mai este în așteptare"
+            ),
+            None => format!("S-a eliberat un loc, rezervarea ta {slot} nu mai este în așteptare"),
+        };
+        send_notification(
+            &state,
+            promoted.user_id,
+            NotificationKind::Promotion,
+            &message,
+            Some("/profile"),
+        )
+        .await;
+    }
+
     Ok(().into_response())
 }
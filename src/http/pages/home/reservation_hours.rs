@@ -1,4 +1,5 @@
 use crate::http::AppState;
+use crate::http::pages::admin::key_holders::get_on_call_key_holder;
 use crate::model::restriction::Restriction;
 use crate::utils::CssColor;
 use crate::utils::queries::{get_alt_day_structure_for_day, get_day_structure};
@@ -24,6 +25,13 @@ pub struct Reservations {
     pub cancelled: Vec<Reservation>,
 }
 
+impl Reservations {
+    /// Whether someone that can open the club is booked, guests don't count even if the member has a key
+    pub fn has_key_holder(&self) -> bool {
+        self.active.iter().any(|reservation| reservation.has_key)
+    }
+}
+
 pub struct ReservationsSlot {
     pub start_hour: u8,
     pub minute: Option<u8>,
@@ -35,6 +43,8 @@ pub struct ReservationHours {
     pub description: Option<String>,
     pub hours: Vec<ReservationsSlot>,
     pub capacity: Option<u8>,
+    /// The key-holder to call when no one with a key is booked
+    pub on_call: Option<String>,
 }
 
 pub async fn get_reservation_hours(state: &AppState, date: Date) -> sqlx::Result<ReservationHours> {
@@ -62,6 +72,7 @@ pub async fn get_reservation_hours(state: &AppState, date: Date) -> sqlx::Result
                 .collect(),
             description: day_structure.description,
             capacity: None,
+            on_call: None,
         });
     }
 
@@ -145,10 +156,14 @@ pub async fn get_reservation_hours(state: &AppState, date: Date) -> sqlx::Result
     let capacity = get_alt_day_structure_for_day(&state.read_pool, date)
         .await
         .and_then(|day| day.slot_capacity.map(|capacity| capacity as u8));
+    let on_call = get_on_call_key_holder(&state.read_pool, date)
+        .await?
+        .map(|on_call| on_call.name);
 
     Ok(ReservationHours {
         description: day_structure.description,
         hours,
         capacity,
+        on_call,
     })
 }
@@ -1,22 +1,25 @@
 use crate::http::AppState;
 use crate::http::error::{HttpError, HttpResult};
 use crate::http::pages::AuthSession;
+use crate::http::pages::home::announcements::announcement_update;
 use crate::http::pages::home::reservation_hours::{ReservationHours, get_reservation_hours};
 use crate::http::pages::home::{DAYS_AHEAD_ALLOWED, check_user_has_paid};
 use crate::http::pages::notification_template::NotificationBubbleResponse;
+use crate::http::pages::user::notifications::notification_update;
 use crate::model::user::User;
 use crate::utils::CssColor;
 use crate::utils::date_formats::READABLE_DATE;
 use crate::utils::date_iter::DateIter;
+use crate::utils::queries::get_celebrated_members;
 use crate::utils::{date_formats, local_time};
 use askama::Template;
 use axum::extract::ws::{Message, WebSocket};
 use axum::extract::{State, WebSocketUpgrade};
 use serde::Deserialize;
 use serde::de::IgnoredAny;
-use sqlx::query;
 use time::Date;
 use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{debug, error, warn};
 
 pub async fn handle_ws(
@@ -97,16 +100,12 @@ impl<'a> HoursTemplate<'a> {
 async fn handle_socket(mut socket: WebSocket, state: AppState, user: User) {
     let mut selected_date = local_time().date();
     let mut reservations_changed = state.reservation_notifier.subscribe();
+    let mut new_announcements = state.announcement_notifier.subscribe();
+    let mut new_notifications = state.notification_notifier.subscribe();
 
     if user.role == "Admin" {
         let current_date = local_time().date();
-        if let Ok(celebrated) = query!(
-            "select name, received_gift from users where strftime('%d%m', birthday) = strftime('%d%m', $1)",
-            current_date
-        )
-        .fetch_all(&state.read_pool)
-        .await
-        {
+        if let Ok(celebrated) = get_celebrated_members(&state.read_pool, current_date).await {
             for user in celebrated {
                 let gift = if let Some(gift_date) = user.received_gift {
                     format!(
@@ -123,7 +122,8 @@ async fn handle_socket(mut socket: WebSocket, state: AppState, user: User) {
                         NotificationBubbleResponse {
                             message: message.as_str(),
                         }
-                        .to_string().into(),
+                        .to_string()
+                        .into(),
                     ))
                     .await;
             }
@@ -148,6 +148,39 @@ async fn handle_socket(mut socket: WebSocket, state: AppState, user: User) {
 
                 HoursTemplate::create_response(&state, selected_date, &user, true).await
             }
+            result = new_announcements.recv() => {
+                let id = match result {
+                    Ok(id) => id,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Missed {skipped} announcements");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+
+                match announcement_update(&state, &user, id).await {
+                    Some(response) => response,
+                    None => continue,
+                }
+            }
+            result = new_notifications.recv() => {
+                let user_id = match result {
+                    Ok(user_id) => user_id,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Missed {skipped} notifications");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                };
+                if user_id != user.id {
+                    continue;
+                }
+
+                match notification_update(&state, user.id).await {
+                    Some(response) => response,
+                    None => continue,
+                }
+            }
             message = recv_task => {
                 let Some(ws_message) = WsMessage::parse(message) else {
                     return;
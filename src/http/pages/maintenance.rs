@@ -0,0 +1,111 @@
+use crate::http::AppState;
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::impersonation::get_impersonation;
+use crate::http::pages::notification_template::{add_page_banner, error_bubble_response};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::user::User;
+use crate::utils::date_formats;
+use crate::utils::queries::get_global_vars;
+use askama::Template;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+use time::OffsetDateTime;
+use tracing::error;
+
+#[cfg(test)]
+mod test;
+
+/// Still reachable by everyone during maintenance, so admins can log in
+/// and the payments made just before it started are still confirmed
+const MAINTENANCE_ALLOWED_PREFIXES: [&str; 3] = ["/login", "/logout", "/payments/webhook"];
+
+#[derive(Template)]
+#[template(path = "maintenance_page.html")]
+struct MaintenanceTemplate {
+    ends_at: Option<OffsetDateTime>,
+    is_logged_in: bool,
+}
+
+#[derive(Template)]
+#[template(path = "components/maintenance_banner.html")]
+struct MaintenanceBannerTemplate {
+    /// Set while the maintenance is going on, only admins see the app then
+    is_active: bool,
+    starts_at: Option<OffsetDateTime>,
+    ends_at: Option<OffsetDateTime>,
+}
+
+fn is_allowed_path(path: &str) -> bool {
+    MAINTENANCE_ALLOWED_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Keeps members out during maintenance, while admins can use everything,
+/// and warns about scheduled maintenance before it begins
+pub async fn maintenance_guard(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    request: Request,
+    next: Next,
+) -> Response {
+    let global_vars = match get_global_vars(&state.read_pool).await {
+        Ok(global_vars) => global_vars,
+        Err(e) => {
+            error!("Failed to check the maintenance mode: {e}");
+            return next.run(request).await;
+        }
+    };
+    let now = OffsetDateTime::now_utc();
+    let is_active = global_vars.is_in_maintenance(now);
+    let upcoming = global_vars.upcoming_maintenance(now);
+    if !is_active && upcoming.is_none() {
+        return next.run(request).await;
+    }
+
+    // Admins viewing the app as a member keep their access too
+    let is_admin = auth_session
+        .user
+        .as_ref()
+        .is_some_and(User::has_admin_panel)
+        || get_impersonation(&auth_session.session).await.is_some();
+    let is_full_page = !request.headers().contains_key("hx-request");
+
+    if is_active && !is_admin && !is_allowed_path(request.uri().path()) {
+        if !is_full_page {
+            return error_bubble_response(
+                "Aplicația este în mentenanță, rezervările nu pot fi modificate momentan",
+            );
+        }
+
+        // Anything else, like a plain form, gets the page with an error status, so it can be retried
+
+        let mut response = MaintenanceTemplate {
+            ends_at: global_vars
+                .maintenance_ends_at
+                .filter(|ends_at| *ends_at > now),
+            is_logged_in: auth_session.user.is_some(),
+        }
+        .into_response();
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        return response;
+    }
+
+    let response = next.run(request).await;
+    if !is_full_page {
+        return response;
+    }
+
+    let banner = MaintenanceBannerTemplate {
+        is_active,
+        starts_at: upcoming,
+        ends_at: global_vars
+            .maintenance_ends_at
+            .filter(|ends_at| *ends_at > now),
+    }
+    .render()
+    .unwrap_or_default();
+    add_page_banner(response, &banner).await
+}
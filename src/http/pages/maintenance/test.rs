@@ -0,0 +1,89 @@
+use super::*;
+use sqlx::{SqlitePool, query};
+use time::macros::datetime;
+
+async fn schedule(
+    pool: &SqlitePool,
+    starts_at: Option<OffsetDateTime>,
+    ends_at: Option<OffsetDateTime>,
+    banner: bool,
+) -> sqlx::Result<()> {
+    query!(
+        "update global_vars set in_maintenance = FALSE, maintenance_starts_at = $1, maintenance_ends_at = $2,
+         maintenance_banner = $3",
+        starts_at,
+        ends_at,
+        banner
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_follow_maintenance_window(pool: SqlitePool) -> sqlx::Result<()> {
+    let starts_at = datetime!(2025-06-01 22:00 UTC);
+    let ends_at = datetime!(2025-06-02 02:00 UTC);
+    schedule(&pool, Some(starts_at), Some(ends_at), true).await?;
+    let global_vars = get_global_vars(&pool).await?;
+
+    let before = datetime!(2025-06-01 12:00 UTC);
+    assert!(!global_vars.is_in_maintenance(before));
+    assert_eq!(global_vars.upcoming_maintenance(before), Some(starts_at));
+
+    assert!(global_vars.is_in_maintenance(starts_at));
+    assert_eq!(global_vars.upcoming_maintenance(starts_at), None);
+
+    assert!(!global_vars.is_in_maintenance(ends_at));
+    assert_eq!(global_vars.upcoming_maintenance(ends_at), None);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_only_announce_when_asked(pool: SqlitePool) -> sqlx::Result<()> {
+    let starts_at = datetime!(2025-06-01 22:00 UTC);
+    schedule(&pool, Some(starts_at), None, false).await?;
+    let global_vars = get_global_vars(&pool).await?;
+
+    assert_eq!(
+        global_vars.upcoming_maintenance(datetime!(2025-06-01 12:00 UTC)),
+        None
+    );
+    // Without an end, it lasts until switched off
+    assert!(global_vars.is_in_maintenance(datetime!(2030-01-01 00:00 UTC)));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_enable_maintenance_switch_any_time(pool: SqlitePool) -> sqlx::Result<()> {
+    schedule(&pool, None, None, true).await?;
+    query!("update global_vars set in_maintenance = TRUE")
+        .execute(&pool)
+        .await?;
+    let global_vars = get_global_vars(&pool).await?;
+
+    let now = datetime!(2025-06-01 12:00 UTC);
+    assert!(global_vars.is_in_maintenance(now));
+    assert_eq!(global_vars.upcoming_maintenance(now), None);
+
+    Ok(())
+}
+
+#[test]
+fn should_let_everyone_log_in() {
+    assert!(is_allowed_path("/login"));
+    assert!(is_allowed_path("/login/two_factor"));
+    assert!(is_allowed_path("/logout"));
+    assert!(!is_allowed_path("/"));
+    assert!(!is_allowed_path("/reserve"));
+}
+
+#[test]
+fn should_let_payment_webhooks_through() {
+    // Otherwise the payments made right before the maintenance would be lost
+    assert!(is_allowed_path("/payments/webhook"));
+    assert!(!is_allowed_path("/payments/mock/checkout/1"));
+}
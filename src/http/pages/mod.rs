@@ -1,15 +1,23 @@
 use crate::http::AppState;
 use crate::http::auth::UserAuthenticator;
 use crate::http::pages::user::login;
+use crate::model::role::Permission;
+use crate::oidc::OidcProvider;
 use axum::Router;
 use axum::routing::{get, post};
 use axum_login::{login_required, permission_required};
 
-mod admin;
-mod home;
+pub mod admin;
+pub mod home;
+mod maintenance;
 pub mod notification_template;
 mod user;
 
+pub use admin::members::impersonation::impersonation_guard;
+pub use maintenance::maintenance_guard;
+pub use user::push::deliver_pending_pushes;
+pub use user::sessions::track_session;
+
 pub type AuthSession = axum_login::AuthSession<UserAuthenticator>;
 
 pub fn router() -> Router<AppState> {
@@ -19,19 +27,40 @@ pub fn router() -> Router<AppState> {
             .route_layer(permission_required!(
                 UserAuthenticator,
                 login_url = "/",
-                "admin_panel"
+                Permission::ADMIN_PANEL
             ));
 
     let authenticated_router = Router::<AppState>::new()
         .merge(home::router())
         .merge(user::user_router())
         .route("/logout", post(login::logout))
+        .route(
+            "/impersonation/stop",
+            post(admin::members::impersonation::stop_impersonation),
+        )
         .route_layer(login_required!(UserAuthenticator, login_url = "/login"));
 
     let unauthenticated_router = Router::<AppState>::new()
         .route("/login", get(login::login_page))
         .route("/login", post(login::login))
-        .route("/forgot_password", get(user::forgot_password));
+        .route("/login/two_factor", get(login::two_factor_page))
+        .route("/login/two_factor", post(login::two_factor_login))
+        .route("/login/magic_link", post(login::request_magic_link))
+        .route("/login/magic_link/{token}", get(login::magic_link_page))
+        .route("/login/magic_link/{token}", post(login::magic_link_login))
+        .route(
+            "/login/oidc",
+            get(user::external_login::start_external_login),
+        )
+        .route(
+            OidcProvider::CALLBACK_PATH,
+            get(user::external_login::callback),
+        )
+        .route("/forgot_password", get(user::forgot_password))
+        .route(
+            "/payments/webhook",
+            post(user::online_payment::payment_webhook),
+        );
 
     Router::new()
         .merge(admin_router)
@@ -1,7 +1,9 @@
 use crate::http::template_into_response::TemplateIntoResponse;
 use askama::Template;
-use axum::http::{HeaderName, HeaderValue};
+use axum::body::{Body, to_bytes};
+use axum::http::{HeaderName, HeaderValue, header};
 use axum::response::Response;
+use tracing::error;
 
 pub fn error_bubble_response(message: impl AsRef<str>) -> Response {
     #[derive(Template)]
@@ -28,3 +30,29 @@ pub fn error_bubble_response(message: impl AsRef<str>) -> Response {
 pub struct NotificationBubbleResponse<'a> {
     pub message: &'a str,
 }
+
+/// Adds the banner at the top of an HTML page. Only for full pages,
+/// the fragments HTMX swaps in are already under the banner of their page.
+pub async fn add_page_banner(response: Response, banner: &str) -> Response {
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let page = match to_bytes(body, usize::MAX).await {
+        Ok(page) => String::from_utf8_lossy(&page).into_owned(),
+        Err(e) => {
+            error!("Failed to read the page to add a banner: {e}");
+            return error_bubble_response("Pagina nu a putut fi afișată");
+        }
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let page = page.replacen("<body>", &format!("<body>\n{banner}"), 1);
+    Response::from_parts(parts, Body::from(page))
+}
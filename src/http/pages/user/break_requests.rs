@@ -0,0 +1,99 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::break_requests::{
+    Attachment, NewBreakRequest, cancel_break_request, create_break_request, get_attachment,
+};
+use crate::http::pages::admin::members::breaks::parse_month_input;
+use axum::extract::{Multipart, Path, State};
+use axum::response::IntoResponse;
+
+pub async fn request_break(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut start_month = String::new();
+    let mut end_month = String::new();
+    let mut reason = String::new();
+    let mut attachment = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .or_bail("Cererea nu a putut fi trimisă")?
+    {
+        match field.name() {
+            Some("start_month") => start_month = field.text().await.unwrap_or_default(),
+            Some("end_month") => end_month = field.text().await.unwrap_or_default(),
+            Some("reason") => reason = field.text().await.unwrap_or_default(),
+            Some("attachment") => {
+                let name = field.file_name().unwrap_or_default().to_string();
+                let content_type = field.content_type().unwrap_or_default().to_string();
+                let content = field
+                    .bytes()
+                    .await
+                    .or_bail("Documentul nu a putut fi încărcat")?;
+                if !content.is_empty() {
+                    attachment = Some(Attachment {
+                        name,
+                        content_type,
+                        content: content.to_vec(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let start_date = parse_month_input(&start_month).or_bail("Început de lună invalid")?;
+    let end_date = parse_month_input(&end_month).or_bail("Sfârșit de lună invalid")?;
+
+    let mut tx = state.write_pool.begin().await?;
+    create_break_request(
+        tx.as_mut(),
+        &user,
+        NewBreakRequest {
+            start_date,
+            end_date,
+            reason: &reason,
+            attachment,
+        },
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+pub async fn cancel_request(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(request_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    if !cancel_break_request(&state.write_pool, request_id, user.id).await? {
+        return Err(HttpError::Message("Cererea nu mai poate fi anulată".into()));
+    }
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+pub async fn download_attachment(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(request_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let (owner_id, attachment) = get_attachment(&state.read_pool, request_id)
+        .await?
+        .or_bail("Cererea nu are un document atașat")?;
+    if owner_id != user.id {
+        return Err(HttpError::Unauthorized);
+    }
+
+    Ok(attachment.into_download())
+}
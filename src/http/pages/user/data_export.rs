@@ -0,0 +1,15 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::gdpr::{export_member_data, export_response};
+use axum::extract::State;
+
+pub async fn export_own_data(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let export = export_member_data(&state.read_pool, user.id).await?;
+    Ok(export_response(user.id, &export))
+}
@@ -0,0 +1,346 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::impersonation::get_impersonation;
+use crate::http::pages::user::login::start_login;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::user::{Credentials, ExternalIdentity};
+use crate::oidc::random_token;
+use askama::Template;
+use axum::extract::{Query, State};
+use axum::response::{IntoResponse, Redirect, Response};
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as, query_scalar};
+use time::{Duration, OffsetDateTime};
+use tracing::{error, info, warn};
+
+#[cfg(test)]
+mod test;
+
+const GENERIC_ERROR: &str =
+    "Serverul a întâmpinat o problemă, dacă eroare persistă te rog contactează un membru fondator";
+
+/// The login or account linking that was sent to the provider, until it comes back to the callback
+const OIDC_FLOW_SESSION_KEY: &str = "oidc_flow";
+const OIDC_FLOW_TIMEOUT: Duration = Duration::minutes(10);
+
+#[derive(Serialize, Deserialize)]
+struct PendingOidcFlow {
+    state: String,
+    code_verifier: String,
+    /// Set when a logged in member links their account instead of logging in
+    link_user_id: Option<i64>,
+    /// Unix timestamp after which the callback is refused
+    expires_at: i64,
+}
+
+pub struct LinkedIdentity {
+    pub email: String,
+    pub linked_at: OffsetDateTime,
+}
+
+/// Links the external account to the member, returning false if it belongs to someone else
+/// or the member already has another account of the same provider
+pub async fn link_identity(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    identity: &ExternalIdentity,
+    now: OffsetDateTime,
+) -> sqlx::Result<bool> {
+    query!(
+        "insert into user_identities (user_id, issuer, subject, email, linked_at)
+         values ($1, $2, $3, $4, $5) on conflict do nothing",
+        user_id,
+        identity.issuer,
+        identity.subject,
+        identity.email,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let owner = query_scalar!(
+        "select user_id from user_identities where issuer = $1 and subject = $2",
+        identity.issuer,
+        identity.subject
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    Ok(owner == Some(user_id))
+}
+
+pub async fn unlink_identity(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    issuer: &str,
+) -> sqlx::Result<bool> {
+    let result = query!(
+        "delete from user_identities where user_id = $1 and issuer = $2",
+        user_id,
+        issuer
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() != 0)
+}
+
+pub async fn get_linked_identity(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    issuer: &str,
+) -> sqlx::Result<Option<LinkedIdentity>> {
+    query_as!(
+        LinkedIdentity,
+        r#"select email, linked_at as "linked_at: OffsetDateTime" from user_identities
+           where user_id = $1 and issuer = $2"#,
+        user_id,
+        issuer
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// A full page, since the provider redirects the browser here instead of HTMX swapping a fragment
+fn external_login_error(message: impl AsRef<str>, back_url: &str) -> Response {
+    #[derive(Template)]
+    #[template(path = "user/external_login_error.html")]
+    struct ExternalLoginErrorTemplate<'a> {
+        error_message: &'a str,
+        back_url: &'a str,
+    }
+
+    ExternalLoginErrorTemplate {
+        error_message: message.as_ref(),
+        back_url,
+    }
+    .into_response()
+}
+
+/// Sends the browser to the provider, remembering what to do once it comes back
+async fn begin_flow(state: &AppState, auth: &AuthSession, link_user_id: Option<i64>) -> Response {
+    let back_url = if link_user_id.is_some() {
+        "/profile"
+    } else {
+        "/login"
+    };
+    let Some(oidc) = &state.oidc else {
+        return Redirect::to(back_url).into_response();
+    };
+
+    let flow = PendingOidcFlow {
+        state: random_token(),
+        code_verifier: random_token(),
+        link_user_id,
+        expires_at: (OffsetDateTime::now_utc() + OIDC_FLOW_TIMEOUT).unix_timestamp(),
+    };
+    let url = match oidc
+        .authorization_url(&flow.state, &flow.code_verifier)
+        .await
+    {
+        Ok(url) => url,
+        Err(e) => {
+            error!("Failed to start the login with {}: {e}", oidc.display_name);
+            return external_login_error(GENERIC_ERROR, back_url);
+        }
+    };
+
+    if let Err(e) = auth.session.insert(OIDC_FLOW_SESSION_KEY, flow).await {
+        error!("Failed to save the external login flow: {e}");
+        return external_login_error(GENERIC_ERROR, back_url);
+    }
+
+    Redirect::to(&url).into_response()
+}
+
+pub async fn start_external_login(
+    State(state): State<AppState>,
+    auth: AuthSession,
+) -> impl IntoResponse {
+    if auth.user.is_some() {
+        return Redirect::to("/").into_response();
+    }
+
+    begin_flow(&state, &auth, None).await
+}
+
+pub async fn start_linking(State(state): State<AppState>, auth: AuthSession) -> HttpResult {
+    let user = auth.user.as_ref().ok_or(HttpError::Unauthorized)?;
+    if get_impersonation(&auth.session).await.is_some() {
+        // Otherwise the admin could link their own account and log in as the member later
+        return Ok(external_login_error(
+            "Nu poți conecta un cont extern în timp ce vizualizezi aplicația ca un alt membru",
+            "/profile",
+        ));
+    }
+
+    Ok(begin_flow(&state, &auth, Some(user.id)).await)
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+pub async fn callback(
+    State(state): State<AppState>,
+    mut auth: AuthSession,
+    Query(query): Query<CallbackQuery>,
+) -> impl IntoResponse {
+    let Some(oidc) = &state.oidc else {
+        return Redirect::to("/login").into_response();
+    };
+
+    // Removed right away, so the same callback can't be replayed
+    let flow = auth
+        .session
+        .remove::<PendingOidcFlow>(OIDC_FLOW_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .filter(|flow| flow.expires_at > OffsetDateTime::now_utc().unix_timestamp())
+        .filter(|flow| query.state.as_ref() == Some(&flow.state));
+    let Some(flow) = flow else {
+        return external_login_error("Autentificarea a expirat, încearcă din nou", "/login");
+    };
+    let back_url = if flow.link_user_id.is_some() {
+        "/profile"
+    } else {
+        "/login"
+    };
+
+    if let Some(error) = query.error {
+        warn!("{} refused the login: {error}", oidc.display_name);
+        return external_login_error("Autentificarea a fost anulată", back_url);
+    }
+    let Some(code) = query.code else {
+        return external_login_error("Autentificarea a fost anulată", back_url);
+    };
+
+    let identity = match oidc.exchange_code(&code, &flow.code_verifier).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            error!("Failed to finish the login with {}: {e}", oidc.display_name);
+            return external_login_error(GENERIC_ERROR, back_url);
+        }
+    };
+
+    match flow.link_user_id {
+        Some(user_id) => link_account(&state, &auth, user_id, identity).await,
+        None => login_with_identity(&state, &mut auth, identity).await,
+    }
+}
+
+async fn link_account(
+    state: &AppState,
+    auth: &AuthSession,
+    user_id: i64,
+    identity: ExternalIdentity,
+) -> Response {
+    // The member could have logged out, or someone else logged in, since starting
+    if auth.user.as_ref().map(|user| user.id) != Some(user_id) {
+        return external_login_error("Autentificarea a expirat, încearcă din nou", "/login");
+    }
+
+    let linked = async {
+        let mut tx = state.write_pool.begin().await?;
+        let linked =
+            link_identity(tx.as_mut(), user_id, &identity, OffsetDateTime::now_utc()).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(linked)
+    }
+    .await;
+
+    match linked {
+        Ok(true) => {
+            info!(
+                "User {user_id} linked the external account {}",
+                identity.email
+            );
+            Redirect::to("/profile").into_response()
+        }
+        Ok(false) => external_login_error(
+            "Acest cont este deja conectat la un alt membru sau ai conectat deja un alt cont",
+            "/profile",
+        ),
+        Err(e) => {
+            error!("Failed to link an external account to user {user_id}: {e}");
+            external_login_error(GENERIC_ERROR, "/profile")
+        }
+    }
+}
+
+async fn login_with_identity(
+    state: &AppState,
+    auth: &mut AuthSession,
+    identity: ExternalIdentity,
+) -> Response {
+    let user = match auth
+        .authenticate(Credentials::External(identity.clone()))
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            info!(
+                "No member found for the external account {}",
+                identity.email
+            );
+            return external_login_error(
+                format!(
+                    "Niciun membru nu folosește adresa {}. Autentifică-te cu parola și conectează contul din profil.",
+                    identity.email
+                ),
+                "/login",
+            );
+        }
+        Err(e) => {
+            error!(
+                "Failed to authenticate the external account {}: {e}",
+                identity.email
+            );
+            return external_login_error(GENERIC_ERROR, "/login");
+        }
+    };
+
+    // Members matched by email get linked, so changing the address later doesn't lock them out
+    let linked = async {
+        let mut tx = state.write_pool.begin().await?;
+        link_identity(tx.as_mut(), user.id, &identity, OffsetDateTime::now_utc()).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = linked {
+        error!(
+            "Failed to link an external account to user {}: {e}",
+            user.id
+        );
+    }
+
+    match start_login(state, auth, &user).await {
+        Some(next) => Redirect::to(next).into_response(),
+        None => external_login_error(GENERIC_ERROR, "/login"),
+    }
+}
+
+pub async fn unlink(State(state): State<AppState>, auth: AuthSession) -> HttpResult {
+    let user = auth.user.as_ref().ok_or(HttpError::Unauthorized)?;
+    if get_impersonation(&auth.session).await.is_some() {
+        // Removing the member's way of logging in isn't the admin's call
+        return Err(HttpError::Message(
+            "Nu poți deconecta contul extern în timp ce vizualizezi aplicația ca un alt membru"
+                .into(),
+        ));
+    }
+    let Some(oidc) = &state.oidc else {
+        return Ok([("HX-Refresh", "true")].into_response());
+    };
+
+    if unlink_identity(&state.write_pool, user.id, oidc.issuer()).await? {
+        info!("User {} unlinked their external account", user.id);
+    }
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
@@ -0,0 +1,112 @@
+use super::*;
+use crate::http::auth::UserAuthenticator;
+use axum_login::AuthnBackend;
+use sqlx::SqlitePool;
+use time::macros::datetime;
+
+const ISSUER: &str = "https://accounts.example.com";
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (1001, 'test2@test.com', 'Test User 2', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn identity(subject: &str, email: &str, email_verified: bool) -> ExternalIdentity {
+    ExternalIdentity {
+        issuer: ISSUER.to_string(),
+        subject: subject.to_string(),
+        email: email.to_string(),
+        email_verified,
+    }
+}
+
+async fn link(pool: &SqlitePool, user_id: i64, identity: &ExternalIdentity) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let linked = link_identity(
+        tx.as_mut(),
+        user_id,
+        identity,
+        datetime!(2025-06-01 12:00 UTC),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(linked)
+}
+
+async fn authenticate(pool: &SqlitePool, identity: ExternalIdentity) -> Option<i64> {
+    UserAuthenticator::new(pool.clone())
+        .authenticate(Credentials::External(identity))
+        .await
+        .unwrap()
+        .map(|user| user.id)
+}
+
+#[sqlx::test]
+async fn should_link_account_to_one_member(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let account = identity("subject-1", "someone@gmail.com", true);
+
+    assert!(link(&pool, 1000, &account).await?);
+    // Linking again is harmless
+    assert!(link(&pool, 1000, &account).await?);
+    assert!(!link(&pool, 1001, &account).await?);
+    // Only one account of each provider
+    assert!(!link(&pool, 1000, &identity("subject-2", "other@gmail.com", true)).await?);
+
+    let linked = get_linked_identity(&pool, 1000, ISSUER).await?.unwrap();
+    assert_eq!(linked.email, "someone@gmail.com");
+    assert!(get_linked_identity(&pool, 1001, ISSUER).await?.is_none());
+
+    assert!(!unlink_identity(&pool, 1001, ISSUER).await?);
+    assert!(unlink_identity(&pool, 1000, ISSUER).await?);
+    assert!(link(&pool, 1001, &account).await?);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_authenticate_by_linked_account(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let account = identity("subject-1", "someone@gmail.com", false);
+
+    assert_eq!(authenticate(&pool, account.clone()).await, None);
+
+    link(&pool, 1001, &account).await?;
+    assert_eq!(authenticate(&pool, account).await, Some(1001));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_authenticate_by_verified_email(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    assert_eq!(
+        authenticate(&pool, identity("subject-1", "TEST1@test.com", true)).await,
+        Some(1000)
+    );
+    // Anyone could claim an address the provider didn't verify
+    assert_eq!(
+        authenticate(&pool, identity("subject-1", "test1@test.com", false)).await,
+        None
+    );
+
+    // Once linked, other accounts with the same address are refused
+    link(&pool, 1000, &identity("subject-1", "test1@test.com", true)).await?;
+    assert_eq!(
+        authenticate(&pool, identity("subject-2", "test1@test.com", true)).await,
+        None
+    );
+
+    Ok(())
+}
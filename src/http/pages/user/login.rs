@@ -1,22 +1,68 @@
+use crate::http::AppState;
+use crate::http::error::HttpResult;
 use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::impersonation::end_impersonation;
+use crate::http::pages::user::login_attempts::{
+    ClientIp, check_login_allowed, clear_failures, normalize_email, record_failure,
+};
+use crate::http::pages::user::magic_link::{
+    consume_login_token, create_login_token, is_login_token_valid, record_magic_link_request,
+    send_magic_link,
+};
+use crate::http::pages::user::sessions::forget_session;
+use crate::http::pages::user::two_factor::verify_second_factor;
 use crate::http::template_into_response::TemplateIntoResponse;
-use crate::model::user::UserCredentials;
+use crate::model::user::{User, UserCredentials};
+use crate::utils::queries::get_user;
 use askama::Template;
 use axum::Form;
+use axum::extract::{Path, State};
 use axum::response::{IntoResponse, Redirect, Response};
+use axum_login::AuthnBackend;
 use email_address::EmailAddress;
-use tracing::{debug, error};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use tracing::{debug, error, warn};
 
-pub async fn login_page(auth_session: AuthSession) -> impl IntoResponse {
+const GENERIC_ERROR: &str =
+    "Serverul a întâmpinat o problemă, dacă eroare persistă te rog contactează un membru fondator";
+
+/// Shown both for known and unknown addresses, so it doesn't reveal who is a member
+const THROTTLED_ERROR: &str = "Prea multe încercări eșuate, încearcă din nou mai târziu";
+const MAGIC_LINK_THROTTLED_ERROR: &str =
+    "Au fost cerute prea multe linkuri de autentificare, încearcă din nou mai târziu";
+
+/// Members who passed the password check, but still have to enter their second factor
+const TWO_FACTOR_SESSION_KEY: &str = "two_factor_login";
+const TWO_FACTOR_TIMEOUT: Duration = Duration::minutes(5);
+
+#[derive(Serialize, Deserialize)]
+struct PendingTwoFactor {
+    email: String,
+    /// Unix timestamp after which the password has to be entered again
+    expires_at: i64,
+}
+
+pub async fn login_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> impl IntoResponse {
     if auth_session.user.is_some() {
         return Redirect::to("/").into_response();
     }
 
     #[derive(Template)]
     #[template(path = "user/login_page.html")]
-    struct LoginTemplate;
+    struct LoginTemplate {
+        magic_link_login: bool,
+        oidc_provider_name: Option<String>,
+    }
 
-    LoginTemplate.into_response()
+    LoginTemplate {
+        magic_link_login: state.magic_link_login,
+        oidc_provider_name: state.oidc.as_ref().map(|oidc| oidc.display_name.clone()),
+    }
+    .into_response()
 }
 
 fn login_error(message: impl AsRef<str>) -> Response {
@@ -32,13 +78,31 @@ fn login_error(message: impl AsRef<str>) -> Response {
     .into_response()
 }
 
+async fn is_throttled(state: &AppState, email: &str, ip: &str) -> sqlx::Result<bool> {
+    let mut conn = state.read_pool.acquire().await?;
+    let retry_at = check_login_allowed(conn.as_mut(), email, ip, OffsetDateTime::now_utc()).await?;
+    Ok(retry_at.is_some())
+}
+
+async fn record_failed_login(state: &AppState, email: &str, ip: &str) {
+    let result = async {
+        let mut tx = state.write_pool.begin().await?;
+        record_failure(tx.as_mut(), email, ip, OffsetDateTime::now_utc()).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = result {
+        error!("Failed to record the failed login of {email}: {e}");
+    }
+}
+
 pub async fn login(
+    State(state): State<AppState>,
     mut auth: AuthSession,
+    ClientIp(ip): ClientIp,
     Form(login_user): Form<UserCredentials>,
 ) -> impl IntoResponse {
-    let generic_error_template = login_error(
-        "Serverul a întâmpinat o problemă, dacă eroare persistă te rog contactează un membru fondator",
-    );
+    let generic_error_template = login_error(GENERIC_ERROR);
 
     if !EmailAddress::is_valid(&login_user.email) {
         return login_error("Adresa de email este invalidă");
@@ -48,11 +112,25 @@ pub async fn login(
         return login_error("Parola este prea scurtă");
     }
 
-    let user = match auth.authenticate(login_user.clone()).await {
+    let email = normalize_email(&login_user.email);
+    match is_throttled(&state, &email, &ip).await {
+        Ok(false) => {}
+        Ok(true) => {
+            warn!("Throttled login for {email} from {ip}");
+            return login_error(THROTTLED_ERROR);
+        }
+        Err(e) => {
+            error!("Failed to check the login attempts of {email}: {e}");
+            return generic_error_template;
+        }
+    }
+
+    let user = match auth.authenticate(login_user.clone().into()).await {
         Ok(user) => {
             if let Some(user) = user {
                 user
             } else {
+                record_failed_login(&state, &email, &ip).await;
                 return login_error("Email sau parolă invalidă");
             }
         }
@@ -65,22 +143,294 @@ pub async fn login(
         }
     };
 
-    match auth.login(&user).await {
-        Ok(()) => {
-            debug!("User has been logged in: {}", user.email);
-            [("HX-Refresh", "true"), ("HX-Replace-Url", "/")].into_response()
+    login_response(start_login(&state, &mut auth, &user).await)
+}
+
+/// The first step of the login passed, the second factor is still needed if the member has one.
+/// Returns where the member continues, `None` when the login failed.
+pub async fn start_login(
+    state: &AppState,
+    auth: &mut AuthSession,
+    user: &User,
+) -> Option<&'static str> {
+    if user.has_two_factor() {
+        let pending = PendingTwoFactor {
+            email: user.email.clone(),
+            expires_at: (OffsetDateTime::now_utc() + TWO_FACTOR_TIMEOUT).unix_timestamp(),
+        };
+        if let Err(e) = auth.session.insert(TWO_FACTOR_SESSION_KEY, pending).await {
+            error!("Failed to save the pending login of {}: {e}", user.email);
+            return None;
+        }
+        return Some("/login/two_factor");
+    }
+
+    finish_login(state, auth, user).await
+}
+
+async fn finish_login(
+    state: &AppState,
+    auth: &mut AuthSession,
+    user: &User,
+) -> Option<&'static str> {
+    if let Err(e) = auth.login(user).await {
+        error!("Failed to login user {} with error: {}", user.email, e);
+        return None;
+    }
+
+    debug!("User has been logged in: {}", user.email);
+    let cleared = async {
+        let mut tx = state.write_pool.begin().await?;
+        clear_failures(tx.as_mut(), &normalize_email(&user.email)).await?;
+        tx.commit().await
+    }
+    .await;
+    if let Err(e) = cleared {
+        error!("Failed to clear the failed logins of {}: {e}", user.email);
+    }
+
+    if user.is_missing_two_factor() {
+        // Their role only grants its permissions after enrolling
+        return Some("/profile/two_factor");
+    }
+    Some("/")
+}
+
+/// Moves the browser on after a login submitted from an HTMX form
+fn login_response(next: Option<&str>) -> Response {
+    match next {
+        None => login_error(GENERIC_ERROR),
+        Some("/") => [("HX-Refresh", "true"), ("HX-Replace-Url", "/")].into_response(),
+        Some(next) => [("HX-Redirect", next)].into_response(),
+    }
+}
+
+async fn get_pending_two_factor(auth: &AuthSession) -> Option<PendingTwoFactor> {
+    auth.session
+        .get::<PendingTwoFactor>(TWO_FACTOR_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .filter(|pending| pending.expires_at > OffsetDateTime::now_utc().unix_timestamp())
+}
+
+pub async fn two_factor_page(auth: AuthSession) -> impl IntoResponse {
+    if get_pending_two_factor(&auth).await.is_none() {
+        return Redirect::to("/login").into_response();
+    }
+
+    #[derive(Template)]
+    #[template(path = "user/two_factor_login_page.html")]
+    struct TwoFactorLoginTemplate;
+
+    TwoFactorLoginTemplate.into_response()
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorForm {
+    code: String,
+}
+
+pub async fn two_factor_login(
+    State(state): State<AppState>,
+    mut auth: AuthSession,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<TwoFactorForm>,
+) -> impl IntoResponse {
+    let Some(pending) = get_pending_two_factor(&auth).await else {
+        return [("HX-Redirect", "/login")].into_response();
+    };
+
+    let email = normalize_email(&pending.email);
+    match is_throttled(&state, &email, &ip).await {
+        Ok(false) => {}
+        Ok(true) => return login_error(THROTTLED_ERROR),
+        Err(e) => {
+            error!("Failed to check the login attempts of {email}: {e}");
+            return login_error(GENERIC_ERROR);
+        }
+    }
+
+    let user = match auth.backend.get_user(&pending.email).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return [("HX-Redirect", "/login")].into_response(),
+        Err(e) => {
+            error!("Failed to load user {} with error: {e}", pending.email);
+            return login_error(GENERIC_ERROR);
+        }
+    };
+
+    let verified = async {
+        let mut tx = state.write_pool.begin().await?;
+        let verified =
+            verify_second_factor(tx.as_mut(), &user, &form.code, OffsetDateTime::now_utc()).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(verified)
+    }
+    .await;
+    match verified {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!("Wrong second factor entered for {}", user.email);
+            record_failed_login(&state, &email, &ip).await;
+            return login_error("Codul introdus nu este corect");
         }
         Err(e) => {
-            error!("Failed to login user {} with error: {}", user.email, e);
-            generic_error_template
+            error!("Failed to verify the second factor of {}: {e}", user.email);
+            return login_error(GENERIC_ERROR);
+        }
+    }
+
+    let _ = auth
+        .session
+        .remove::<PendingTwoFactor>(TWO_FACTOR_SESSION_KEY)
+        .await;
+    login_response(finish_login(&state, &mut auth, &user).await)
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkForm {
+    email: String,
+}
+
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    auth: AuthSession,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<MagicLinkForm>,
+) -> impl IntoResponse {
+    #[derive(Template)]
+    #[template(path = "user/magic_link_sent.html")]
+    struct MagicLinkSentTemplate;
+
+    if !state.magic_link_login {
+        return login_error("Autentificarea prin email nu este disponibilă");
+    }
+    if !EmailAddress::is_valid(&form.email) {
+        return login_error("Adresa de email este invalidă");
+    }
+
+    let email = normalize_email(&form.email);
+    let allowed = async {
+        let mut tx = state.write_pool.begin().await?;
+        let allowed =
+            record_magic_link_request(tx.as_mut(), &email, &ip, OffsetDateTime::now_utc()).await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(allowed)
+    }
+    .await;
+    match allowed {
+        Ok(true) => {}
+        Ok(false) => return login_error(MAGIC_LINK_THROTTLED_ERROR),
+        Err(e) => {
+            error!("Failed to record the login link request of {email}: {e}");
+            return login_error(GENERIC_ERROR);
+        }
+    }
+
+    match auth.backend.get_user(&form.email.trim().to_string()).await {
+        Ok(Some(user)) => {
+            let now = OffsetDateTime::now_utc();
+            let token = async {
+                let mut tx = state.write_pool.begin().await?;
+                let token = create_login_token(tx.as_mut(), user.id, now).await?;
+                tx.commit().await?;
+                Ok::<_, sqlx::Error>(token)
+            }
+            .await;
+            match token {
+                Ok(token) => {
+                    if let Err(e) =
+                        send_magic_link(&state.mailer, &state.public_url, &user, &token).await
+                    {
+                        error!("Failed to send the login link to {}: {e}", user.email);
+                    }
+                }
+                Err(e) => error!("Failed to create a login link for {}: {e}", user.email),
+            }
         }
+        Ok(None) => debug!("Login link requested for unknown address {email}"),
+        Err(e) => error!("Failed to load user {email} with error: {e}"),
+    }
+
+    // The same answer either way, so it doesn't reveal who is a member
+    MagicLinkSentTemplate.into_response()
+}
+
+pub async fn magic_link_page(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "user/magic_link_page.html")]
+    struct MagicLinkTemplate {
+        token: String,
+        is_valid: bool,
     }
+
+    if !state.magic_link_login {
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    // Only checked here, it's used after confirming, since email scanners also open links
+    let is_valid =
+        is_login_token_valid(&state.read_pool, &token, OffsetDateTime::now_utc()).await?;
+
+    MagicLinkTemplate { token, is_valid }.try_into_response()
 }
 
-pub async fn logout(mut auth: AuthSession) -> impl IntoResponse {
+pub async fn magic_link_login(
+    State(state): State<AppState>,
+    mut auth: AuthSession,
+    Path(token): Path<String>,
+) -> impl IntoResponse {
+    if !state.magic_link_login {
+        return login_error("Autentificarea prin email nu este disponibilă");
+    }
+
+    let user_id =
+        match consume_login_token(&state.write_pool, &token, OffsetDateTime::now_utc()).await {
+            Ok(Some(user_id)) => user_id,
+            Ok(None) => return login_error("Linkul a expirat sau a fost deja folosit"),
+            Err(e) => {
+                error!("Failed to use a login link: {e}");
+                return login_error(GENERIC_ERROR);
+            }
+        };
+
+    let user = match get_user(&state.read_pool, user_id).await {
+        Ok(user) if user.membership_status().can_log_in() => user,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => {
+            return login_error("Contul tău nu mai permite autentificarea");
+        }
+        Err(e) => {
+            error!("Failed to load user {user_id} with error: {e}");
+            return login_error(GENERIC_ERROR);
+        }
+    };
+
+    login_response(start_login(&state, &mut auth, &user).await)
+}
+
+pub async fn logout(State(state): State<AppState>, mut auth: AuthSession) -> impl IntoResponse {
     if let Some(user) = &auth.user {
         debug!("Logging out user: {}", user.id);
 
+        let now = OffsetDateTime::now_utc();
+        match end_impersonation(&state.write_pool, &auth.session, now).await {
+            // The admin's own session was already forgotten when the impersonation started
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                if let Some(session_id) = auth.session.id() {
+                    let session_id = session_id.to_string();
+                    if let Err(e) = forget_session(&state.write_pool, user.id, &session_id).await {
+                        error!("Failed to forget the session of user {}: {e}", user.id);
+                    }
+                }
+            }
+            Err(e) => error!("Failed to log the end of impersonation: {e}"),
+        }
+
         if let Err(e) = auth.logout().await {
             error!("Failed to log out user: {e}");
         }
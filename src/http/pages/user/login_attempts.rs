@@ -0,0 +1,205 @@
+use crate::http::AppState;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_as, query_scalar};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use time::{Duration, OffsetDateTime};
+use tracing::warn;
+
+#[cfg(test)]
+mod test;
+
+/// Failed attempts allowed before each new one has to wait, doubling the wait every time
+const FREE_ATTEMPTS: i64 = 3;
+const MAX_BACKOFF: Duration = Duration::minutes(5);
+/// Failures of an email address within the window after which it's locked out
+const LOCKOUT_THRESHOLD: i64 = 10;
+const FAILURES_WINDOW: Duration = Duration::hours(1);
+const LOCKOUT_DURATION: Duration = Duration::minutes(30);
+
+/// The address of the client, taken from `X-Forwarded-For` only when running behind a trusted proxy
+pub struct ClientIp(pub String);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let forwarded = parts
+            .headers
+            .get("x-forwarded-for")
+            .filter(|_| state.trust_forwarded_for)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.split(',').next())
+            .map(|ip| ip.trim().to_string());
+        let ip = forwarded
+            .or_else(|| {
+                parts
+                    .extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(address)| address.ip().to_string())
+            })
+            .unwrap_or_default();
+
+        Ok(Self(ip))
+    }
+}
+
+/// Attempts are tracked for any email address, existing or not, so the throttling
+/// doesn't reveal which addresses belong to members
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn backoff(failures: i64) -> Duration {
+    if failures < FREE_ATTEMPTS {
+        return Duration::ZERO;
+    }
+    let exponent = (failures - FREE_ATTEMPTS).min(16) as u32;
+    Duration::seconds(2i64.pow(exponent)).min(MAX_BACKOFF)
+}
+
+struct RecentFailures {
+    count: i64,
+    last_failure: Option<OffsetDateTime>,
+}
+
+impl RecentFailures {
+    fn retry_at(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.last_failure
+            .map(|last_failure| last_failure + backoff(self.count))
+            .filter(|retry_at| *retry_at > now)
+    }
+}
+
+/// When the login is throttled, returns the time after which it can be tried again
+pub async fn check_login_allowed(
+    conn: &mut SqliteConnection,
+    email: &str,
+    ip: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<OffsetDateTime>> {
+    let locked_until = query_scalar!(
+        r#"select locked_until as "locked_until: OffsetDateTime" from login_lockouts
+           where email = $1 and locked_until > $2"#,
+        email,
+        now
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    if locked_until.is_some() {
+        return Ok(locked_until);
+    }
+
+    let since = now - FAILURES_WINDOW;
+    let by_email = query_as!(
+        RecentFailures,
+        r#"select count(*) as "count!: i64", max(attempted_at) as "last_failure: OffsetDateTime"
+           from login_attempts where email = $1 and attempted_at > $2"#,
+        email,
+        since
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    let by_ip = query_as!(
+        RecentFailures,
+        r#"select count(*) as "count!: i64", max(attempted_at) as "last_failure: OffsetDateTime"
+           from login_attempts where ip = $1 and attempted_at > $2"#,
+        ip,
+        since
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(by_email.retry_at(now).max(by_ip.retry_at(now)))
+}
+
+pub async fn record_failure(
+    tx: &mut SqliteConnection,
+    email: &str,
+    ip: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    // Old attempts no longer count towards anything
+    let expired = now - Duration::days(1);
+    query!(
+        "delete from login_attempts where attempted_at < $1",
+        expired
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    query!(
+        "insert into login_attempts (email, ip, attempted_at) values ($1, $2, $3)",
+        email,
+        ip,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let since = now - FAILURES_WINDOW;
+    let failures = query_scalar!(
+        r#"select count(*) as "count!: i64" from login_attempts where email = $1 and attempted_at > $2"#,
+        email,
+        since
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if failures >= LOCKOUT_THRESHOLD {
+        let locked_until = now + LOCKOUT_DURATION;
+        query!(
+            "insert into login_lockouts (email, locked_until) values ($1, $2)
+             on conflict (email) do update set locked_until = excluded.locked_until",
+            email,
+            locked_until
+        )
+        .execute(&mut *tx)
+        .await?;
+        warn!("Logins for {email} locked after {failures} failed attempts, the last one from {ip}");
+    }
+
+    Ok(())
+}
+
+/// Forgets the failures of the email address, after a successful login or when an admin unlocks it
+pub async fn clear_failures(tx: &mut SqliteConnection, email: &str) -> sqlx::Result<()> {
+    query!("delete from login_attempts where email = $1", email)
+        .execute(&mut *tx)
+        .await?;
+    query!("delete from login_lockouts where email = $1", email)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
+
+pub struct LoginStatus {
+    pub failed_attempts: i64,
+    pub last_failure: Option<OffsetDateTime>,
+    pub locked_until: Option<OffsetDateTime>,
+}
+
+pub async fn get_login_status(
+    executor: impl SqliteExecutor<'_>,
+    email: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<LoginStatus> {
+    let since = now - FAILURES_WINDOW;
+    query_as!(
+        LoginStatus,
+        r#"select count(a.id) as "failed_attempts!: i64",
+            max(a.attempted_at) as "last_failure: OffsetDateTime",
+            (select l.locked_until from login_lockouts l where l.email = $1 and l.locked_until > $3)
+                as "locked_until: OffsetDateTime"
+           from login_attempts a where a.email = $1 and a.attempted_at > $2"#,
+        email,
+        since,
+        now
+    )
+    .fetch_one(executor)
+    .await
+}
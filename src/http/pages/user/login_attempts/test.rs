@@ -0,0 +1,111 @@
+use super::*;
+use sqlx::SqlitePool;
+use time::macros::datetime;
+
+const EMAIL: &str = "test1@test.com";
+const IP: &str = "10.0.0.1";
+
+async fn fail(pool: &SqlitePool, email: &str, ip: &str, now: OffsetDateTime) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    record_failure(tx.as_mut(), email, ip, now).await?;
+    tx.commit().await
+}
+
+async fn retry_at(
+    pool: &SqlitePool,
+    email: &str,
+    ip: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<OffsetDateTime>> {
+    let mut conn = pool.acquire().await?;
+    check_login_allowed(conn.as_mut(), email, ip, now).await
+}
+
+#[test]
+fn should_back_off_exponentially() {
+    assert_eq!(backoff(0), Duration::ZERO);
+    assert_eq!(backoff(FREE_ATTEMPTS - 1), Duration::ZERO);
+    assert_eq!(backoff(FREE_ATTEMPTS), Duration::seconds(1));
+    assert_eq!(backoff(FREE_ATTEMPTS + 3), Duration::seconds(8));
+    assert_eq!(backoff(100), MAX_BACKOFF);
+}
+
+#[sqlx::test]
+async fn should_throttle_after_free_attempts(pool: SqlitePool) -> sqlx::Result<()> {
+    let now = datetime!(2024-05-01 12:00:00 UTC);
+
+    for _ in 0..FREE_ATTEMPTS - 1 {
+        fail(&pool, EMAIL, IP, now).await?;
+    }
+    assert_eq!(retry_at(&pool, EMAIL, IP, now).await?, None);
+
+    fail(&pool, EMAIL, IP, now).await?;
+    assert_eq!(
+        retry_at(&pool, EMAIL, IP, now).await?,
+        Some(now + Duration::seconds(1))
+    );
+    assert_eq!(
+        retry_at(&pool, EMAIL, IP, now + Duration::seconds(2)).await?,
+        None
+    );
+
+    // Another address from the same IP is throttled too, the same address from elsewhere as well
+    assert!(retry_at(&pool, "other@test.com", IP, now).await?.is_some());
+    assert!(retry_at(&pool, EMAIL, "10.0.0.2", now).await?.is_some());
+    assert!(
+        retry_at(&pool, "other@test.com", "10.0.0.2", now)
+            .await?
+            .is_none()
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_lock_out_and_unlock(pool: SqlitePool) -> sqlx::Result<()> {
+    let now = datetime!(2024-05-01 12:00:00 UTC);
+
+    for attempt in 0..LOCKOUT_THRESHOLD {
+        let ip = format!("10.0.1.{attempt}");
+        fail(&pool, EMAIL, &ip, now + Duration::minutes(attempt)).await?;
+    }
+    let last_failure = now + Duration::minutes(LOCKOUT_THRESHOLD - 1);
+    let later = last_failure + MAX_BACKOFF;
+
+    // The backoff has passed, but the address is still locked, from any IP
+    assert_eq!(
+        retry_at(&pool, EMAIL, "10.0.2.1", later).await?,
+        Some(last_failure + LOCKOUT_DURATION)
+    );
+
+    let status = get_login_status(&pool, EMAIL, later).await?;
+    assert_eq!(status.failed_attempts, LOCKOUT_THRESHOLD);
+    assert_eq!(status.last_failure, Some(last_failure));
+    assert_eq!(status.locked_until, Some(last_failure + LOCKOUT_DURATION));
+
+    let mut tx = pool.begin().await?;
+    clear_failures(tx.as_mut(), EMAIL).await?;
+    tx.commit().await?;
+
+    assert_eq!(retry_at(&pool, EMAIL, "10.0.2.1", later).await?, None);
+    let status = get_login_status(&pool, EMAIL, later).await?;
+    assert_eq!(status.failed_attempts, 0);
+    assert_eq!(status.locked_until, None);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_forget_old_failures(pool: SqlitePool) -> sqlx::Result<()> {
+    let now = datetime!(2024-05-01 12:00:00 UTC);
+
+    for _ in 0..FREE_ATTEMPTS + 2 {
+        fail(&pool, EMAIL, IP, now).await?;
+    }
+    assert!(retry_at(&pool, EMAIL, IP, now).await?.is_some());
+
+    let later = now + FAILURES_WINDOW + Duration::minutes(1);
+    assert_eq!(retry_at(&pool, EMAIL, IP, later).await?, None);
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use crate::mail::{Email, MailError, Mailer};
+use crate::model::user::User;
+use askama::Template;
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_scalar};
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+pub const MAGIC_LINK_VALIDITY: Duration = Duration::minutes(15);
+/// Links sent to an address within the window, so they can't be used to flood someone's inbox
+const MAX_REQUESTS_PER_EMAIL: i64 = 3;
+/// Requests from an IP within the window, for someone trying many addresses
+const MAX_REQUESTS_PER_IP: i64 = 10;
+const REQUESTS_WINDOW: Duration = Duration::hours(1);
+
+#[derive(Template)]
+#[template(path = "mail/magic_link.txt")]
+struct MagicLinkTemplate<'a> {
+    member: &'a User,
+    login_url: String,
+    validity_minutes: i64,
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Records the request for a login link, returning false if too many were already requested
+/// for the address or from the IP. Counted for any address, so it doesn't reveal who is a member.
+pub async fn record_magic_link_request(
+    tx: &mut SqliteConnection,
+    email: &str,
+    ip: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<bool> {
+    let expired = now - Duration::days(1);
+    query!(
+        "delete from magic_link_requests where requested_at < $1",
+        expired
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let since = now - REQUESTS_WINDOW;
+    let by_email = query_scalar!(
+        r#"select count(*) as "count!: i64" from magic_link_requests where email = $1 and requested_at > $2"#,
+        email,
+        since
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let by_ip = query_scalar!(
+        r#"select count(*) as "count!: i64" from magic_link_requests where ip = $1 and requested_at > $2"#,
+        ip,
+        since
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if by_email >= MAX_REQUESTS_PER_EMAIL || by_ip >= MAX_REQUESTS_PER_IP {
+        return Ok(false);
+    }
+
+    query!(
+        "insert into magic_link_requests (email, ip, requested_at) values ($1, $2, $3)",
+        email,
+        ip,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(true)
+}
+
+/// Creates a new login token for the user, returning it in plain text to be sent by email
+pub async fn create_login_token(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<String> {
+    // Expired tokens are useless, no need to keep them around
+    query!("delete from login_tokens where expires_at < $1", now)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("The system random number generator is unavailable");
+    let token = hex::encode(bytes);
+    let token_hash = hash_token(&token);
+    let expires_at = now + MAGIC_LINK_VALIDITY;
+    query!(
+        "insert into login_tokens (user_id, token_hash, created_at, expires_at) values ($1, $2, $3, $4)",
+        user_id,
+        token_hash,
+        now,
+        expires_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(token)
+}
+
+/// Whether the token can still be used, without using it, so email scanners opening the link don't burn it
+pub async fn is_login_token_valid(
+    executor: impl SqliteExecutor<'_>,
+    token: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<bool> {
+    let token_hash = hash_token(token);
+    let count = query_scalar!(
+        "select count(*) from login_tokens where token_hash = $1 and used_at is null and expires_at > $2",
+        token_hash,
+        now
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(count != 0)
+}
+
+/// Marks the token as used, returning the user it logs in if it was still valid
+pub async fn consume_login_token(
+    executor: impl SqliteExecutor<'_>,
+    token: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<i64>> {
+    let token_hash = hash_token(token);
+    query_scalar!(
+        r#"update login_tokens set used_at = $2
+           where token_hash = $1 and used_at is null and expires_at > $2
+           returning user_id as "user_id!""#,
+        token_hash,
+        now
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn send_magic_link(
+    mailer: &Mailer,
+    public_url: &str,
+    member: &User,
+    token: &str,
+) -> Result<(), MailError> {
+    let body = MagicLinkTemplate {
+        member,
+        login_url: format!("{public_url}/login/magic_link/{token}"),
+        validity_minutes: MAGIC_LINK_VALIDITY.whole_minutes(),
+    }
+    .render()
+    .map_err(std::io::Error::other)?;
+
+    mailer
+        .send(&Email {
+            to: member.email.clone(),
+            subject: "Link de autentificare ACSPA".to_string(),
+            body,
+        })
+        .await?;
+    info!("Sent a login link to user {}", member.id);
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use super::*;
+use crate::utils::queries::get_user;
+use sqlx::SqlitePool;
+use time::macros::datetime;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn create_token(pool: &SqlitePool, now: OffsetDateTime) -> sqlx::Result<String> {
+    let mut tx = pool.begin().await?;
+    let token = create_login_token(tx.as_mut(), 1000, now).await?;
+    tx.commit().await?;
+    Ok(token)
+}
+
+#[sqlx::test]
+async fn should_use_token_only_once(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-01 12:00 UTC);
+    let token = create_token(&pool, now).await?;
+
+    assert!(is_login_token_valid(&pool, &token, now).await?);
+    // Checking it doesn't use it up
+    assert!(is_login_token_valid(&pool, &token, now).await?);
+
+    assert_eq!(consume_login_token(&pool, &token, now).await?, Some(1000));
+    assert_eq!(consume_login_token(&pool, &token, now).await?, None);
+    assert!(!is_login_token_valid(&pool, &token, now).await?);
+
+    assert_eq!(consume_login_token(&pool, "wrong", now).await?, None);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_expire_token(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-01 12:00 UTC);
+    let token = create_token(&pool, now).await?;
+
+    let later = now + MAGIC_LINK_VALIDITY + Duration::seconds(1);
+    assert!(!is_login_token_valid(&pool, &token, later).await?);
+    assert_eq!(consume_login_token(&pool, &token, later).await?, None);
+
+    // Creating another token cleans up the expired ones
+    create_token(&pool, later).await?;
+    let stored = query_scalar!("select count(*) from login_tokens")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(stored, 1);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_store_only_token_hash(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let token = create_token(&pool, datetime!(2025-06-01 12:00 UTC)).await?;
+
+    let stored = query_scalar!("select token_hash from login_tokens")
+        .fetch_one(&pool)
+        .await?;
+    assert_ne!(stored, token);
+    assert_eq!(stored, hash_token(&token));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_email_login_link(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let member = get_user(&pool, 1000).await?;
+    let directory = std::env::temp_dir().join(format!(
+        "acspa_magic_link_{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos()
+    ));
+    let mailer = Mailer::file(&directory, "ACSPA <noreply@acspa.ro>".parse().unwrap());
+
+    send_magic_link(&mailer, "https://acspa.test", &member, "abc123")
+        .await
+        .unwrap();
+
+    let mut entries = std::fs::read_dir(&directory).unwrap();
+    let email = std::fs::read_to_string(entries.next().unwrap().unwrap().path()).unwrap();
+    assert!(email.contains("To: test1@test.com"));
+    assert!(email.contains("https://acspa.test/login/magic_link/abc123"));
+    assert!(entries.next().is_none());
+
+    std::fs::remove_dir_all(directory).unwrap();
+    Ok(())
+}
+
+async fn request_link(pool: &SqlitePool, email: &str, ip: &str, now: OffsetDateTime) -> bool {
+    let mut tx = pool.begin().await.unwrap();
+    let allowed = record_magic_link_request(tx.as_mut(), email, ip, now)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    allowed
+}
+
+#[sqlx::test]
+async fn should_limit_link_requests(pool: SqlitePool) -> sqlx::Result<()> {
+    let now = datetime!(2025-06-01 12:00 UTC);
+
+    for _ in 0..MAX_REQUESTS_PER_EMAIL {
+        assert!(request_link(&pool, "test1@test.com", "10.0.0.1", now).await);
+    }
+    assert!(!request_link(&pool, "test1@test.com", "10.0.0.2", now).await);
+    // Other addresses can still get links
+    assert!(request_link(&pool, "test2@test.com", "10.0.0.2", now).await);
+    // Until the window passes
+    let later = now + REQUESTS_WINDOW + Duration::seconds(1);
+    assert!(request_link(&pool, "test1@test.com", "10.0.0.1", later).await);
+
+    // Many addresses from the same IP
+    for i in 0..MAX_REQUESTS_PER_IP {
+        let allowed = request_link(&pool, &format!("other{i}@test.com"), "10.0.0.3", now).await;
+        assert!(allowed);
+    }
+    assert!(!request_link(&pool, "last@test.com", "10.0.0.3", now).await);
+
+    // Password logins are throttled separately
+    let attempts = query_scalar!("select count(*) from login_attempts")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(attempts, 0);
+
+    Ok(())
+}
@@ -1,23 +1,85 @@
 use crate::http::AppState;
+use crate::http::pages::admin::members::break_requests::MAX_ATTACHMENT_SIZE;
 use crate::http::template_into_response::TemplateIntoResponse;
 use askama::Template;
 use axum::Router;
+use axum::extract::DefaultBodyLimit;
 use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post};
 
+mod break_requests;
 mod change_password;
+mod data_export;
+pub mod external_login;
 pub mod login;
+pub mod login_attempts;
+mod magic_link;
+pub mod notifications;
+pub mod online_payment;
+mod payments;
 mod profile;
+pub mod push;
+pub mod sessions;
+pub mod two_factor;
 
 pub fn user_router() -> Router<AppState> {
     Router::new()
         .route("/profile", get(profile::profile_page))
         .route("/profile/reservations", post(profile::profile_reservations))
+        .route(
+            "/profile/payment_status/{year}",
+            get(payments::payment_status_partial),
+        )
+        .route(
+            "/profile/receipts/{payment_id}",
+            get(payments::download_receipt),
+        )
+        .route(
+            "/profile/break_requests",
+            // Room for the form fields, so too large attachments get a proper error
+            post(break_requests::request_break)
+                .layer(DefaultBodyLimit::max(MAX_ATTACHMENT_SIZE + 64 * 1024)),
+        )
+        .route(
+            "/profile/break_requests/{id}",
+            delete(break_requests::cancel_request),
+        )
+        .route(
+            "/profile/break_requests/{id}/attachment",
+            get(break_requests::download_attachment),
+        )
+        .route("/profile/export", get(data_export::export_own_data))
+        .route("/profile/two_factor", get(two_factor::two_factor_page))
+        .route("/profile/two_factor", post(two_factor::enroll))
+        .route(
+            "/profile/two_factor/recovery_codes",
+            post(two_factor::regenerate_recovery_codes),
+        )
+        .route("/profile/two_factor/disable", post(two_factor::disable))
+        .route("/profile/sessions", get(sessions::sessions_page))
+        .route("/profile/sessions/{id}", delete(sessions::revoke_session))
+        .route(
+            "/profile/sessions/revoke_others",
+            post(sessions::revoke_other_sessions),
+        )
+        .route("/profile/push", post(push::subscribe))
+        .route("/profile/push/unsubscribe", post(push::unsubscribe))
+        .route("/profile/oidc/link", get(external_login::start_linking))
+        .route("/profile/oidc/unlink", post(external_login::unlink))
+        .route("/notifications", get(notifications::notifications_page))
+        .route("/notifications/badge", get(notifications::badge))
+        .route("/notifications/push", get(push::latest_notification))
+        .route(
+            "/notifications/read_all",
+            post(notifications::mark_all_read),
+        )
+        .route("/notifications/{id}", get(notifications::open_notification))
         .route(
             "/change_password",
             get(change_password::change_password_page),
         )
         .route("/change_password", post(change_password::change_password))
+        .merge(online_payment::router())
 }
 
 pub async fn forgot_password() -> impl IntoResponse {
@@ -0,0 +1,226 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::AuthSession;
+use crate::http::pages::notification_template::NotificationBubbleResponse;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::notification::{Notification, NotificationKind};
+use crate::model::user::User;
+use crate::utils::date_formats;
+use askama::Template;
+use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Redirect};
+use sqlx::{SqliteExecutor, query, query_as, query_scalar};
+use time::OffsetDateTime;
+use tracing::error;
+
+#[cfg(test)]
+mod test;
+
+/// Older notifications are still kept, but not shown
+const NOTIFICATIONS_SHOWN: i64 = 50;
+
+#[derive(Template)]
+#[template(path = "components/notifications_badge.html")]
+pub struct NotificationsBadgeTemplate {
+    pub unread: i64,
+}
+
+pub async fn create_notification(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    kind: NotificationKind,
+    message: &str,
+    link: Option<&str>,
+    now: OffsetDateTime,
+) -> sqlx::Result<i64> {
+    let kind = kind.as_ref();
+    query_scalar!(
+        "insert into notifications (user_id, kind, message, link, created_at) values ($1, $2, $3, $4, $5)
+         returning id",
+        user_id,
+        kind,
+        message,
+        link,
+        now
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Saves the notification and shows it right away if the member is online.
+/// Failing to notify doesn't undo what the member is notified about, so errors are only logged.
+pub async fn send_notification(
+    state: &AppState,
+    user_id: i64,
+    kind: NotificationKind,
+    message: &str,
+    link: Option<&str>,
+) {
+    let now = OffsetDateTime::now_utc();
+    match create_notification(&state.write_pool, user_id, kind, message, link, now).await {
+        // Fails only when no one is connected
+        Ok(_) => {
+            let _ = state.notification_notifier.send(user_id);
+        }
+        Err(e) => error!("Failed to notify user {user_id}: {e}"),
+    }
+}
+
+pub async fn get_unread_count(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<i64> {
+    query_scalar!(
+        "select count(*) as 'count!' from notifications where user_id = $1 and read_at is null",
+        user_id
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// The most recent notifications of the member, newest first
+pub async fn get_notifications(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    limit: i64,
+) -> sqlx::Result<Vec<Notification>> {
+    query_as!(
+        Notification,
+        r#"select id, kind, message, link, created_at as "created_at: OffsetDateTime",
+            read_at as "read_at: OffsetDateTime"
+           from notifications where user_id = $1
+           order by created_at desc, id desc limit $2"#,
+        user_id,
+        limit
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Returns the link of the notification, `None` if the member has no such notification
+pub async fn mark_notification_read(
+    executor: impl SqliteExecutor<'_>,
+    id: i64,
+    user_id: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<Option<Option<String>>> {
+    query_scalar!(
+        "update notifications set read_at = coalesce(read_at, $3) where id = $1 and user_id = $2
+         returning link",
+        id,
+        user_id,
+        now
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn mark_all_notifications_read(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<u64> {
+    let result = query!(
+        "update notifications set read_at = $2 where user_id = $1 and read_at is null",
+        user_id,
+        now
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn notifications_badge(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<NotificationsBadgeTemplate> {
+    Ok(NotificationsBadgeTemplate {
+        unread: get_unread_count(executor, user_id).await?,
+    })
+}
+
+/// What is sent over the socket of a member when they get a notification:
+/// the newest unread one and the updated badge
+pub async fn notification_update(state: &AppState, user_id: i64) -> Option<String> {
+    let result = async {
+        let latest = get_notifications(&state.read_pool, user_id, 1)
+            .await?
+            .pop()
+            .filter(|notification| !notification.is_read());
+        let badge = notifications_badge(&state.read_pool, user_id).await?;
+        Ok::<_, sqlx::Error>((latest, badge))
+    }
+    .await;
+
+    match result {
+        Ok((Some(notification), badge)) => Some(format!(
+            "{}{}",
+            NotificationBubbleResponse {
+                message: notification.message.as_str()
+            },
+            badge
+        )),
+        Ok((None, badge)) => Some(badge.to_string()),
+        Err(e) => {
+            error!("Failed to get the notifications of user {user_id}: {e}");
+            None
+        }
+    }
+}
+
+pub async fn notifications_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "user/notifications_page.html")]
+    struct NotificationsTemplate {
+        user: User,
+        notifications: Vec<Notification>,
+        unread: i64,
+        /// Set when the notifications can also be pushed to the phone
+        push_public_key: Option<String>,
+    }
+
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let notifications = get_notifications(&state.read_pool, user.id, NOTIFICATIONS_SHOWN).await?;
+    let unread = get_unread_count(&state.read_pool, user.id).await?;
+
+    NotificationsTemplate {
+        user,
+        notifications,
+        unread,
+        push_public_key: state.web_push.as_ref().map(|sender| sender.public_key()),
+    }
+    .try_into_response()
+}
+
+pub async fn badge(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    notifications_badge(&state.read_pool, user.id)
+        .await?
+        .try_into_response()
+}
+
+/// Marks the notification as read and takes the member to what it is about
+pub async fn open_notification(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let link = mark_notification_read(&state.write_pool, id, user.id, OffsetDateTime::now_utc())
+        .await?
+        .flatten();
+
+    Ok(Redirect::to(link.as_deref().unwrap_or("/notifications")).into_response())
+}
+
+pub async fn mark_all_read(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    mark_all_notifications_read(&state.write_pool, user.id, OffsetDateTime::now_utc()).await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
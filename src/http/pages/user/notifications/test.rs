@@ -0,0 +1,145 @@
+use super::*;
+use crate::http::pages::admin::announcements::{NewAnnouncement, create_announcement};
+use crate::http::pages::admin::members::payments::{NewPaymentRecord, create_payment};
+use crate::http::pages::home::announcements::publish_due_announcements;
+use crate::utils::queries::YearMonth;
+use sqlx::SqlitePool;
+use time::Month;
+use time::macros::{date, datetime};
+use tokio::sync::broadcast;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into user_roles VALUES (200, 'Other Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (2000, 'test2@test.com', 'Test 2', '', 200, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn messages(notifications: &[Notification]) -> Vec<&str> {
+    notifications
+        .iter()
+        .map(|notification| notification.message.as_str())
+        .collect()
+}
+
+#[sqlx::test]
+async fn should_keep_notifications_per_member(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-10 12:00 UTC);
+
+    let first = create_notification(
+        &pool,
+        1000,
+        NotificationKind::Promotion,
+        "First",
+        Some("/profile"),
+        now,
+    )
+    .await?;
+    create_notification(
+        &pool,
+        1000,
+        NotificationKind::Cancellation,
+        "Second",
+        None,
+        now + time::Duration::minutes(1),
+    )
+    .await?;
+    create_notification(&pool, 2000, NotificationKind::Payment, "Other", None, now).await?;
+
+    assert_eq!(get_unread_count(&pool, 1000).await?, 2);
+    let notifications = get_notifications(&pool, 1000, 10).await?;
+    assert_eq!(messages(&notifications), vec!["Second", "First"]);
+    assert_eq!(notifications[1].kind(), Some(NotificationKind::Promotion));
+
+    // Members can't open the notifications of others
+    assert_eq!(mark_notification_read(&pool, first, 2000, now).await?, None);
+    assert_eq!(
+        mark_notification_read(&pool, first, 1000, now).await?,
+        Some(Some("/profile".to_string()))
+    );
+    assert_eq!(get_unread_count(&pool, 1000).await?, 1);
+
+    assert_eq!(mark_all_notifications_read(&pool, 1000, now).await?, 1);
+    assert_eq!(get_unread_count(&pool, 1000).await?, 0);
+    assert_eq!(get_unread_count(&pool, 2000).await?, 1);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_notify_the_audience_of_published_announcements(
+    pool: SqlitePool,
+) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let now = datetime!(2025-06-10 12:00 UTC);
+
+    let mut tx = pool.begin().await?;
+    create_announcement(
+        tx.as_mut(),
+        &NewAnnouncement {
+            title: "Other role".to_string(),
+            body: "Body".to_string(),
+            pinned: false,
+            starts_at: now,
+            ends_at: None,
+            role_ids: vec![200],
+        },
+        0,
+        now,
+    )
+    .await?;
+    tx.commit().await?;
+
+    let (notifier, _receiver) = broadcast::channel(16);
+    assert_eq!(publish_due_announcements(&pool, &notifier, now).await?, 1);
+
+    assert_eq!(get_unread_count(&pool, 1000).await?, 0);
+    let notifications = get_notifications(&pool, 2000, 10).await?;
+    assert_eq!(messages(&notifications), vec!["Anunț nou: Other role"]);
+    assert_eq!(
+        notifications[0].kind(),
+        Some(NotificationKind::Announcement)
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_notify_recorded_payments(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let mut tx = pool.begin().await?;
+    create_payment(
+        tx.as_mut(),
+        NewPaymentRecord {
+            member_id: 1000,
+            amount: 15050,
+            payment_date: date!(2024 - 03 - 05),
+            notes: None,
+            allocations: &[YearMonth::new(2024, Month::March)],
+            created_by: 0,
+        },
+    )
+    .await
+    .unwrap();
+    tx.commit().await?;
+
+    let notifications = get_notifications(&pool, 1000, 10).await?;
+    assert_eq!(
+        messages(&notifications),
+        vec!["Plata de 150.50 RON a fost înregistrată"]
+    );
+    assert_eq!(notifications[0].kind(), Some(NotificationKind::Payment));
+
+    Ok(())
+}
@@ -0,0 +1,398 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::payments::{
+    NewPaymentRecord, create_payment, format_allocations, parse_requested_allocations,
+    validate_requested_allocations,
+};
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::payment::display_amount;
+use crate::model::user::User;
+use crate::online_payment::{
+    CheckoutRequest, MockOutcome, OnlinePaymentProvider, PaymentProvider, WebhookEvent,
+};
+use crate::utils::local_date;
+use crate::utils::queries::get_global_vars;
+use askama::Template;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use axum_extra::extract::Form as AxumExtraForm;
+use serde::Deserialize;
+use sqlx::{SqlitePool, query, query_as};
+use std::sync::Arc;
+use time::{Date, OffsetDateTime};
+use tracing::{error, info, warn};
+
+#[cfg(test)]
+mod test;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/profile/payments/checkout", post(create_checkout))
+        .route(
+            "/payments/mock/checkout/{session_id}",
+            get(mock_checkout_page),
+        )
+        .route(
+            "/payments/mock/checkout/{session_id}",
+            post(mock_checkout_complete),
+        )
+}
+
+fn get_provider(state: &AppState) -> HttpResult<&Arc<OnlinePaymentProvider>> {
+    state
+        .payment_provider
+        .as_ref()
+        .or_bail("Plata online nu este disponibilă")
+}
+
+#[derive(Deserialize)]
+struct CheckoutForm {
+    #[serde(default)]
+    months: Vec<String>, // From checkboxes (format: "M-YYYY")
+}
+
+async fn create_checkout(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    AxumExtraForm(form): AxumExtraForm<CheckoutForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let provider = get_provider(&state)?;
+    let monthly_fee = get_global_vars(&state.read_pool).await?.monthly_fee;
+    if monthly_fee == 0 {
+        return Err(HttpError::Message(
+            "Plata online nu este disponibilă".into(),
+        ));
+    }
+
+    let allocations =
+        parse_requested_allocations(&user, form.months.iter().map(String::as_str), local_date())?;
+    let mut conn = state.read_pool.acquire().await?;
+    validate_requested_allocations(conn.as_mut(), user.id, &allocations).await?;
+
+    let amount = monthly_fee * allocations.len() as i64;
+    let description = format!("Cotizație ACSPA - {} luni", allocations.len());
+    let success_url = format!("{}/profile?payment=success", state.public_url);
+    let cancel_url = format!("{}/profile?payment=cancelled", state.public_url);
+
+    let session = provider
+        .create_checkout_session(&CheckoutRequest {
+            user_id: user.id,
+            email: &user.email,
+            amount,
+            description: &description,
+            success_url: &success_url,
+            cancel_url: &cancel_url,
+        })
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to create checkout session for user {}: {e}",
+                user.id
+            );
+            HttpError::Message("Nu s-a putut iniția plata online".into())
+        })?;
+
+    let provider_name = provider.name();
+    let months = format_allocations(&allocations);
+    query!(
+        "insert into online_payments (provider, session_id, user_id, amount, months) values ($1, $2, $3, $4, $5)",
+        provider_name,
+        session.id,
+        user.id,
+        amount,
+        months
+    )
+        .execute(&state.write_pool)
+        .await?;
+
+    info!(
+        "Checkout session {} created for user {} with {} months",
+        session.id,
+        user.id,
+        allocations.len()
+    );
+
+    Ok([("HX-Redirect", session.url)].into_response())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CheckoutOutcome {
+    Completed {
+        payment_id: i64,
+    },
+    Cancelled,
+    /// The money was received, but the payment could not be recorded and needs manual review
+    Rejected(String),
+    AlreadyProcessed,
+    UnknownSession,
+    Ignored,
+}
+
+struct PendingCheckout {
+    id: i64,
+    user_id: i64,
+    amount: i64,
+    months: String,
+    status: String,
+}
+
+async fn mark_checkout_failed(
+    pool: &SqlitePool,
+    checkout_id: i64,
+    reason: &str,
+) -> sqlx::Result<CheckoutOutcome> {
+    query!(
+        "update online_payments set status = 'failed', failure_reason = $2 where id = $1",
+        checkout_id,
+        reason
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(CheckoutOutcome::Rejected(reason.to_string()))
+}
+
+/// Records the payment of a completed checkout exactly like an admin would through `add_payment`.
+/// Providers may deliver the same event multiple times, so only pending checkouts are processed.
+pub async fn handle_webhook_event(
+    pool: &SqlitePool,
+    provider_name: &str,
+    event: WebhookEvent,
+    today: Date,
+) -> sqlx::Result<CheckoutOutcome> {
+    let (session_id, completed_amount) = match event {
+        WebhookEvent::CheckoutCompleted { session_id, amount } => (session_id, Some(amount)),
+        WebhookEvent::CheckoutCancelled { session_id } => (session_id, None),
+        WebhookEvent::Ignored => return Ok(CheckoutOutcome::Ignored),
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let Some(checkout) = query_as!(
+        PendingCheckout,
+        "select id, user_id, amount, months, status from online_payments where provider = $1 and session_id = $2",
+        provider_name,
+        session_id
+    )
+        .fetch_optional(tx.as_mut())
+        .await?
+    else {
+        return Ok(CheckoutOutcome::UnknownSession);
+    };
+
+    if checkout.status != "pending" {
+        return Ok(CheckoutOutcome::AlreadyProcessed);
+    }
+
+    let Some(amount) = completed_amount else {
+        query!(
+            "update online_payments set status = 'cancelled' where id = $1",
+            checkout.id
+        )
+        .execute(tx.as_mut())
+        .await?;
+        tx.commit().await?;
+
+        return Ok(CheckoutOutcome::Cancelled);
+    };
+
+    if amount != checkout.amount {
+        drop(tx);
+        return mark_checkout_failed(
+            pool,
+            checkout.id,
+            &format!(
+                "Suma primită ({} RON) diferă de cea așteptată ({} RON)",
+                display_amount(amount),
+                display_amount(checkout.amount)
+            ),
+        )
+        .await;
+    }
+
+    let Some(member) = query_as!(
+        User,
+        "select * from users_with_role where id = $1",
+        checkout.user_id
+    )
+    .fetch_optional(tx.as_mut())
+    .await?
+    else {
+        drop(tx);
+        return mark_checkout_failed(pool, checkout.id, "Membrul nu mai există").await;
+    };
+
+    let notes = format!("Plată online ({provider_name}: {session_id})");
+    let payment = match parse_requested_allocations(&member, checkout.months.split(','), today) {
+        Ok(allocations) => {
+            create_payment(
+                tx.as_mut(),
+                NewPaymentRecord {
+                    member_id: member.id,
+                    amount,
+                    payment_date: today,
+                    notes: Some(&notes),
+                    allocations: &allocations,
+                    created_by: member.id,
+                },
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    let payment_id = match payment {
+        Ok(payment_id) => payment_id,
+        Err(HttpError::Database(e)) => return Err(e),
+        Err(e) => {
+            // Something changed since the checkout started (ex: an admin recorded the same month)
+            drop(tx);
+            return mark_checkout_failed(pool, checkout.id, &e.to_string()).await;
+        }
+    };
+
+    query!(
+        "update online_payments set status = 'completed', payment_id = $2, completed_at = CURRENT_TIMESTAMP where id = $1",
+        checkout.id,
+        payment_id
+    )
+        .execute(tx.as_mut())
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(CheckoutOutcome::Completed { payment_id })
+}
+
+async fn process_webhook(
+    state: &AppState,
+    provider: &OnlinePaymentProvider,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Response {
+    let event = match provider.verify_webhook(headers, body, OffsetDateTime::now_utc()) {
+        Ok(event) => event,
+        Err(e) => {
+            warn!("Rejected payment webhook: {e}");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    match handle_webhook_event(&state.write_pool, provider.name(), event, local_date()).await {
+        Ok(outcome) => {
+            if let CheckoutOutcome::Rejected(reason) = &outcome {
+                error!("Online payment needs manual review: {reason}");
+            } else {
+                info!("Payment webhook processed: {outcome:?}");
+            }
+            StatusCode::OK.into_response()
+        }
+        Err(e) => {
+            // The provider will retry the delivery later
+            error!("Failed to process payment webhook: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn payment_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(provider) = state.payment_provider.clone() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    process_webhook(&state, &provider, &headers, &body).await
+}
+
+struct MockCheckout {
+    amount: i64,
+    months: String,
+    status: String,
+}
+
+async fn get_mock_checkout(
+    state: &AppState,
+    user: &User,
+    session_id: &str,
+) -> HttpResult<MockCheckout> {
+    query_as!(
+        MockCheckout,
+        "select amount, months, status from online_payments where provider = 'mock' and session_id = $1 and user_id = $2",
+        session_id,
+        user.id
+    )
+        .fetch_optional(&state.read_pool)
+        .await?
+        .or_bail("Sesiunea de plată nu există")
+}
+
+async fn mock_checkout_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(session_id): Path<String>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "user/mock_checkout_page.html")]
+    struct MockCheckoutTemplate {
+        user: User,
+        session_id: String,
+        amount: String,
+        months: String,
+        is_pending: bool,
+    }
+
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    get_provider(&state)?
+        .as_mock()
+        .or_bail("Plata de test nu este activă")?;
+    let checkout = get_mock_checkout(&state, &user, &session_id).await?;
+
+    MockCheckoutTemplate {
+        user,
+        session_id,
+        amount: display_amount(checkout.amount),
+        months: checkout.months,
+        is_pending: checkout.status == "pending",
+    }
+    .try_into_response()
+}
+
+#[derive(Deserialize)]
+struct MockCheckoutForm {
+    outcome: MockOutcome,
+}
+
+/// Simulates the provider delivering its webhook after the member completes the checkout
+async fn mock_checkout_complete(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(session_id): Path<String>,
+    Form(form): Form<MockCheckoutForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let provider = get_provider(&state)?.clone();
+    let mock = provider.as_mock().or_bail("Plata de test nu este activă")?;
+    let checkout = get_mock_checkout(&state, &user, &session_id).await?;
+
+    let redirect = match form.outcome {
+        MockOutcome::Paid => "/profile?payment=success",
+        MockOutcome::Cancelled => "/profile?payment=cancelled",
+    };
+    let (headers, body) = mock.webhook(&session_id, checkout.amount, form.outcome);
+
+    let response = process_webhook(&state, &provider, &headers, &body).await;
+    if !response.status().is_success() {
+        return Err(HttpError::Message("Webhook-ul de test a eșuat".into()));
+    }
+
+    Ok([("HX-Redirect", redirect)].into_response())
+}
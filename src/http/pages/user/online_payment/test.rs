@@ -0,0 +1,107 @@
+use super::*;
+use sqlx::{SqlitePool, query, query_scalar};
+use time::macros::date;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2020-01-01');
+
+        insert into online_payments (provider, session_id, user_id, amount, months)
+        VALUES ('mock', 'session_1', 1000, 10000, '1-2024,2-2024');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+fn completed(amount: i64) -> WebhookEvent {
+    WebhookEvent::CheckoutCompleted {
+        session_id: "session_1".into(),
+        amount,
+    }
+}
+
+#[sqlx::test]
+async fn should_record_payment_only_once(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let today = date!(2024 - 02 - 10);
+
+    let outcome = handle_webhook_event(&pool, "mock", completed(10000), today).await?;
+    let CheckoutOutcome::Completed { payment_id } = outcome else {
+        panic!("Expected the checkout to complete, got {outcome:?}");
+    };
+
+    // Providers retry webhooks, the second delivery must not create another payment
+    assert_eq!(
+        handle_webhook_event(&pool, "mock", completed(10000), today).await?,
+        CheckoutOutcome::AlreadyProcessed
+    );
+
+    let payments = query_scalar!("select count(*) from payments where user_id = 1000")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(payments, 1);
+
+    let allocations = query_scalar!(
+        "select count(*) from payment_allocations where payment_id = $1",
+        payment_id
+    )
+    .fetch_one(&pool)
+    .await?;
+    assert_eq!(allocations, 2);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_reject_amount_mismatch(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let today = date!(2024 - 02 - 10);
+
+    assert!(matches!(
+        handle_webhook_event(&pool, "mock", completed(5000), today).await?,
+        CheckoutOutcome::Rejected(_)
+    ));
+
+    let status = query_scalar!("select status from online_payments where session_id = 'session_1'")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(status, "failed");
+
+    let payments = query_scalar!("select count(*) from payments")
+        .fetch_one(&pool)
+        .await?;
+    assert_eq!(payments, 0);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_cancel_and_ignore_unknown_sessions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let today = date!(2024 - 02 - 10);
+
+    assert_eq!(
+        handle_webhook_event(&pool, "stripe", completed(10000), today).await?,
+        CheckoutOutcome::UnknownSession
+    );
+
+    let cancelled = WebhookEvent::CheckoutCancelled {
+        session_id: "session_1".into(),
+    };
+    assert_eq!(
+        handle_webhook_event(&pool, "mock", cancelled, today).await?,
+        CheckoutOutcome::Cancelled
+    );
+    assert_eq!(
+        handle_webhook_event(&pool, "mock", completed(10000), today).await?,
+        CheckoutOutcome::AlreadyProcessed
+    );
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::payments::receipt_response;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::payment::{PaymentBreak, PaymentWithAllocations, display_amount};
+use crate::model::user::User;
+use crate::receipt::{Receipt, get_receipt, get_user_receipts};
+use crate::utils::date_formats;
+use crate::utils::local_date;
+use crate::utils::queries::{
+    MonthStatus, MonthStatusView, YearMonth, calculate_payable_months, calculate_year_status,
+    get_user_payment_breaks, get_user_payments,
+};
+use askama::Template;
+use axum::extract::{Path, State};
+use sqlx::query;
+use time::Date;
+
+#[cfg(test)]
+mod test;
+
+/// The member's own payment situation, as shown on the profile
+pub struct PaymentsOverview {
+    pub current_year: i32,
+    pub unpaid_months: Vec<YearMonth>,
+    /// Only known when the monthly fee is configured
+    pub outstanding_amount: Option<String>,
+    pub active_breaks: Vec<PaymentBreak>,
+    pub payments: Vec<PaymentWithAllocations>,
+    pub receipts: Vec<Receipt>,
+}
+
+impl PaymentsOverview {
+    pub fn new(
+        member: &User,
+        payments: Vec<PaymentWithAllocations>,
+        breaks: Vec<PaymentBreak>,
+        receipts: Vec<Receipt>,
+        monthly_fee: i64,
+        today: Date,
+    ) -> Self {
+        let unpaid_months: Vec<_> = calculate_payable_months(member, &payments, &breaks, today)
+            .into_iter()
+            .filter(|(_, status)| *status == MonthStatus::Unpaid)
+            .map(|(month, _)| month)
+            .collect();
+        let outstanding_amount =
+            (monthly_fee > 0).then(|| display_amount(monthly_fee * unpaid_months.len() as i64));
+
+        let current_month = YearMonth::from(today).to_date();
+        let active_breaks = breaks
+            .into_iter()
+            .filter(|b| b.end_date >= current_month)
+            .collect();
+        let payments = payments.into_iter().filter(|p| !p.is_voided()).collect();
+
+        Self {
+            current_year: today.year(),
+            unpaid_months,
+            outstanding_amount,
+            active_breaks,
+            payments,
+            receipts,
+        }
+    }
+
+    pub fn display_unpaid_months(&self) -> String {
+        self.unpaid_months
+            .iter()
+            .map(|month| {
+                format!(
+                    "{} {}",
+                    date_formats::month_as_str(&month.month),
+                    month.year
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub async fn get_payments_overview(
+    state: &AppState,
+    member: &User,
+    monthly_fee: i64,
+) -> sqlx::Result<PaymentsOverview> {
+    let payments = get_user_payments(&mut *state.read_pool.acquire().await?, member.id).await?;
+    let breaks = get_user_payment_breaks(&state.read_pool, member.id).await?;
+    let receipts = get_user_receipts(&state.read_pool, member.id).await?;
+
+    Ok(PaymentsOverview::new(
+        member,
+        payments,
+        breaks,
+        receipts,
+        monthly_fee,
+        local_date(),
+    ))
+}
+
+/// The status grid of the logged-in member for another year
+pub async fn payment_status_partial(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(year): Path<i32>,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "admin/members/status_grid_partial.html")]
+    struct StatusGridTemplate {
+        member_since_year: i32,
+        current_year: i32,
+        selected_year: i32,
+        months_status_view: Vec<MonthStatusView>,
+        status_url: &'static str,
+    }
+
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let payments = get_user_payments(&mut *state.read_pool.acquire().await?, user.id).await?;
+    let breaks = get_user_payment_breaks(&state.read_pool, user.id).await?;
+    let current_date = local_date();
+
+    StatusGridTemplate {
+        member_since_year: user.member_since.year(),
+        current_year: current_date.year(),
+        selected_year: year,
+        months_status_view: calculate_year_status(year, &user, &payments, &breaks, current_date),
+        status_url: "/profile/payment_status",
+    }
+    .try_into_response()
+}
+
+pub async fn download_receipt(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(payment_id): Path<i64>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let is_own_payment = query!(
+        "select id from payments where id = $1 and user_id = $2",
+        payment_id,
+        user.id
+    )
+    .fetch_optional(&state.read_pool)
+    .await?
+    .is_some();
+    if !is_own_payment {
+        return Err(HttpError::Unauthorized);
+    }
+
+    let receipt = get_receipt(&state.read_pool, payment_id)
+        .await?
+        .ok_or_else(|| HttpError::Message("Plata nu are o chitanță".into()))?;
+
+    Ok(receipt_response(&receipt))
+}
@@ -0,0 +1,80 @@
+use super::*;
+use time::Month;
+use time::macros::{date, datetime};
+
+fn payment(id: i64, months: &[Month], voided: bool) -> PaymentWithAllocations {
+    PaymentWithAllocations {
+        id,
+        amount: 5000 * months.len() as i64,
+        payment_date: date!(2024 - 01 - 05),
+        notes: None,
+        allocations: months.iter().map(|m| YearMonth::new(2024, *m)).collect(),
+        created_at: datetime!(2024-01-05 10:00:00 UTC),
+        created_by: 0,
+        created_by_name: "Admin".into(),
+        voided_at: voided.then_some(datetime!(2024-01-06 10:00:00 UTC)),
+        void_reason: None,
+        refund_of: None,
+        receipt_number: None,
+    }
+}
+
+fn payment_break(start_date: Date, end_date: Date) -> PaymentBreak {
+    PaymentBreak {
+        id: 1,
+        user_id: 1000,
+        start_date,
+        end_date,
+        reason: None,
+        created_at: datetime!(2024-01-05 10:00:00 UTC),
+        created_by: 0,
+        created_by_name: "Admin".into(),
+    }
+}
+
+#[test]
+fn should_summarize_own_payments() {
+    let member = User {
+        id: 1000,
+        member_since: date!(2024 - 01 - 01),
+        ..User::empty()
+    };
+    let payments = vec![
+        payment(1, &[Month::January], false),
+        // Voiding a payment also removes its months
+        payment(2, &[], true),
+    ];
+    let breaks = vec![
+        payment_break(date!(2024 - 03 - 01), date!(2024 - 03 - 01)),
+        payment_break(date!(2024 - 06 - 01), date!(2024 - 08 - 01)),
+    ];
+
+    let overview = PaymentsOverview::new(
+        &member,
+        payments,
+        breaks,
+        vec![],
+        5000,
+        date!(2024 - 06 - 15),
+    );
+
+    // March is a break, June is covered by the ongoing break
+    assert_eq!(
+        overview.unpaid_months,
+        vec![
+            YearMonth::new(2024, Month::February),
+            YearMonth::new(2024, Month::April),
+            YearMonth::new(2024, Month::May),
+        ]
+    );
+    assert_eq!(overview.outstanding_amount.as_deref(), Some("150"));
+    assert_eq!(overview.active_breaks.len(), 1);
+    assert_eq!(overview.payments.len(), 1);
+    assert_eq!(
+        overview.display_unpaid_months(),
+        "Februarie 2024, Aprilie 2024, Mai 2024"
+    );
+
+    let overview = PaymentsOverview::new(&member, vec![], vec![], vec![], 0, date!(2024 - 01 - 15));
+    assert_eq!(overview.outstanding_amount, None);
+}
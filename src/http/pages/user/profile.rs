@@ -1,19 +1,87 @@
 use crate::http::AppState;
 use crate::http::error::{HttpError, HttpResult};
 use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::break_requests::{BreakRequest, get_break_requests};
+use crate::http::pages::user::external_login::{LinkedIdentity, get_linked_identity};
+use crate::http::pages::user::payments::{PaymentsOverview, get_payments_overview};
 use crate::http::template_into_response::TemplateIntoResponse;
 use crate::model::user::User;
 use crate::utils::queries::{
-    GroupedUserReservations, ReservationsCount, get_user_reservations,
+    GroupedUserReservations, MonthStatus, ReservationsCount, calculate_payable_months,
+    get_global_vars, get_user_payment_breaks, get_user_payments, get_user_reservations,
     get_user_weeks_reservations_count,
 };
-use crate::utils::{date_formats, local_time};
+use crate::utils::{date_formats, local_date, local_time};
 use askama::Template;
 use axum::extract::{Query, State};
 use serde::Deserialize;
 use sqlx::query;
 
-pub async fn profile_page(auth_session: AuthSession, State(state): State<AppState>) -> HttpResult {
+struct PayableMonth {
+    value: String,
+    name: String,
+    unpaid: bool,
+}
+
+struct OnlinePayment {
+    monthly_fee: String,
+    months: Vec<PayableMonth>,
+}
+
+async fn get_online_payment(state: &AppState, user: &User) -> HttpResult<Option<OnlinePayment>> {
+    let global_vars = get_global_vars(&state.read_pool).await?;
+    if state.payment_provider.is_none() || global_vars.monthly_fee == 0 {
+        return Ok(None);
+    }
+
+    let payments = get_user_payments(&mut *state.read_pool.acquire().await?, user.id).await?;
+    let breaks = get_user_payment_breaks(&state.read_pool, user.id).await?;
+
+    let months = calculate_payable_months(user, &payments, &breaks, local_date())
+        .into_iter()
+        .map(|(year_month, status)| PayableMonth {
+            value: format!("{}-{}", year_month.month as u8, year_month.year),
+            name: format!(
+                "{} {}",
+                date_formats::month_as_str(&year_month.month),
+                year_month.year
+            ),
+            unpaid: status == MonthStatus::Unpaid,
+        })
+        .collect();
+
+    Ok(Some(OnlinePayment {
+        monthly_fee: global_vars.display_monthly_fee(),
+        months,
+    }))
+}
+
+struct ExternalLogin {
+    provider_name: String,
+    linked: Option<LinkedIdentity>,
+}
+
+async fn get_external_login(state: &AppState, user: &User) -> HttpResult<Option<ExternalLogin>> {
+    let Some(oidc) = &state.oidc else {
+        return Ok(None);
+    };
+
+    Ok(Some(ExternalLogin {
+        provider_name: oidc.display_name.clone(),
+        linked: get_linked_identity(&state.read_pool, user.id, oidc.issuer()).await?,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    payment: Option<String>,
+}
+
+pub async fn profile_page(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(query): Query<ProfileQuery>,
+) -> HttpResult {
     #[derive(Template)]
     #[template(path = "user/profile_page.html")]
     struct ProfileTemplate {
@@ -22,6 +90,11 @@ pub async fn profile_page(auth_session: AuthSession, State(state): State<AppStat
         show_cancelled: bool,
         this_weeks_reservations: ReservationsCount,
         max_reservations: ReservationsCount,
+        online_payment: Option<OnlinePayment>,
+        payment_result: Option<String>,
+        payments_overview: PaymentsOverview,
+        break_requests: Vec<BreakRequest>,
+        external_login: Option<ExternalLogin>,
     }
 
     let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
@@ -36,6 +109,12 @@ pub async fn profile_page(auth_session: AuthSession, State(state): State<AppStat
     let this_weeks_reservations =
         get_user_weeks_reservations_count(&state.read_pool, &user, local_time().date()).await?;
 
+    let online_payment = get_online_payment(&state, &user).await?;
+    let monthly_fee = get_global_vars(&state.read_pool).await?.monthly_fee;
+    let payments_overview = get_payments_overview(&state, &user, monthly_fee).await?;
+    let break_requests = get_break_requests(&state.read_pool, None, Some(user.id)).await?;
+    let external_login = get_external_login(&state, &user).await?;
+
     ProfileTemplate {
         reservations: get_user_reservations(&state.read_pool, user.id, false).await,
         user,
@@ -45,6 +124,11 @@ pub async fn profile_page(auth_session: AuthSession, State(state): State<AppStat
             member: role.reservations,
             guest: role.guest_reservations,
         },
+        online_payment,
+        payment_result: query.payment,
+        payments_overview,
+        break_requests,
+        external_login,
     }
     .try_into_response()
 }
@@ -0,0 +1,201 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::user::notifications::get_notifications;
+use crate::model::notification::NotificationKind;
+use crate::web_push::{WebPushError, WebPushSender};
+use axum::Json;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteExecutor, SqlitePool, query, query_scalar};
+use time::{Duration, OffsetDateTime};
+use tracing::{error, info};
+
+#[cfg(test)]
+mod test;
+
+/// Older notifications are not worth a push anymore, the slot might have already started
+const PUSH_MAX_AGE: Duration = Duration::hours(1);
+
+/// Moves the subscription to the member when the browser was used by someone else before
+pub async fn save_subscription(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    endpoint: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    query!(
+        "insert into push_subscriptions (user_id, endpoint, created_at) values ($1, $2, $3)
+         on conflict (endpoint) do update set user_id = excluded.user_id, created_at = excluded.created_at",
+        user_id,
+        endpoint,
+        now
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_subscription(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    endpoint: &str,
+) -> sqlx::Result<bool> {
+    let result = query!(
+        "delete from push_subscriptions where user_id = $1 and endpoint = $2",
+        user_id,
+        endpoint
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() != 0)
+}
+
+/// Pushes the recent reservation notifications to the phones of their members,
+/// returns how many notifications reached at least one phone.
+/// A notification is retried later only if a push service failed for it.
+pub async fn deliver_pending_pushes(
+    pool: &SqlitePool,
+    sender: &WebPushSender,
+    now: OffsetDateTime,
+) -> sqlx::Result<usize> {
+    let promotion = NotificationKind::Promotion.as_ref();
+    let cancellation = NotificationKind::Cancellation.as_ref();
+    let oldest = now - PUSH_MAX_AGE;
+    let pending = query!(
+        "select n.id, n.user_id from notifications n
+         where n.pushed_at is null and n.kind in ($1, $2) and n.created_at > $3
+           and exists (select 1 from push_subscriptions s where s.user_id = n.user_id)
+         order by n.id",
+        promotion,
+        cancellation,
+        oldest
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut pushed = 0;
+
+    for notification in pending {
+        let endpoints = query_scalar!(
+            "select endpoint from push_subscriptions where user_id = $1",
+            notification.user_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut delivered = false;
+        let mut retry = false;
+        for endpoint in endpoints {
+            match sender.send(&endpoint, now).await {
+                Ok(()) => delivered = true,
+                Err(WebPushError::Gone) => {
+                    info!(
+                        "Removing the expired push subscription of user {}",
+                        notification.user_id
+                    );
+                    delete_subscription(pool, notification.user_id, &endpoint).await?;
+                }
+                Err(e) => {
+                    error!("Failed to push to user {}: {e}", notification.user_id);
+                    retry = true;
+                }
+            }
+        }
+        if retry && !delivered {
+            continue;
+        }
+
+        query!(
+            "update notifications set pushed_at = $2 where id = $1",
+            notification.id,
+            now
+        )
+        .execute(pool)
+        .await?;
+        if delivered {
+            pushed += 1;
+        }
+    }
+
+    Ok(pushed)
+}
+
+#[derive(Deserialize)]
+pub struct PushSubscription {
+    endpoint: String,
+}
+
+impl PushSubscription {
+    fn endpoint(&self, sender: &WebPushSender) -> HttpResult<&str> {
+        let endpoint = self.endpoint.trim();
+        (endpoint.starts_with("https://")
+            && endpoint.len() <= 2048
+            && sender.accepts_endpoint(endpoint))
+        .then_some(endpoint)
+        .or_bail("Adresa de notificare a browserului nu este validă")
+    }
+}
+
+pub async fn subscribe(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Json(subscription): Json<PushSubscription>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let Some(sender) = &state.web_push else {
+        return Err(HttpError::Message(
+            "Notificările pe telefon nu sunt disponibile".into(),
+        ));
+    };
+
+    save_subscription(
+        &state.write_pool,
+        user.id,
+        subscription.endpoint(sender)?,
+        OffsetDateTime::now_utc(),
+    )
+    .await?;
+    info!("User {} subscribed to push notifications", user.id);
+
+    Ok(().into_response())
+}
+
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Json(subscription): Json<PushSubscription>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    if delete_subscription(&state.write_pool, user.id, subscription.endpoint.trim()).await? {
+        info!("User {} unsubscribed from push notifications", user.id);
+    }
+
+    Ok(().into_response())
+}
+
+#[derive(Serialize)]
+struct PushedNotification {
+    message: String,
+    link: String,
+}
+
+/// Asked by the service worker when a push arrives, since pushes carry no payload
+pub async fn latest_notification(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let notification = get_notifications(&state.read_pool, user.id, 1)
+        .await?
+        .pop()
+        .or_bail("Nu ai nicio notificare")?;
+
+    Ok(Json(PushedNotification {
+        link: format!("/notifications/{}", notification.id),
+        message: notification.message,
+    })
+    .into_response())
+}
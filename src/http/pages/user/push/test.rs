@@ -0,0 +1,138 @@
+use super::*;
+use crate::http::pages::user::notifications::create_notification;
+use crate::web_push::VapidKey;
+use axum::Router;
+use axum::http::StatusCode;
+use axum::routing::post;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use time::macros::datetime;
+use tokio::net::TcpListener;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (2000, 'test2@test.com', 'Test 2', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// A push service accepting pushes on `/push/ok`, returns its origin and how many it received
+async fn push_service() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let origin = format!("http://{}", listener.local_addr().unwrap());
+    let received = Arc::new(AtomicUsize::new(0));
+    let counter = received.clone();
+    let app = Router::new()
+        .route(
+            "/push/ok",
+            post(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { StatusCode::CREATED }
+            }),
+        )
+        .route("/push/gone", post(|| async { StatusCode::GONE }))
+        .route("/push/failing", post(|| async { StatusCode::BAD_GATEWAY }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (origin, received)
+}
+
+fn sender() -> WebPushSender {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).unwrap();
+    let key = VapidKey::from_base64(&URL_SAFE_NO_PAD.encode(bytes)).unwrap();
+    WebPushSender::new(key, "mailto:admin@acspa.ro").with_allowed_hosts(["127.0.0.1"])
+}
+
+async fn subscriptions(pool: &SqlitePool, user_id: i64) -> sqlx::Result<i64> {
+    query_scalar!(
+        "select count(*) as 'count!' from push_subscriptions where user_id = $1",
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[sqlx::test]
+async fn should_push_reservation_notifications_once(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let (origin, received) = push_service().await;
+    let sender = sender();
+    let now = datetime!(2025-06-10 12:00 UTC);
+
+    save_subscription(&pool, 1000, &format!("{origin}/push/ok"), now).await?;
+    save_subscription(&pool, 1000, &format!("{origin}/push/gone"), now).await?;
+    for (kind, created_at) in [
+        (NotificationKind::Promotion, now),
+        (NotificationKind::Cancellation, now),
+        // Only the reservations are pushed
+        (NotificationKind::Payment, now),
+        // Too old to matter anymore
+        (NotificationKind::Promotion, now - Duration::hours(2)),
+    ] {
+        create_notification(&pool, 1000, kind, "Message", None, created_at).await?;
+    }
+    // Without a subscription
+    create_notification(
+        &pool,
+        2000,
+        NotificationKind::Promotion,
+        "Message",
+        None,
+        now,
+    )
+    .await?;
+
+    assert_eq!(deliver_pending_pushes(&pool, &sender, now).await?, 2);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+    // The expired subscription is forgotten
+    assert_eq!(subscriptions(&pool, 1000).await?, 1);
+
+    assert_eq!(deliver_pending_pushes(&pool, &sender, now).await?, 0);
+    assert_eq!(received.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_retry_when_the_push_service_fails(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let (origin, received) = push_service().await;
+    let sender = sender();
+    let now = datetime!(2025-06-10 12:00 UTC);
+
+    save_subscription(&pool, 1000, &format!("{origin}/push/failing"), now).await?;
+    create_notification(
+        &pool,
+        1000,
+        NotificationKind::Promotion,
+        "Message",
+        None,
+        now,
+    )
+    .await?;
+    assert_eq!(deliver_pending_pushes(&pool, &sender, now).await?, 0);
+
+    // The browser is now used by another member
+    save_subscription(&pool, 2000, &format!("{origin}/push/failing"), now).await?;
+    assert_eq!(subscriptions(&pool, 1000).await?, 0);
+    save_subscription(&pool, 1000, &format!("{origin}/push/ok"), now).await?;
+
+    assert_eq!(deliver_pending_pushes(&pool, &sender, now).await?, 1);
+    assert_eq!(received.load(Ordering::SeqCst), 1);
+
+    assert!(delete_subscription(&pool, 1000, &format!("{origin}/push/ok")).await?);
+    assert!(!delete_subscription(&pool, 1000, &format!("{origin}/push/ok")).await?);
+
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::pages::admin::members::impersonation::get_impersonation;
+use crate::http::pages::user::login_attempts::ClientIp;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::http::{AppState, SESSION_INACTIVITY_EXPIRY};
+use crate::model::user::User;
+use crate::utils::date_formats;
+use askama::Template;
+use axum::extract::{Path, Request, State};
+use axum::http::header::USER_AGENT;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum_login::tower_sessions::SessionStore;
+use axum_login::tower_sessions::session::Id;
+use sqlx::{SqliteExecutor, query, query_as, query_scalar};
+use std::str::FromStr;
+use time::{Duration, OffsetDateTime};
+use tower_sessions_sqlx_store::SqliteStore;
+use tracing::{error, info};
+
+#[cfg(test)]
+mod test;
+
+/// How stale the last seen time is allowed to get, so not every request writes to the database
+const LAST_SEEN_RESOLUTION: Duration = Duration::minutes(5);
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+pub struct ActiveSession {
+    /// What the pages refer to the session by, unlike its id it can't be used as a cookie
+    pub id: i64,
+    session_id: String,
+    pub user_agent: String,
+    pub ip: String,
+    pub created_at: OffsetDateTime,
+    pub last_seen_at: OffsetDateTime,
+}
+
+impl ActiveSession {
+    pub fn device(&self) -> String {
+        describe_user_agent(&self.user_agent)
+    }
+
+    pub fn is_current(&self, current_session_id: &str) -> bool {
+        self.session_id == current_session_id
+    }
+}
+
+/// A short, readable name for the browser and operating system of a user agent
+fn describe_user_agent(user_agent: &str) -> String {
+    // Order matters, most user agents also mention the browsers they are based on
+    const BROWSERS: [(&str, &str); 8] = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("SamsungBrowser/", "Samsung Internet"),
+        ("Firefox/", "Firefox"),
+        ("FxiOS/", "Firefox"),
+        ("CriOS/", "Chrome"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ];
+    const SYSTEMS: [(&str, &str); 7] = [
+        ("Android", "Android"),
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ];
+
+    let find = |names: &[(&str, &'static str)]| {
+        names
+            .iter()
+            .find(|(marker, _)| user_agent.contains(marker))
+            .map(|(_, name)| *name)
+    };
+
+    match (find(&BROWSERS), find(&SYSTEMS)) {
+        (Some(browser), Some(system)) => format!("{browser} pe {system}"),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => "Dispozitiv necunoscut".to_string(),
+    }
+}
+
+pub async fn save_session_activity(
+    executor: impl SqliteExecutor<'_>,
+    session_id: &str,
+    user_id: i64,
+    user_agent: &str,
+    ip: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<()> {
+    query!(
+        "insert into user_sessions (session_id, user_id, user_agent, ip, created_at, last_seen_at)
+         values ($1, $2, $3, $4, $5, $5)
+         on conflict (session_id) do update
+         set user_agent = excluded.user_agent, ip = excluded.ip, last_seen_at = excluded.last_seen_at",
+        session_id,
+        user_id,
+        user_agent,
+        ip,
+        now
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_activity(
+    state: &AppState,
+    session_id: &str,
+    user_id: i64,
+    user_agent: &str,
+    ip: &str,
+) -> sqlx::Result<()> {
+    let now = OffsetDateTime::now_utc();
+    let last_seen_at = query_scalar!(
+        r#"select last_seen_at as "last_seen_at: OffsetDateTime" from user_sessions
+           where session_id = $1 and user_id = $2 and ip = $3"#,
+        session_id,
+        user_id,
+        ip
+    )
+    .fetch_optional(&state.read_pool)
+    .await?;
+    if last_seen_at.is_some_and(|last_seen_at| now - last_seen_at < LAST_SEEN_RESOLUTION) {
+        return Ok(());
+    }
+
+    save_session_activity(&state.write_pool, session_id, user_id, user_agent, ip, now).await
+}
+
+/// Keeps track of the device and address behind each logged in session and when it was last used
+pub async fn track_session(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    ClientIp(ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    // The admin's device isn't one of the member's sessions
+    let is_impersonating = get_impersonation(&auth_session.session).await.is_some();
+    if let (Some(user), Some(session_id), false) = (
+        &auth_session.user,
+        auth_session.session.id(),
+        is_impersonating,
+    ) {
+        let user_agent: String = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|header| header.to_str().ok())
+            .unwrap_or_default()
+            .chars()
+            .take(MAX_USER_AGENT_LENGTH)
+            .collect();
+
+        let session_id = session_id.to_string();
+        if let Err(e) = record_activity(&state, &session_id, user.id, &user_agent, &ip).await {
+            error!(
+                "Failed to record the session activity of user {}: {e}",
+                user.id
+            );
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Sessions used within the inactivity expiry, most recently used first
+pub async fn get_active_sessions(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    now: OffsetDateTime,
+) -> sqlx::Result<Vec<ActiveSession>> {
+    let since = now - SESSION_INACTIVITY_EXPIRY;
+    query_as!(
+        ActiveSession,
+        r#"select id, session_id, user_agent, ip, created_at as "created_at: OffsetDateTime",
+            last_seen_at as "last_seen_at: OffsetDateTime"
+           from user_sessions where user_id = $1 and last_seen_at > $2
+           order by last_seen_at desc"#,
+        user_id,
+        since
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Stops tracking the session of the user, returning its id if it was theirs
+pub async fn forget_session(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    session_id: &str,
+) -> sqlx::Result<Option<String>> {
+    query_scalar!(
+        r#"delete from user_sessions where user_id = $1 and session_id = $2
+           returning session_id as "session_id!""#,
+        user_id,
+        session_id
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Stops tracking a session of the user other than the current one, returning its id if it was theirs
+pub async fn forget_other_session(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    id: i64,
+    current_session_id: &str,
+) -> sqlx::Result<Option<String>> {
+    query_scalar!(
+        r#"delete from user_sessions where user_id = $1 and id = $2 and session_id != $3
+           returning session_id as "session_id!""#,
+        user_id,
+        id,
+        current_session_id
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Stops tracking all the sessions of the user, except the given one, returning their ids
+pub async fn forget_sessions(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+    except: Option<&str>,
+) -> sqlx::Result<Vec<String>> {
+    query_scalar!(
+        r#"delete from user_sessions where user_id = $1 and ($2 is null or session_id != $2)
+           returning session_id as "session_id!""#,
+        user_id,
+        except
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Deletes the sessions from the store, so their cookies no longer log anyone in
+pub async fn end_sessions(store: &SqliteStore, session_ids: &[String]) -> HttpResult<()> {
+    for session_id in session_ids {
+        let Ok(id) = Id::from_str(session_id) else {
+            continue;
+        };
+        store
+            .delete(&id)
+            .await
+            .or_bail("Sesiunea nu a putut fi închisă")?;
+    }
+
+    Ok(())
+}
+
+fn current_session_id(auth_session: &AuthSession) -> String {
+    auth_session
+        .session
+        .id()
+        .map(|id| id.to_string())
+        .unwrap_or_default()
+}
+
+pub async fn sessions_page(State(state): State<AppState>, auth_session: AuthSession) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "user/sessions_page.html")]
+    struct SessionsTemplate {
+        user: User,
+        current_session_id: String,
+        sessions: Vec<ActiveSession>,
+    }
+
+    let current_session_id = current_session_id(&auth_session);
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    let sessions =
+        get_active_sessions(&state.read_pool, user.id, OffsetDateTime::now_utc()).await?;
+
+    SessionsTemplate {
+        user,
+        current_session_id,
+        sessions,
+    }
+    .try_into_response()
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Path(id): Path<i64>,
+) -> HttpResult {
+    let current_session_id = current_session_id(&auth_session);
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    // The current session is left to the logout
+    let session_id = forget_other_session(&state.write_pool, user.id, id, &current_session_id)
+        .await?
+        .or_bail("Sesiunea nu a fost găsită")?;
+    end_sessions(&state.session_store, &[session_id]).await?;
+    info!("User {} ended one of their sessions", user.id);
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
+
+pub async fn revoke_other_sessions(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    let current_session_id = current_session_id(&auth_session);
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let session_ids =
+        forget_sessions(&state.write_pool, user.id, Some(&current_session_id)).await?;
+    end_sessions(&state.session_store, &session_ids).await?;
+    info!(
+        "User {} ended {} other sessions",
+        user.id,
+        session_ids.len()
+    );
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
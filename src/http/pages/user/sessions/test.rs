@@ -0,0 +1,135 @@
+use super::*;
+use axum_login::tower_sessions::session::Record;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use time::macros::datetime;
+
+const FIREFOX_WINDOWS: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:140.0) Gecko/20100101 Firefox/140.0";
+const CHROME_ANDROID: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Mobile Safari/537.36";
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test User', '', 100, FALSE, '2000-01-01', '2024-01-01'),
+               (1001, 'test2@test.com', 'Other User', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let now = datetime!(2025-06-01 12:00 UTC);
+    save_session_activity(pool, "first", 1000, FIREFOX_WINDOWS, "10.0.0.1", now).await?;
+    save_session_activity(pool, "second", 1000, CHROME_ANDROID, "10.0.0.2", now).await?;
+    save_session_activity(pool, "third", 1000, CHROME_ANDROID, "10.0.0.3", now).await?;
+    save_session_activity(pool, "other", 1001, FIREFOX_WINDOWS, "10.0.0.4", now).await
+}
+
+async fn session_key(pool: &SqlitePool, session_id: &str) -> sqlx::Result<i64> {
+    query_scalar!(
+        "select id from user_sessions where session_id = $1",
+        session_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[test]
+fn should_describe_user_agents() {
+    assert_eq!(describe_user_agent(FIREFOX_WINDOWS), "Firefox pe Windows");
+    assert_eq!(describe_user_agent(CHROME_ANDROID), "Chrome pe Android");
+    assert_eq!(
+        describe_user_agent(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1"
+        ),
+        "Safari pe iPhone"
+    );
+    assert_eq!(describe_user_agent("curl/8.0"), "Dispozitiv necunoscut");
+}
+
+#[sqlx::test]
+async fn should_list_recently_used_sessions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let later = datetime!(2025-06-02 08:00 UTC);
+    save_session_activity(&pool, "second", 1000, CHROME_ANDROID, "10.0.0.9", later).await?;
+
+    let sessions = get_active_sessions(&pool, 1000, later).await?;
+    assert_eq!(sessions.len(), 3);
+    assert!(sessions[0].is_current("second"));
+    assert_eq!(sessions[0].id, session_key(&pool, "second").await?);
+    assert_eq!(sessions[0].ip, "10.0.0.9");
+    assert_eq!(sessions[0].created_at, datetime!(2025-06-01 12:00 UTC));
+    assert_eq!(sessions[0].last_seen_at, later);
+
+    let expired = datetime!(2025-06-01 12:00 UTC) + SESSION_INACTIVITY_EXPIRY;
+    let sessions = get_active_sessions(&pool, 1000, expired).await?;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].is_current("second"));
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_only_forget_own_sessions(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    assert_eq!(forget_session(&pool, 1000, "other").await?, None);
+    let other = session_key(&pool, "other").await?;
+    let first = session_key(&pool, "first").await?;
+    let second = session_key(&pool, "second").await?;
+    assert_eq!(
+        forget_other_session(&pool, 1000, other, "second").await?,
+        None
+    );
+    // The current session is only ended by logging out
+    assert_eq!(
+        forget_other_session(&pool, 1000, second, "second").await?,
+        None
+    );
+    assert_eq!(
+        forget_other_session(&pool, 1000, first, "second").await?,
+        Some("first".to_string())
+    );
+
+    let mut forgotten = forget_sessions(&pool, 1000, Some("second")).await?;
+    forgotten.sort();
+    assert_eq!(forgotten, vec!["third".to_string()]);
+
+    let now = datetime!(2025-06-01 12:00 UTC);
+    let sessions = get_active_sessions(&pool, 1000, now).await?;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].is_current("second"));
+    assert_eq!(get_active_sessions(&pool, 1001, now).await?.len(), 1);
+
+    assert_eq!(forget_sessions(&pool, 1000, None).await?, vec!["second"]);
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_end_sessions_in_store(pool: SqlitePool) -> sqlx::Result<()> {
+    let store = SqliteStore::new(pool.clone());
+    store.migrate().await?;
+
+    let mut records = Vec::new();
+    for _ in 0..2 {
+        let mut record = Record {
+            id: Id::default(),
+            data: HashMap::new(),
+            expiry_date: OffsetDateTime::now_utc() + Duration::days(1),
+        };
+        store.create(&mut record).await.unwrap();
+        records.push(record);
+    }
+
+    end_sessions(&store, &[records[0].id.to_string(), "invalid".to_string()])
+        .await
+        .unwrap();
+
+    assert!(store.load(&records[0].id).await.unwrap().is_none());
+    assert!(store.load(&records[1].id).await.unwrap().is_some());
+
+    Ok(())
+}
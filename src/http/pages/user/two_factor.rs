@@ -0,0 +1,287 @@
+use crate::http::AppState;
+use crate::http::error::{HttpError, HttpResult, OrBail};
+use crate::http::pages::AuthSession;
+use crate::http::template_into_response::TemplateIntoResponse;
+use crate::model::user::User;
+use crate::totp;
+use crate::utils::queries::get_global_vars;
+use askama::Template;
+use axum::Form;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use sqlx::{SqliteConnection, SqliteExecutor, query, query_scalar};
+use time::OffsetDateTime;
+use tracing::info;
+
+#[cfg(test)]
+mod test;
+
+/// Secret shown to the member while enrolling, only saved once they prove their app generates codes
+const ENROLLMENT_SESSION_KEY: &str = "two_factor_enrollment";
+
+/// Accepts each code of the authenticator app only once, and none older than the last accepted one,
+/// so a code seen or intercepted can't be replayed while it's still valid
+async fn accept_app_code(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    secret: &str,
+    code: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<bool> {
+    let Some(step) = totp::matching_step(secret, code, now) else {
+        return Ok(false);
+    };
+
+    let accepted = query!(
+        "update users set totp_last_step = $2
+         where id = $1 and (totp_last_step is null or totp_last_step < $2)",
+        user_id,
+        step
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    Ok(accepted != 0)
+}
+
+/// Accepts either a code from the authenticator app or one of the unused recovery codes
+pub async fn verify_second_factor(
+    tx: &mut SqliteConnection,
+    user: &User,
+    code: &str,
+    now: OffsetDateTime,
+) -> sqlx::Result<bool> {
+    let Some(secret) = &user.totp_secret else {
+        return Ok(false);
+    };
+    if accept_app_code(&mut *tx, user.id, secret, code, now).await? {
+        return Ok(true);
+    }
+
+    let code_hash = totp::hash_recovery_code(code);
+    let used = query!(
+        "update recovery_codes set used_at = CURRENT_TIMESTAMP
+         where user_id = $1 and code_hash = $2 and used_at is null",
+        user.id,
+        code_hash
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    if used != 0 {
+        info!("User {} logged in with a recovery code", user.id);
+    }
+
+    Ok(used != 0)
+}
+
+/// Replaces the recovery codes of the user, returning the new ones in plain text to be shown once
+pub async fn replace_recovery_codes(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+) -> sqlx::Result<Vec<String>> {
+    query!("delete from recovery_codes where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let codes = totp::generate_recovery_codes();
+    for code in &codes {
+        let code_hash = totp::hash_recovery_code(code);
+        query!(
+            "insert into recovery_codes (user_id, code_hash) values ($1, $2)",
+            user_id,
+            code_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(codes)
+}
+
+pub async fn enable_two_factor(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    secret: &str,
+) -> sqlx::Result<Vec<String>> {
+    query!(
+        "update users set totp_secret = $2, totp_last_step = NULL where id = $1",
+        user_id,
+        secret
+    )
+    .execute(&mut *tx)
+    .await?;
+    info!("Two-factor authentication enabled for user {user_id}");
+
+    replace_recovery_codes(tx, user_id).await
+}
+
+pub async fn disable_two_factor(tx: &mut SqliteConnection, user_id: i64) -> sqlx::Result<()> {
+    query!(
+        "update users set totp_secret = NULL, totp_last_step = NULL where id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!("delete from recovery_codes where user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    info!("Two-factor authentication disabled for user {user_id}");
+
+    Ok(())
+}
+
+async fn count_unused_recovery_codes(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<i64> {
+    query_scalar!(
+        "select count(*) from recovery_codes where user_id = $1 and used_at is null",
+        user_id
+    )
+    .fetch_one(executor)
+    .await
+}
+
+#[derive(Template)]
+#[template(path = "user/recovery_codes.html")]
+struct RecoveryCodesTemplate {
+    codes: Vec<String>,
+}
+
+pub async fn two_factor_page(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+) -> HttpResult {
+    #[derive(Template)]
+    #[template(path = "user/two_factor_page.html")]
+    struct TwoFactorTemplate {
+        user: User,
+        unused_recovery_codes: i64,
+        secret: String,
+        qr_code: String,
+    }
+
+    let user = auth_session.user.clone().ok_or(HttpError::Unauthorized)?;
+
+    if user.has_two_factor() {
+        return TwoFactorTemplate {
+            unused_recovery_codes: count_unused_recovery_codes(&state.read_pool, user.id).await?,
+            user,
+            secret: String::new(),
+            qr_code: String::new(),
+        }
+        .try_into_response();
+    }
+
+    // A new secret every time the page is opened, so an abandoned enrollment can't be reused
+    let secret = totp::generate_secret();
+    auth_session
+        .session
+        .insert(ENROLLMENT_SESSION_KEY, &secret)
+        .await
+        .or_bail("Sesiunea nu a putut fi salvată")?;
+
+    let issuer = get_global_vars(&state.read_pool).await?.club_name;
+    let uri = totp::provisioning_uri(&secret, &issuer, &user.email);
+
+    TwoFactorTemplate {
+        user,
+        unused_recovery_codes: 0,
+        qr_code: totp::qr_code_svg(&uri),
+        secret,
+    }
+    .try_into_response()
+}
+
+#[derive(Deserialize)]
+pub struct CodeForm {
+    code: String,
+}
+
+pub async fn enroll(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Form(form): Form<CodeForm>,
+) -> HttpResult {
+    let user = auth_session.user.clone().ok_or(HttpError::Unauthorized)?;
+    let secret: String = auth_session
+        .session
+        .get(ENROLLMENT_SESSION_KEY)
+        .await
+        .ok()
+        .flatten()
+        .or_bail("Sesiunea a expirat, reîncarcă pagina")?;
+
+    let mut tx = state.write_pool.begin().await?;
+    let codes = enable_two_factor(tx.as_mut(), user.id, &secret).await?;
+    // Also marks the code as used, the transaction is rolled back if it's wrong
+    if !accept_app_code(
+        tx.as_mut(),
+        user.id,
+        &secret,
+        &form.code,
+        OffsetDateTime::now_utc(),
+    )
+    .await?
+    {
+        return Err(HttpError::Message("Codul introdus nu este corect".into()));
+    }
+    tx.commit().await?;
+
+    let _ = auth_session
+        .session
+        .remove::<String>(ENROLLMENT_SESSION_KEY)
+        .await;
+
+    RecoveryCodesTemplate { codes }.try_into_response()
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Form(form): Form<CodeForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+
+    let mut tx = state.write_pool.begin().await?;
+    if !accept_app_code(
+        tx.as_mut(),
+        user.id,
+        user.totp_secret.as_deref().unwrap_or_default(),
+        &form.code,
+        OffsetDateTime::now_utc(),
+    )
+    .await?
+    {
+        return Err(HttpError::Message("Codul introdus nu este corect".into()));
+    }
+    let codes = replace_recovery_codes(tx.as_mut(), user.id).await?;
+    tx.commit().await?;
+
+    RecoveryCodesTemplate { codes }.try_into_response()
+}
+
+pub async fn disable(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    Form(form): Form<CodeForm>,
+) -> HttpResult {
+    let user = auth_session.user.ok_or(HttpError::Unauthorized)?;
+    if user.require_two_factor {
+        return Err(HttpError::Message(
+            "Rolul tău necesită autentificarea în doi pași".into(),
+        ));
+    }
+
+    let mut tx = state.write_pool.begin().await?;
+    if !verify_second_factor(tx.as_mut(), &user, &form.code, OffsetDateTime::now_utc()).await? {
+        return Err(HttpError::Message("Codul introdus nu este corect".into()));
+    }
+    disable_two_factor(tx.as_mut(), user.id).await?;
+    tx.commit().await?;
+
+    Ok([("HX-Refresh", "true")].into_response())
+}
@@ -0,0 +1,90 @@
+use super::*;
+use crate::model::role::Permission;
+use crate::utils::queries::get_user;
+use sqlx::SqlitePool;
+use time::Duration;
+use time::macros::datetime;
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, TRUE);
+        insert into role_permissions (role_id, permission) VALUES (100, 'manage_members');
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_accept_app_codes_and_single_use_recovery_codes(
+    pool: SqlitePool,
+) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    let now = datetime!(2024-05-01 12:00:00 UTC);
+
+    let mut tx = pool.begin().await?;
+    let recovery_codes = enable_two_factor(tx.as_mut(), 1000, secret).await?;
+    tx.commit().await?;
+    assert_eq!(recovery_codes.len(), totp::RECOVERY_CODES_COUNT);
+    assert_eq!(
+        count_unused_recovery_codes(&pool, 1000).await?,
+        totp::RECOVERY_CODES_COUNT as i64
+    );
+
+    let user = get_user(&pool, 1000).await?;
+    let mut conn = pool.acquire().await?;
+    let code = totp::code_at(secret, now).unwrap();
+    assert!(verify_second_factor(conn.as_mut(), &user, &code, now).await?);
+    assert!(!verify_second_factor(conn.as_mut(), &user, "123456", now).await?);
+
+    // Each code works only once, and older ones not at all
+    assert!(!verify_second_factor(conn.as_mut(), &user, &code, now).await?);
+    let later = now + Duration::seconds(30);
+    let next_code = totp::code_at(secret, later).unwrap();
+    assert!(verify_second_factor(conn.as_mut(), &user, &next_code, later).await?);
+    assert!(!verify_second_factor(conn.as_mut(), &user, &code, later).await?);
+
+    assert!(verify_second_factor(conn.as_mut(), &user, &recovery_codes[0], now).await?);
+    assert!(!verify_second_factor(conn.as_mut(), &user, &recovery_codes[0], now).await?);
+    assert_eq!(
+        count_unused_recovery_codes(&pool, 1000).await?,
+        totp::RECOVERY_CODES_COUNT as i64 - 1
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_withhold_permissions_until_enrolled(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let user = get_user(&pool, 1000).await?;
+    assert!(user.is_missing_two_factor());
+    assert!(!user.has_admin_panel());
+    assert!(!user.has_permission(Permission::ManageMembers));
+
+    let mut tx = pool.begin().await?;
+    enable_two_factor(tx.as_mut(), 1000, &totp::generate_secret()).await?;
+    tx.commit().await?;
+
+    let user = get_user(&pool, 1000).await?;
+    assert!(!user.is_missing_two_factor());
+    assert!(user.has_permission(Permission::ManageMembers));
+
+    let mut tx = pool.begin().await?;
+    disable_two_factor(tx.as_mut(), 1000).await?;
+    tx.commit().await?;
+
+    let user = get_user(&pool, 1000).await?;
+    assert!(!user.has_two_factor());
+    assert!(!user.has_admin_panel());
+    assert_eq!(count_unused_recovery_codes(&pool, 1000).await?, 0);
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("Invalid email address: `{0}`")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("Failed to build email: `{0}`")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: `{0}`")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("Failed to write email: `{0}`")]
+    Io(#[from] std::io::Error),
+}
+
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub enum Mailer {
+    Smtp {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: Mailbox,
+    },
+    /// Writes every email as an `.eml` file in a directory, for development and tests
+    File {
+        directory: PathBuf,
+        from: Mailbox,
+        emails_written: AtomicU64,
+    },
+}
+
+impl Mailer {
+    /// `MAIL_TRANSPORT` selects between `smtp` and `file`, the default one
+    pub fn from_env() -> Self {
+        let from = std::env::var("MAIL_FROM")
+            .unwrap_or_else(|_| "ACSPA <noreply@acspa.ro>".into())
+            .parse()
+            .expect("Failed to parse sender email address");
+
+        match std::env::var("MAIL_TRANSPORT").as_deref() {
+            Ok("smtp") => {
+                let host = std::env::var("SMTP_HOST").expect("Failed to get SMTP host");
+                let port = std::env::var("SMTP_PORT")
+                    .map(|port| port.parse().expect("Failed to parse SMTP port"))
+                    .unwrap_or(587);
+                let credentials = Credentials::new(
+                    std::env::var("SMTP_USERNAME").expect("Failed to get SMTP username"),
+                    std::env::var("SMTP_PASSWORD").expect("Failed to get SMTP password"),
+                );
+
+                let transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+                    .expect("Failed to create SMTP transport")
+                    .port(port)
+                    .credentials(credentials)
+                    .build();
+
+                Self::Smtp { transport, from }
+            }
+            Ok("file") | Err(_) => Self::file(
+                std::env::var("MAIL_DIR").unwrap_or_else(|_| "mail".into()),
+                from,
+            ),
+            Ok(other) => panic!("Unknown mail transport: {other}"),
+        }
+    }
+
+    pub fn file(directory: impl Into<PathBuf>, from: Mailbox) -> Self {
+        Self::File {
+            directory: directory.into(),
+            from,
+            emails_written: AtomicU64::new(0),
+        }
+    }
+
+    fn build_message(from: &Mailbox, email: &Email) -> Result<Message, MailError> {
+        Ok(Message::builder()
+            .from(from.clone())
+            .to(email.to.parse()?)
+            .subject(&email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())?)
+    }
+
+    pub async fn send(&self, email: &Email) -> Result<(), MailError> {
+        match self {
+            Self::Smtp { transport, from } => {
+                transport.send(Self::build_message(from, email)?).await?;
+            }
+            Self::File {
+                directory,
+                from,
+                emails_written,
+            } => {
+                let message = Self::build_message(from, email)?;
+                let count = emails_written.fetch_add(1, Ordering::Relaxed);
+                let file_name = format!(
+                    "{}_{count}_{}.eml",
+                    OffsetDateTime::now_utc().unix_timestamp_nanos(),
+                    email.to.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+                );
+
+                tokio::fs::create_dir_all(directory).await?;
+                tokio::fs::write(directory.join(file_name), message.formatted()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
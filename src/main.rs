@@ -9,12 +9,24 @@ use tracing_subscriber::EnvFilter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use crate::http::{AppState, http_server, periodic_cleanup_of_waiting_reservations};
+use crate::http::{
+    AppState, http_server, periodic_announcements, periodic_cleanup_of_waiting_reservations,
+    periodic_dunning, periodic_entrance_codes, periodic_key_holder_warnings,
+    periodic_membership_review, periodic_push_delivery,
+};
 
+mod bank_statement;
 mod http;
+mod mail;
 mod model;
+mod oidc;
+mod online_payment;
+mod receipt;
 mod reservation;
+mod smart_lock;
+mod totp;
 mod utils;
+mod web_push;
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
@@ -67,8 +79,14 @@ async fn main() -> std::io::Result<()> {
     let app_state = AppState::new(read_pool, write_pool).await;
 
     task::spawn(periodic_cleanup_of_waiting_reservations(app_state.clone()));
+    task::spawn(periodic_dunning(app_state.clone()));
+    task::spawn(periodic_membership_review(app_state.clone()));
+    task::spawn(periodic_entrance_codes(app_state.clone()));
+    task::spawn(periodic_key_holder_warnings(app_state.clone()));
+    task::spawn(periodic_announcements(app_state.clone()));
+    task::spawn(periodic_push_delivery(app_state.clone()));
 
-    http_server(app_state, session_store).await;
+    http_server(app_state).await;
 
     Ok(())
 }
@@ -0,0 +1,20 @@
+use crate::utils::markdown::render_markdown;
+use time::OffsetDateTime;
+
+/// An announcement as a member sees it
+pub struct Announcement {
+    pub id: i64,
+    pub title: String,
+    /// Markdown
+    pub body: String,
+    pub pinned: bool,
+    pub starts_at: OffsetDateTime,
+    /// Whether the member marked it as read
+    pub read: bool,
+}
+
+impl Announcement {
+    pub fn body_html(&self) -> String {
+        render_markdown(&self.body)
+    }
+}
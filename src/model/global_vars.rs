@@ -1,5 +1,50 @@
+use crate::model::payment::display_amount;
+use time::OffsetDateTime;
+
 pub struct GlobalVars {
     pub in_maintenance: bool,
     pub entrance_code: String,
-    pub homepage_message: String,
+    pub monthly_fee: i64,
+    pub dunning_enabled: bool,
+    pub club_name: String,
+    pub club_details: String,
+    /// Members with more unpaid months are suspended automatically, `None` disables it
+    pub auto_suspend_unpaid_months: Option<i64>,
+    /// Scheduled maintenance, it lasts until switched off when there is no end
+    pub maintenance_starts_at: Option<OffsetDateTime>,
+    pub maintenance_ends_at: Option<OffsetDateTime>,
+    /// Whether members are warned before the scheduled maintenance begins
+    pub maintenance_banner: bool,
+    /// The entrance code is replaced automatically this often, `None` only changes it by hand
+    pub entrance_code_rotation_days: Option<i64>,
+    /// Whether members get a code for each slot they reserved instead of the entrance code
+    pub slot_entrance_codes: bool,
+    /// Hours before a slot without a key-holder when the members are warned, `None` disables it
+    pub key_holder_warning_hours: Option<i64>,
+}
+
+impl GlobalVars {
+    pub fn display_monthly_fee(&self) -> String {
+        display_amount(self.monthly_fee)
+    }
+
+    pub fn is_in_maintenance(&self, now: OffsetDateTime) -> bool {
+        if self.in_maintenance {
+            return true;
+        }
+
+        self.maintenance_starts_at
+            .is_some_and(|starts_at| starts_at <= now)
+            && self.maintenance_ends_at.is_none_or(|ends_at| now < ends_at)
+    }
+
+    /// When the scheduled maintenance begins, if members should be warned about it
+    pub fn upcoming_maintenance(&self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        if !self.maintenance_banner || self.is_in_maintenance(now) {
+            return None;
+        }
+
+        self.maintenance_starts_at
+            .filter(|starts_at| *starts_at > now)
+    }
 }
@@ -1,6 +1,8 @@
+pub mod announcement;
 pub mod day_structure;
 pub mod global_vars;
 pub mod location;
+pub mod notification;
 pub mod payment;
 pub mod restriction;
 pub mod role;
@@ -0,0 +1,44 @@
+use std::str::FromStr;
+use strum::{AsRefStr, EnumString};
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum NotificationKind {
+    /// The reservation moved out of the waiting list
+    Promotion,
+    /// The reservation was cancelled by an admin
+    Cancellation,
+    Payment,
+    Announcement,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub id: i64,
+    pub kind: String,
+    pub message: String,
+    pub link: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub read_at: Option<OffsetDateTime>,
+}
+
+impl Notification {
+    pub fn kind(&self) -> Option<NotificationKind> {
+        NotificationKind::from_str(&self.kind).ok()
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.read_at.is_some()
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self.kind() {
+            Some(NotificationKind::Promotion) => "event_available",
+            Some(NotificationKind::Cancellation) => "event_busy",
+            Some(NotificationKind::Payment) => "payments",
+            Some(NotificationKind::Announcement) => "campaign",
+            None => "notifications",
+        }
+    }
+}
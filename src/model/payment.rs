@@ -1,4 +1,6 @@
 use crate::utils::queries::YearMonth;
+use std::str::FromStr;
+use strum::{AsRefStr, EnumString};
 use time::{Date, OffsetDateTime};
 
 #[derive(Debug, Clone)]
@@ -24,6 +26,7 @@ impl PaymentBreak {
 
 #[derive(Debug, Clone)]
 pub struct PaymentWithAllocations {
+    pub id: i64,
     pub amount: i64,
     pub payment_date: Date,
     pub notes: Option<String>,
@@ -31,14 +34,67 @@ pub struct PaymentWithAllocations {
     pub created_at: OffsetDateTime,
     pub created_by: i64,
     pub created_by_name: String,
+    pub voided_at: Option<OffsetDateTime>,
+    pub void_reason: Option<String>,
+    pub refund_of: Option<i64>,
+    pub receipt_number: Option<i64>,
 }
 
 impl PaymentWithAllocations {
     pub fn display_amount(&self) -> String {
-        if self.amount % 100 == 0 {
-            (self.amount / 100).to_string()
-        } else {
-            format!("{:.02}", self.amount as f64 / 100.0)
+        display_amount(self.amount)
+    }
+
+    pub fn is_voided(&self) -> bool {
+        self.voided_at.is_some()
+    }
+
+    pub fn is_refund(&self) -> bool {
+        self.refund_of.is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum PaymentAction {
+    Created,
+    Edited,
+    Voided,
+    Refunded,
+}
+
+#[derive(Debug, Clone)]
+pub struct PaymentHistoryEntry {
+    pub action: String,
+    pub amount: i64,
+    pub payment_date: Date,
+    pub notes: Option<String>,
+    pub months: String,
+    pub created_at: OffsetDateTime,
+    pub created_by_name: String,
+}
+
+impl PaymentHistoryEntry {
+    pub fn display_amount(&self) -> String {
+        display_amount(self.amount)
+    }
+
+    pub fn display_action(&self) -> &'static str {
+        match PaymentAction::from_str(&self.action) {
+            Ok(PaymentAction::Created) => "Înregistrată",
+            Ok(PaymentAction::Edited) => "Modificată",
+            Ok(PaymentAction::Voided) => "Anulată",
+            Ok(PaymentAction::Refunded) => "Rambursată",
+            Err(_) => "Necunoscut",
         }
     }
 }
+
+/// Formats an amount stored in cents, omitting the decimals when they are zero
+pub fn display_amount(amount: i64) -> String {
+    if amount % 100 == 0 {
+        (amount / 100).to_string()
+    } else {
+        format!("{:.02}", amount as f64 / 100.0)
+    }
+}
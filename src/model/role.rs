@@ -1,3 +1,5 @@
+use strum::{AsRefStr, EnumIter, EnumString};
+
 pub struct UserRole {
     pub id: i64,
     pub name: String,
@@ -6,4 +8,43 @@ pub struct UserRole {
     pub color: Option<String>,
     #[allow(dead_code)]
     pub admin_panel_access: bool,
+    /// How many unpaid months are tolerated before reservations are limited, `None` disables it
+    pub unpaid_grace_months: Option<i64>,
+    /// Weekly reservations allowed once the grace is exceeded, 0 blocks them entirely
+    pub unpaid_reservations: i64,
+    /// Members with admin permissions must enroll an authenticator app to use them
+    pub require_two_factor: bool,
+}
+
+/// What a role is allowed to do in the admin panel, any of them grants access to the panel itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, AsRefStr, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Permission {
+    ManageMembers,
+    ManagePayments,
+    ManageSchedule,
+    ManageRoles,
+    ManageSettings,
+    ViewReports,
+    /// Registering guests at the entrance
+    CheckIn,
+    /// Using the app as a member sees it, to debug their issues
+    ImpersonateMembers,
+}
+
+impl Permission {
+    pub const ADMIN_PANEL: &'static str = "admin_panel";
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Permission::ManageMembers => "Gestionează membrii",
+            Permission::ManagePayments => "Gestionează plățile",
+            Permission::ManageSchedule => "Gestionează programul",
+            Permission::ManageRoles => "Gestionează rolurile",
+            Permission::ManageSettings => "Modifică setările",
+            Permission::ViewReports => "Vede rapoartele",
+            Permission::CheckIn => "Înregistrează invitați",
+            Permission::ImpersonateMembers => "Vede aplicația ca un membru",
+        }
+    }
 }
@@ -1,6 +1,52 @@
+use crate::model::role::Permission;
 use axum_login::AuthUser;
 use serde::Deserialize;
-use time::Date;
+use std::str::FromStr;
+use strum::{AsRefStr, EnumIter, EnumString};
+use time::{Date, OffsetDateTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, AsRefStr, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum MembershipStatus {
+    /// Signed up, but not yet accepted as a member
+    Applicant,
+    Active,
+    /// Temporarily not allowed to book, optionally until a given date
+    Suspended,
+    /// The membership was not renewed
+    Expired,
+    Resigned,
+    Deleted,
+}
+
+impl MembershipStatus {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            MembershipStatus::Applicant => "Aplicant",
+            MembershipStatus::Active => "Activ",
+            MembershipStatus::Suspended => "Suspendat",
+            MembershipStatus::Expired => "Expirat",
+            MembershipStatus::Resigned => "Retras",
+            MembershipStatus::Deleted => "Șters",
+        }
+    }
+
+    pub fn badge_class(&self) -> &'static str {
+        match self {
+            MembershipStatus::Applicant => "badge-info",
+            MembershipStatus::Active => "badge-success",
+            MembershipStatus::Suspended => "badge-warning",
+            MembershipStatus::Expired | MembershipStatus::Resigned | MembershipStatus::Deleted => {
+                "badge-error"
+            }
+        }
+    }
+
+    /// Members who left the club can no longer log in, everyone else can, at least to see why they can't book
+    pub fn can_log_in(&self) -> bool {
+        !matches!(self, MembershipStatus::Resigned | MembershipStatus::Deleted)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct User {
@@ -18,6 +64,18 @@ pub struct User {
     pub received_gift: Option<Date>,
     #[allow(dead_code)]
     pub is_deleted: bool,
+    pub membership_status: String,
+    pub status_reason: Option<String>,
+    pub status_until: Option<Date>,
+    pub erased_at: Option<OffsetDateTime>,
+    /// Base32 encoded secret of the authenticator app, `None` when two-factor authentication is off
+    pub totp_secret: Option<String>,
+    /// Time step of the last accepted authenticator code
+    #[allow(dead_code)]
+    pub totp_last_step: Option<i64>,
+    /// Comma separated permissions of the role
+    pub permissions: String,
+    pub require_two_factor: bool,
 }
 
 impl User {
@@ -36,6 +94,62 @@ impl User {
             birthday: Date::MIN,
             received_gift: None,
             is_deleted: false,
+            membership_status: MembershipStatus::Active.as_ref().to_string(),
+            status_reason: None,
+            status_until: None,
+            erased_at: None,
+            totp_secret: None,
+            totp_last_step: None,
+            permissions: String::new(),
+            require_two_factor: false,
+        }
+    }
+
+    pub fn has_two_factor(&self) -> bool {
+        self.totp_secret.is_some()
+    }
+
+    /// The role requires two-factor authentication, but the member hasn't enrolled yet
+    pub fn is_missing_two_factor(&self) -> bool {
+        self.require_two_factor && !self.has_two_factor()
+    }
+
+    /// The permissions of the role are withheld until the required second factor is enrolled
+    fn granted_permissions(&self) -> &str {
+        if self.is_missing_two_factor() {
+            ""
+        } else {
+            &self.permissions
+        }
+    }
+
+    pub fn permissions(&self) -> impl Iterator<Item = Permission> + '_ {
+        self.granted_permissions()
+            .split(',')
+            .filter_map(|permission| Permission::from_str(permission).ok())
+    }
+
+    pub fn has_permission(&self, permission: impl AsRef<str>) -> bool {
+        self.granted_permissions()
+            .split(',')
+            .any(|granted| granted == permission.as_ref())
+    }
+
+    pub fn has_admin_panel(&self) -> bool {
+        !self.granted_permissions().is_empty()
+    }
+
+    pub fn membership_status(&self) -> MembershipStatus {
+        MembershipStatus::from_str(&self.membership_status).unwrap_or(MembershipStatus::Active)
+    }
+
+    /// A suspension that has run out no longer applies, even before it is lifted automatically
+    pub fn effective_membership_status(&self, today: Date) -> MembershipStatus {
+        match self.membership_status() {
+            MembershipStatus::Suspended if self.status_until.is_some_and(|until| until < today) => {
+                MembershipStatus::Active
+            }
+            status => status,
         }
     }
 }
@@ -57,3 +171,24 @@ pub struct UserCredentials {
     pub email: String,
     pub password: String,
 }
+
+/// An account of an external OpenID Connect provider, as reported by the provider
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+}
+
+#[derive(Clone)]
+pub enum Credentials {
+    Password(UserCredentials),
+    External(ExternalIdentity),
+}
+
+impl From<UserCredentials> for Credentials {
+    fn from(credentials: UserCredentials) -> Self {
+        Self::Password(credentials)
+    }
+}
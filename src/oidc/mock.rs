@@ -0,0 +1,241 @@
+use crate::oidc::{OidcError, pkce_challenge};
+use askama::Template;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::sync::Arc;
+
+/// Local issuer used for development and tests, its pages are served by this app.
+/// Codes and access tokens are signed instead of stored, so they never expire and can be reused.
+pub struct MockIssuer {
+    secret: String,
+}
+
+/// Whoever the developer pretends to be on the mock sign in page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MockUser {
+    pub sub: String,
+    pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MockGrant {
+    user: MockUser,
+    code_challenge: String,
+    redirect_uri: String,
+}
+
+impl MockIssuer {
+    pub const PATH: &str = "/oidc/mock";
+    pub const CLIENT_ID: &str = "mock_client";
+    pub const CLIENT_SECRET: &str = "mock_client_secret";
+
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("MOCK_OIDC_SECRET").unwrap_or_else(|_| "mock_secret".into()))
+    }
+
+    pub fn discovery(issuer: &str) -> Value {
+        json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+            "userinfo_endpoint": format!("{issuer}/userinfo"),
+        })
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, value: &impl Serialize) -> String {
+        let payload =
+            hex::encode(serde_json::to_vec(value).expect("Failed to serialize mock token"));
+        let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+        format!("{payload}.{signature}")
+    }
+
+    fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, OidcError> {
+        let invalid = || OidcError::Provider("Invalid mock token".into());
+
+        let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let payload = hex::decode(payload).map_err(|_| invalid())?;
+        serde_json::from_slice(&payload).map_err(|_| invalid())
+    }
+
+    /// The code the sign in page sends back to the client
+    pub fn issue_code(&self, user: MockUser, code_challenge: &str, redirect_uri: &str) -> String {
+        self.sign(&MockGrant {
+            user,
+            code_challenge: code_challenge.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+        })
+    }
+
+    /// Checks the code like a real issuer would, returning an access token for the userinfo endpoint
+    pub fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        redirect_uri: &str,
+        client_secret: &str,
+    ) -> Result<String, OidcError> {
+        let grant = self.verify::<MockGrant>(code)?;
+
+        if client_secret != Self::CLIENT_SECRET {
+            return Err(OidcError::Provider("Invalid client secret".into()));
+        }
+        if grant.redirect_uri != redirect_uri {
+            return Err(OidcError::Provider("Redirect URI mismatch".into()));
+        }
+        if pkce_challenge(code_verifier) != grant.code_challenge {
+            return Err(OidcError::Provider("Invalid code verifier".into()));
+        }
+
+        Ok(self.sign(&grant.user))
+    }
+
+    pub fn userinfo(&self, access_token: &str) -> Result<MockUser, OidcError> {
+        self.verify(access_token)
+    }
+}
+
+#[derive(Clone)]
+struct MockState {
+    mock: Arc<MockIssuer>,
+    issuer: Arc<str>,
+}
+
+impl MockIssuer {
+    pub fn router(self: Arc<Self>, issuer: String) -> Router {
+        Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/authorize", get(authorize_page))
+            .route("/authorize", post(authorize))
+            .route("/token", post(token))
+            .route("/userinfo", get(userinfo))
+            .with_state(MockState {
+                mock: self,
+                issuer: issuer.into(),
+            })
+    }
+}
+
+async fn discovery(State(state): State<MockState>) -> Json<Value> {
+    Json(MockIssuer::discovery(&state.issuer))
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    redirect_uri: String,
+    state: String,
+    code_challenge: String,
+}
+
+async fn authorize_page(Query(query): Query<AuthorizeQuery>) -> Response {
+    #[derive(Template)]
+    #[template(path = "oidc/mock_authorize_page.html")]
+    struct MockAuthorizeTemplate {
+        query: AuthorizeQuery,
+    }
+
+    match (MockAuthorizeTemplate { query }).render() {
+        Ok(body) => Html(body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthorizeForm {
+    redirect_uri: String,
+    state: String,
+    code_challenge: String,
+    sub: String,
+    email: String,
+    email_verified: Option<String>,
+}
+
+async fn authorize(State(state): State<MockState>, Form(form): Form<AuthorizeForm>) -> Response {
+    let user = MockUser {
+        sub: form.sub,
+        email: form.email,
+        email_verified: form.email_verified.is_some(),
+    };
+    let code = state
+        .mock
+        .issue_code(user, &form.code_challenge, &form.redirect_uri);
+
+    match Url::parse_with_params(
+        &form.redirect_uri,
+        [("code", code.as_str()), ("state", form.state.as_str())],
+    ) {
+        Ok(url) => Redirect::to(url.as_str()).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Invalid redirect URI").into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    code: String,
+    code_verifier: String,
+    redirect_uri: String,
+    client_secret: String,
+}
+
+async fn token(State(state): State<MockState>, Form(form): Form<TokenForm>) -> Response {
+    let access_token = state.mock.exchange_code(
+        &form.code,
+        &form.code_verifier,
+        &form.redirect_uri,
+        &form.client_secret,
+    );
+
+    match access_token {
+        Ok(access_token) => Json(json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "invalid_grant", "error_description": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+async fn userinfo(State(state): State<MockState>, headers: HeaderMap) -> Response {
+    let user = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|access_token| state.mock.userinfo(access_token).ok());
+
+    match user {
+        Some(user) => Json(user).into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
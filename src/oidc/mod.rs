@@ -0,0 +1,230 @@
+mod mock;
+#[cfg(test)]
+mod test;
+
+use crate::model::user::ExternalIdentity;
+use axum::Router;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+pub use mock::MockIssuer;
+
+#[derive(Error, Debug)]
+pub enum OidcError {
+    #[error("OpenID provider request failed: `{0}`")]
+    Request(#[from] reqwest::Error),
+    #[error("OpenID provider error: `{0}`")]
+    Provider(String),
+}
+
+/// The endpoints published by the issuer under `/.well-known/openid-configuration`
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+/// Signs members in with an OpenID Connect provider, like Google, using the authorization code flow with PKCE.
+/// The identity is read from the userinfo endpoint, so there is no ID token signature to verify.
+pub struct OidcProvider {
+    client: reqwest::Client,
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    redirect_url: String,
+    /// Shown on the login button
+    pub display_name: String,
+    discovery: OnceCell<Discovery>,
+    mock: Option<Arc<MockIssuer>>,
+}
+
+impl OidcProvider {
+    pub const CALLBACK_PATH: &str = "/login/oidc/callback";
+
+    pub fn new(
+        issuer: String,
+        client_id: String,
+        client_secret: String,
+        redirect_url: String,
+        display_name: String,
+    ) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            redirect_url,
+            display_name,
+            discovery: OnceCell::new(),
+            mock: None,
+        }
+    }
+
+    /// Enabled when `OIDC_ISSUER` is set, `mock` serves a local issuer from this app for development
+    pub fn from_env(public_url: &str) -> Option<Self> {
+        let issuer = std::env::var("OIDC_ISSUER").ok()?;
+        let redirect_url = format!("{public_url}{}", Self::CALLBACK_PATH);
+
+        if issuer == "mock" {
+            let mut provider = Self::new(
+                format!("{public_url}{}", MockIssuer::PATH),
+                MockIssuer::CLIENT_ID.into(),
+                MockIssuer::CLIENT_SECRET.into(),
+                redirect_url,
+                "Mock".into(),
+            );
+            provider.mock = Some(Arc::new(MockIssuer::from_env()));
+            return Some(provider);
+        }
+
+        Some(Self::new(
+            issuer,
+            std::env::var("OIDC_CLIENT_ID").expect("Failed to get OIDC client id"),
+            std::env::var("OIDC_CLIENT_SECRET").expect("Failed to get OIDC client secret"),
+            redirect_url,
+            std::env::var("OIDC_DISPLAY_NAME").unwrap_or_else(|_| "Google".into()),
+        ))
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    /// The routes of the local issuer, to be served under [`MockIssuer::PATH`], when it's used
+    pub fn mock_router(&self) -> Option<Router> {
+        self.mock
+            .clone()
+            .map(|mock| mock.router(self.issuer.clone()))
+    }
+
+    async fn discovery(&self) -> Result<&Discovery, OidcError> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let discovery = self
+                    .client
+                    .get(format!("{}/.well-known/openid-configuration", self.issuer))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<Discovery>()
+                    .await?;
+
+                if discovery.issuer.trim_end_matches('/') != self.issuer {
+                    return Err(OidcError::Provider(format!(
+                        "Unexpected issuer {}",
+                        discovery.issuer
+                    )));
+                }
+
+                Ok(discovery)
+            })
+            .await
+    }
+
+    /// Where the member is sent to sign in, `state` comes back unchanged in the callback
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        code_verifier: &str,
+    ) -> Result<String, OidcError> {
+        let discovery = self.discovery().await?;
+        let code_challenge = pkce_challenge(code_verifier);
+
+        let url = Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("scope", "openid email"),
+                ("state", state),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::Provider(format!("Invalid authorization endpoint: {e}")))?;
+
+        Ok(url.into())
+    }
+
+    /// Exchanges the code from the callback for the identity of the member
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<ExternalIdentity, OidcError> {
+        let discovery = self.discovery().await?;
+
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        let response = self
+            .client
+            .post(&discovery.token_endpoint)
+            .form(&params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OidcError::Provider(format!("{status}: {body}")));
+        }
+        let token = response.json::<TokenResponse>().await?;
+
+        let user_info = self
+            .client
+            .get(&discovery.userinfo_endpoint)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<UserInfo>()
+            .await?;
+
+        Ok(ExternalIdentity {
+            issuer: self.issuer.clone(),
+            subject: user_info.sub,
+            email: user_info.email.unwrap_or_default(),
+            email_verified: user_info.email_verified,
+        })
+    }
+}
+
+/// The S256 code challenge of RFC 7636
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// A random value for the `state` parameter or the PKCE code verifier
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("The system random number generator is unavailable");
+    hex::encode(bytes)
+}
@@ -0,0 +1,116 @@
+use super::*;
+use crate::oidc::mock::MockUser;
+use tokio::net::TcpListener;
+
+const REDIRECT_URL: &str = "http://localhost/login/oidc/callback";
+
+/// Serves the mock issuer on a random local port
+async fn start_mock_issuer() -> (Arc<MockIssuer>, OidcProvider) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let issuer = format!(
+        "http://{}{}",
+        listener.local_addr().unwrap(),
+        MockIssuer::PATH
+    );
+
+    let mock = Arc::new(MockIssuer::new("test_secret"));
+    let app = Router::new().nest(MockIssuer::PATH, mock.clone().router(issuer.clone()));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let provider = OidcProvider::new(
+        issuer,
+        MockIssuer::CLIENT_ID.into(),
+        MockIssuer::CLIENT_SECRET.into(),
+        REDIRECT_URL.into(),
+        "Mock".into(),
+    );
+    (mock, provider)
+}
+
+fn mock_user() -> MockUser {
+    MockUser {
+        sub: "subject-1".into(),
+        email: "someone@gmail.com".into(),
+        email_verified: true,
+    }
+}
+
+#[test]
+fn should_compute_pkce_challenge() {
+    // The example of RFC 7636, appendix B
+    assert_eq!(
+        pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+    );
+}
+
+#[test]
+fn should_reject_tampered_mock_codes() {
+    let mock = MockIssuer::new("test_secret");
+    let verifier = random_token();
+    let code = mock.issue_code(mock_user(), &pkce_challenge(&verifier), REDIRECT_URL);
+
+    let secret = MockIssuer::CLIENT_SECRET;
+    assert!(
+        mock.exchange_code(&code, &verifier, REDIRECT_URL, secret)
+            .is_ok()
+    );
+    assert!(
+        mock.exchange_code(&code, &random_token(), REDIRECT_URL, secret)
+            .is_err()
+    );
+    assert!(
+        mock.exchange_code(&code, &verifier, "http://evil.com", secret)
+            .is_err()
+    );
+    assert!(
+        mock.exchange_code(&code, &verifier, REDIRECT_URL, "wrong")
+            .is_err()
+    );
+
+    let other = MockIssuer::new("other_secret");
+    assert!(
+        other
+            .exchange_code(&code, &verifier, REDIRECT_URL, secret)
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn should_sign_in_with_mock_issuer() {
+    let (mock, provider) = start_mock_issuer().await;
+    let verifier = random_token();
+
+    let url = provider
+        .authorization_url("some_state", &verifier)
+        .await
+        .unwrap();
+    let url = Url::parse(&url).unwrap();
+    let params: Vec<_> = url.query_pairs().into_owned().collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    assert_eq!(param("state").as_deref(), Some("some_state"));
+    assert_eq!(param("redirect_uri").as_deref(), Some(REDIRECT_URL));
+    let code_challenge = param("code_challenge").unwrap();
+    assert_eq!(code_challenge, pkce_challenge(&verifier));
+
+    // What the mock sign in page does once the developer submits it
+    let code = mock.issue_code(mock_user(), &code_challenge, REDIRECT_URL);
+
+    let identity = provider.exchange_code(&code, &verifier).await.unwrap();
+    assert_eq!(identity.issuer, provider.issuer());
+    assert_eq!(identity.subject, "subject-1");
+    assert_eq!(identity.email, "someone@gmail.com");
+    assert!(identity.email_verified);
+
+    assert!(
+        provider
+            .exchange_code(&code, &random_token())
+            .await
+            .is_err()
+    );
+}
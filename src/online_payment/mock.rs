@@ -0,0 +1,119 @@
+use crate::online_payment::{
+    CheckoutRequest, CheckoutSession, OnlinePaymentError, PaymentProvider, WebhookEvent, sign_hmac,
+    verify_hmac_signature,
+};
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use time::OffsetDateTime;
+
+/// Local provider used for development and tests, the checkout is a page served by this app
+pub struct MockProvider {
+    secret: String,
+    sessions_created: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MockOutcome {
+    Paid,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MockEvent {
+    session_id: String,
+    amount: i64,
+    outcome: MockOutcome,
+}
+
+impl MockProvider {
+    pub const SIGNATURE_HEADER: &str = "x-mock-signature";
+
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            sessions_created: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("MOCK_PAYMENT_SECRET").unwrap_or_else(|_| "mock_secret".into()))
+    }
+
+    /// Builds the signed webhook the mock checkout page delivers
+    pub fn webhook(
+        &self,
+        session_id: &str,
+        amount: i64,
+        outcome: MockOutcome,
+    ) -> (HeaderMap, Vec<u8>) {
+        let body = serde_json::to_vec(&MockEvent {
+            session_id: session_id.to_string(),
+            amount,
+            outcome,
+        })
+        .expect("Failed to serialize mock event");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            Self::SIGNATURE_HEADER,
+            sign_hmac(&self.secret, &body)
+                .parse()
+                .expect("Hex is a valid header value"),
+        );
+
+        (headers, body)
+    }
+}
+
+impl PaymentProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn create_checkout_session(
+        &self,
+        _request: &CheckoutRequest<'_>,
+    ) -> Result<CheckoutSession, OnlinePaymentError> {
+        let count = self.sessions_created.fetch_add(1, Ordering::Relaxed);
+        let id = format!(
+            "mock_{}_{count}",
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        );
+
+        Ok(CheckoutSession {
+            url: format!("/payments/mock/checkout/{id}"),
+            id,
+        })
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        _now: OffsetDateTime,
+    ) -> Result<WebhookEvent, OnlinePaymentError> {
+        let signature = headers
+            .get(Self::SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(OnlinePaymentError::InvalidSignature)?;
+
+        if !verify_hmac_signature(&self.secret, body, signature) {
+            return Err(OnlinePaymentError::InvalidSignature);
+        }
+
+        let event = serde_json::from_slice::<MockEvent>(body)
+            .map_err(|e| OnlinePaymentError::InvalidPayload(e.to_string()))?;
+
+        Ok(match event.outcome {
+            MockOutcome::Paid => WebhookEvent::CheckoutCompleted {
+                session_id: event.session_id,
+                amount: event.amount,
+            },
+            MockOutcome::Cancelled => WebhookEvent::CheckoutCancelled {
+                session_id: event.session_id,
+            },
+        })
+    }
+}
@@ -0,0 +1,142 @@
+mod mock;
+mod stripe;
+#[cfg(test)]
+mod test;
+
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+pub use mock::{MockOutcome, MockProvider};
+pub use stripe::StripeProvider;
+
+#[derive(Error, Debug)]
+pub enum OnlinePaymentError {
+    #[error("Payment provider request failed: `{0}`")]
+    Request(#[from] reqwest::Error),
+    #[error("Payment provider error: `{0}`")]
+    Provider(String),
+    #[error("Invalid webhook signature")]
+    InvalidSignature,
+    #[error("Invalid webhook payload: `{0}`")]
+    InvalidPayload(String),
+}
+
+pub struct CheckoutRequest<'a> {
+    pub user_id: i64,
+    pub email: &'a str,
+    /// Amount in cents
+    pub amount: i64,
+    pub description: &'a str,
+    pub success_url: &'a str,
+    pub cancel_url: &'a str,
+}
+
+pub struct CheckoutSession {
+    pub id: String,
+    pub url: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookEvent {
+    CheckoutCompleted { session_id: String, amount: i64 },
+    CheckoutCancelled { session_id: String },
+    Ignored,
+}
+
+pub trait PaymentProvider {
+    /// Identifies the provider in the `online_payments` table
+    fn name(&self) -> &'static str;
+
+    async fn create_checkout_session(
+        &self,
+        request: &CheckoutRequest<'_>,
+    ) -> Result<CheckoutSession, OnlinePaymentError>;
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: OffsetDateTime,
+    ) -> Result<WebhookEvent, OnlinePaymentError>;
+}
+
+pub enum OnlinePaymentProvider {
+    Stripe(StripeProvider),
+    Mock(MockProvider),
+}
+
+impl OnlinePaymentProvider {
+    /// Online payments are only enabled when `PAYMENT_PROVIDER` is set
+    pub fn from_env() -> Option<Self> {
+        let provider = std::env::var("PAYMENT_PROVIDER").ok()?;
+
+        match provider.as_str() {
+            "stripe" => Some(Self::Stripe(StripeProvider::from_env())),
+            "mock" => Some(Self::Mock(MockProvider::from_env())),
+            other => panic!("Unknown payment provider: {other}"),
+        }
+    }
+
+    pub fn as_mock(&self) -> Option<&MockProvider> {
+        match self {
+            Self::Mock(mock) => Some(mock),
+            _ => None,
+        }
+    }
+}
+
+impl PaymentProvider for OnlinePaymentProvider {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Stripe(stripe) => stripe.name(),
+            Self::Mock(mock) => mock.name(),
+        }
+    }
+
+    async fn create_checkout_session(
+        &self,
+        request: &CheckoutRequest<'_>,
+    ) -> Result<CheckoutSession, OnlinePaymentError> {
+        match self {
+            Self::Stripe(stripe) => stripe.create_checkout_session(request).await,
+            Self::Mock(mock) => mock.create_checkout_session(request).await,
+        }
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: OffsetDateTime,
+    ) -> Result<WebhookEvent, OnlinePaymentError> {
+        match self {
+            Self::Stripe(stripe) => stripe.verify_webhook(headers, body, now),
+            Self::Mock(mock) => mock.verify_webhook(headers, body, now),
+        }
+    }
+}
+
+fn hmac_sha256(secret: &str, payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac
+}
+
+/// Checks a hex encoded HMAC-SHA256 signature in constant time
+fn verify_hmac_signature(secret: &str, payload: &[u8], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+
+    hmac_sha256(secret, payload)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn sign_hmac(secret: &str, payload: &[u8]) -> String {
+    hex::encode(hmac_sha256(secret, payload).finalize().into_bytes())
+}
@@ -0,0 +1,167 @@
+use crate::online_payment::{
+    CheckoutRequest, CheckoutSession, OnlinePaymentError, PaymentProvider, WebhookEvent,
+    verify_hmac_signature,
+};
+use axum::http::HeaderMap;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+/// Talks to the Stripe Checkout API, or any server compatible with it
+pub struct StripeProvider {
+    client: reqwest::Client,
+    api_base: String,
+    secret_key: String,
+    webhook_secret: String,
+}
+
+impl StripeProvider {
+    const SIGNATURE_HEADER: &str = "stripe-signature";
+    /// Webhooks older than this are rejected to prevent replay attacks
+    const SIGNATURE_TOLERANCE_SECONDS: i64 = 5 * 60;
+
+    pub fn new(api_base: String, secret_key: String, webhook_secret: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_base,
+            secret_key,
+            webhook_secret,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("STRIPE_API_BASE").unwrap_or_else(|_| "https://api.stripe.com".into()),
+            std::env::var("STRIPE_SECRET_KEY").expect("Failed to get Stripe secret key"),
+            std::env::var("STRIPE_WEBHOOK_SECRET").expect("Failed to get Stripe webhook secret"),
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct StripeCheckoutSession {
+    id: String,
+    url: Option<String>,
+    amount_total: Option<i64>,
+    payment_status: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StripeEventData {
+    object: StripeCheckoutSession,
+}
+
+#[derive(Deserialize)]
+struct StripeEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    data: StripeEventData,
+}
+
+impl PaymentProvider for StripeProvider {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    async fn create_checkout_session(
+        &self,
+        request: &CheckoutRequest<'_>,
+    ) -> Result<CheckoutSession, OnlinePaymentError> {
+        let user_id = request.user_id.to_string();
+        let amount = request.amount.to_string();
+        let params = [
+            ("mode", "payment"),
+            ("success_url", request.success_url),
+            ("cancel_url", request.cancel_url),
+            ("client_reference_id", user_id.as_str()),
+            ("customer_email", request.email),
+            ("line_items[0][quantity]", "1"),
+            ("line_items[0][price_data][currency]", "ron"),
+            ("line_items[0][price_data][unit_amount]", amount.as_str()),
+            (
+                "line_items[0][price_data][product_data][name]",
+                request.description,
+            ),
+        ];
+
+        let response = self
+            .client
+            .post(format!("{}/v1/checkout/sessions", self.api_base))
+            .bearer_auth(&self.secret_key)
+            .form(&params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(OnlinePaymentError::Provider(format!("{status}: {body}")));
+        }
+
+        let session = response.json::<StripeCheckoutSession>().await?;
+
+        Ok(CheckoutSession {
+            url: session.url.ok_or_else(|| {
+                OnlinePaymentError::Provider("Checkout session has no URL".into())
+            })?,
+            id: session.id,
+        })
+    }
+
+    fn verify_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: OffsetDateTime,
+    ) -> Result<WebhookEvent, OnlinePaymentError> {
+        // Format: "t=1492774577,v1=5257a869...,v0=6ffbb59b..."
+        let signature_header = headers
+            .get(Self::SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(OnlinePaymentError::InvalidSignature)?;
+
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for part in signature_header.split(',') {
+            match part.trim().split_once('=') {
+                Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+                Some(("v1", value)) => signatures.push(value),
+                _ => {}
+            }
+        }
+
+        let timestamp = timestamp.ok_or(OnlinePaymentError::InvalidSignature)?;
+        if (now.unix_timestamp() - timestamp).abs() > Self::SIGNATURE_TOLERANCE_SECONDS {
+            return Err(OnlinePaymentError::InvalidSignature);
+        }
+
+        let mut signed_payload = format!("{timestamp}.").into_bytes();
+        signed_payload.extend_from_slice(body);
+
+        if !signatures.iter().any(|signature| {
+            verify_hmac_signature(&self.webhook_secret, &signed_payload, signature)
+        }) {
+            return Err(OnlinePaymentError::InvalidSignature);
+        }
+
+        let event = serde_json::from_slice::<StripeEvent>(body)
+            .map_err(|e| OnlinePaymentError::InvalidPayload(e.to_string()))?;
+        let session = event.data.object;
+
+        Ok(match event.event_type.as_str() {
+            "checkout.session.completed" | "checkout.session.async_payment_succeeded"
+                if session.payment_status.as_deref() == Some("paid") =>
+            {
+                WebhookEvent::CheckoutCompleted {
+                    session_id: session.id,
+                    amount: session.amount_total.unwrap_or_default(),
+                }
+            }
+            "checkout.session.expired" | "checkout.session.async_payment_failed" => {
+                WebhookEvent::CheckoutCancelled {
+                    session_id: session.id,
+                }
+            }
+            _ => WebhookEvent::Ignored,
+        })
+    }
+}
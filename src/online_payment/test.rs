@@ -0,0 +1,110 @@
+use super::*;
+use time::macros::datetime;
+
+#[test]
+fn mock_webhook_roundtrip() {
+    let provider = MockProvider::new("secret");
+    let (headers, body) = provider.webhook("session_1", 5000, MockOutcome::Paid);
+
+    assert_eq!(
+        provider
+            .verify_webhook(&headers, &body, OffsetDateTime::UNIX_EPOCH)
+            .unwrap(),
+        WebhookEvent::CheckoutCompleted {
+            session_id: "session_1".into(),
+            amount: 5000,
+        }
+    );
+}
+
+#[test]
+fn mock_webhook_rejects_tampered_body() {
+    let provider = MockProvider::new("secret");
+    let (headers, body) = provider.webhook("session_1", 5000, MockOutcome::Paid);
+    let tampered = String::from_utf8(body).unwrap().replace("5000", "1");
+
+    assert!(matches!(
+        provider.verify_webhook(&headers, tampered.as_bytes(), OffsetDateTime::UNIX_EPOCH),
+        Err(OnlinePaymentError::InvalidSignature)
+    ));
+
+    let other_provider = MockProvider::new("other_secret");
+    let (headers, body) = other_provider.webhook("session_1", 5000, MockOutcome::Paid);
+    assert!(matches!(
+        provider.verify_webhook(&headers, &body, OffsetDateTime::UNIX_EPOCH),
+        Err(OnlinePaymentError::InvalidSignature)
+    ));
+}
+
+fn stripe_webhook(body: &str, timestamp: i64) -> HeaderMap {
+    let signature = sign_hmac("whsec_test", format!("{timestamp}.{body}").as_bytes());
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "stripe-signature",
+        format!("t={timestamp},v1={signature}").parse().unwrap(),
+    );
+    headers
+}
+
+fn stripe_provider() -> StripeProvider {
+    StripeProvider::new(
+        "http://localhost".into(),
+        "sk_test".into(),
+        "whsec_test".into(),
+    )
+}
+
+#[test]
+fn stripe_webhook_completed() {
+    let now = datetime!(2024-07-11 16:00:00 UTC);
+    let body = r#"{"type":"checkout.session.completed","data":{"object":{"id":"cs_1","amount_total":10000,"payment_status":"paid"}}}"#;
+    let headers = stripe_webhook(body, now.unix_timestamp());
+
+    assert_eq!(
+        stripe_provider()
+            .verify_webhook(&headers, body.as_bytes(), now)
+            .unwrap(),
+        WebhookEvent::CheckoutCompleted {
+            session_id: "cs_1".into(),
+            amount: 10000,
+        }
+    );
+}
+
+#[test]
+fn stripe_webhook_ignores_unpaid_and_other_events() {
+    let now = datetime!(2024-07-11 16:00:00 UTC);
+    let provider = stripe_provider();
+
+    // Delayed payment methods complete the checkout before the money arrives
+    let body = r#"{"type":"checkout.session.completed","data":{"object":{"id":"cs_1","amount_total":10000,"payment_status":"unpaid"}}}"#;
+    let headers = stripe_webhook(body, now.unix_timestamp());
+    assert_eq!(
+        provider
+            .verify_webhook(&headers, body.as_bytes(), now)
+            .unwrap(),
+        WebhookEvent::Ignored
+    );
+
+    let body = r#"{"type":"customer.created","data":{"object":{"id":"cus_1"}}}"#;
+    let headers = stripe_webhook(body, now.unix_timestamp());
+    assert_eq!(
+        provider
+            .verify_webhook(&headers, body.as_bytes(), now)
+            .unwrap(),
+        WebhookEvent::Ignored
+    );
+}
+
+#[test]
+fn stripe_webhook_rejects_replays() {
+    let now = datetime!(2024-07-11 16:00:00 UTC);
+    let body = r#"{"type":"checkout.session.expired","data":{"object":{"id":"cs_1"}}}"#;
+    let headers = stripe_webhook(body, now.unix_timestamp() - 10 * 60);
+
+    assert!(matches!(
+        stripe_provider().verify_webhook(&headers, body.as_bytes(), now),
+        Err(OnlinePaymentError::InvalidSignature)
+    ));
+}
@@ -0,0 +1,268 @@
+mod pdf;
+#[cfg(test)]
+mod test;
+
+use crate::model::payment::display_amount;
+use crate::utils::date_formats;
+use crate::utils::queries::YearMonth;
+use itertools::Itertools;
+use pdf::{Font, PAGE_HEIGHT, PAGE_WIDTH, PdfPage};
+use sqlx::{SqliteConnection, SqliteExecutor, query_as, query_scalar};
+use time::{Date, Month, OffsetDateTime};
+
+const MARGIN: f32 = 50.0;
+const MONTHS_PER_LINE: usize = 6;
+
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub number: i64,
+    pub payment_id: i64,
+    pub club_name: String,
+    pub club_details: String,
+    pub member_name: String,
+    pub member_email: String,
+    pub amount: i64,
+    pub payment_date: Date,
+    pub months: String,
+    pub issued_by_name: String,
+    pub issued_at: OffsetDateTime,
+    /// The receipt replaced by this one after an edit, or stornoed by it for a refund
+    pub corrects: Option<i64>,
+}
+
+impl Receipt {
+    pub fn display_number(&self) -> String {
+        format!("{:06}", self.number)
+    }
+
+    pub fn display_amount(&self) -> String {
+        display_amount(self.amount)
+    }
+
+    pub fn is_storno(&self) -> bool {
+        self.amount < 0
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("chitanta_{}.pdf", self.display_number())
+    }
+
+    fn display_months(&self) -> Vec<String> {
+        self.months
+            .split(',')
+            .filter_map(|month_year| {
+                let (month, year) = month_year.split_once('-')?;
+                let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+                Some(format!("{} {year}", date_formats::month_as_str(&month)))
+            })
+            .collect()
+    }
+
+    /// Only the data saved when the receipt was issued is used, so the output never changes
+    pub fn render_pdf(&self) -> Vec<u8> {
+        let mut page = PdfPage::default();
+        let right_column = PAGE_WIDTH - MARGIN - 160.0;
+        let mut y = PAGE_HEIGHT - MARGIN - 20.0;
+
+        page.text(MARGIN, y, Font::Bold, 20.0, &self.club_name);
+        let title = if self.is_storno() {
+            "CHITANȚĂ STORNO"
+        } else {
+            "CHITANȚĂ"
+        };
+        page.text(right_column, y, Font::Bold, 16.0, title);
+        page.text(
+            right_column,
+            y - 18.0,
+            Font::Regular,
+            11.0,
+            &format!("Nr. {}", self.display_number()),
+        );
+        page.text(
+            right_column,
+            y - 33.0,
+            Font::Regular,
+            11.0,
+            &format!("Data: {}", date_formats::as_readable(&self.payment_date)),
+        );
+        if let Some(corrects) = self.corrects {
+            let action = if self.is_storno() {
+                "Stornează"
+            } else {
+                "Înlocuiește"
+            };
+            page.text(
+                right_column,
+                y - 48.0,
+                Font::Regular,
+                10.0,
+                &format!("{action} nr. {corrects:06}"),
+            );
+        }
+
+        for line in self
+            .club_details
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+        {
+            y -= 14.0;
+            page.text(MARGIN, y, Font::Regular, 10.0, line.trim());
+        }
+
+        y = y.min(PAGE_HEIGHT - MARGIN - 60.0) - 20.0;
+        page.line(MARGIN, y, PAGE_WIDTH - MARGIN, y);
+
+        y -= 35.0;
+        let counterpart = if self.is_storno() {
+            "Am restituit lui:"
+        } else {
+            "Am primit de la:"
+        };
+        page.text(MARGIN, y, Font::Regular, 12.0, counterpart);
+        page.text(MARGIN + 110.0, y, Font::Bold, 12.0, &self.member_name);
+        y -= 16.0;
+        page.text(MARGIN + 110.0, y, Font::Regular, 10.0, &self.member_email);
+
+        y -= 30.0;
+        page.text(MARGIN, y, Font::Regular, 12.0, "Suma de:");
+        page.text(
+            MARGIN + 110.0,
+            y,
+            Font::Bold,
+            12.0,
+            &format!("{} RON", self.display_amount()),
+        );
+
+        y -= 30.0;
+        page.text(MARGIN, y, Font::Regular, 12.0, "Reprezentând:");
+        let months = self.display_months();
+        if self.is_storno() {
+            page.text(
+                MARGIN + 110.0,
+                y,
+                Font::Regular,
+                12.0,
+                "Rambursare cotizație",
+            );
+        } else if months.is_empty() {
+            page.text(MARGIN + 110.0, y, Font::Regular, 12.0, "Cotizație");
+        } else {
+            page.text(
+                MARGIN + 110.0,
+                y,
+                Font::Regular,
+                12.0,
+                "Cotizație pentru lunile:",
+            );
+            for chunk in &months.iter().chunks(MONTHS_PER_LINE) {
+                y -= 16.0;
+                page.text(
+                    MARGIN + 110.0,
+                    y,
+                    Font::Regular,
+                    10.0,
+                    &chunk.into_iter().join(", "),
+                );
+            }
+        }
+
+        y -= 50.0;
+        page.text(
+            MARGIN,
+            y,
+            Font::Regular,
+            11.0,
+            &format!("Emisă de: {}", self.issued_by_name),
+        );
+        page.text(
+            right_column,
+            y,
+            Font::Regular,
+            11.0,
+            &format!(
+                "Data emiterii: {}",
+                date_formats::as_readable(&self.issued_at.date())
+            ),
+        );
+
+        y -= 25.0;
+        page.line(MARGIN, y, PAGE_WIDTH - MARGIN, y);
+        page.text(
+            MARGIN,
+            y - 15.0,
+            Font::Regular,
+            8.0,
+            "Document generat electronic, valabil fără semnătură și ștampilă.",
+        );
+
+        page.render()
+    }
+}
+
+/// The months as they are stored on a receipt
+pub fn receipt_months(allocations: &[YearMonth]) -> String {
+    allocations
+        .iter()
+        .sorted_by_key(|allocation| (allocation.year, allocation.month as u8))
+        .map(|allocation| format!("{}-{}", allocation.month as u8, allocation.year))
+        .join(",")
+}
+
+/// Assigns the next receipt number to a payment, copying the details that are printed on it
+pub async fn issue_receipt(
+    tx: &mut SqliteConnection,
+    payment_id: i64,
+    allocations: &[YearMonth],
+    issued_by: i64,
+    corrects: Option<i64>,
+) -> sqlx::Result<i64> {
+    let months = receipt_months(allocations);
+
+    query_scalar!(
+        "insert into receipts (payment_id, club_name, club_details, member_name, member_email, amount, payment_date, months, issued_by_name, corrects)
+         select p.id, g.club_name, g.club_details, m.name, m.email, p.amount, p.payment_date, $2, a.name, $4
+         from payments p
+            join users m on m.id = p.user_id
+            join users a on a.id = $3
+            cross join global_vars g
+         where p.id = $1
+         returning number",
+        payment_id,
+        months,
+        issued_by,
+        corrects
+    )
+    .fetch_one(&mut *tx)
+    .await
+}
+
+/// The latest receipt of a payment, which replaced any previous ones
+pub async fn get_receipt(
+    executor: impl SqliteExecutor<'_>,
+    payment_id: i64,
+) -> sqlx::Result<Option<Receipt>> {
+    query_as!(
+        Receipt,
+        "select * from receipts where payment_id = $1 order by number desc limit 1",
+        payment_id
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+/// Only the receipts still in effect, without the replaced ones
+pub async fn get_user_receipts(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<Vec<Receipt>> {
+    query_as!(
+        Receipt,
+        "select r.* from receipts r join payments p on p.id = r.payment_id
+         where p.user_id = $1 and p.voided_at is null
+            and not exists (select 1 from receipts c where c.corrects = r.number and c.payment_id = r.payment_id)
+         order by r.number desc",
+        user_id
+    )
+    .fetch_all(executor)
+    .await
+}
@@ -0,0 +1,121 @@
+use std::fmt::Write as _;
+
+/// A4 in points
+pub const PAGE_WIDTH: f32 = 595.0;
+pub const PAGE_HEIGHT: f32 = 842.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource_name(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// A single page PDF document, drawn with the standard Helvetica fonts.
+/// Nothing time or environment dependent is written, so the same page always produces the same bytes.
+#[derive(Default)]
+pub struct PdfPage {
+    content: Vec<u8>,
+}
+
+impl PdfPage {
+    pub fn text(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        let mut operators = String::new();
+        let _ = write!(
+            operators,
+            "BT /{} {size} Tf {x} {y} Td (",
+            font.resource_name()
+        );
+        self.content.extend_from_slice(operators.as_bytes());
+        self.content.extend(encode_text(text));
+        self.content.extend_from_slice(b") Tj ET\n");
+    }
+
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        let operators = format!("0.5 w {x1} {y1} m {x2} {y2} l S\n");
+        self.content.extend_from_slice(operators.as_bytes());
+    }
+
+    pub fn render(&self) -> Vec<u8> {
+        let font = |name: &str| {
+            format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{name} /Encoding /WinAnsiEncoding >>"
+            )
+            .into_bytes()
+        };
+
+        let mut content_object =
+            format!("<< /Length {} >>\nstream\n", self.content.len()).into_bytes();
+        content_object.extend_from_slice(&self.content);
+        content_object.extend_from_slice(b"endstream");
+
+        let objects = [
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                 /Resources << /Font << /F1 4 0 R /F2 5 0 R >> >> /Contents 6 0 R >>"
+            )
+            .into_bytes(),
+            font("Helvetica"),
+            font("Helvetica-Bold"),
+            content_object,
+        ];
+
+        let mut output = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(output.len());
+            output.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            output.extend_from_slice(object);
+            output.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = output.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(xref, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            xref,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        );
+        output.extend_from_slice(xref.as_bytes());
+
+        output
+    }
+}
+
+/// Encodes text for a PDF string in WinAnsi. The Romanian letters missing from it
+/// (ă, ș, ț) lose their diacritics, anything else that can't be represented becomes `?`.
+fn encode_text(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let c = match c {
+            'ă' => 'a',
+            'Ă' => 'A',
+            'ș' | 'ş' => 's',
+            'Ș' | 'Ş' => 'S',
+            'ț' | 'ţ' => 't',
+            'Ț' | 'Ţ' => 'T',
+            '–' | '—' => '-',
+            c => c,
+        };
+
+        match c {
+            '(' | ')' | '\\' => bytes.extend_from_slice(&[b'\\', c as u8]),
+            ' '..='~' | '\u{A0}'..='\u{FF}' => bytes.push(c as u32 as u8),
+            _ => bytes.push(b'?'),
+        }
+    }
+    bytes
+}
@@ -0,0 +1,99 @@
+use super::*;
+use sqlx::{SqlitePool, query, query_scalar};
+
+async fn setup(pool: &SqlitePool) -> sqlx::Result<()> {
+    query!(
+        r#"
+        update global_vars set club_name = 'ACS Test', club_details = 'Str. Exemplu 1
+CIF 123';
+        insert into user_roles VALUES (100, 'Test Role', 1, 1, null, FALSE, null, 0, FALSE);
+        insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
+        VALUES (1000, 'test1@test.com', 'Ștefan Țurcanu (junior)', '', 100, FALSE, '2000-01-01', '2024-01-01');
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn record_payment(pool: &SqlitePool, allocations: &[YearMonth]) -> i64 {
+    let mut tx = pool.begin().await.unwrap();
+    let payment_id = query_scalar!(
+        "insert into payments (user_id, amount, payment_date, created_by)
+         values (1000, 15050, '2024-03-05', 0) returning id"
+    )
+    .fetch_one(tx.as_mut())
+    .await
+    .unwrap();
+    issue_receipt(tx.as_mut(), payment_id, allocations, 0, None)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+    payment_id
+}
+
+#[sqlx::test]
+async fn should_number_receipts_sequentially(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+
+    let first = record_payment(
+        &pool,
+        &[
+            YearMonth::new(2024, Month::March),
+            YearMonth::new(2024, Month::February),
+        ],
+    )
+    .await;
+    let second = record_payment(&pool, &[YearMonth::new(2024, Month::April)]).await;
+
+    let first = get_receipt(&pool, first).await?.unwrap();
+    let second = get_receipt(&pool, second).await?.unwrap();
+
+    assert_eq!(second.number, first.number + 1);
+    assert_eq!(first.months, "2-2024,3-2024");
+    assert_eq!(first.club_name, "ACS Test");
+    assert_eq!(first.member_name, "Ștefan Țurcanu (junior)");
+    assert_eq!(
+        get_user_receipts(&pool, 1000)
+            .await?
+            .iter()
+            .map(|r| r.number)
+            .collect::<Vec<_>>(),
+        vec![second.number, first.number]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn should_regenerate_identical_receipts(pool: SqlitePool) -> sqlx::Result<()> {
+    setup(&pool).await?;
+    let payment_id = record_payment(&pool, &[YearMonth::new(2024, Month::March)]).await;
+
+    let original = get_receipt(&pool, payment_id).await?.unwrap().render_pdf();
+    assert!(original.starts_with(b"%PDF-1.4"));
+    assert!(original.ends_with(b"%%EOF\n"));
+    // Diacritics missing from the standard fonts are dropped and parentheses escaped
+    let text = String::from_utf8_lossy(&original);
+    assert!(text.contains("(Stefan Turcanu \\(junior\\))"));
+    assert!(text.contains("(Martie 2024)"));
+    assert!(text.contains("(150.50 RON)"));
+
+    // Later changes to the member, payment or club details don't affect it
+    query!(
+        r#"
+        update users set name = 'Alt Nume' where id = 1000;
+        update payments set amount = 100 where id = $1;
+        update global_vars set club_name = 'Alt Club';
+        "#,
+        payment_id
+    )
+    .execute(&pool)
+    .await?;
+
+    let regenerated = get_receipt(&pool, payment_id).await?.unwrap().render_pdf();
+    assert_eq!(original, regenerated);
+
+    Ok(())
+}
@@ -1,7 +1,20 @@
 use crate::model::location::Location;
-use sqlx::{SqliteTransaction, query, query_scalar};
+use sqlx::{SqliteTransaction, query, query_as, query_scalar};
 use time::Date;
 
+/// A reservation moved out of the waiting list by a cancellation
+#[derive(Debug, PartialEq)]
+pub struct PromotedReservation {
+    pub user_id: i64,
+    pub created_for: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cancellation {
+    pub promoted: Option<PromotedReservation>,
+}
+
+/// Returns `None` when there was no such reservation to cancel
 pub async fn cancel_reservation(
     mut tx: SqliteTransaction<'_>,
     location: &Location,
@@ -9,7 +22,7 @@ pub async fn cancel_reservation(
     hour: u8,
     user_id: i64,
     created_for: Option<&str>,
-) -> sqlx::Result<bool> {
+) -> sqlx::Result<Option<Cancellation>> {
     let rows = if let Some(created_for) = created_for {
         query!("delete from reservations where date = $1 and hour = $2 and user_id = $3 and location = $4 and created_for = $5",
             date, hour, user_id, location.id, created_for)
@@ -24,7 +37,7 @@ pub async fn cancel_reservation(
     }.rows_affected();
 
     if rows != 1 {
-        return Ok(false);
+        return Ok(None);
     }
 
     let count = query_scalar!(
@@ -37,18 +50,22 @@ pub async fn cancel_reservation(
     .fetch_one(tx.as_mut())
     .await?;
 
-    if count < location.slot_capacity {
-        query!(
+    let promoted = if count < location.slot_capacity {
+        query_as!(
+            PromotedReservation,
             "update reservations set in_waiting = false where rowid =
                 (select rowid from reservations where
                     date = $1 and hour = $2 and location = $3 and cancelled = false and in_waiting = true
-                    order by as_guest, created_at limit 1)",
+                    order by as_guest, created_at limit 1)
+             returning user_id, created_for",
             date, hour, location.id)
-            .execute(tx.as_mut())
-            .await?;
-    }
+            .fetch_optional(tx.as_mut())
+            .await?
+    } else {
+        None
+    };
 
     tx.commit().await?;
 
-    Ok(true)
+    Ok(Some(Cancellation { promoted }))
 }
@@ -1,10 +1,10 @@
 use crate::model::day_structure::DayStructure;
 use crate::model::location::Location;
 use crate::model::role::UserRole;
-use crate::model::user::User;
+use crate::model::user::{MembershipStatus, User};
 use crate::reservation::{Referral, ReservationError, ReservationResult, ReservationSuccess};
 use crate::utils::queries::{
-    get_alt_day_structure_for_day, get_reservations_count_for_slot,
+    get_alt_day_structure_for_day, get_reservations_count_for_slot, get_unpaid_months,
     get_user_weeks_reservations_count,
 };
 use sqlx::{SqliteConnection, query, query_as};
@@ -92,6 +92,38 @@ async fn check_restriction(
     Ok(())
 }
 
+/// Only active members can book, for themselves or for others
+fn check_membership_status(user: &User, today: Date) -> ReservationResult<()> {
+    match user.effective_membership_status(today) {
+        MembershipStatus::Active => Ok(()),
+        status => Err(ReservationError::InactiveMembership(status)),
+    }
+}
+
+/// Members who owe more months than their role tolerates can only make a limited number of
+/// reservations each week. Reservations made on behalf of others are not affected.
+async fn check_payment_status(
+    tx: &mut SqliteConnection,
+    role: &UserRole,
+    user: &User,
+    today: Date,
+    weekly_reservations: i64,
+) -> ReservationResult<()> {
+    let Some(grace_months) = role.unpaid_grace_months else {
+        return Ok(());
+    };
+    if user.admin_panel_access {
+        return Ok(());
+    }
+
+    let unpaid_months = get_unpaid_months(&mut *tx, user, today).await?.len();
+    if unpaid_months as i64 > grace_months && weekly_reservations >= role.unpaid_reservations {
+        return Err(ReservationError::UnpaidMembership { unpaid_months });
+    }
+
+    Ok(())
+}
+
 pub async fn is_reservation_possible(
     tx: &mut SqliteConnection,
     location: &Location,
@@ -106,6 +138,7 @@ pub async fn is_reservation_possible(
         .unwrap_or_else(|| location.day_structure());
 
     check_parameters_validity(now, &day_structure, selected_date, selected_hour)?;
+    check_membership_status(user, now.date())?;
 
     check_reservation_already_exists(
         &mut *tx,
@@ -138,6 +171,17 @@ pub async fn is_reservation_possible(
     let user_reservations_count =
         get_user_weeks_reservations_count(&mut *tx, user, selected_date).await?;
 
+    if referral.is_none() {
+        check_payment_status(
+            &mut *tx,
+            &role,
+            user,
+            now.date(),
+            user_reservations_count.member + user_reservations_count.guest,
+        )
+        .await?;
+    }
+
     // Attempt to create a normal reservation
     if (referral.is_none() && user_reservations_count.member < role.reservations)
         || referral.is_some_and(|r| r.is_special)
@@ -1,3 +1,4 @@
+use crate::model::user::MembershipStatus;
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq)]
@@ -13,6 +14,8 @@ pub enum ReservationError {
     Restriction(String),
     DatabaseError(String),
     NoMoreReservations,
+    UnpaidMembership { unpaid_months: usize },
+    InactiveMembership(MembershipStatus),
     Other(&'static str),
 }
 
@@ -43,6 +46,24 @@ impl Display for ReservationError {
             ReservationError::NoMoreReservations => {
                 write!(f, "Ți-ai epuizat rezervările pe săptămâna aceasta")
             }
+            ReservationError::UnpaidMembership { unpaid_months } => write!(
+                f,
+                "Ai {} luni de cotizație neachitate, plătește cotizația pentru a putea face rezervări",
+                unpaid_months
+            ),
+            ReservationError::InactiveMembership(status) => write!(
+                f,
+                "{}",
+                match status {
+                    MembershipStatus::Applicant =>
+                        "Cererea ta de înscriere nu a fost încă aprobată",
+                    MembershipStatus::Suspended =>
+                        "Contul tău este suspendat, contactează un Fondator",
+                    MembershipStatus::Expired =>
+                        "Calitatea ta de membru a expirat, contactează un Fondator",
+                    _ => "Contul tău nu este activ",
+                }
+            ),
             ReservationError::Other(message) => write!(f, "{}", message),
         }
     }
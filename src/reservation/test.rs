@@ -1,4 +1,5 @@
 use super::*;
+use super::cancel::{Cancellation, PromotedReservation};
 use sqlx::{SqlitePool, query, query_as};
 use time::macros::{date, datetime};
 
@@ -9,7 +10,7 @@ async fn setup(
 ) -> sqlx::Result<(Location, User, User, User)> {
     query!(
         r#"
-        insert into user_roles VALUES (100, 'Test Role', $1, $2, null, FALSE);
+        insert into user_roles VALUES (100, 'Test Role', $1, $2, null, FALSE, null, 0, FALSE);
         insert into users (id, email, name, password_hash, role_id, has_key, birthday, member_since)
         VALUES (1000, 'test1@test.com', 'Test 1', '', 100, FALSE, '2000-01-01', '2000-01-01'),
         (2000, 'test2@test.com', 'Test 2', '', 100, FALSE, '2000-01-01', '2000-01-01'),
@@ -355,7 +356,13 @@ mod queue_logic {
         // 4. User 1 cancels
         let res =
             cancel_reservation(pool.begin().await?, &location, date, 18, user_1.id, None).await?;
-        assert!(res);
+        assert_eq!(
+            res.and_then(|cancellation| cancellation.promoted),
+            Some(PromotedReservation {
+                user_id: user_2.id,
+                created_for: None
+            })
+        );
 
         // 5. User 2 (Oldest Waiter) should be promoted
         let u2 = query!(
@@ -446,7 +453,11 @@ mod cancellation {
 
         // 1. Cancel
         let tx = pool.begin().await?;
-        assert!(cancel_reservation(tx, &location, date, 18, user.id, None).await?);
+        assert!(
+            cancel_reservation(tx, &location, date, 18, user.id, None)
+                .await?
+                .is_some()
+        );
 
         // 2. Verify Cancelled in DB
         let saved = query!(
@@ -496,7 +507,7 @@ mod cancellation {
         // Cancel
         let tx = pool.begin().await?;
         let res = cancel_reservation(tx, &location, date, 18, user.id, Some(name)).await?;
-        assert!(res);
+        assert_eq!(res, Some(Cancellation { promoted: None }));
 
         // Verify Hard Delete (Count should be 0)
         let count = query!(
@@ -531,7 +542,11 @@ mod cancellation {
         );
 
         let tx = pool.begin().await?;
-        assert!(cancel_reservation(tx, &location, date, 18, user.id, None).await?);
+        assert!(
+            cancel_reservation(tx, &location, date, 18, user.id, None)
+                .await?
+                .is_some()
+        );
 
         // Verify it's cancelled
         let res_18 = query!("select cancelled from reservations where hour = 18")
@@ -587,7 +602,7 @@ mod cancellation {
         // 4. User 1 cancels
         let res =
             cancel_reservation(pool.begin().await?, &location, date, 18, user_1.id, None).await?;
-        assert!(res);
+        assert!(res.is_some());
 
         // 5. Verify User 3 (Member) got promoted, NOT User 2 (Guest)
         let user_3_status = query!(
@@ -680,7 +695,7 @@ mod constraints_and_schedule {
         let date = date!(2024 - 07 - 11);
 
         // Create Holiday with custom capacity of 2 and different start hour (10:00)
-        query!("insert into schedule_overrides (date, type, slots_start_hour, slot_duration, slots_per_day, slot_capacity) values ('2024-07-11', 'holiday', 10, 3, 4, 2)")
+        query!("insert into alternative_days (date, type, slots_start_hour, slot_duration, slots_per_day, slot_capacity) values ('2024-07-11', 'holiday', 10, 3, 4, 2)")
             .execute(&pool).await?;
 
         // 1. Standard hour (18:00) should now be invalid
@@ -730,3 +745,226 @@ mod constraints_and_schedule {
         Ok(())
     }
 }
+
+mod payment_status {
+    use super::*;
+
+    async fn set_policy(
+        pool: &SqlitePool,
+        grace_months: Option<i64>,
+        unpaid_reservations: i64,
+    ) -> sqlx::Result<()> {
+        query!(
+            "update user_roles set unpaid_grace_months = $1, unpaid_reservations = $2 where id = 100",
+            grace_months,
+            unpaid_reservations
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn pay_months(pool: &SqlitePool, user: &User, months: &[i64]) -> sqlx::Result<i64> {
+        let payment_id = query!(
+            "insert into payments (user_id, amount, payment_date, created_by) values ($1, 10000, '2024-07-01', 0)",
+            user.id
+        )
+        .execute(pool)
+        .await?
+        .last_insert_rowid();
+
+        for month in months {
+            query!(
+                "insert into payment_allocations (payment_id, year, month) values ($1, 2024, $2)",
+                payment_id,
+                month
+            )
+            .execute(pool)
+            .await?;
+        }
+
+        Ok(payment_id)
+    }
+
+    #[sqlx::test]
+    async fn should_block_members_behind_on_payments(pool: SqlitePool) -> sqlx::Result<()> {
+        let (location, user, _, _) = setup(&pool, 1, 1).await?;
+        set_policy(&pool, Some(1), 0).await?;
+        // May, June and July are unpaid
+        let user = User {
+            member_since: date!(2024 - 05 - 01),
+            ..user
+        };
+        let now = datetime!(2024-07-11 10:00:00 +00:00:00);
+        let date = date!(2024 - 07 - 11);
+
+        assert_eq!(
+            create_reservation(&pool, &location, now, &user, date, 18, None).await,
+            Err(ReservationError::UnpaidMembership { unpaid_months: 3 })
+        );
+
+        // A voided payment does not count, voiding frees its months
+        let payment_id = pay_months(&pool, &user, &[5, 6]).await?;
+        query!(
+            "update payments set voided_at = CURRENT_TIMESTAMP where id = $1",
+            payment_id
+        )
+        .execute(&pool)
+        .await?;
+        query!(
+            "delete from payment_allocations where payment_id = $1",
+            payment_id
+        )
+        .execute(&pool)
+        .await?;
+        assert_eq!(
+            create_reservation(&pool, &location, now, &user, date, 18, None).await,
+            Err(ReservationError::UnpaidMembership { unpaid_months: 3 })
+        );
+
+        // Only the current month is left, which is within the grace
+        pay_months(&pool, &user, &[5]).await?;
+        query!(
+            "insert into payment_breaks (user_id, start_date, end_date, created_by) values ($1, '2024-06-01', '2024-06-01', 0)",
+            user.id
+        )
+        .execute(&pool)
+        .await?;
+        assert_eq!(
+            create_reservation(&pool, &location, now, &user, date, 18, None).await,
+            Ok(ReservationSuccess::Reservation {
+                deletes_guest: false
+            })
+        );
+
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn should_limit_weekly_reservations(pool: SqlitePool) -> sqlx::Result<()> {
+        let (location, user, _, _) = setup(&pool, 2, 1).await?;
+        set_policy(&pool, Some(0), 1).await?;
+        let user = User {
+            member_since: date!(2024 - 07 - 01),
+            ..user
+        };
+        let now = datetime!(2024-07-11 10:00:00 +00:00:00);
+
+        assert_eq!(
+            create_reservation(
+                &pool,
+                &location,
+                now,
+                &user,
+                date!(2024 - 07 - 11),
+                18,
+                None
+            )
+            .await,
+            Ok(ReservationSuccess::Reservation {
+                deletes_guest: false
+            })
+        );
+        assert_eq!(
+            create_reservation(
+                &pool,
+                &location,
+                now,
+                &user,
+                date!(2024 - 07 - 12),
+                18,
+                None
+            )
+            .await,
+            Err(ReservationError::UnpaidMembership { unpaid_months: 1 })
+        );
+
+        // Reservations made on behalf of someone else are not limited
+        let referral = Referral {
+            is_special: false,
+            created_for: "Guest Name",
+        };
+        assert_eq!(
+            create_reservation(
+                &pool,
+                &location,
+                now,
+                &user,
+                date!(2024 - 07 - 12),
+                18,
+                Some(referral)
+            )
+            .await,
+            Ok(ReservationSuccess::Guest)
+        );
+
+        // Without a policy the role limits apply as usual
+        set_policy(&pool, None, 0).await?;
+        assert_eq!(
+            create_reservation(
+                &pool,
+                &location,
+                now,
+                &user,
+                date!(2024 - 07 - 12),
+                20,
+                None
+            )
+            .await,
+            Ok(ReservationSuccess::Reservation {
+                deletes_guest: false
+            })
+        );
+
+        Ok(())
+    }
+}
+
+mod membership_status {
+    use super::*;
+    use crate::model::user::MembershipStatus;
+
+    #[sqlx::test]
+    async fn should_only_allow_active_members(pool: SqlitePool) -> sqlx::Result<()> {
+        let (location, user, _, _) = setup(&pool, 1, 1).await?;
+        let now = datetime!(2024-07-11 10:00:00 +00:00:00);
+        let date = date!(2024 - 07 - 11);
+
+        let applicant = User {
+            membership_status: MembershipStatus::Applicant.as_ref().to_string(),
+            ..user.clone()
+        };
+        assert_eq!(
+            create_reservation(&pool, &location, now, &applicant, date, 18, None).await,
+            Err(ReservationError::InactiveMembership(
+                MembershipStatus::Applicant
+            ))
+        );
+
+        let suspended = User {
+            membership_status: MembershipStatus::Suspended.as_ref().to_string(),
+            status_until: Some(date!(2024 - 07 - 15)),
+            ..user.clone()
+        };
+        assert_eq!(
+            create_reservation(&pool, &location, now, &suspended, date, 18, None).await,
+            Err(ReservationError::InactiveMembership(
+                MembershipStatus::Suspended
+            ))
+        );
+
+        // Once the suspension runs out, booking works even before it is lifted
+        let suspension_ended = User {
+            status_until: Some(date!(2024 - 07 - 10)),
+            ..suspended
+        };
+        assert_eq!(
+            create_reservation(&pool, &location, now, &suspension_ended, date, 18, None).await,
+            Ok(ReservationSuccess::Reservation {
+                deletes_guest: false
+            })
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use crate::smart_lock::{LockCode, LockCodesPayload, SmartLock, SmartLockError};
+use std::path::PathBuf;
+
+/// Writes the codes as JSON to a file read by the lock controller, for controllers
+/// on the same machine and for development
+pub struct FileSmartLock {
+    path: PathBuf,
+}
+
+impl FileSmartLock {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("SMART_LOCK_FILE").unwrap_or_else(|_| "smart_lock.json".into()))
+    }
+}
+
+impl SmartLock for FileSmartLock {
+    async fn push_codes(&self, codes: &[LockCode]) -> Result<(), SmartLockError> {
+        let body = serde_json::to_vec_pretty(&LockCodesPayload { codes })
+            .map_err(|e| SmartLockError::Controller(e.to_string()))?;
+
+        if let Some(directory) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+        // Renamed into place, so the controller never reads a half written file
+        let temporary = self.path.with_extension("tmp");
+        tokio::fs::write(&temporary, body).await?;
+        tokio::fs::rename(&temporary, &self.path).await?;
+
+        Ok(())
+    }
+}
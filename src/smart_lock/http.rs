@@ -0,0 +1,61 @@
+use crate::smart_lock::{LockCode, LockCodesPayload, SmartLock, SmartLockError};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Posts the codes as JSON to the lock controller, signed with a shared secret
+pub struct HttpSmartLock {
+    client: reqwest::Client,
+    url: String,
+    secret: String,
+}
+
+impl HttpSmartLock {
+    pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+    pub fn new(url: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+            secret: secret.into(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("SMART_LOCK_URL").expect("Failed to get smart lock URL"),
+            std::env::var("SMART_LOCK_SECRET").expect("Failed to get smart lock secret"),
+        )
+    }
+
+    /// Hex encoded HMAC-SHA256 of the body, sent in [`Self::SIGNATURE_HEADER`]
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+impl SmartLock for HttpSmartLock {
+    async fn push_codes(&self, codes: &[LockCode]) -> Result<(), SmartLockError> {
+        let body = serde_json::to_vec(&LockCodesPayload { codes })
+            .map_err(|e| SmartLockError::Controller(e.to_string()))?;
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(Self::SIGNATURE_HEADER, self.sign(&body))
+            .body(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SmartLockError::Controller(format!("{status}: {body}")));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+mod file;
+mod http;
+#[cfg(test)]
+mod test;
+
+use serde::Serialize;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+pub use file::FileSmartLock;
+pub use http::HttpSmartLock;
+
+#[derive(Error, Debug)]
+pub enum SmartLockError {
+    #[error("Smart lock request failed: `{0}`")]
+    Request(#[from] reqwest::Error),
+    #[error("Failed to write the smart lock codes: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("Smart lock controller error: `{0}`")]
+    Controller(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockCodeKind {
+    /// The code of the entrance, known by every member
+    Entrance,
+    /// Only given to the members with a reservation in the slot
+    Slot,
+}
+
+/// A code the lock should accept, timestamps are in seconds since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LockCode {
+    pub code: String,
+    pub kind: LockCodeKind,
+    pub valid_from: i64,
+    /// `None` until the code is replaced
+    pub valid_until: Option<i64>,
+}
+
+impl LockCode {
+    pub fn new(
+        code: impl Into<String>,
+        kind: LockCodeKind,
+        valid_from: OffsetDateTime,
+        valid_until: Option<OffsetDateTime>,
+    ) -> Self {
+        Self {
+            code: code.into(),
+            kind,
+            valid_from: valid_from.unix_timestamp(),
+            valid_until: valid_until.map(OffsetDateTime::unix_timestamp),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LockCodesPayload<'a> {
+    codes: &'a [LockCode],
+}
+
+pub trait SmartLock {
+    /// Replaces every code the lock accepts, so pushing the same codes again is harmless
+    async fn push_codes(&self, codes: &[LockCode]) -> Result<(), SmartLockError>;
+}
+
+pub enum SmartLockController {
+    Http(HttpSmartLock),
+    File(FileSmartLock),
+}
+
+impl SmartLockController {
+    /// Codes are only pushed when `SMART_LOCK` is set
+    pub fn from_env() -> Option<Self> {
+        let controller = std::env::var("SMART_LOCK").ok()?;
+
+        match controller.as_str() {
+            "http" => Some(Self::Http(HttpSmartLock::from_env())),
+            "file" => Some(Self::File(FileSmartLock::from_env())),
+            other => panic!("Unknown smart lock controller: {other}"),
+        }
+    }
+}
+
+impl SmartLock for SmartLockController {
+    async fn push_codes(&self, codes: &[LockCode]) -> Result<(), SmartLockError> {
+        match self {
+            Self::Http(http) => http.push_codes(codes).await,
+            Self::File(file) => file.push_codes(codes).await,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::*;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use std::sync::{Arc, Mutex};
+use time::macros::datetime;
+use tokio::net::TcpListener;
+
+fn codes() -> Vec<LockCode> {
+    vec![
+        LockCode::new(
+            "123456",
+            LockCodeKind::Entrance,
+            datetime!(2025-06-01 00:00 UTC),
+            None,
+        ),
+        LockCode::new(
+            "654321",
+            LockCodeKind::Slot,
+            datetime!(2025-06-01 17:45 UTC),
+            Some(datetime!(2025-06-01 20:00 UTC)),
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn file_lock_replaces_codes() {
+    let path = std::env::temp_dir()
+        .join(format!(
+            "acspa_smart_lock_{}",
+            OffsetDateTime::now_utc().unix_timestamp_nanos()
+        ))
+        .join("codes.json");
+    let lock = FileSmartLock::new(&path);
+
+    lock.push_codes(&codes()).await.unwrap();
+    lock.push_codes(&codes()[..1]).await.unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(
+        written,
+        serde_json::json!({
+            "codes": [{
+                "code": "123456",
+                "kind": "entrance",
+                "valid_from": 1748736000,
+                "valid_until": null,
+            }]
+        })
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+async fn controller(State(received): State<Received>, headers: HeaderMap, body: Bytes) {
+    received.lock().unwrap().push((headers, body));
+}
+
+#[tokio::test]
+async fn http_lock_signs_codes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/codes", listener.local_addr().unwrap());
+    let received = Received::default();
+    let app = Router::new()
+        .route("/codes", post(controller))
+        .route("/failing", post(|| async { StatusCode::BAD_GATEWAY }))
+        .with_state(received.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let lock = HttpSmartLock::new(&url, "lock_secret");
+    lock.push_codes(&codes()).await.unwrap();
+
+    let (headers, body) = received.lock().unwrap().pop().unwrap();
+    assert_eq!(
+        headers
+            .get(HttpSmartLock::SIGNATURE_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap(),
+        lock.sign(&body)
+    );
+    assert_ne!(
+        HttpSmartLock::new(&url, "other_secret").sign(&body),
+        lock.sign(&body)
+    );
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["codes"][1]["code"], "654321");
+    assert_eq!(payload["codes"][1]["kind"], "slot");
+
+    let failing = HttpSmartLock::new(url.replace("/codes", "/failing"), "lock_secret");
+    assert!(matches!(
+        failing.push_codes(&codes()).await,
+        Err(SmartLockError::Controller(_))
+    ));
+}
@@ -0,0 +1,171 @@
+//! Time-based one-time passwords (RFC 6238), as used by authenticator apps
+
+use hmac::{Hmac, Mac};
+use qrcode::QrCode;
+use qrcode::render::svg;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+
+#[cfg(test)]
+mod test;
+
+/// Seconds each code is valid for
+const PERIOD: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes of the previous and next period are accepted, to allow for clock drift
+const ALLOWED_DRIFT: i64 = 1;
+pub const RECOVERY_CODES_COUNT: usize = 8;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::fill(&mut bytes).expect("The system random number generator is unavailable");
+    bytes
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in encoded.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|letter| *letter as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+/// A new base32 encoded secret, to be shared with the authenticator app
+pub fn generate_secret() -> String {
+    base32_encode(&random_bytes::<20>())
+}
+
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let code = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    code % 10u32.pow(DIGITS)
+}
+
+/// The code shown by an authenticator app at the given time, `None` if the secret is invalid
+pub fn code_at(secret: &str, time: OffsetDateTime) -> Option<String> {
+    let key = base32_decode(secret)?;
+    let counter = time.unix_timestamp().div_euclid(PERIOD) as u64;
+    Some(format!(
+        "{:0width$}",
+        hotp(&key, counter),
+        width = DIGITS as usize
+    ))
+}
+
+/// The time step the code belongs to, if it's valid now, so it can be refused once used
+pub fn matching_step(secret: &str, code: &str, now: OffsetDateTime) -> Option<i64> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+
+    (-ALLOWED_DRIFT..=ALLOWED_DRIFT).find_map(|drift| {
+        let time = now + Duration::seconds(drift * PERIOD);
+        code_at(secret, time)
+            .filter(|expected| *expected == code)
+            .map(|_| time.unix_timestamp().div_euclid(PERIOD))
+    })
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+/// The `otpauth://` URI that authenticator apps import, usually by scanning it as a QR code
+pub fn provisioning_uri(secret: &str, issuer: &str, account: &str) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}",
+        percent_encode(account)
+    )
+}
+
+pub fn qr_code_svg(data: &str) -> String {
+    QrCode::new(data.as_bytes())
+        .map(|code| {
+            code.render::<svg::Color>()
+                .min_dimensions(200, 200)
+                .dark_color(svg::Color("#000000"))
+                .light_color(svg::Color("#ffffff"))
+                .build()
+        })
+        .unwrap_or_default()
+}
+
+/// Single use codes for when the authenticator app is lost, formatted as `xxxx-xxxx-xxxx-xxxx`
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let code = base32_encode(&random_bytes::<10>()).to_lowercase();
+            code.as_bytes()
+                .chunks(4)
+                .map(|chunk| String::from_utf8_lossy(chunk))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect()
+}
+
+/// Recovery codes are random enough for a fast hash, the separators and case are ignored
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
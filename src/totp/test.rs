@@ -0,0 +1,78 @@
+use super::*;
+use time::macros::datetime;
+
+// The SHA1 secret from the test vectors of RFC 6238, "12345678901234567890" in base32
+const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+#[test]
+fn should_encode_and_decode_base32() {
+    assert_eq!(base32_encode(b"12345678901234567890"), RFC_SECRET);
+    assert_eq!(base32_encode(b"f"), "MY");
+    assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+    assert_eq!(base32_decode("mzxw 6ytb oi======").unwrap(), b"foobar");
+    assert!(base32_decode("MZXW1").is_none());
+
+    let secret = generate_secret();
+    assert_eq!(secret.len(), 32);
+    assert_eq!(base32_decode(&secret).unwrap().len(), 20);
+}
+
+#[test]
+fn should_generate_rfc_codes() {
+    assert_eq!(
+        code_at(RFC_SECRET, datetime!(1970-01-01 00:00:59 UTC)).unwrap(),
+        "287082"
+    );
+    assert_eq!(
+        code_at(RFC_SECRET, datetime!(2005-03-18 01:58:29 UTC)).unwrap(),
+        "081804"
+    );
+    assert_eq!(
+        code_at(RFC_SECRET, datetime!(2009-02-13 23:31:30 UTC)).unwrap(),
+        "005924"
+    );
+}
+
+#[test]
+fn should_verify_codes_with_drift() {
+    let now = datetime!(2024-05-01 12:00:10 UTC);
+    let code = code_at(RFC_SECRET, now).unwrap();
+
+    let step = now.unix_timestamp() / 30;
+    assert_eq!(matching_step(RFC_SECRET, &code, now), Some(step));
+    // Still the step the code was shown in, even when the clock drifted
+    assert_eq!(
+        matching_step(RFC_SECRET, &code, now + Duration::seconds(30)),
+        Some(step)
+    );
+    assert_eq!(matching_step(RFC_SECRET, &code[..3], now), None);
+    assert_eq!(
+        matching_step(RFC_SECRET, &code, now + Duration::minutes(2)),
+        None
+    );
+    assert_eq!(matching_step(RFC_SECRET, "abcdef", now), None);
+}
+
+#[test]
+fn should_build_provisioning_uri() {
+    assert_eq!(
+        provisioning_uri("ABC", "ACS Palatul Copiilor", "ion@test.com"),
+        "otpauth://totp/ACS%20Palatul%20Copiilor:ion%40test.com?secret=ABC&issuer=ACS%20Palatul%20Copiilor&algorithm=SHA1&digits=6&period=30"
+    );
+    assert!(qr_code_svg("otpauth://totp/test").contains("<svg"));
+}
+
+#[test]
+fn should_generate_distinct_recovery_codes() {
+    let codes = generate_recovery_codes();
+    assert_eq!(codes.len(), RECOVERY_CODES_COUNT);
+    assert!(codes.iter().all(|code| code.len() == 19));
+    assert_ne!(codes[0], codes[1]);
+
+    assert_eq!(
+        hash_recovery_code(&codes[0]),
+        hash_recovery_code(&codes[0].to_uppercase().replace('-', " "))
+    );
+    assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+}
@@ -1,5 +1,5 @@
 use time::macros::format_description;
-use time::{Date, Month, OffsetDateTime, UtcOffset};
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, UtcOffset};
 
 pub const ISO_DATE: &[time::format_description::BorrowedFormatItem] =
     format_description!("[year]-[month]-[day]");
@@ -10,6 +10,10 @@ pub const READABLE_DATE: &[time::format_description::BorrowedFormatItem] =
 pub const READABLE_DATE_TIME: &[time::format_description::BorrowedFormatItem] =
     format_description!("[day].[month].[year] [hour]:[minute]");
 
+/// The value of a `datetime-local` input
+pub const DATE_TIME_INPUT: &[time::format_description::BorrowedFormatItem] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]");
+
 pub const MONTH_YEAR: &[time::format_description::BorrowedFormatItem] =
     format_description!("[year].[month]");
 
@@ -34,6 +38,19 @@ pub fn as_local(time: &OffsetDateTime) -> String {
     time.to_offset(offset).format(READABLE_DATE_TIME).unwrap()
 }
 
+pub fn as_local_input(time: &OffsetDateTime) -> String {
+    let offset = UtcOffset::current_local_offset().expect("Failed to determine local offset");
+    time.to_offset(offset).format(DATE_TIME_INPUT).unwrap()
+}
+
+/// Reads a `datetime-local` input, which is in local time
+pub fn parse_local_input(input: &str) -> Option<OffsetDateTime> {
+    let offset = UtcOffset::current_local_offset().expect("Failed to determine local offset");
+    PrimitiveDateTime::parse(input, DATE_TIME_INPUT)
+        .ok()
+        .map(|time| time.assume_offset(offset))
+}
+
 pub fn month_as_str(month: &Month) -> &'static str {
     match month {
         Month::January => "Ianuarie",
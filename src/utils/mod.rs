@@ -1,6 +1,7 @@
 mod color;
 pub mod date_formats;
 pub mod date_iter;
+pub mod markdown;
 pub mod queries;
 
 pub use color::*;
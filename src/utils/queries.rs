@@ -2,18 +2,22 @@ use crate::http::AppState;
 use crate::model::day_structure::{DayStructure, HOLIDAY_DAY_STRUCTURE};
 use crate::model::global_vars::GlobalVars;
 use crate::model::location::Location;
+use crate::model::payment::{PaymentBreak, PaymentWithAllocations};
 use crate::model::user::User;
 use crate::model::user_reservation::UserReservation;
-use crate::utils::local_time;
+use crate::utils::{date_formats, local_time};
 use itertools::Itertools;
-use sqlx::{SqliteExecutor, SqlitePool, query, query_as};
-use time::{Date, Month, Weekday};
+use sqlx::{SqliteConnection, SqliteExecutor, SqlitePool, query, query_as};
+use time::{Date, Month, OffsetDateTime, Weekday};
 use tracing::error;
 
 pub async fn get_global_vars(pool: &SqlitePool) -> sqlx::Result<GlobalVars> {
     query_as!(
         GlobalVars,
-        "select in_maintenance, entrance_code, homepage_message from global_vars"
+        r#"select in_maintenance, entrance_code, monthly_fee, dunning_enabled, club_name, club_details,
+            auto_suspend_unpaid_months, maintenance_starts_at as "maintenance_starts_at: OffsetDateTime",
+            maintenance_ends_at as "maintenance_ends_at: OffsetDateTime", maintenance_banner,
+            entrance_code_rotation_days, slot_entrance_codes, key_holder_warning_hours from global_vars"#
     )
     .fetch_one(pool)
     .await
@@ -25,6 +29,20 @@ pub async fn get_user(pool: &SqlitePool, id: i64) -> sqlx::Result<User> {
         .await
 }
 
+/// Members whose birthday is on the same day and month as `date`
+pub async fn get_celebrated_members(
+    executor: impl SqliteExecutor<'_>,
+    date: Date,
+) -> sqlx::Result<Vec<User>> {
+    query_as!(
+        User,
+        "select * from users_with_role where strftime('%d%m', birthday) = strftime('%d%m', $1)",
+        date
+    )
+    .fetch_all(executor)
+    .await
+}
+
 pub async fn get_alt_day_structure_for_day(
     executor: impl SqliteExecutor<'_>,
     date: Date,
@@ -203,7 +221,210 @@ pub async fn delete_reservations_on_day(
     .map(|result| result.rows_affected())
 }
 
+pub async fn get_user_payments(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+) -> sqlx::Result<Vec<PaymentWithAllocations>> {
+    let payments = query!(
+        "select p.id, p.amount, p.payment_date, p.notes, p.created_at, p.created_by, u.name as created_by_name,
+         p.voided_at, p.void_reason, p.refund_of, r.number as 'receipt_number?' from payments p
+         join users u on u.id = p.created_by
+         left join receipts r on r.number = (select max(number) from receipts where payment_id = p.id)
+         where p.user_id = $1 order by p.payment_date desc, p.id desc",
+        user_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let all_allocations = query!(
+        "select payment_id, year, month from payment_allocations where payment_id in (select id from payments where user_id = ?) order by year desc, month desc",
+        user_id
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(payments
+        .into_iter()
+        .map(|p| {
+            let allocations = all_allocations
+                .iter()
+                .filter(|a| a.payment_id == p.id)
+                .filter_map(|a| {
+                    Some(YearMonth::new(
+                        a.year as i32,
+                        Month::try_from(a.month as u8).ok()?,
+                    ))
+                })
+                .collect();
+
+            PaymentWithAllocations {
+                id: p.id,
+                amount: p.amount,
+                payment_date: p.payment_date,
+                notes: p.notes,
+                created_at: p.created_at,
+                created_by: p.created_by,
+                created_by_name: p.created_by_name,
+                voided_at: p.voided_at,
+                void_reason: p.void_reason,
+                refund_of: p.refund_of,
+                receipt_number: p.receipt_number,
+                allocations,
+            }
+        })
+        .collect())
+}
+
+pub async fn get_user_payment_breaks(
+    executor: impl SqliteExecutor<'_>,
+    user_id: i64,
+) -> sqlx::Result<Vec<PaymentBreak>> {
+    query_as!(
+        PaymentBreak,
+        "select m.*, u.name as created_by_name
+         from payment_breaks m join users u on u.id = m.created_by
+         where user_id = $1 order by start_date desc",
+        user_id
+    )
+    .fetch_all(executor)
+    .await
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonthStatus {
+    Paid(String),  // Notes
+    Break(String), // Reason
+    Unpaid,
+    NotJoined,
+    Future,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonthStatusView {
+    pub month: Month,
+    pub month_name: &'static str,
+    pub status: MonthStatus,
+}
+
+pub fn calculate_year_status(
+    year: i32,
+    member: &User,
+    payments: &[PaymentWithAllocations],
+    breaks: &[PaymentBreak],
+    current_date: Date,
+) -> Vec<MonthStatusView> {
+    (1..=12)
+        .map(|m| {
+            let month = Month::try_from(m).unwrap();
+            let month_start = Date::from_calendar_date(year, month, 1).unwrap();
+            let month_name = date_formats::month_as_str(&month);
+
+            // 1. Check if before member joined (approximate to month)
+            let member_start_month = Date::from_calendar_date(
+                member.member_since.year(),
+                member.member_since.month(),
+                1,
+            )
+            .unwrap();
+
+            if month_start < member_start_month {
+                return MonthStatusView {
+                    month,
+                    month_name,
+                    status: MonthStatus::NotJoined,
+                };
+            }
+
+            // 2. Check if Paid
+            let is_paid = payments.iter().find(|p| {
+                p.allocations
+                    .iter()
+                    .any(|a| a.year == year && a.month == month)
+            });
+
+            if let Some(paid) = is_paid {
+                return MonthStatusView {
+                    month,
+                    month_name,
+                    status: MonthStatus::Paid(paid.notes.clone().unwrap_or_default()),
+                };
+            }
+
+            // 3. Check if Break
+            let is_break = breaks.iter().find(|b| {
+                // Check if this month (e.g. 2024-05-01) is within break start..=end
+                // Breaks are stored as 1st of month.
+                month_start >= b.start_date && month_start <= b.end_date
+            });
+
+            if let Some(brk) = is_break {
+                return MonthStatusView {
+                    month,
+                    month_name,
+                    status: MonthStatus::Break(brk.reason.clone().unwrap_or_default()),
+                };
+            }
+
+            // 4. Check Future vs Unpaid
+            // If the month is in the future compared to now
+            let current_month_start =
+                Date::from_calendar_date(current_date.year(), current_date.month(), 1).unwrap();
+
+            if month_start > current_month_start {
+                return MonthStatusView {
+                    month,
+                    month_name,
+                    status: MonthStatus::Future,
+                };
+            }
+
+            MonthStatusView {
+                month,
+                month_name,
+                status: MonthStatus::Unpaid,
+            }
+        })
+        .collect()
+}
+
+/// Unpaid months since joining, followed by the remaining months of the current year
+pub fn calculate_payable_months(
+    member: &User,
+    payments: &[PaymentWithAllocations],
+    breaks: &[PaymentBreak],
+    current_date: Date,
+) -> Vec<(YearMonth, MonthStatus)> {
+    (member.member_since.year()..=current_date.year())
+        .flat_map(|year| {
+            calculate_year_status(year, member, payments, breaks, current_date)
+                .into_iter()
+                .filter(|view| matches!(view.status, MonthStatus::Unpaid | MonthStatus::Future))
+                .map(move |view| (YearMonth::new(year, view.month), view.status))
+        })
+        .collect()
+}
+
+/// The months since joining, up to and including the current one,
+/// which are neither paid for nor covered by a break
+pub async fn get_unpaid_months(
+    conn: &mut SqliteConnection,
+    member: &User,
+    current_date: Date,
+) -> sqlx::Result<Vec<YearMonth>> {
+    let payments = get_user_payments(&mut *conn, member.id).await?;
+    let breaks = get_user_payment_breaks(&mut *conn, member.id).await?;
+
+    Ok(
+        calculate_payable_months(member, &payments, &breaks, current_date)
+            .into_iter()
+            .filter(|(_, status)| *status == MonthStatus::Unpaid)
+            .map(|(month, _)| month)
+            .collect(),
+    )
+}
+
 /// Checks if a user has a valid payment allocation or break for a specific year/month.
+/// Household payments are recorded as a payment for each covered member, so they count too.
 async fn is_month_covered(
     executor: impl SqliteExecutor<'_>,
     user_id: i64,
@@ -0,0 +1,208 @@
+#[cfg(test)]
+mod test;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use thiserror::Error;
+use time::{Duration, OffsetDateTime};
+
+/// How long a push service keeps a push for a phone that is offline
+const PUSH_TTL: Duration = Duration::hours(12);
+/// Push services reject tokens that are valid for more than a day
+const TOKEN_VALIDITY: Duration = Duration::hours(12);
+/// The push services of the browsers, `*.` also allows any subdomain.
+/// Endpoints come from the browsers, so pushes can't be sent anywhere else
+const PUSH_SERVICE_HOSTS: [&str; 4] = [
+    "fcm.googleapis.com",
+    "*.push.services.mozilla.com",
+    "*.notify.windows.com",
+    "*.push.apple.com",
+];
+
+#[derive(Error, Debug)]
+pub enum WebPushError {
+    #[error("Push request failed: `{0}`")]
+    Request(#[from] reqwest::Error),
+    #[error("Invalid VAPID key: `{0}`")]
+    Key(String),
+    #[error("Invalid push endpoint: `{0}`")]
+    Endpoint(String),
+    /// The member unsubscribed or the browser dropped the subscription, it won't work again
+    #[error("Push subscription expired")]
+    Gone,
+    #[error("Push service error: `{0}`")]
+    Service(String),
+}
+
+#[derive(Serialize)]
+struct TokenHeader {
+    typ: &'static str,
+    alg: &'static str,
+}
+
+#[derive(Serialize)]
+struct TokenClaims<'a> {
+    aud: &'a str,
+    exp: i64,
+    sub: &'a str,
+}
+
+/// The P-256 key identifying the app to the push services (RFC 8292)
+pub struct VapidKey {
+    key: SigningKey,
+}
+
+impl VapidKey {
+    /// From the raw private key, base64url encoded, as printed by the usual VAPID key generators
+    pub fn from_base64(private_key: &str) -> Result<Self, WebPushError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(private_key.trim().trim_end_matches('='))
+            .map_err(|e| WebPushError::Key(e.to_string()))?;
+        let key = SigningKey::from_slice(&bytes).map_err(|e| WebPushError::Key(e.to_string()))?;
+
+        Ok(Self { key })
+    }
+
+    /// Base64url encoded uncompressed point, given to the browsers as the `applicationServerKey`
+    pub fn public_key(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.key.verifying_key().to_encoded_point(false).as_bytes())
+    }
+
+    /// ES256 signed JWT allowing pushes to the push service at `audience`
+    pub fn sign(&self, audience: &str, subject: &str, expires_at: OffsetDateTime) -> String {
+        let header = serde_json::to_vec(&TokenHeader {
+            typ: "JWT",
+            alg: "ES256",
+        })
+        .expect("The token header is always serializable");
+        let claims = serde_json::to_vec(&TokenClaims {
+            aud: audience,
+            exp: expires_at.unix_timestamp(),
+            sub: subject,
+        })
+        .expect("The token claims are always serializable");
+
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let signature: Signature = self.key.sign(message.as_bytes());
+
+        format!("{message}.{}", URL_SAFE_NO_PAD.encode(signature.to_bytes()))
+    }
+}
+
+/// Sends pushes without a payload, so nothing has to be encrypted for the subscription:
+/// the service worker asks the app what the notification is about when the push arrives
+pub struct WebPushSender {
+    client: reqwest::Client,
+    key: VapidKey,
+    /// Contact of the club for the push services, a `mailto:` or `https:` URL
+    subject: String,
+    allowed_hosts: Vec<String>,
+}
+
+impl WebPushSender {
+    pub fn new(key: VapidKey, subject: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            key,
+            subject: subject.into(),
+            allowed_hosts: PUSH_SERVICE_HOSTS.map(str::to_string).to_vec(),
+        }
+    }
+
+    /// Replaces the known push services, for a self-hosted or stub push service
+    pub fn with_allowed_hosts(
+        mut self,
+        hosts: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.allowed_hosts = hosts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Pushes are only sent when `VAPID_PRIVATE_KEY` is set,
+    /// `PUSH_SERVICE_HOSTS` can replace the known push services with a comma separated list
+    pub fn from_env() -> Option<Self> {
+        let private_key = std::env::var("VAPID_PRIVATE_KEY").ok()?;
+        let key = VapidKey::from_base64(&private_key).expect("Failed to parse the VAPID key");
+
+        let sender = Self::new(
+            key,
+            std::env::var("VAPID_SUBJECT").expect("Failed to get VAPID subject"),
+        );
+        Some(match std::env::var("PUSH_SERVICE_HOSTS") {
+            Ok(hosts) => sender.with_allowed_hosts(
+                hosts
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|host| !host.is_empty()),
+            ),
+            Err(_) => sender,
+        })
+    }
+
+    /// Only the push services are accepted, any other host could be an internal service
+    pub fn accepts_endpoint(&self, endpoint: &str) -> bool {
+        Url::parse(endpoint).is_ok_and(|url| self.accepts_url(&url))
+    }
+
+    fn accepts_url(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.ends_with('.')),
+                None => host == allowed,
+            })
+    }
+
+    pub fn public_key(&self) -> String {
+        self.key.public_key()
+    }
+
+    pub async fn send(&self, endpoint: &str, now: OffsetDateTime) -> Result<(), WebPushError> {
+        let url = Url::parse(endpoint).map_err(|e| WebPushError::Endpoint(e.to_string()))?;
+        if !self.accepts_url(&url) {
+            return Err(WebPushError::Endpoint(format!(
+                "{} is not a known push service",
+                url.host_str().unwrap_or_default()
+            )));
+        }
+        let audience = url.origin().ascii_serialization();
+        let token = self
+            .key
+            .sign(&audience, &self.subject, now + TOKEN_VALIDITY);
+
+        let response = self
+            .client
+            .post(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("vapid t={token}, k={}", self.key.public_key()),
+            )
+            .header("TTL", PUSH_TTL.whole_seconds())
+            .header("Urgency", "high")
+            .header(reqwest::header::CONTENT_LENGTH, 0)
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Err(WebPushError::Gone),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(WebPushError::Service(format!("{status}: {body}")))
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use super::*;
+use axum::Router;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::post;
+use p256::ecdsa::VerifyingKey;
+use p256::ecdsa::signature::Verifier;
+use std::sync::{Arc, Mutex};
+use time::macros::datetime;
+use tokio::net::TcpListener;
+
+fn generate_key() -> VapidKey {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).unwrap();
+    VapidKey::from_base64(&URL_SAFE_NO_PAD.encode(bytes)).unwrap()
+}
+
+/// Checks the signature of the token with the public key, returns its claims
+fn verify_token(token: &str, public_key: &str) -> serde_json::Value {
+    let (message, signature) = token.rsplit_once('.').unwrap();
+    let key = VerifyingKey::from_sec1_bytes(&URL_SAFE_NO_PAD.decode(public_key).unwrap()).unwrap();
+    let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap()).unwrap();
+    key.verify(message.as_bytes(), &signature).unwrap();
+
+    let (header, claims) = message.split_once('.').unwrap();
+    let header: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).unwrap()).unwrap();
+    assert_eq!(header["alg"], "ES256");
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap()
+}
+
+#[test]
+fn vapid_key_signs_tokens() {
+    let key = generate_key();
+    let token = key.sign(
+        "https://push.example.com",
+        "mailto:admin@acspa.ro",
+        datetime!(2025-06-01 12:00 UTC),
+    );
+
+    let claims = verify_token(&token, &key.public_key());
+    assert_eq!(claims["aud"], "https://push.example.com");
+    assert_eq!(claims["sub"], "mailto:admin@acspa.ro");
+    assert_eq!(claims["exp"], 1748779200);
+
+    // The public key is an uncompressed P-256 point
+    assert_eq!(URL_SAFE_NO_PAD.decode(key.public_key()).unwrap().len(), 65);
+    assert!(matches!(
+        VapidKey::from_base64("not a key"),
+        Err(WebPushError::Key(_))
+    ));
+}
+
+type Received = Arc<Mutex<Vec<HeaderMap>>>;
+
+async fn push_service(State(received): State<Received>, headers: HeaderMap) -> StatusCode {
+    received.lock().unwrap().push(headers);
+    StatusCode::CREATED
+}
+
+#[tokio::test]
+async fn sender_authorizes_pushes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let origin = format!("http://{}", listener.local_addr().unwrap());
+    let received = Received::default();
+    let app = Router::new()
+        .route("/push/1", post(push_service))
+        .route("/push/gone", post(|| async { StatusCode::GONE }))
+        .route("/push/failing", post(|| async { StatusCode::BAD_GATEWAY }))
+        .with_state(received.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let sender = WebPushSender::new(generate_key(), "mailto:admin@acspa.ro")
+        .with_allowed_hosts(["127.0.0.1"]);
+    let now = OffsetDateTime::now_utc();
+    sender.send(&format!("{origin}/push/1"), now).await.unwrap();
+
+    let headers = received.lock().unwrap().pop().unwrap();
+    let authorization = headers
+        .get(reqwest::header::AUTHORIZATION)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let (token, public_key) = authorization
+        .strip_prefix("vapid t=")
+        .unwrap()
+        .split_once(", k=")
+        .unwrap();
+    assert_eq!(public_key, sender.public_key());
+    let claims = verify_token(token, public_key);
+    assert_eq!(claims["aud"], origin);
+    assert_eq!(claims["exp"], (now + TOKEN_VALIDITY).unix_timestamp());
+    assert_eq!(headers.get("TTL").unwrap(), "43200");
+
+    assert!(matches!(
+        sender.send(&format!("{origin}/push/gone"), now).await,
+        Err(WebPushError::Gone)
+    ));
+    assert!(matches!(
+        sender.send(&format!("{origin}/push/failing"), now).await,
+        Err(WebPushError::Service(_))
+    ));
+    assert!(matches!(
+        sender.send("not an endpoint", now).await,
+        Err(WebPushError::Endpoint(_))
+    ));
+}
+
+#[test]
+fn sender_accepts_only_push_services() {
+    let sender = WebPushSender::new(generate_key(), "mailto:admin@acspa.ro");
+
+    assert!(sender.accepts_endpoint("https://fcm.googleapis.com/fcm/send/abc"));
+    assert!(sender.accepts_endpoint("https://updates.push.services.mozilla.com/wpush/v2/abc"));
+    assert!(sender.accepts_endpoint("https://web.push.apple.com/abc"));
+    assert!(sender.accepts_endpoint("https://db5p.notify.windows.com/w/?token=abc"));
+
+    assert!(!sender.accepts_endpoint("https://127.0.0.1/push"));
+    assert!(!sender.accepts_endpoint("https://[::1]/push"));
+    assert!(!sender.accepts_endpoint("https://localhost/push"));
+    assert!(!sender.accepts_endpoint("https://169.254.169.254/latest/meta-data"));
+    assert!(!sender.accepts_endpoint("https://push.apple.com/abc"));
+    assert!(!sender.accepts_endpoint("https://evilpush.apple.com/abc"));
+    assert!(!sender.accepts_endpoint("https://fcm.googleapis.com.evil.com/abc"));
+    assert!(!sender.accepts_endpoint("not an endpoint"));
+
+    let stub = sender.with_allowed_hosts(["127.0.0.1"]);
+    assert!(stub.accepts_endpoint("http://127.0.0.1:8080/push"));
+    assert!(!stub.accepts_endpoint("https://fcm.googleapis.com/fcm/send/abc"));
+}